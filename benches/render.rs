@@ -0,0 +1,146 @@
+
+extern crate swr_rs;
+
+use std::env;
+use std::f64;
+
+use swr_rs::math::vector::Vector3;
+use swr_rs::math::quaternion::Quaternion;
+use swr_rs::geometry::mesh::Mesh;
+use swr_rs::render::color::Color;
+use swr_rs::render::device::{Camera, Device, Handedness, Msaa, Precision, Projection};
+use swr_rs::render::light::PointLight;
+use swr_rs::render::material::{Diffuse, Shading};
+use swr_rs::render::stats::Benchmark;
+use swr_rs::scene::graph::{Node, Scene};
+
+// Rendering benchmarks, run with cargo bench, or cargo bench -- NAME for
+// the ones whose names contain NAME. Each renders its scene headless in a
+// few samples after warming up and reports the median, to compare before
+// and after a change to the pipeline.
+
+const WIDTH: usize = 600;
+const HEIGHT: usize = 360;
+const FRAMES: usize = 10;
+const SAMPLES: usize = 5;
+
+struct Case {
+    name: &'static str,
+    scene: Scene,
+    setup: fn(&mut Device),
+}
+
+fn camera(distance: f64) -> Camera {
+    Camera {
+        position: Vector3::new(0.0, 0.0, distance),
+        target: Vector3::zero(),
+        fov: 45.0 * f64::consts::PI / 180.0,
+        znear: 0.1,
+        zfar: 100.0,
+        projection: Projection::Perspective,
+        handedness: Handedness::Right,
+    }
+}
+
+fn scene(meshes: Vec<Mesh>) -> Scene {
+    let mut scene = Scene::new();
+    for mesh in meshes {
+        scene.add(Node::with_mesh(mesh), None);
+    }
+    scene
+}
+
+// Small triangles, mostly vertex work.
+fn spheres(shading: Shading) -> Scene {
+    let mut meshes = Vec::new();
+    for i in 0..100 {
+        let mut sphere = Mesh::sphere(Vector3::zero(), 0.4, 32, 32);
+        sphere.transform.position = Vector3::new((i % 10) as f64 - 4.5, (i / 10) as f64 - 4.5, 0.0);
+        sphere.material.shading = shading;
+        meshes.push(sphere);
+    }
+    scene(meshes)
+}
+
+// Big quads one behind the other, nearest first, mostly pixel work.
+fn layers() -> Scene {
+    let meshes = (0..8)
+        .map(|i| {
+            let mut plane = Mesh::plane(12.0, 12.0, 1, 1);
+            // Turned about x to face the camera
+            let facing = Vector3::new(0.0, 0.0, 90.0);
+            plane.transform.rotation = Quaternion::from_euler_angle_degrees(facing);
+            plane.transform.position = Vector3::new(0.0, 0.0, -(i as f64));
+            plane
+        })
+        .collect();
+    scene(meshes)
+}
+
+fn lights(device: &mut Device) {
+    device.lights = (0..4)
+        .map(|i| {
+            let angle = i as f64 * f64::consts::PI * 0.5;
+            PointLight::new(Vector3::new(angle.cos() * 6.0, angle.sin() * 6.0, 4.0),
+                            Color::white(),
+                            20.0)
+        })
+        .collect();
+}
+
+fn cases() -> Vec<Case> {
+    vec![Case {
+             name: "cube",
+             scene: scene(vec![Mesh::cube()]),
+             setup: |_| {},
+         },
+         Case {
+             name: "spheres",
+             scene: spheres(Shading::Basic),
+             setup: |_| {},
+         },
+         Case {
+             name: "spheres_f32",
+             scene: spheres(Shading::Basic),
+             setup: |device| device.set_precision(Precision::Single),
+         },
+         Case {
+             name: "spheres_pbr",
+             scene: spheres(Shading::Pbr(Diffuse::Lambert)),
+             setup: lights,
+         },
+         Case {
+             name: "layers",
+             scene: layers(),
+             setup: |_| {},
+         },
+         Case {
+             name: "layers_msaa",
+             scene: layers(),
+             setup: |device| device.set_msaa(Msaa::X4),
+         },
+         Case {
+             name: "shell",
+             scene: scene(vec![Mesh::shell(0.1, 0.5, 3.0, 3, 128, 128)]),
+             setup: |_| {},
+         }]
+}
+
+fn main() {
+    // cargo bench passes --bench
+    let filters: Vec<String> = env::args().skip(1).filter(|a| !a.starts_with("--")).collect();
+    for case in cases() {
+        if !filters.is_empty() && !filters.iter().any(|f| case.name.contains(f.as_str())) {
+            continue;
+        }
+        let mut device = Device::new(WIDTH, HEIGHT);
+        (case.setup)(&mut device);
+        let camera = camera(if case.name == "cube" { 3.0 } else { 12.0 });
+        device.render_benchmark(&camera, &case.scene, 2);
+        let mut samples: Vec<Benchmark> = (0..SAMPLES)
+            .map(|_| device.render_benchmark(&camera, &case.scene, FRAMES))
+            .collect();
+        samples.sort_by(|a, b| a.seconds.partial_cmp(&b.seconds).unwrap());
+        println!("{:<12} {}", case.name, samples[SAMPLES / 2]);
+    }
+}
@@ -0,0 +1,133 @@
+
+#[cfg(feature = "window")]
+use minifb::{Key, MouseButton};
+
+use std::f64;
+
+use math::vector::Vector3;
+use render::device::Camera;
+#[cfg(feature = "window")]
+use input::Input;
+
+// Free-flying first person camera. With yaw and pitch at zero it looks down
+// the -Z axis; positive yaw turns towards +X and positive pitch looks up.
+#[derive(Debug,Clone)]
+pub struct FpsCamera {
+    pub position: Vector3,
+    pub yaw: f64,
+    pub pitch: f64,
+    pub speed: f64,
+    pub sensitivity: f64,
+}
+
+impl FpsCamera {
+    pub fn new(position: Vector3) -> FpsCamera {
+        FpsCamera {
+            position: position,
+            yaw: 0.0,
+            pitch: 0.0,
+            speed: 5.0,
+            sensitivity: 0.005,
+        }
+    }
+
+    pub fn forward(&self) -> Vector3 {
+        Vector3::new(self.pitch.cos() * self.yaw.sin(),
+                     self.pitch.sin(),
+                     -self.pitch.cos() * self.yaw.cos())
+    }
+
+    pub fn right(&self) -> Vector3 {
+        self.forward().cross(Vector3::unit_y()).normalize()
+    }
+
+    pub fn look_at(&mut self, target: Vector3) {
+        let direction = (target - self.position).normalize();
+        self.yaw = direction.x.atan2(-direction.z);
+        self.pitch = direction.y.asin();
+    }
+
+    pub fn look(&mut self, dx: f64, dy: f64) {
+        let limit = f64::consts::PI / 2.0 - 0.001;
+        self.yaw += dx * self.sensitivity;
+        self.pitch = (self.pitch - dy * self.sensitivity).max(-limit).min(limit);
+    }
+
+    // Moves relative to the view direction. Amounts are scaled by speed and
+    // the elapsed time in seconds; up is always world space Y.
+    pub fn translate(&mut self, forward: f64, right: f64, up: f64, dt: f64) {
+        let offset = self.forward() * forward + self.right() * right + Vector3::unit_y() * up;
+        self.position = self.position + offset * (self.speed * dt);
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.position = self.position;
+        camera.target = self.position + self.forward();
+    }
+
+    // WASD moves, E/Q rise and sink, dragging with the right mouse button
+    // looks around.
+    #[cfg(feature = "window")]
+    pub fn update(&mut self, input: &Input, dt: f64) {
+        let axis = |positive: Key, negative: Key| {
+            let mut value = 0.0;
+            if input.is_pressed(positive) {
+                value += 1.0;
+            }
+            if input.is_pressed(negative) {
+                value -= 1.0;
+            }
+            value
+        };
+        let forward = axis(Key::W, Key::S);
+        let right = axis(Key::D, Key::A);
+        let up = axis(Key::E, Key::Q);
+        self.translate(forward, right, up, dt);
+
+        if input.is_mouse_pressed(MouseButton::Right) {
+            let (dx, dy) = input.mouse_delta();
+            self.look(dx, dy);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use super::FpsCamera;
+
+    fn assert_near(expected: Vector3, actual: Vector3) {
+        assert!((expected - actual).length() < 1e-9, "{:?} != {:?}", expected, actual);
+    }
+
+    #[test]
+    fn basis() {
+        let camera = FpsCamera::new(Vector3::zero());
+        assert_near(Vector3::new(0.0, 0.0, -1.0), camera.forward());
+        assert_near(Vector3::new(1.0, 0.0, 0.0), camera.right());
+    }
+
+    #[test]
+    fn translate() {
+        let mut camera = FpsCamera::new(Vector3::zero());
+        camera.speed = 2.0;
+        camera.translate(1.0, 1.0, 1.0, 0.5);
+        assert_near(Vector3::new(1.0, 1.0, -1.0), camera.position);
+    }
+
+    #[test]
+    fn look_at() {
+        let mut camera = FpsCamera::new(Vector3::new(1.0, 2.0, 3.0));
+        camera.look_at(Vector3::new(4.0, -2.0, 3.0));
+        assert_near(Vector3::new(0.6, -0.8, 0.0), camera.forward());
+    }
+
+    #[test]
+    fn look() {
+        let mut camera = FpsCamera::new(Vector3::zero());
+        camera.look(0.0, -1000.0);
+        assert!(camera.forward().y > 0.99);
+        assert!(camera.right().length() > 0.99);
+    }
+}
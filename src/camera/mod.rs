@@ -0,0 +1,2 @@
+pub mod orbit;
+pub mod fps;
@@ -0,0 +1,111 @@
+
+#[cfg(feature = "window")]
+use minifb::MouseButton;
+
+use std::f64;
+
+use math::vector::Vector3;
+use render::device::Camera;
+#[cfg(feature = "window")]
+use input::Input;
+
+// Orbits a target point. Yaw rotates around the world Y axis and pitch tilts
+// towards the poles; with both at zero the camera looks down the -Z axis.
+#[derive(Debug,Clone)]
+pub struct OrbitCamera {
+    pub target: Vector3,
+    pub distance: f64,
+    pub yaw: f64,
+    pub pitch: f64,
+    pub min_distance: f64,
+    pub max_distance: f64,
+    pub rotate_speed: f64,
+    pub zoom_speed: f64,
+}
+
+impl OrbitCamera {
+    pub fn new(target: Vector3, distance: f64) -> OrbitCamera {
+        OrbitCamera {
+            target: target,
+            distance: distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            min_distance: 0.1,
+            max_distance: 1000.0,
+            rotate_speed: 0.01,
+            zoom_speed: 0.1,
+        }
+    }
+
+    pub fn rotate(&mut self, dx: f64, dy: f64) {
+        let limit = f64::consts::PI / 2.0 - 0.001;
+        self.yaw += dx * self.rotate_speed;
+        self.pitch = (self.pitch + dy * self.rotate_speed).max(-limit).min(limit);
+    }
+
+    // Positive amounts move towards the target.
+    pub fn dolly(&mut self, amount: f64) {
+        let distance = self.distance * (1.0 - amount * self.zoom_speed);
+        self.distance = distance.max(self.min_distance).min(self.max_distance);
+    }
+
+    pub fn position(&self) -> Vector3 {
+        let offset = Vector3::new(self.pitch.cos() * self.yaw.sin(),
+                                  self.pitch.sin(),
+                                  self.pitch.cos() * self.yaw.cos());
+        self.target + offset * self.distance
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.position = self.position();
+        camera.target = self.target;
+    }
+
+    // Left mouse drag orbits, the scroll wheel dollies.
+    #[cfg(feature = "window")]
+    pub fn update(&mut self, input: &Input) {
+        if input.is_mouse_pressed(MouseButton::Left) {
+            let (dx, dy) = input.mouse_delta();
+            self.rotate(dx, dy);
+        }
+        self.dolly(input.scroll().1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::f64;
+    use math::vector::Vector3;
+    use super::OrbitCamera;
+
+    fn assert_near(expected: Vector3, actual: Vector3) {
+        assert!((expected - actual).length() < 1e-9, "{:?} != {:?}", expected, actual);
+    }
+
+    #[test]
+    fn position() {
+        let mut orbit = OrbitCamera::new(Vector3::new(1.0, 0.0, 0.0), 10.0);
+        assert_near(Vector3::new(1.0, 0.0, 10.0), orbit.position());
+
+        orbit.yaw = f64::consts::PI / 2.0;
+        assert_near(Vector3::new(11.0, 0.0, 0.0), orbit.position());
+    }
+
+    #[test]
+    fn pitch_is_clamped() {
+        let mut orbit = OrbitCamera::new(Vector3::zero(), 10.0);
+        orbit.rotate(0.0, 1000.0);
+        assert!(orbit.pitch < f64::consts::PI / 2.0);
+        assert!(orbit.position().y < 10.0);
+    }
+
+    #[test]
+    fn dolly_is_clamped() {
+        let mut orbit = OrbitCamera::new(Vector3::zero(), 10.0);
+        orbit.dolly(1.0);
+        assert_eq!(9.0, orbit.distance);
+        orbit.dolly(100.0);
+        assert_eq!(orbit.min_distance, orbit.distance);
+    }
+}
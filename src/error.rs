@@ -0,0 +1,103 @@
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::result;
+
+// What can go wrong in the library. It is returned rather than panicked
+// with, so an application embedding the renderer can report a bad file and
+// carry on.
+#[derive(Debug)]
+pub enum SwrError {
+    // Opening or reading a file failed.
+    Io(io::Error),
+    // A file read fine but doesn't hold what it should, e.g. a truncated
+    // PLY or a scene naming an unknown primitive.
+    Format(String),
+    // A face of the mesh refers to a vertex it doesn't have.
+    InvalidIndex {
+        mesh: String,
+        face: usize,
+        index: u32,
+        vertices: usize,
+    },
+    // The window couldn't be opened or updated.
+    Window(String),
+}
+
+pub type Result<T> = result::Result<T, SwrError>;
+
+impl SwrError {
+    pub fn format<S: Into<String>>(message: S) -> SwrError {
+        SwrError::Format(message.into())
+    }
+}
+
+impl fmt::Display for SwrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SwrError::Io(ref e) => write!(f, "{}", e),
+            SwrError::Format(ref message) => write!(f, "{}", message),
+            SwrError::InvalidIndex { ref mesh, face, index, vertices } => {
+                write!(f,
+                       "face {} of {} refers to vertex {} of {}",
+                       face,
+                       mesh,
+                       index,
+                       vertices)
+            }
+            SwrError::Window(ref message) => write!(f, "window: {}", message),
+        }
+    }
+}
+
+impl Error for SwrError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            SwrError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SwrError {
+    fn from(e: io::Error) -> SwrError {
+        SwrError::Io(e)
+    }
+}
+
+#[cfg(feature = "window")]
+impl From<::minifb::Error> for SwrError {
+    fn from(e: ::minifb::Error) -> SwrError {
+        SwrError::Window(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::error::Error;
+    use std::io;
+    use geometry::mesh::Mesh;
+    use super::SwrError;
+
+    #[test]
+    fn errors() {
+        let e = SwrError::from(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        assert_eq!("no such file", e.to_string());
+        assert!(e.source().is_some());
+
+        // Loaders say what was wrong instead of panicking
+        match Mesh::read_ply(b"ply\nformat ascii 1.0\n", "broken") {
+            Err(SwrError::Format(message)) => assert_eq!("PLY header without an end", message),
+            other => panic!("{:?}", other),
+        }
+
+        let mut mesh = Mesh::triangle();
+        assert!(mesh.validate().is_ok());
+        mesh.add_face(0, 1, 7);
+        let e = mesh.validate().unwrap_err();
+        assert_eq!("face 1 of Triangle refers to vertex 7 of 3", e.to_string());
+        assert!(e.source().is_none());
+    }
+}
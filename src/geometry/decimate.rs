@@ -0,0 +1,306 @@
+
+use super::super::math::vector::Vector3;
+use super::mesh::{Indices, Mesh, Vertex};
+
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, BinaryHeap};
+
+// Simplification by collapsing edges, cheapest first, where the cost of
+// moving a vertex is its squared distance to the planes of the faces it
+// started out on (Garland and Heckbert's quadric error metric). An edge
+// collapses onto either end or its middle, whichever strays least. Edges
+// with a face on one side only also keep to a plane upright along them,
+// so the outlines of open meshes and seams hold their shape.
+
+// Weight of the planes along open edges against those of the faces.
+const BOUNDARY_WEIGHT: f64 = 1000.0;
+
+impl Mesh {
+    // Collapses edges until at most the number of triangles is left, or no
+    // edge can go without turning a face over. Normals and tangents are
+    // computed again for the new shape where the mesh had them.
+    pub fn decimate(&mut self, target_triangle_count: usize) {
+        let mut faces: Vec<[u32; 3]> = self.faces().map(|f| [f.a, f.b, f.c]).collect();
+        let mut vertices = self.vertices.clone();
+        let count = vertices.len();
+
+        let mut quadrics = vec![Quadric::zero(); count];
+        let mut around = vec![Vec::new(); count];
+        for (i, face) in faces.iter().enumerate() {
+            let normal = face_normal(&vertices, face);
+            if normal.length_sqr() == 0.0 {
+                continue;
+            }
+            let normal = normal.normalize();
+            let quadric = Quadric::plane(normal, vertices[face[0] as usize].position);
+            for &corner in face {
+                quadrics[corner as usize].add(&quadric);
+                around[corner as usize].push(i);
+            }
+        }
+        for face in &faces {
+            for k in 0..3 {
+                let (a, b) = (face[k], face[(k + 1) % 3]);
+                let shared = around[a as usize].iter()
+                    .filter(|&&f| faces[f].contains(&b))
+                    .count();
+                if shared == 1 {
+                    let (pa, pb) = (vertices[a as usize].position, vertices[b as usize].position);
+                    let upright = (pb - pa).cross(face_normal(&vertices, face));
+                    if upright.length_sqr() > 0.0 {
+                        let mut quadric = Quadric::plane(upright.normalize(), pa);
+                        quadric.scale(BOUNDARY_WEIGHT);
+                        quadrics[a as usize].add(&quadric);
+                        quadrics[b as usize].add(&quadric);
+                    }
+                }
+            }
+        }
+
+        let mut alive = vec![true; faces.len()];
+        let mut remaining = faces.len();
+        let mut version = vec![0u32; count];
+        let mut heap = BinaryHeap::new();
+        for v in 0..count as u32 {
+            for u in neighbours(&faces, &alive, &around[v as usize], v) {
+                if u > v {
+                    heap.push(collapse(&vertices, &quadrics, &version, u, v));
+                }
+            }
+        }
+
+        while remaining > target_triangle_count {
+            let c = match heap.pop() {
+                Some(c) => c,
+                None => break,
+            };
+            let (u, v) = (c.from as usize, c.to as usize);
+            if version[u] != c.versions.0 || version[v] != c.versions.1 {
+                continue;
+            }
+            let target = vertices[v].position.lerp(vertices[u].position, c.t);
+            // Faces that stay must not turn over
+            let flips = around[u].iter().chain(&around[v]).any(|&f| {
+                if !alive[f] || (faces[f].contains(&c.from) && faces[f].contains(&c.to)) {
+                    return false;
+                }
+                let mut moved = faces[f];
+                let before = face_normal(&vertices, &moved);
+                let mut positions = [Vector3::zero(); 3];
+                for k in 0..3 {
+                    if moved[k] == c.from {
+                        moved[k] = c.to;
+                    }
+                    positions[k] = if moved[k] == c.to {
+                        target
+                    } else {
+                        vertices[moved[k] as usize].position
+                    };
+                }
+                let after = (positions[1] - positions[0]).cross(positions[2] - positions[0]);
+                after.dot(before) <= 0.0
+            });
+            if flips {
+                continue;
+            }
+
+            let (from, to) = (vertices[u], vertices[v]);
+            vertices[v] = Vertex {
+                position: target,
+                uv: to.uv + (from.uv - to.uv) * c.t,
+                color: to.color.lerp(from.color, c.t),
+                ..to
+            };
+            let quadric = quadrics[u];
+            quadrics[v].add(&quadric);
+            version[u] += 1;
+            version[v] += 1;
+            let moved = around[u].clone();
+            for f in moved {
+                if !alive[f] {
+                    continue;
+                }
+                if faces[f].contains(&c.to) {
+                    alive[f] = false;
+                    remaining -= 1;
+                } else {
+                    for corner in faces[f].iter_mut().filter(|corner| **corner == c.from) {
+                        *corner = c.to;
+                    }
+                    around[v].push(f);
+                }
+            }
+            around[u].clear();
+            around[v].retain(|&f| alive[f]);
+            for w in neighbours(&faces, &alive, &around[v], c.to) {
+                heap.push(collapse(&vertices, &quadrics, &version, w, c.to));
+            }
+        }
+
+        // Only the vertices still used, in their order
+        let mut remap = vec![u32::MAX; count];
+        let mut kept = Vec::new();
+        let mut indices = Vec::with_capacity(remaining * 3);
+        for (face, _) in faces.iter().zip(&alive).filter(|&(_, &alive)| alive) {
+            for &corner in face {
+                if remap[corner as usize] == u32::MAX {
+                    remap[corner as usize] = kept.len() as u32;
+                    kept.push(vertices[corner as usize]);
+                }
+                indices.push(remap[corner as usize]);
+            }
+        }
+        let (had_normals, had_tangents) = (self.has_normals(), self.has_tangents());
+        self.vertices = kept;
+        self.indices = Indices::from_u32(indices);
+        if had_normals {
+            self.compute_normals();
+        }
+        if had_tangents {
+            self.compute_tangents();
+        }
+    }
+}
+
+fn face_normal(vertices: &[Vertex], face: &[u32; 3]) -> Vector3 {
+    let p = vertices[face[0] as usize].position;
+    (vertices[face[1] as usize].position - p).cross(vertices[face[2] as usize].position - p)
+}
+
+// The vertices sharing a live face with v.
+fn neighbours(faces: &[[u32; 3]], alive: &[bool], around: &[usize], v: u32) -> BTreeSet<u32> {
+    around.iter()
+        .filter(|&&f| alive[f])
+        .flat_map(|&f| faces[f].to_vec())
+        .filter(|&w| w != v)
+        .collect()
+}
+
+// Moving from onto to, to lerped towards from by t.
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    t: f64,
+    // Of from and to when it was costed, out of date once either changes
+    versions: (u32, u32),
+}
+
+fn collapse(vertices: &[Vertex],
+            quadrics: &[Quadric],
+            version: &[u32],
+            from: u32,
+            to: u32)
+            -> Collapse {
+    let (a, b) = (from as usize, to as usize);
+    let mut quadric = quadrics[a];
+    quadric.add(&quadrics[b]);
+    let (pa, pb) = (vertices[a].position, vertices[b].position);
+    let (cost, t) = [0.0, 0.5, 1.0]
+        .iter()
+        .map(|&t| (quadric.error(pb.lerp(pa, t)), t))
+        .fold((f64::INFINITY, 0.0), |best, c| if c.0 < best.0 { c } else { best });
+    Collapse {
+        cost: cost,
+        from: from,
+        to: to,
+        t: t,
+        versions: (version[a], version[b]),
+    }
+}
+
+// Ordered so BinaryHeap, a max heap, pops the cheapest first.
+impl Ord for Collapse {
+    fn cmp(&self, other: &Collapse) -> Ordering {
+        other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Collapse) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Collapse) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+// Symmetric 4x4 matrix summing the squared distance to planes, the upper
+// triangle stored by rows.
+#[derive(Debug,Clone,Copy)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn zero() -> Quadric {
+        Quadric([0.0; 10])
+    }
+
+    // Through the point, with the unit normal.
+    fn plane(normal: Vector3, point: Vector3) -> Quadric {
+        let (a, b, c, d) = (normal.x, normal.y, normal.z, -normal.dot(point));
+        Quadric([a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d])
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (q, o) in self.0.iter_mut().zip(other.0.iter()) {
+            *q += *o;
+        }
+    }
+
+    fn scale(&mut self, factor: f64) {
+        for q in self.0.iter_mut() {
+            *q *= factor;
+        }
+    }
+
+    fn error(&self, p: Vector3) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (p.x, p.y, p.z);
+        q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x +
+        q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y + q[7] * z * z +
+        2.0 * q[8] * z + q[9]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use geometry::mesh::Mesh;
+
+    #[test]
+    fn decimate_sphere() {
+        let mut sphere = Mesh::icosphere(2.0, 3);
+        assert_eq!(1280, sphere.face_count());
+        sphere.decimate(300);
+        assert!(sphere.face_count() <= 300 && sphere.face_count() > 250);
+        // Still round, and no face turned over
+        for v in &sphere.vertices {
+            let distance = v.position.length();
+            assert!(distance > 1.8 && distance < 2.0 + 1e-9, "{}", distance);
+        }
+        for face in sphere.faces() {
+            let (a, b, c) = (sphere.vertices[face.a as usize].position,
+                             sphere.vertices[face.b as usize].position,
+                             sphere.vertices[face.c as usize].position);
+            assert!((b - a).cross(c - a).dot(a + b + c) > 0.0);
+        }
+        assert!(sphere.has_normals() && sphere.has_tangents());
+    }
+
+    #[test]
+    fn decimate_flat() {
+        // Flat all over, so only the outline holds it back
+        let mut plane = Mesh::plane(2.0, 2.0, 8, 8);
+        plane.decimate(2);
+        assert_eq!(2, plane.face_count());
+        assert_eq!(4, plane.vertices.len());
+        assert_eq!((Vector3::new(-1.0, 0.0, -1.0), Vector3::new(1.0, 0.0, 1.0)), plane.bounds());
+        assert!(plane.vertices.iter().all(|v| v.normal == Vector3::unit_y()));
+    }
+}
@@ -0,0 +1,186 @@
+
+use super::super::math::vector::{Vector2, Vector3};
+use super::mesh::{Mesh, Vertex};
+
+// Polygons are outlines in the xy plane, without holes and not crossing
+// themselves, wound either way.
+
+impl Mesh {
+    // A prism with the polygon as its ends, depth long along z and centered
+    // on the origin. The ends are textured with the polygon's bounds, v down
+    // along y, and the sides with u around the outline and v from front to
+    // back. Each face shades flat.
+    pub fn extrude(polygon: &[Vector2], depth: f64) -> Mesh {
+        let mut outline = polygon.to_vec();
+        if signed_area(&outline) < 0.0 {
+            outline.reverse();
+        }
+        let mut mesh = Mesh::new("Extrusion");
+        if outline.len() < 3 {
+            return mesh;
+        }
+
+        let (min, max) = outline.iter()
+            .fold((outline[0], outline[0]), |(min, max), &p| (min.min(p), max.max(p)));
+        let size = max - min;
+        let cap_uv = |p: Vector2| {
+            Vector2::new(if size.x > 0.0 { (p.x - min.x) / size.x } else { 0.0 },
+                         if size.y > 0.0 { (max.y - p.y) / size.y } else { 0.0 })
+        };
+        let triangles = triangulate(&outline);
+        for &(z, facing) in &[(depth * 0.5, 1.0), (-depth * 0.5, -1.0)] {
+            let first = mesh.vertices.len() as u32;
+            for &p in &outline {
+                mesh.add_vertex(Vertex::new(Vector3::new(p.x, p.y, z))
+                    .with_normal(Vector3::new(0.0, 0.0, facing))
+                    .with_uv(cap_uv(p)));
+            }
+            for t in &triangles {
+                let (a, b, c) = (first + t[0] as u32, first + t[1] as u32, first + t[2] as u32);
+                if facing > 0.0 {
+                    mesh.add_face(a, b, c);
+                } else {
+                    mesh.add_face(a, c, b);
+                }
+            }
+        }
+
+        let perimeter: f64 = (0..outline.len())
+            .map(|i| (outline[(i + 1) % outline.len()] - outline[i]).length())
+            .sum();
+        let mut along = 0.0;
+        for i in 0..outline.len() {
+            let (p, q) = (outline[i], outline[(i + 1) % outline.len()]);
+            let edge = q - p;
+            let length = edge.length();
+            if length == 0.0 {
+                continue;
+            }
+            // Counterclockwise, outwards is to the right
+            let normal = Vector3::new(edge.y / length, -edge.x / length, 0.0);
+            let (u0, u1) = (along / perimeter, (along + length) / perimeter);
+            along += length;
+            let first = mesh.vertices.len() as u32;
+            for &(point, u) in &[(p, u0), (q, u1)] {
+                for &(z, v) in &[(depth * 0.5, 0.0), (-depth * 0.5, 1.0)] {
+                    mesh.add_vertex(Vertex::new(Vector3::new(point.x, point.y, z))
+                        .with_normal(normal)
+                        .with_uv(Vector2::new(u, v)));
+                }
+            }
+            // Front and back of p, then of q
+            mesh.add_face(first, first + 1, first + 2);
+            mesh.add_face(first + 2, first + 1, first + 3);
+        }
+        mesh.compute_tangents();
+        mesh
+    }
+}
+
+// Twice the area of the polygon, positive when it winds counterclockwise.
+fn signed_area(polygon: &[Vector2]) -> f64 {
+    (0..polygon.len())
+        .map(|i| polygon[i].cross(polygon[(i + 1) % polygon.len()]))
+        .sum()
+}
+
+// Splits the polygon into triangles of indices into it, wound the same way
+// as the polygon, by cutting off ears: corners turning the way the polygon
+// winds with no other corner inside.
+pub fn triangulate(polygon: &[Vector2]) -> Vec<[usize; 3]> {
+    let mut triangles = Vec::new();
+    if polygon.len() < 3 {
+        return triangles;
+    }
+    let winding = if signed_area(polygon) < 0.0 { -1.0 } else { 1.0 };
+    let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let is_ear = |i: usize| {
+            let (a, b, c) = (remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]);
+            let (pa, pb, pc) = (polygon[a], polygon[b], polygon[c]);
+            if (pb - pa).cross(pc - pb) * winding <= 0.0 {
+                return false;
+            }
+            remaining.iter()
+                .filter(|&&k| k != a && k != b && k != c)
+                .all(|&k| !inside_triangle(polygon[k], pa, pb, pc))
+        };
+        // Where none is left the polygon is degenerate, and any corner goes
+        let ear = (0..n).find(|&i| is_ear(i)).unwrap_or(0);
+        triangles.push([remaining[(ear + n - 1) % n], remaining[ear], remaining[(ear + 1) % n]]);
+        remaining.remove(ear);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+// On the edges counts as inside, so ears never touch another corner.
+fn inside_triangle(p: Vector2, a: Vector2, b: Vector2, c: Vector2) -> bool {
+    let (d1, d2, d3) = ((b - a).cross(p - a), (c - b).cross(p - b), (a - c).cross(p - c));
+    let negative = d1 < 0.0 || d2 < 0.0 || d3 < 0.0;
+    let positive = d1 > 0.0 || d2 > 0.0 || d3 > 0.0;
+    !(negative && positive)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::{Vector2, Vector3};
+    use geometry::mesh::Mesh;
+    use super::{signed_area, triangulate};
+
+    // An L, with a corner pointing in
+    fn ell() -> Vec<Vector2> {
+        vec![Vector2::new(0.0, 0.0),
+             Vector2::new(2.0, 0.0),
+             Vector2::new(2.0, 1.0),
+             Vector2::new(1.0, 1.0),
+             Vector2::new(1.0, 2.0),
+             Vector2::new(0.0, 2.0)]
+    }
+
+    #[test]
+    fn triangulate_concave() {
+        for &clockwise in &[false, true] {
+            let mut polygon = ell();
+            if clockwise {
+                polygon.reverse();
+            }
+            let triangles = triangulate(&polygon);
+            assert_eq!(4, triangles.len());
+            // Covering the polygon once, each wound like it
+            let mut area = 0.0;
+            for t in &triangles {
+                let triangle = [polygon[t[0]], polygon[t[1]], polygon[t[2]]];
+                let a = signed_area(&triangle);
+                assert!(a * signed_area(&polygon) > 0.0);
+                area += a;
+            }
+            assert!((area - signed_area(&polygon)).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn extrude() {
+        let mut polygon = ell();
+        polygon.reverse();
+        let prism = Mesh::extrude(&polygon, 2.0);
+        // The ends, then two triangles along each edge
+        assert_eq!(4 * 2 + 6 * 2, prism.face_count());
+        assert_eq!((Vector3::new(0.0, 0.0, -1.0), Vector3::new(2.0, 2.0, 1.0)), prism.bounds());
+        for face in prism.faces() {
+            let (a, b, c) = (prism.vertices[face.a as usize],
+                             prism.vertices[face.b as usize],
+                             prism.vertices[face.c as usize]);
+            let normal = (b.position - a.position).cross(c.position - a.position).normalize();
+            assert!((normal - a.normal).length() < 1e-9, "{:?}", face);
+        }
+        // The sides at the inner corner face out of the L
+        let side = |x: f64, y: f64, normal: Vector3| {
+            let at = Vector2::new(x, y);
+            prism.vertices.iter().any(|v| v.position.xy() == at && v.normal == normal)
+        };
+        assert!(side(1.0, 2.0, Vector3::unit_x()) && side(2.0, 1.0, Vector3::unit_y()));
+    }
+}
@@ -0,0 +1,662 @@
+
+use super::super::error::{Result, SwrError};
+use super::super::math::matrix::Matrix4;
+use super::super::math::quaternion::Quaternion;
+use super::super::math::transform::Transform;
+use super::super::math::vector::{Vector2, Vector3, Vector4};
+use super::super::render::color::Color;
+use super::super::render::material::Material;
+use super::super::render::texture::Texture;
+use super::super::scene::graph::{Node, NodeId, Scene};
+use super::mesh::{Indices, Mesh, Vertex};
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde_json;
+use serde_json::Value;
+
+// Loader for glTF 2.0 files, both .gltf with its buffers embedded or next to
+// it and binary .glb. The nodes of the default scene keep their names,
+// transforms and hierarchy. A mesh with a single triangle primitive goes on
+// its node, one with several gets a child node for each.
+//
+// Materials map onto the metallic-roughness model. Base color textures are
+// linearized, as glTF authors them in sRGB, while the other maps are used as
+// they are. Only PNG images are decoded, textures in other formats are left
+// out. Animations, skins and morph targets are not read yet.
+
+const GLB_MAGIC: &[u8] = b"glTF";
+const CHUNK_JSON: usize = 0x4e4f_534a;
+const CHUNK_BIN: usize = 0x004e_4942;
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene> {
+    let path = path.as_ref();
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    read(&bytes, path.parent().unwrap_or_else(|| Path::new("")))
+}
+
+// The contents of a .gltf or .glb file, with the files it refers to looked
+// up in the directory.
+pub fn read(bytes: &[u8], directory: &Path) -> Result<Scene> {
+    let (json, bin) = if bytes.starts_with(GLB_MAGIC) {
+        split_glb(bytes)?
+    } else {
+        (bytes, None)
+    };
+    let document: Value = serde_json::from_slice(json).map_err(invalid)?;
+    Gltf::new(&document, bin, directory)?.scene()
+}
+
+fn invalid<E: ToString>(e: E) -> SwrError {
+    SwrError::format(e.to_string())
+}
+
+// The JSON chunk of a .glb file and its binary chunk, if any.
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>)> {
+    let word = |offset: usize| {
+        bytes.get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    };
+    let (version, length) = match (word(4), word(8)) {
+        (Some(version), Some(length)) => (version, length.min(bytes.len())),
+        _ => return Err(invalid("truncated .glb header")),
+    };
+    if version != 2 {
+        return Err(invalid(format!("unsupported .glb version {}", version)));
+    }
+
+    let (mut json, mut bin) = (None, None);
+    let mut offset = 12;
+    while let (Some(chunk_length), Some(kind)) = (word(offset), word(offset + 4)) {
+        let start = offset + 8;
+        if start + chunk_length > length {
+            return Err(invalid("truncated .glb chunk"));
+        }
+        let data = &bytes[start..start + chunk_length];
+        match kind {
+            CHUNK_JSON if json.is_none() => json = Some(data),
+            CHUNK_BIN if bin.is_none() => bin = Some(data),
+            _ => {}
+        }
+        offset = start + chunk_length;
+    }
+    match json {
+        Some(json) => Ok((json, bin)),
+        None => Err(invalid("no JSON chunk in .glb")),
+    }
+}
+
+// Bytes of a data URI or of the file the URI names in the directory.
+fn read_uri(uri: &str, directory: &Path) -> Result<Vec<u8>> {
+    if uri.starts_with("data:") {
+        return match uri.find(',') {
+            Some(comma) if uri[..comma].ends_with(";base64") => decode_base64(&uri[comma + 1..]),
+            _ => Err(invalid("data URI is not base64")),
+        };
+    }
+    let mut bytes = Vec::new();
+    File::open(directory.join(percent_decode(uri)))?.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+// Escapes like %20 are replaced by the bytes they stand for.
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes.get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(byte) if bytes[i] == b'%' => {
+                out.push(byte);
+                i += 3;
+            }
+            _ => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// Standard or URL safe base64, with or without padding.
+fn decode_base64(text: &str) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut bits, mut count) = (0u32, 0);
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            b' ' | b'\t' | b'\r' | b'\n' => continue,
+            _ => return Err(invalid("invalid base64")),
+        };
+        bits = bits << 6 | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+    Ok(out)
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> &'a [Value] {
+    value.get(key).and_then(Value::as_array).map_or(&[], |a| &a[..])
+}
+
+fn index(value: &Value, key: &str) -> Option<usize> {
+    value.get(key).and_then(Value::as_u64).map(|i| i as usize)
+}
+
+fn number(value: &Value, key: &str, default: f64) -> f64 {
+    value.get(key).and_then(Value::as_f64).unwrap_or(default)
+}
+
+// The array of the key if it holds that many numbers.
+fn numbers(value: &Value, key: &str, count: usize) -> Option<Vec<f64>> {
+    let values: Vec<f64> = array(value, key).iter().filter_map(Value::as_f64).collect();
+    if values.len() == count { Some(values) } else { None }
+}
+
+// A node's local transform, from its matrix or its translation, rotation
+// and scale.
+fn node_transform(node: &Value) -> Transform {
+    if let Some(m) = numbers(node, "matrix", 16) {
+        // Column major for column vectors is row major for the row vectors
+        // used here, the translation in the last row
+        let row = |i: usize| Vector3::new(m[i * 4], m[i * 4 + 1], m[i * 4 + 2]);
+        let scale = Vector3::new(row(0).length(), row(1).length(), row(2).length());
+        let axis = |i: usize, s: f64| if s > 0.0 { row(i) * (1.0 / s) } else { row(i) };
+        let (x, y, z) = (axis(0, scale.x), axis(1, scale.y), axis(2, scale.z));
+        let rotation = Matrix4::new(x.x, x.y, x.z, 0.0,
+                                    y.x, y.y, y.z, 0.0,
+                                    z.x, z.y, z.z, 0.0,
+                                    0.0, 0.0, 0.0, 1.0);
+        let position = Vector3::new(m[12], m[13], m[14]);
+        return Transform::new(position, Quaternion::from_matrix(&rotation), scale);
+    }
+    let vector = |key, default| {
+        numbers(node, key, 3).map_or(default, |v| Vector3::new(v[0], v[1], v[2]))
+    };
+    // glTF quaternions rotate as q v q*, those here by their conjugate
+    let rotation = numbers(node, "rotation", 4)
+        .map_or(Quaternion::identity(), |q| Quaternion::new(-q[0], -q[1], -q[2], q[3]));
+    Transform::new(vector("translation", Vector3::zero()), rotation, vector("scale", Vector3::one()))
+}
+
+// A parsed document with its buffers and images loaded.
+struct Gltf<'a> {
+    document: &'a Value,
+    buffers: Vec<Vec<u8>>,
+    images: Vec<Option<Texture>>,
+}
+
+impl<'a> Gltf<'a> {
+    fn new(document: &'a Value, bin: Option<&[u8]>, directory: &Path) -> Result<Gltf<'a>> {
+        let mut gltf = Gltf {
+            document: document,
+            buffers: Vec::new(),
+            images: Vec::new(),
+        };
+        for buffer in array(document, "buffers") {
+            let bytes = match buffer.get("uri").and_then(Value::as_str) {
+                Some(uri) => read_uri(uri, directory)?,
+                None => bin.ok_or_else(|| invalid("buffer without data"))?.to_vec(),
+            };
+            gltf.buffers.push(bytes);
+        }
+        for image in array(document, "images") {
+            let uri = image.get("uri").and_then(Value::as_str);
+            let bytes = match (uri, index(image, "bufferView")) {
+                (Some(uri), _) => Some(read_uri(uri, directory)?),
+                (None, Some(view)) => Some(gltf.buffer_view(view)?.to_vec()),
+                _ => None,
+            };
+            gltf.images.push(bytes.and_then(|b| Texture::read_png(&b)));
+        }
+        Ok(gltf)
+    }
+
+    fn element(&self, kind: &str, i: usize) -> Result<&'a Value> {
+        array(self.document, kind)
+            .get(i)
+            .ok_or_else(|| invalid(format!("no element {} in {}", i, kind)))
+    }
+
+    fn buffer_view(&self, i: usize) -> Result<&[u8]> {
+        let view = self.element("bufferViews", i)?;
+        let buffer = index(view, "buffer")
+            .and_then(|b| self.buffers.get(b))
+            .ok_or_else(|| invalid(format!("buffer view {} without a buffer", i)))?;
+        let start = index(view, "byteOffset").unwrap_or(0);
+        let end = start + index(view, "byteLength").unwrap_or(0);
+        buffer.get(start..end).ok_or_else(|| invalid(format!("buffer view {} out of its buffer", i)))
+    }
+
+    // The accessor's elements, each as many numbers as it has components,
+    // with normalized integers mapped to [0, 1] or [-1, 1].
+    fn accessor(&self, i: usize, components: usize) -> Result<Vec<f64>> {
+        let accessor = self.element("accessors", i)?;
+        let expected = match components {
+            1 => "SCALAR",
+            2 => "VEC2",
+            3 => "VEC3",
+            _ => "VEC4",
+        };
+        if accessor.get("type").and_then(Value::as_str) != Some(expected) {
+            return Err(invalid(format!("accessor {} is not {}", i, expected)));
+        }
+        if accessor.get("sparse").is_some() {
+            return Err(invalid("sparse accessors are not supported"));
+        }
+        let count = index(accessor, "count").unwrap_or(0);
+        let kind = index(accessor, "componentType").unwrap_or(0);
+        let size = match kind {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _ => return Err(invalid(format!("unknown component type {}", kind))),
+        };
+        let normalized = accessor.get("normalized").and_then(Value::as_bool).unwrap_or(false);
+
+        let view_index = match index(accessor, "bufferView") {
+            Some(view) => view,
+            // Zeros, when there is no data
+            None => return Ok(vec![0.0; count * components]),
+        };
+        let view = self.buffer_view(view_index)?;
+        let stride = index(self.element("bufferViews", view_index)?, "byteStride")
+            .unwrap_or(size * components);
+        let start = index(accessor, "byteOffset").unwrap_or(0);
+        let mut values = Vec::with_capacity(count * components);
+        for element in 0..count {
+            for c in 0..components {
+                let offset = start + element * stride + c * size;
+                let b = view.get(offset..offset + size)
+                    .ok_or_else(|| invalid(format!("accessor {} out of its buffer view", i)))?;
+                values.push(component(b, kind, normalized));
+            }
+        }
+        Ok(values)
+    }
+
+    // The default scene, or the first one. Without any scenes, the nodes
+    // that are no other node's child.
+    fn scene(&self) -> Result<Scene> {
+        let scenes = array(self.document, "scenes");
+        let roots: Vec<usize> = match scenes.get(index(self.document, "scene").unwrap_or(0)) {
+            Some(scene) => {
+                array(scene, "nodes").iter().filter_map(Value::as_u64).map(|i| i as usize).collect()
+            }
+            None => {
+                let nodes = array(self.document, "nodes");
+                let children: Vec<u64> = nodes.iter()
+                    .flat_map(|n| array(n, "children").iter().filter_map(Value::as_u64))
+                    .collect();
+                (0..nodes.len()).filter(|&i| !children.contains(&(i as u64))).collect()
+            }
+        };
+        let mut scene = Scene::new();
+        for root in roots {
+            self.add_node(&mut scene, root, None, 0)?;
+        }
+        Ok(scene)
+    }
+
+    fn add_node(&self,
+                scene: &mut Scene,
+                i: usize,
+                parent: Option<NodeId>,
+                depth: usize)
+                -> Result<()> {
+        if depth > array(self.document, "nodes").len() {
+            return Err(invalid("nodes form a cycle"));
+        }
+        let node = self.element("nodes", i)?;
+        let name = node.get("name")
+            .and_then(Value::as_str)
+            .map_or(format!("node{}", i), String::from);
+        let mut meshes = match index(node, "mesh") {
+            Some(mesh) => self.meshes(mesh)?,
+            None => Vec::new(),
+        };
+
+        let mut new = Node::new(&name);
+        new.transform = node_transform(node);
+        if meshes.len() == 1 {
+            new.mesh = meshes.pop();
+        }
+        let id = scene.add(new, parent);
+        for mesh in meshes {
+            scene.add(Node::with_mesh(mesh), Some(id));
+        }
+        for child in array(node, "children").iter().filter_map(Value::as_u64) {
+            self.add_node(scene, child as usize, Some(id), depth + 1)?;
+        }
+        Ok(())
+    }
+
+    // A Mesh for each triangle primitive of the mesh. Other primitives are
+    // points and lines, which have no surface to fill.
+    fn meshes(&self, i: usize) -> Result<Vec<Mesh>> {
+        let mesh = self.element("meshes", i)?;
+        let name = mesh.get("name")
+            .and_then(Value::as_str)
+            .map_or(format!("mesh{}", i), String::from);
+        let primitives = array(mesh, "primitives");
+        let mut meshes = Vec::new();
+        for (p, primitive) in primitives.iter().enumerate() {
+            if index(primitive, "mode").unwrap_or(4) != 4 {
+                continue;
+            }
+            let name = if primitives.len() > 1 { format!("{}.{}", name, p) } else { name.clone() };
+            meshes.push(self.primitive(name, primitive)?);
+        }
+        Ok(meshes)
+    }
+
+    fn primitive(&self, name: String, primitive: &Value) -> Result<Mesh> {
+        let attributes = primitive.get("attributes").unwrap_or(&Value::Null);
+        let position = index(attributes, "POSITION")
+            .ok_or_else(|| invalid(format!("{} has no positions", name)))?;
+        let positions = self.accessor(position, 3)?;
+        let count = positions.len() / 3;
+        // Attributes not given for every vertex are left out
+        let attribute = |key, components| -> Result<Vec<f64>> {
+            match index(attributes, key) {
+                Some(i) => {
+                    let values = self.accessor(i, components)?;
+                    Ok(if values.len() == count * components { values } else { Vec::new() })
+                }
+                None => Ok(Vec::new()),
+            }
+        };
+        let (normals, uvs) = (attribute("NORMAL", 3)?, attribute("TEXCOORD_0", 2)?);
+        let tangents = attribute("TANGENT", 4)?;
+        let vertices: Vec<Vertex> = (0..count)
+            .map(|i| {
+                let mut vertex = Vertex::new(Vector3::new(positions[i * 3],
+                                                          positions[i * 3 + 1],
+                                                          positions[i * 3 + 2]));
+                if let Some(n) = normals.get(i * 3..i * 3 + 3) {
+                    vertex.normal = Vector3::new(n[0], n[1], n[2]);
+                }
+                if let Some(uv) = uvs.get(i * 2..i * 2 + 2) {
+                    vertex.uv = Vector2::new(uv[0], uv[1]);
+                }
+                if let Some(t) = tangents.get(i * 4..i * 4 + 4) {
+                    vertex.tangent = Vector4::new(t[0], t[1], t[2], t[3]);
+                }
+                vertex
+            })
+            .collect();
+
+        let mut indices: Vec<u32> = match index(primitive, "indices") {
+            Some(i) => self.accessor(i, 1)?.iter().map(|&v| v as u32).collect(),
+            None => (0..count as u32).collect(),
+        };
+        if indices.iter().any(|&v| v as usize >= count) {
+            return Err(invalid(format!("{} has indices past its vertices", name)));
+        }
+        // Whole triangles only
+        indices.truncate(indices.len() / 3 * 3);
+        let material = match index(primitive, "material") {
+            Some(material) => self.material(material)?,
+            None => Material::new(),
+        };
+
+        let mut mesh = Mesh {
+            name: name,
+            vertices: vertices,
+            indices: Indices::from_u32(indices),
+            transform: Transform::identity(),
+            material: material,
+            morph_targets: Vec::new(),
+            morph_weights: Vec::new(),
+            modifiers: Vec::new(),
+        };
+        if normals.is_empty() {
+            mesh.compute_normals();
+        }
+        if tangents.is_empty() {
+            mesh.compute_tangents();
+        }
+        Ok(mesh)
+    }
+
+    fn material(&self, i: usize) -> Result<Material> {
+        let material = self.element("materials", i)?;
+        let pbr = material.get("pbrMetallicRoughness").unwrap_or(&Value::Null);
+        let color = numbers(pbr, "baseColorFactor", 4)
+            .map_or(Color::white(), |c| Color::new(c[0], c[1], c[2], c[3]));
+        let texture = |info: Option<&Value>| {
+            info.and_then(|info| index(info, "index"))
+                .and_then(|t| array(self.document, "textures").get(t))
+                .and_then(|t| index(t, "source"))
+                .and_then(|image| self.images.get(image))
+                .and_then(Option::clone)
+        };
+        let base = texture(pbr.get("baseColorTexture")).map(|t| {
+            let texels = t.texels().iter().map(|c| c.to_linear()).collect();
+            Texture::new(t.width(), t.height(), texels)
+        });
+        Ok(Material {
+            texture: base,
+            normal_map: texture(material.get("normalTexture")),
+            metallic_roughness_map: texture(pbr.get("metallicRoughnessTexture")),
+            occlusion_map: texture(material.get("occlusionTexture")),
+            translucent: material.get("alphaMode").and_then(Value::as_str) == Some("BLEND"),
+            ..Material::pbr(color,
+                            number(pbr, "metallicFactor", 1.0),
+                            number(pbr, "roughnessFactor", 1.0))
+        })
+    }
+}
+
+// One little endian component of the type.
+fn component(b: &[u8], kind: usize, normalized: bool) -> f64 {
+    match kind {
+        5120 => {
+            let v = b[0] as i8 as f64;
+            if normalized { (v / 127.0).max(-1.0) } else { v }
+        }
+        5121 => {
+            let v = b[0] as f64;
+            if normalized { v / 255.0 } else { v }
+        }
+        5122 => {
+            let v = i16::from_le_bytes([b[0], b[1]]) as f64;
+            if normalized { (v / 32767.0).max(-1.0) } else { v }
+        }
+        5123 => {
+            let v = u16::from_le_bytes([b[0], b[1]]) as f64;
+            if normalized { v / 65535.0 } else { v }
+        }
+        5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+        _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::path::Path;
+
+    use math::transform::Transform;
+    use math::vector::Vector3;
+    use math::quaternion::Quaternion;
+    use render::color::Color;
+    use render::image::write_png;
+    use serde_json::Value;
+    use super::{decode_base64, node_transform, read};
+
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    fn encode_base64(bytes: &[u8]) -> String {
+        let mut out = String::new();
+        for chunk in bytes.chunks(3) {
+            let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+            let bits = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    out.push(ALPHABET[bits >> (18 - i * 6) & 63] as char);
+                } else {
+                    out.push('=');
+                }
+            }
+        }
+        out
+    }
+
+    // A triangle's positions as floats, then its indices as shorts.
+    fn triangle() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for &v in &[0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        for &i in &[0u16, 1, 2] {
+            bytes.extend_from_slice(&i.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn document(buffer: &str, extra: &str) -> String {
+        format!(r#"{{
+            "asset": {{ "version": "2.0" }},
+            "scene": 0,
+            "scenes": [{{ "nodes": [0] }}],
+            "nodes": [
+                {{ "name": "root", "translation": [1, 2, 3], "children": [1] }},
+                {{ "mesh": 0 }}
+            ],
+            "meshes": [{{ "name": "tri", "primitives": [{{
+                "attributes": {{ "POSITION": 0 }}, "indices": 1, "material": 0
+            }}] }}],
+            "accessors": [
+                {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }},
+                {{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+            ],
+            "bufferViews": [
+                {{ "buffer": 0, "byteLength": 36 }},
+                {{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }}
+            ],
+            "buffers": [{}],
+            {}
+        }}"#,
+                buffer,
+                extra)
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(b"Man".to_vec(), decode_base64("TWFu").unwrap());
+        assert_eq!(b"Ma".to_vec(), decode_base64("TWE=").unwrap());
+        assert_eq!(b"M".to_vec(), decode_base64("TQ").unwrap());
+        assert!(decode_base64("T*").is_err());
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(bytes, decode_base64(&encode_base64(&bytes)).unwrap());
+    }
+
+    #[test]
+    fn embedded() {
+        let uri = format!("data:application/octet-stream;base64,{}", encode_base64(&triangle()));
+        let buffer = format!(r#"{{ "byteLength": 42, "uri": "{}" }}"#, uri);
+        let materials = r#""materials": [{
+            "pbrMetallicRoughness": { "baseColorFactor": [1, 0.5, 0, 1], "roughnessFactor": 0.25 }
+        }]"#;
+        let scene = read(document(&buffer, materials).as_bytes(), Path::new("")).unwrap();
+
+        let root = scene.roots()[0];
+        assert_eq!("root", scene.node(root).name);
+        assert_eq!(Vector3::new(1.0, 2.0, 3.0), scene.node(root).transform.position);
+        let child = scene.node(scene.node(root).children()[0]);
+        let mesh = child.mesh.as_ref().unwrap();
+        assert_eq!("tri", mesh.name);
+        assert_eq!(Vector3::unit_x(), mesh.vertices[1].position);
+        assert_eq!((0, 1, 2), (mesh.face(0).a, mesh.face(0).b, mesh.face(0).c));
+        // Missing normals are computed
+        assert!(mesh.vertices.iter().all(|v| v.normal == Vector3::unit_z()));
+        assert_eq!(Color::new(1.0, 0.5, 0.0, 1.0), mesh.material.color);
+        assert_eq!((1.0, 0.25), (mesh.material.metallic, mesh.material.roughness));
+    }
+
+    #[test]
+    fn binary() {
+        // A 2x1 texture of a red and an sRGB mid gray texel
+        let mut png = Vec::new();
+        write_png(&mut png, 2, 1, &[255, 0, 0, 255, 188, 188, 188, 255]).unwrap();
+        let mut bin = triangle();
+        bin.extend_from_slice(&[0, 0]);
+        let png_offset = bin.len();
+        bin.extend_from_slice(&png);
+        let padded = bin.len().div_ceil(4) * 4;
+        bin.resize(padded, 0);
+
+        let buffer = format!(r#"{{ "byteLength": {} }}"#, bin.len());
+        let extra = r#""images": [{ "bufferView": 2, "mimeType": "image/png" }],
+            "textures": [{ "source": 0 }],
+            "materials": [{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }]"#;
+        let mut json: Value = ::serde_json::from_str(&document(&buffer, extra)).unwrap();
+        json["bufferViews"].as_array_mut().unwrap().push(::serde_json::json!({
+            "buffer": 0, "byteOffset": png_offset, "byteLength": png.len()
+        }));
+        let mut json = ::serde_json::to_vec(&json).unwrap();
+        let padded = json.len().div_ceil(4) * 4;
+        json.resize(padded, b' ');
+
+        let mut glb = b"glTF".to_vec();
+        let total = 12 + 8 + json.len() + 8 + bin.len();
+        for &word in &[2, total, json.len(), 0x4e4f_534a] {
+            glb.extend_from_slice(&(word as u32).to_le_bytes());
+        }
+        glb.extend_from_slice(&json);
+        for &word in &[bin.len(), 0x004e_4942] {
+            glb.extend_from_slice(&(word as u32).to_le_bytes());
+        }
+        glb.extend_from_slice(&bin);
+
+        let scene = read(&glb, Path::new("")).unwrap();
+        let (mesh, _) = scene.meshes()[0];
+        assert_eq!(Vector3::unit_y(), mesh.vertices[2].position);
+        let texture = mesh.material.texture.as_ref().unwrap();
+        assert_eq!((2, 1), (texture.width(), texture.height()));
+        assert_eq!(Color::rgb(1.0, 0.0, 0.0), texture.texels()[0]);
+        // Linearized
+        assert!((texture.texels()[1].r - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn transforms() {
+        // A quarter turn around y takes +x to -z, glTF being right handed
+        let node: Value = ::serde_json::from_str(r#"{
+            "rotation": [0, 0.7071067811865476, 0, 0.7071067811865476], "scale": [2, 2, 2]
+        }"#)
+            .unwrap();
+        let trs = node_transform(&node);
+        let p = trs.transform_point(Vector3::unit_x());
+        assert!((p - Vector3::new(0.0, 0.0, -2.0)).length() < 1e-9, "{:?}", p);
+
+        // The same transform as a matrix decomposes back into it
+        let m = Transform::new(Vector3::new(1.0, 2.0, 3.0), trs.rotation, trs.scale).matrix();
+        let matrix = [m.m11, m.m12, m.m13, m.m14, m.m21, m.m22, m.m23, m.m24, m.m31, m.m32, m.m33,
+                      m.m34, m.m41, m.m42, m.m43, m.m44];
+        let node: Value = ::serde_json::json!({ "matrix": matrix.to_vec() });
+        let decomposed = node_transform(&node);
+        assert_eq!(Vector3::new(1.0, 2.0, 3.0), decomposed.position);
+        assert!((decomposed.scale - trs.scale).length() < 1e-9);
+        let turn = decomposed.rotation.dot(trs.rotation).abs();
+        assert!((turn - 1.0).abs() < 1e-9);
+        assert!(Quaternion::identity().dot(decomposed.rotation).abs() < 0.8);
+    }
+}
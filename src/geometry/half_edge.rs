@@ -0,0 +1,303 @@
+
+use super::mesh::{Indices, Mesh, Vertex};
+
+use std::collections::HashMap;
+
+// Connectivity of a triangle mesh as pairs of half-edges, one along each
+// side of every edge, so the faces and vertices around anything can be
+// walked without searching. Each face is three half-edges in a cycle,
+// wound like the face. A side with no face belongs to a boundary, and the
+// half-edges along each boundary link up in a cycle of their own, going
+// around the hole the other way.
+
+// Marks a half-edge along a boundary.
+const NO_FACE: u32 = u32::MAX;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct HalfEdge {
+    // The vertex it points to.
+    pub vertex: u32,
+    pub face: u32,
+    pub next: u32,
+    pub prev: u32,
+    // The one along the same edge the other way.
+    pub twin: u32,
+}
+
+impl HalfEdge {
+    pub fn is_boundary(&self) -> bool {
+        self.face == NO_FACE
+    }
+}
+
+#[derive(Debug,Clone)]
+pub struct HalfEdgeMesh {
+    pub name: String,
+    pub vertices: Vec<Vertex>,
+    pub half_edges: Vec<HalfEdge>,
+    // A half-edge leaving each vertex, one along a boundary where there is
+    // one, or None for a vertex on no face.
+    outgoing: Vec<Option<u32>>,
+    // The first half-edge of each face, from its first corner.
+    faces: Vec<u32>,
+}
+
+impl HalfEdgeMesh {
+    // None where the mesh isn't manifold: an edge with more than two faces,
+    // or two faces on the same side of one. Faces with a repeated corner
+    // are left out. A vertex where separate fans of faces touch at a point
+    // is only walked around one of them.
+    pub fn from_mesh(mesh: &Mesh) -> Option<HalfEdgeMesh> {
+        let mut half_edges = Vec::with_capacity(mesh.indices.len() * 2);
+        let mut faces = Vec::with_capacity(mesh.face_count());
+        let mut by_ends = HashMap::with_capacity(mesh.indices.len());
+        for face in mesh.faces() {
+            let corners = [face.a, face.b, face.c];
+            if face.a == face.b || face.b == face.c || face.c == face.a {
+                continue;
+            }
+            let first = half_edges.len() as u32;
+            let f = faces.len() as u32;
+            for k in 0..3 {
+                let ends = (corners[k], corners[(k + 1) % 3]);
+                if by_ends.insert(ends, first + k as u32).is_some() {
+                    return None;
+                }
+                half_edges.push(HalfEdge {
+                    vertex: ends.1,
+                    face: f,
+                    next: first + (k as u32 + 1) % 3,
+                    prev: first + (k as u32 + 2) % 3,
+                    twin: 0,
+                });
+            }
+            faces.push(first);
+        }
+
+        let mut outgoing = vec![None; mesh.vertices.len()];
+        let mut boundary_from = HashMap::new();
+        for h in 0..half_edges.len() as u32 {
+            let to = half_edges[h as usize].vertex;
+            let from = half_edges[half_edges[h as usize].prev as usize].vertex;
+            outgoing[from as usize] = Some(h);
+            half_edges[h as usize].twin = match by_ends.get(&(to, from)) {
+                Some(&twin) => twin,
+                None => {
+                    let b = half_edges.len() as u32;
+                    half_edges.push(HalfEdge {
+                        vertex: from,
+                        face: NO_FACE,
+                        next: b,
+                        prev: b,
+                        twin: h,
+                    });
+                    boundary_from.insert(to, b);
+                    b
+                }
+            };
+        }
+        for (&from, &b) in &boundary_from {
+            outgoing[from as usize] = Some(b);
+        }
+        for &b in boundary_from.values() {
+            if let Some(&next) = boundary_from.get(&half_edges[b as usize].vertex) {
+                half_edges[b as usize].next = next;
+                half_edges[next as usize].prev = b;
+            }
+        }
+
+        Some(HalfEdgeMesh {
+            name: mesh.name.clone(),
+            vertices: mesh.vertices.clone(),
+            half_edges: half_edges,
+            outgoing: outgoing,
+            faces: faces,
+        })
+    }
+
+    // The faces in order, as indices into the same vertices.
+    pub fn to_mesh(&self) -> Mesh {
+        let mut mesh = Mesh::new(&self.name);
+        mesh.vertices = self.vertices.clone();
+        let indices = self.faces.iter().flat_map(|&h| self.corners(h).to_vec()).collect();
+        mesh.indices = Indices::from_u32(indices);
+        mesh
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.faces.len()
+    }
+
+    pub fn face(&self, f: u32) -> [u32; 3] {
+        self.corners(self.faces[f as usize])
+    }
+
+    fn corners(&self, h: u32) -> [u32; 3] {
+        let edge = self.half_edges[h as usize];
+        [self.origin(h), edge.vertex, self.half_edges[edge.next as usize].vertex]
+    }
+
+    // The vertex the half-edge leaves.
+    pub fn origin(&self, h: u32) -> u32 {
+        self.half_edges[self.half_edges[h as usize].twin as usize].vertex
+    }
+
+    // The half-edges leaving the vertex, turning against the winding of the
+    // faces around it.
+    pub fn outgoing(&self, v: u32) -> Vec<u32> {
+        let mut around = Vec::new();
+        let start = match self.outgoing[v as usize] {
+            Some(h) => h,
+            None => return around,
+        };
+        let mut h = start;
+        loop {
+            around.push(h);
+            h = self.half_edges[self.half_edges[h as usize].twin as usize].next;
+            if h == start || around.len() >= self.half_edges.len() {
+                return around;
+            }
+        }
+    }
+
+    pub fn neighbours(&self, v: u32) -> Vec<u32> {
+        self.outgoing(v).iter().map(|&h| self.half_edges[h as usize].vertex).collect()
+    }
+
+    pub fn faces_around(&self, v: u32) -> Vec<u32> {
+        self.outgoing(v)
+            .iter()
+            .map(|&h| self.half_edges[h as usize].face)
+            .filter(|&f| f != NO_FACE)
+            .collect()
+    }
+
+    pub fn is_boundary_vertex(&self, v: u32) -> bool {
+        self.outgoing[v as usize].is_some_and(|h| self.half_edges[h as usize].is_boundary())
+    }
+
+    // Whether either side of the half-edge's edge has no face.
+    pub fn is_boundary_edge(&self, h: u32) -> bool {
+        let edge = self.half_edges[h as usize];
+        edge.is_boundary() || self.half_edges[edge.twin as usize].is_boundary()
+    }
+
+    // The vertices around each hole, in order.
+    pub fn boundary_loops(&self) -> Vec<Vec<u32>> {
+        let mut seen = vec![false; self.half_edges.len()];
+        let mut loops = Vec::new();
+        for start in 0..self.half_edges.len() {
+            if seen[start] || !self.half_edges[start].is_boundary() {
+                continue;
+            }
+            let mut vertices = Vec::new();
+            let mut h = start;
+            while !seen[h] {
+                seen[h] = true;
+                vertices.push(self.half_edges[h].vertex);
+                h = self.half_edges[h].next as usize;
+            }
+            loops.push(vertices);
+        }
+        loops
+    }
+
+    // The half-edges from h on straight through each vertex, leaving by the
+    // edge opposite the one it came in on. That only exists where a vertex
+    // has an even number of edges, so the loop ends at the first with an
+    // odd number, at a boundary, or when it comes back around to h.
+    pub fn edge_loop(&self, h: u32) -> Vec<u32> {
+        let mut edges = vec![h];
+        let mut current = h;
+        loop {
+            let v = self.half_edges[current as usize].vertex;
+            let around = self.outgoing(v);
+            if !around.len().is_multiple_of(2) || self.is_boundary_vertex(v) {
+                return edges;
+            }
+            let back = self.half_edges[current as usize].twin;
+            let i = match around.iter().position(|&o| o == back) {
+                Some(i) => i,
+                None => return edges,
+            };
+            current = around[(i + around.len() / 2) % around.len()];
+            if current == h {
+                return edges;
+            }
+            edges.push(current);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use geometry::mesh::Mesh;
+    use super::HalfEdgeMesh;
+
+    #[test]
+    fn plane_adjacency() {
+        // 5 by 5 vertices, 0 in a corner
+        let plane = Mesh::plane(2.0, 2.0, 4, 4);
+        let half = HalfEdgeMesh::from_mesh(&plane).unwrap();
+        assert_eq!(plane.face_count(), half.face_count());
+        assert_eq!(plane.indices, half.to_mesh().indices);
+        // Three edges for each face, inside twice and around the outline once
+        assert_eq!(32 * 3 + 16, half.half_edges.len());
+
+        let mut middle = half.neighbours(12);
+        middle.sort();
+        assert_eq!(vec![7, 8, 11, 13, 16, 17], middle);
+        assert_eq!(6, half.faces_around(12).len());
+        assert!(!half.is_boundary_vertex(12) && half.is_boundary_vertex(0));
+        let mut corner = half.neighbours(0);
+        corner.sort();
+        assert_eq!(vec![1, 5], corner);
+
+        let loops = half.boundary_loops();
+        assert_eq!(1, loops.len());
+        assert_eq!(16, loops[0].len());
+        assert!(loops[0].iter().all(|&v| half.is_boundary_vertex(v)));
+        for (h, edge) in half.half_edges.iter().enumerate() {
+            let h = h as u32;
+            assert_eq!(h, half.half_edges[edge.twin as usize].twin);
+            assert_eq!(h, half.half_edges[edge.next as usize].prev);
+            assert_eq!(edge.is_boundary() || half.half_edges[edge.twin as usize].is_boundary(),
+                       half.is_boundary_edge(h));
+        }
+    }
+
+    #[test]
+    fn edge_loops() {
+        let mut torus = Mesh::torus(1.0, 0.25, 8, 12);
+        assert!(!HalfEdgeMesh::from_mesh(&torus).unwrap().boundary_loops().is_empty());
+        // Closed once its seams are welded, 6 edges at every vertex
+        torus.weld(1e-9, false);
+        let half = HalfEdgeMesh::from_mesh(&torus).unwrap();
+        assert!(half.boundary_loops().is_empty());
+        assert!((0..torus.vertices.len() as u32).all(|v| half.neighbours(v).len() == 6));
+
+        let start = (0..half.half_edges.len() as u32)
+            .find(|&h| half.origin(h) == 0 && half.half_edges[h as usize].vertex == 1)
+            .unwrap();
+        let ring = half.edge_loop(start);
+        assert_eq!(8, ring.len());
+        assert_eq!(0, half.half_edges[ring[7] as usize].vertex);
+
+        // Stopping at the outline of an open mesh
+        let plane = HalfEdgeMesh::from_mesh(&Mesh::plane(2.0, 2.0, 4, 4)).unwrap();
+        let across = (0..plane.half_edges.len() as u32)
+            .find(|&h| plane.origin(h) == 10 && plane.half_edges[h as usize].vertex == 11)
+            .unwrap();
+        let edges = plane.edge_loop(across);
+        assert_eq!(vec![11, 12, 13, 14],
+                   edges.iter().map(|&h| plane.half_edges[h as usize].vertex).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn non_manifold() {
+        let mut mesh = Mesh::triangle();
+        mesh.add_face(0, 1, 2);
+        assert!(HalfEdgeMesh::from_mesh(&mesh).is_none());
+    }
+}
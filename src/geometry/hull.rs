@@ -0,0 +1,223 @@
+
+use super::super::math::vector::Vector3;
+use super::mesh::{Face, Mesh};
+
+use std::collections::HashSet;
+
+// Quickhull: starting from a tetrahedron of points far apart, the point
+// farthest out from a face is added to the hull by replacing every face it
+// can see with a fan of faces from it to the outline, the horizon, of the
+// ones it replaced. Each point is kept with a face it lies outside of until
+// it is added or ends up inside.
+
+struct HullFace {
+    corners: [usize; 3],
+    normal: Vector3,
+    offset: f64,
+    outside: Vec<usize>,
+    alive: bool,
+}
+
+impl HullFace {
+    fn new(points: &[Vector3], corners: [usize; 3]) -> HullFace {
+        let (a, b, c) = (points[corners[0]], points[corners[1]], points[corners[2]]);
+        let normal = (b - a).cross(c - a).normalize();
+        HullFace {
+            corners: corners,
+            normal: normal,
+            offset: normal.dot(a),
+            outside: Vec::new(),
+            alive: true,
+        }
+    }
+
+    fn distance(&self, p: Vector3) -> f64 {
+        self.normal.dot(p) - self.offset
+    }
+}
+
+impl Mesh {
+    // The smallest convex mesh around the points, made of those on it in
+    // the order given, wound outwards and shaded flat. Points all in one
+    // plane enclose nothing and give an empty mesh.
+    pub fn convex_hull(points: &[Vector3]) -> Mesh {
+        let faces = quickhull(points);
+        let mut remap = vec![None; points.len()];
+        for face in &faces {
+            for &corner in face {
+                remap[corner] = Some(0);
+            }
+        }
+        let mut positions = Vec::new();
+        for (i, index) in remap.iter_mut().enumerate() {
+            if index.is_some() {
+                *index = Some(positions.len() as u32);
+                positions.push(points[i]);
+            }
+        }
+        let index = |corner: usize| remap[corner].unwrap();
+        let faces = faces.iter().map(|f| Face::new(index(f[0]), index(f[1]), index(f[2]))).collect();
+        Mesh::from_positions("Convex Hull", &positions, faces)
+    }
+}
+
+fn quickhull(points: &[Vector3]) -> Vec<[usize; 3]> {
+    let simplex = match initial_simplex(points) {
+        Some(simplex) => simplex,
+        None => return Vec::new(),
+    };
+    let (min, max) = points.iter()
+        .fold((points[0], points[0]), |(min, max), &p| (min.min(p), max.max(p)));
+    let epsilon = (max - min).length() * 1e-10;
+
+    // The fourth corner below each face of the tetrahedron
+    let mut faces = Vec::new();
+    for &(a, b, c, d) in &[(0, 1, 2, 3), (0, 3, 1, 2), (1, 3, 2, 0), (2, 3, 0, 1)] {
+        let mut corners = [simplex[a], simplex[b], simplex[c]];
+        if HullFace::new(points, corners).distance(points[simplex[d]]) > 0.0 {
+            corners.swap(1, 2);
+        }
+        faces.push(HullFace::new(points, corners));
+    }
+    let all: Vec<usize> = (0..points.len()).filter(|i| !simplex.contains(i)).collect();
+    assign(points, &mut faces, 0, &all, epsilon);
+
+    while let Some(f) = faces.iter().position(|f| f.alive && !f.outside.is_empty()) {
+        let eye = *faces[f].outside
+            .iter()
+            .max_by(|&&a, &&b| {
+                let (da, db) = (faces[f].distance(points[a]), faces[f].distance(points[b]));
+                da.partial_cmp(&db).unwrap()
+            })
+            .unwrap();
+
+        let mut edges = HashSet::new();
+        let mut orphans = Vec::new();
+        for face in faces.iter_mut().filter(|face| face.alive) {
+            if face.distance(points[eye]) > epsilon {
+                face.alive = false;
+                orphans.append(&mut face.outside);
+                for k in 0..3 {
+                    edges.insert((face.corners[k], face.corners[(k + 1) % 3]));
+                }
+            }
+        }
+        let first = faces.len();
+        for &(a, b) in &edges {
+            if !edges.contains(&(b, a)) {
+                faces.push(HullFace::new(points, [a, b, eye]));
+            }
+        }
+        orphans.retain(|&p| p != eye);
+        assign(points, &mut faces, first, &orphans, epsilon);
+    }
+    faces.iter().filter(|f| f.alive).map(|f| f.corners).collect()
+}
+
+// Gives each point to the first face from the given one that it lies
+// outside of, if any.
+fn assign(points: &[Vector3], faces: &mut [HullFace], first: usize, which: &[usize], epsilon: f64) {
+    for &p in which {
+        if let Some(face) = faces[first..].iter_mut().find(|f| f.distance(points[p]) > epsilon) {
+            face.outside.push(p);
+        }
+    }
+}
+
+// Four points spanning a volume: the two farthest apart along an axis, the
+// farthest from the line through them, and the farthest from the plane
+// through those three.
+fn initial_simplex(points: &[Vector3]) -> Option<[usize; 4]> {
+    if points.len() < 4 {
+        return None;
+    }
+    let farthest = |measure: &dyn Fn(Vector3) -> f64| {
+        (0..points.len())
+            .map(|i| (i, measure(points[i])))
+            .fold((0, f64::NEG_INFINITY), |best, c| if c.1 > best.1 { c } else { best })
+    };
+    let axes = [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()];
+    let (a, b) = axes.iter()
+        .map(|&axis| (farthest(&|p| -p.dot(axis)).0, farthest(&|p| p.dot(axis)).0))
+        .max_by(|&(a0, b0), &(a1, b1)| {
+            let (s0, s1) = ((points[b0] - points[a0]).length(), (points[b1] - points[a1]).length());
+            s0.partial_cmp(&s1).unwrap()
+        })
+        .unwrap();
+    let size = (points[b] - points[a]).length();
+    if size == 0.0 {
+        return None;
+    }
+    let line = (points[b] - points[a]) * (1.0 / size);
+    let (c, off_line) = farthest(&|p| (p - points[a]).cross(line).length());
+    if off_line <= size * 1e-10 {
+        return None;
+    }
+    let normal = (points[b] - points[a]).cross(points[c] - points[a]).normalize();
+    let (d, off_plane) = farthest(&|p| (p - points[a]).dot(normal).abs());
+    if off_plane <= size * 1e-10 {
+        return None;
+    }
+    Some([a, b, c, d])
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use geometry::mesh::Mesh;
+
+    fn check_hull(hull: &Mesh, points: &[Vector3]) {
+        for face in hull.faces() {
+            let (a, b, c) = (hull.vertices[face.a as usize].position,
+                             hull.vertices[face.b as usize].position,
+                             hull.vertices[face.c as usize].position);
+            let normal = (b - a).cross(c - a).normalize();
+            // Every point on or behind every face
+            for &p in points {
+                assert!(normal.dot(p - a) < 1e-9);
+            }
+        }
+        // Closed, each edge shared by two faces: V - E + F = 2
+        assert_eq!(hull.vertices.len() + hull.face_count(), hull.face_count() * 3 / 2 + 2);
+    }
+
+    #[test]
+    fn hull_of_sphere() {
+        let mut points = Mesh::icosphere(1.0, 2).positions();
+        let inner = Mesh::icosphere(0.5, 1).positions();
+        points.extend(inner.iter().map(|&p| p + Vector3::new(0.1, 0.2, 0.0)));
+        let hull = Mesh::convex_hull(&points);
+        assert_eq!(Mesh::icosphere(1.0, 2).face_count(), hull.face_count());
+        assert!(hull.vertices.iter().all(|v| (v.position.length() - 1.0).abs() < 1e-9));
+        check_hull(&hull, &points);
+    }
+
+    #[test]
+    fn hull_of_cube() {
+        // The corners, the middles of the faces and the center, only the
+        // corners on the hull
+        let mut points = Vec::new();
+        for &x in &[-1.0, 0.0, 1.0] {
+            for &y in &[-1.0, 0.0, 1.0] {
+                for &z in &[-1.0, 0.0, 1.0] {
+                    let p = Vector3::new(x, y, z);
+                    if p.x.abs() + p.y.abs() + p.z.abs() != 2.0 {
+                        points.push(p);
+                    }
+                }
+            }
+        }
+        let hull = Mesh::convex_hull(&points);
+        assert_eq!((8, 12), (hull.vertices.len(), hull.face_count()));
+        assert!(!hull.has_normals());
+        check_hull(&hull, &points);
+    }
+
+    #[test]
+    fn hull_of_flat() {
+        let square = Mesh::plane(2.0, 2.0, 3, 3).positions();
+        assert_eq!(0, Mesh::convex_hull(&square).face_count());
+        assert_eq!(0, Mesh::convex_hull(&square[..3]).face_count());
+    }
+}
@@ -0,0 +1,164 @@
+
+use super::super::math::vector::Vector3;
+use super::super::math::matrix::Matrix4;
+use super::super::math::transform::Transform;
+use super::super::render::device::{Camera, Projection};
+use super::mesh::Mesh;
+
+// How a Lod measures the mesh to choose a level.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum LodSelection {
+    // From the camera to the center of the bounding sphere, in world units.
+    Distance,
+    // The height of the bounding sphere on screen, as a fraction of the
+    // viewport's.
+    ScreenSize,
+}
+
+// Versions of one mesh in less and less detail, drawn by
+// Device::render_lods with whichever suits its size at the time. Each level
+// after the first takes over from the one before at its switch: once the
+// distance is at least as far, or the screen size at most as large. All
+// levels are placed by the Lod's transform, following their own, and
+// measured by the bounds of the first.
+#[derive(Debug)]
+pub struct Lod {
+    levels: Vec<(Mesh, f64)>,
+    pub selection: LodSelection,
+    pub transform: Transform,
+}
+
+impl Lod {
+    pub fn new(mesh: Mesh, selection: LodSelection) -> Lod {
+        Lod {
+            levels: vec![(mesh, 0.0)],
+            selection: selection,
+            transform: Transform::identity(),
+        }
+    }
+
+    // Levels follow in order of their switches.
+    pub fn with_level(mut self, mesh: Mesh, switch: f64) -> Lod {
+        self.levels.push((mesh, switch));
+        self
+    }
+
+    // A level for each switch made with Mesh::decimate, each with half the
+    // triangles of the one before.
+    pub fn generate(mesh: Mesh, selection: LodSelection, switches: &[f64]) -> Lod {
+        let mut lod = Lod::new(mesh, selection);
+        for &switch in switches {
+            let mut level = lod.levels[lod.levels.len() - 1].0.clone();
+            let target = level.face_count() / 2;
+            level.decimate(target);
+            lod = lod.with_level(level, switch);
+        }
+        lod
+    }
+
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn mesh(&self, level: usize) -> &Mesh {
+        &self.levels[level].0
+    }
+
+    // Center and radius in world space.
+    pub fn bounding_sphere(&self) -> (Vector3, f64) {
+        let mesh = &self.levels[0].0;
+        let world = mesh.transform.matrix() * self.transform.matrix();
+        let (min, max) = mesh.bounds();
+        let center = (min + max) * 0.5;
+        let radius = (max - min).length() * 0.5;
+        let world_center = Vector3::transform_coordinate(&center, &world);
+        // Stretched by the largest scale
+        let scale = [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()]
+            .iter()
+            .map(|&axis| Vector3::transform_coordinate(&(center + axis), &world) - world_center)
+            .map(Vector3::length)
+            .fold(0.0, f64::max);
+        (world_center, radius * scale)
+    }
+
+    pub fn select(&self, camera: &Camera) -> usize {
+        let (center, radius) = self.bounding_sphere();
+        let distance = (center - camera.position).length();
+        let size = match camera.projection {
+            // Filling the view from inside
+            _ if distance <= radius => f64::INFINITY,
+            Projection::Perspective => radius / (distance * (camera.fov * 0.5).tan()),
+            Projection::Orthographic { height } => 2.0 * radius / height,
+        };
+        let mut level = 0;
+        for (i, &(_, switch)) in self.levels.iter().enumerate().skip(1) {
+            let past = match self.selection {
+                LodSelection::Distance => distance >= switch,
+                LodSelection::ScreenSize => size <= switch,
+            };
+            if past {
+                level = i;
+            }
+        }
+        level
+    }
+
+    // The chosen level, with the matrix placing it.
+    pub fn selected(&self, camera: &Camera) -> (&Mesh, Matrix4) {
+        (self.mesh(self.select(camera)), self.transform.matrix())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::f64;
+    use math::vector::Vector3;
+    use geometry::mesh::Mesh;
+    use render::device::{Camera, Handedness, Projection};
+    use super::{Lod, LodSelection};
+
+    fn camera(distance: f64) -> Camera {
+        Camera {
+            position: Vector3::new(0.0, 0.0, distance),
+            target: Vector3::zero(),
+            fov: f64::consts::PI / 2.0,
+            znear: 0.01,
+            zfar: 1000.0,
+            projection: Projection::Perspective,
+            handedness: Handedness::Right,
+        }
+    }
+
+    #[test]
+    fn select_by_distance() {
+        let mut lod = Lod::new(Mesh::icosphere(1.0, 3), LodSelection::Distance)
+            .with_level(Mesh::icosphere(1.0, 1), 10.0)
+            .with_level(Mesh::icosahedron(1.0), 50.0);
+        assert_eq!(3, lod.levels());
+        assert_eq!((0, 1, 2), (lod.select(&camera(5.0)), lod.select(&camera(10.0)),
+                               lod.select(&camera(80.0))));
+        // Placed by its transform
+        lod.transform.position = Vector3::new(0.0, 0.0, 30.0);
+        assert_eq!(0, lod.select(&camera(35.0)));
+        assert_eq!(20, lod.selected(&camera(100.0)).0.face_count());
+    }
+
+    #[test]
+    fn select_by_screen_size() {
+        let sphere = Mesh::icosphere(1.0, 3);
+        let mut lod = Lod::generate(sphere, LodSelection::ScreenSize, &[0.5, 0.1]);
+        // Each level about half the one before
+        let counts: Vec<usize> = (0..3).map(|i| lod.mesh(i).face_count()).collect();
+        assert_eq!(1280, counts[0]);
+        assert!(counts[1] <= 640 && counts[1] > 600 && counts[2] <= 320 && counts[2] > 280);
+        // With a 90 degree field of view, the radius of the sphere around the
+        // bounds, the square root of 3, over the distance
+        assert_eq!((0, 1, 2), (lod.select(&camera(2.5)), lod.select(&camera(5.0)),
+                               lod.select(&camera(20.0))));
+        // Larger, so it keeps its detail further away
+        lod.transform.scale = Vector3::new(1.0, 4.0, 1.0);
+        assert_eq!(1, lod.select(&camera(20.0)));
+        assert_eq!(0, lod.select(&camera(0.5)));
+    }
+}
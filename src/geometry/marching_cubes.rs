@@ -0,0 +1,211 @@
+
+use super::super::math::vector::Vector3;
+use super::mesh::{Mesh, Vertex};
+
+use std::collections::HashMap;
+
+// Marching cubes: the field is sampled at the corners of a grid of cells,
+// and a vertex is placed on each cell edge where it crosses the level,
+// shared with the cells around the edge. Rather than looking up the
+// polygons for each of the 256 ways a cell's corners can be inside or out,
+// they are traced: each face of the cell is crossed by one or two segments
+// between its crossed edges, and those join up around the cell into loops.
+// A face crossed four times is split by the value at its center, which the
+// cells on both sides agree on, so the surface has no holes.
+
+// Corners of a cell as offsets along x, y and z in the bits of their index.
+fn corner(i: usize) -> (usize, usize, usize) {
+    (i & 1, (i >> 1) & 1, (i >> 2) & 1)
+}
+
+// The corners around each face, counterclockwise seen from outside the cell.
+const FACES: [[usize; 4]; 6] = [[0, 4, 6, 2],
+                                [1, 3, 7, 5],
+                                [0, 1, 5, 4],
+                                [2, 6, 7, 3],
+                                [0, 2, 3, 1],
+                                [4, 5, 7, 6]];
+
+impl Mesh {
+    // The surface where the field equals the level, within the box from min
+    // to max split into resolution cells along each axis. Points below the
+    // level are inside, as for a signed distance field with a level of 0,
+    // and the faces are wound with their fronts outside. The normals are
+    // smooth.
+    pub fn marching_cubes<F>(field: F,
+                             min: Vector3,
+                             max: Vector3,
+                             resolution: usize,
+                             level: f64)
+                             -> Mesh
+        where F: Fn(Vector3) -> f64
+    {
+        let n = resolution.max(1);
+        let step = (max - min) * (1.0 / n as f64);
+        let point = |x: usize, y: usize, z: usize| {
+            min + Vector3::new(x as f64 * step.x, y as f64 * step.y, z as f64 * step.z)
+        };
+        let index = |x: usize, y: usize, z: usize| (z * (n + 1) + y) * (n + 1) + x;
+        let mut samples = vec![0.0; (n + 1) * (n + 1) * (n + 1)];
+        for z in 0..n + 1 {
+            for y in 0..n + 1 {
+                for x in 0..n + 1 {
+                    samples[index(x, y, z)] = field(point(x, y, z));
+                }
+            }
+        }
+
+        let mut mesh = Mesh::new("Isosurface");
+        // Vertices by the grid point at the low end of their edge and its axis
+        let mut crossings: HashMap<(usize, usize, usize, usize), u32> = HashMap::new();
+        for z in 0..n {
+            for y in 0..n {
+                for x in 0..n {
+                    let at = |i: usize| {
+                        let (dx, dy, dz) = corner(i);
+                        (x + dx, y + dy, z + dz)
+                    };
+                    let value = |i: usize| {
+                        let (cx, cy, cz) = at(i);
+                        samples[index(cx, cy, cz)]
+                    };
+                    let inside: Vec<bool> = (0..8).map(|i| value(i) < level).collect();
+                    if inside.iter().all(|&i| i) || inside.iter().all(|&i| !i) {
+                        continue;
+                    }
+
+                    // The segments across each face, from the edge where
+                    // going around it leaves the inside to one where it
+                    // enters again
+                    let mut segments = HashMap::new();
+                    for face in &FACES {
+                        let mut leaving = Vec::new();
+                        let mut entering = Vec::new();
+                        for k in 0..4 {
+                            let (a, b) = (face[k], face[(k + 1) % 4]);
+                            // The same way around from both faces on it
+                            let edge = (a.min(b), a.max(b));
+                            if inside[a] && !inside[b] {
+                                leaving.push((k, edge));
+                            } else if !inside[a] && inside[b] {
+                                entering.push((k, edge));
+                            }
+                        }
+                        if leaving.len() == 1 {
+                            segments.insert(leaving[0].1, entering[0].1);
+                            continue;
+                        }
+                        if leaving.is_empty() {
+                            continue;
+                        }
+                        // Four crossings, around two inside corners on opposite
+                        // sides. With the center inside as well they are
+                        // joined, cutting off the outside corners, and
+                        // otherwise each is cut off on its own.
+                        let center = face.iter().map(|&i| value(i)).sum::<f64>() * 0.25;
+                        let joined = center < level;
+                        for &(k, edge) in &leaving {
+                            let after = entering.iter().find(|&&(j, _)| (j + 4 - k) % 4 == 1);
+                            let before = entering.iter().find(|&&(j, _)| (k + 4 - j) % 4 == 1);
+                            let to = if joined { after } else { before };
+                            segments.insert(edge, to.unwrap().1);
+                        }
+                    }
+
+                    let mut vertex = |(a, b): (usize, usize)| {
+                        let (ax, ay, az) = at(a);
+                        let axis = (b - a).trailing_zeros() as usize;
+                        *crossings.entry((ax, ay, az, axis)).or_insert_with(|| {
+                            let (va, vb) = (value(a), value(b));
+                            let t = (level - va) / (vb - va);
+                            let (bx, by, bz) = at(b);
+                            let position = point(ax, ay, az).lerp(point(bx, by, bz), t);
+                            mesh.add_vertex(Vertex::new(position))
+                        })
+                    };
+
+                    // Each crossed edge is shared by two faces, on one as
+                    // the start of a segment and the other as the end, so
+                    // following them goes around a loop, clockwise seen
+                    // from outside
+                    let mut rings = Vec::new();
+                    while let Some(&start) = segments.keys().next() {
+                        let mut ring = Vec::new();
+                        let mut edge = start;
+                        while let Some(next) = segments.remove(&edge) {
+                            ring.push(vertex(edge));
+                            edge = next;
+                        }
+                        rings.push(ring);
+                    }
+                    for ring in rings {
+                        for i in 1..ring.len().saturating_sub(1) {
+                            let (a, b, c) = (ring[0], ring[i + 1], ring[i]);
+                            if a != b && b != c && c != a {
+                                mesh.add_face(a, b, c);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        mesh.compute_normals();
+        mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use geometry::half_edge::HalfEdgeMesh;
+    use geometry::mesh::Mesh;
+
+    fn check_closed(mesh: &Mesh) {
+        let half = HalfEdgeMesh::from_mesh(mesh).unwrap();
+        assert!(half.boundary_loops().is_empty());
+    }
+
+    #[test]
+    fn sphere() {
+        let field = |p: Vector3| p.length() - 1.0;
+        let extent = Vector3::new(1.5, 1.5, 1.5);
+        let mesh = Mesh::marching_cubes(field, extent * -1.0, extent, 12, 0.0);
+        assert!(mesh.face_count() > 100);
+        check_closed(&mesh);
+        for v in &mesh.vertices {
+            assert!((v.position.length() - 1.0).abs() < 0.05);
+            assert!(v.normal.dot(v.position.normalize()) > 0.9);
+        }
+        // Wound facing out
+        for face in mesh.faces() {
+            let (a, b, c) = (mesh.vertices[face.a as usize].position,
+                             mesh.vertices[face.b as usize].position,
+                             mesh.vertices[face.c as usize].position);
+            assert!((b - a).cross(c - a).dot(a + b + c) > 0.0);
+        }
+    }
+
+    #[test]
+    fn ambiguous_faces() {
+        // A gyroid inside a ball, full of saddles that leave faces crossed
+        // four times, where the surface must still close up
+        let gyroid = |p: Vector3| {
+            let q = p * 4.0;
+            let g = q.x.sin() * q.y.cos() + q.y.sin() * q.z.cos() + q.z.sin() * q.x.cos();
+            g.max(p.length() - 1.0)
+        };
+        let extent = Vector3::new(1.2, 1.2, 1.2);
+        for &resolution in &[7, 10, 16] {
+            let mesh = Mesh::marching_cubes(gyroid, extent * -1.0, extent, resolution, 0.0);
+            assert!(mesh.face_count() > 0);
+            check_closed(&mesh);
+        }
+        // A level below everything leaves nothing, and the field outside
+        // the box is never sampled
+        let empty = Mesh::marching_cubes(gyroid, extent * -1.0, extent, 8, -5.0);
+        assert_eq!(0, empty.face_count());
+        let cut = Mesh::marching_cubes(|p| p.length() - 3.0, extent * -1.0, extent, 4, 0.0);
+        assert_eq!(0, cut.face_count());
+    }
+}
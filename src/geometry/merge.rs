@@ -0,0 +1,121 @@
+
+use super::super::math::vector::{Vector3, Vector4};
+use super::super::math::matrix::{Matrix3, Matrix4};
+use super::super::math::transform::Transform;
+use super::mesh::{Indices, Mesh};
+
+// Flattening static geometry: a mesh's transform applied to its vertices
+// once, rather than each frame by Device::render, and meshes sharing a
+// material joined to be drawn as one.
+
+impl Mesh {
+    // One mesh with the vertices and faces of all of them, each placed by
+    // its own transform, and the material of the first.
+    pub fn merge(meshes: &[&Mesh]) -> Mesh {
+        let mut merged = Mesh::new("Merged");
+        if let Some(first) = meshes.first() {
+            merged.material = first.material.clone();
+        }
+        let mut indices = Vec::with_capacity(meshes.iter().map(|m| m.indices.len()).sum());
+        for mesh in meshes {
+            let mut placed = (*mesh).clone();
+            placed.bake_transform(&mesh.transform.matrix());
+            let first = merged.vertices.len() as u32;
+            indices.extend(placed.faces().flat_map(|f| vec![f.a, f.b, f.c]).map(|i| first + i));
+            merged.vertices.extend(placed.vertices);
+        }
+        merged.indices = Indices::from_u32(indices);
+        merged
+    }
+
+    // Moves the vertices by the matrix, turning their normals and tangents
+    // with it, and leaves the mesh's transform as it is. A matrix mirroring
+    // the mesh also turns its faces around so they keep facing out.
+    pub fn bake_transform(&mut self, matrix: &Matrix4) {
+        let normal_matrix = Matrix3::normal_matrix(matrix);
+        let tangent_matrix = Matrix3::from_matrix4(matrix);
+        let mirrored = tangent_matrix.determinant() < 0.0;
+        let direction = |v: Vector3| if v.length_sqr() > 0.0 { v.normalize() } else { v };
+        for vertex in &mut self.vertices {
+            vertex.position = Vector3::transform_coordinate(&vertex.position, matrix);
+            vertex.normal = direction(normal_matrix.transform(vertex.normal));
+            if vertex.tangent != Vector4::zero() {
+                let tangent = direction(tangent_matrix.transform(vertex.tangent.xyz()));
+                let w = if mirrored { -vertex.tangent.w } else { vertex.tangent.w };
+                vertex.tangent = Vector4::new(tangent.x, tangent.y, tangent.z, w);
+            }
+        }
+        if mirrored {
+            let indices = self.faces().flat_map(|f| vec![f.a, f.c, f.b]).collect();
+            self.indices = Indices::from_u32(indices);
+        }
+    }
+
+    // Bakes the mesh's own transform into it, leaving the identity.
+    pub fn flatten(&mut self) {
+        let matrix = self.transform.matrix();
+        self.bake_transform(&matrix);
+        self.transform = Transform::identity();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use math::matrix::Matrix4;
+    use math::transform::Transform;
+    use geometry::mesh::Mesh;
+    use render::color::Color;
+
+    #[test]
+    fn bake_transform() {
+        let mut plane = Mesh::plane(2.0, 2.0, 1, 1);
+        let matrix = Matrix4::scale(Vector3::new(2.0, 1.0, 3.0)) *
+                     Matrix4::translation(Vector3::new(0.0, 1.0, 0.0));
+        plane.bake_transform(&matrix);
+        assert_eq!((Vector3::new(-2.0, 1.0, -3.0), Vector3::new(2.0, 1.0, 3.0)), plane.bounds());
+        for v in &plane.vertices {
+            assert_eq!(Vector3::unit_y(), v.normal);
+            assert!((v.tangent.xyz() - Vector3::unit_x()).length() < 1e-9);
+        }
+
+        // Mirrored upside down, and still facing the way its normals do
+        plane.bake_transform(&Matrix4::scale(Vector3::new(1.0, -1.0, 1.0)));
+        for face in plane.faces() {
+            let (a, b, c) = (plane.vertices[face.a as usize],
+                             plane.vertices[face.b as usize],
+                             plane.vertices[face.c as usize]);
+            let normal = (b.position - a.position).cross(c.position - a.position).normalize();
+            assert_eq!(Vector3::unit_y() * -1.0, a.normal);
+            assert!((normal - a.normal).length() < 1e-9);
+        }
+        let mut unbaked = Mesh::plane(2.0, 2.0, 1, 1);
+        unbaked.compute_tangents();
+        assert_eq!(-unbaked.vertices[0].tangent.w, plane.vertices[0].tangent.w);
+    }
+
+    #[test]
+    fn merge() {
+        let mut red = Mesh::triangle();
+        red.material.color = Color::rgb(1.0, 0.0, 0.0);
+        let mut moved = Mesh::cube();
+        moved.transform = Transform::from_position(Vector3::new(5.0, 0.0, 0.0));
+
+        let merged = Mesh::merge(&[&red, &moved]);
+        assert_eq!(red.vertices.len() + 8, merged.vertices.len());
+        assert_eq!(1 + 12, merged.face_count());
+        assert_eq!(red.material.color, merged.material.color);
+        assert_eq!(Transform::identity().matrix(), merged.transform.matrix());
+        // The cube's faces after the triangle's, placed where it was
+        let first = red.vertices.len() as u32;
+        assert!(merged.faces().skip(1).all(|f| f.a >= first && f.b >= first && f.c >= first));
+        let (_, max) = merged.bounds();
+        assert_eq!(6.0, max.x);
+
+        let mut flat = moved.clone();
+        flat.flatten();
+        assert_eq!(merged.vertices[first as usize..].to_vec(), flat.vertices);
+        assert!(Mesh::merge(&[]).vertices.is_empty());
+    }
+}
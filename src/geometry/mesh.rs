@@ -1,9 +1,18 @@
 
-use super::super::math::vector::Vector3;
+use super::super::math::vector::{Vector2, Vector3, Vector4};
+use super::super::math::matrix::Matrix4;
+use super::super::math::transform::Transform;
+use super::super::error::{Result, SwrError};
+use super::super::render::color::Color;
+use super::super::render::material::Material;
+use super::modifier::Modifier;
+use super::morph::MorphTarget;
+use super::ray::{Ray, RayHit};
 
+use std::collections::HashMap;
 use std::f64;
 
-#[derive(Debug)]
+#[derive(Debug,Clone,Copy,PartialEq)]
 pub struct Face {
     pub a: u32,
     pub b: u32,
@@ -16,24 +25,268 @@ impl Face {
     }
 }
 
-#[derive(Debug)]
+// Index buffer, three to a face. Small meshes keep 16 bit indices, half
+// the memory, and are promoted to 32 bits once an index does not fit.
+#[derive(Debug,Clone,PartialEq)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    pub fn new() -> Indices {
+        Indices::U16(Vec::new())
+    }
+
+    // In 16 bits when every index fits.
+    pub fn from_u32(indices: Vec<u32>) -> Indices {
+        if indices.iter().all(|&i| i <= u16::MAX as u32) {
+            Indices::U16(indices.into_iter().map(|i| i as u16).collect())
+        } else {
+            Indices::U32(indices)
+        }
+    }
+
+    pub fn from_faces(faces: &[Face]) -> Indices {
+        Indices::from_u32(faces.iter().flat_map(|f| vec![f.a, f.b, f.c]).collect())
+    }
+
+    pub fn len(&self) -> usize {
+        match *self {
+            Indices::U16(ref indices) => indices.len(),
+            Indices::U32(ref indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, i: usize) -> u32 {
+        match *self {
+            Indices::U16(ref indices) => indices[i] as u32,
+            Indices::U32(ref indices) => indices[i],
+        }
+    }
+
+    pub fn push(&mut self, index: u32) {
+        if index > u16::MAX as u32 {
+            self.promote();
+        }
+        match *self {
+            Indices::U16(ref mut indices) => indices.push(index as u16),
+            Indices::U32(ref mut indices) => indices.push(index),
+        }
+    }
+
+    // Widens the indices to 32 bits, if they are not already.
+    pub fn promote(&mut self) {
+        if let Indices::U16(ref indices) = *self {
+            *self = Indices::U32(indices.iter().map(|&i| i as u32).collect());
+        }
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.len() / 3
+    }
+
+    pub fn face(&self, i: usize) -> Face {
+        Face::new(self.get(i * 3), self.get(i * 3 + 1), self.get(i * 3 + 2))
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        match *self {
+            Indices::U16(ref indices) => indices.len() * 2,
+            Indices::U32(ref indices) => indices.len() * 4,
+        }
+    }
+}
+
+impl Default for Indices {
+    fn default() -> Indices {
+        Indices::new()
+    }
+}
+
+// One corner of a mesh's surface, with the attributes interpolated across
+// the faces around it. Built from its position by the with_ methods.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Vertex {
+    pub position: Vector3,
+    // Unit length, or zero for none, the faces then shading flat.
+    pub normal: Vector3,
+    pub uv: Vector2,
+    // Along increasing u, with the handedness in w: the bitangent, up the
+    // texture, is the normal crossed with the tangent times w. Zero for
+    // none.
+    pub tangent: Vector4,
+    // Multiplies the surface color, in sRGB as authored. Gamma correct
+    // devices linearize it.
+    pub color: Color,
+}
+
+impl Vertex {
+    pub fn new(position: Vector3) -> Vertex {
+        Vertex {
+            position: position,
+            normal: Vector3::zero(),
+            uv: Vector2::zero(),
+            tangent: Vector4::zero(),
+            color: Color::white(),
+        }
+    }
+
+    pub fn with_normal(mut self, normal: Vector3) -> Vertex {
+        self.normal = normal;
+        self
+    }
+
+    pub fn with_uv(mut self, uv: Vector2) -> Vertex {
+        self.uv = uv;
+        self
+    }
+
+    pub fn with_tangent(mut self, tangent: Vector4) -> Vertex {
+        self.tangent = tangent;
+        self
+    }
+
+    pub fn with_color(mut self, color: Color) -> Vertex {
+        self.color = color;
+        self
+    }
+}
+
+// One copy of a mesh drawn by Device::render_instanced, placed by the
+// transform following the mesh's own. The color multiplies its vertex
+// colors, in sRGB like them.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Instance {
+    pub transform: Matrix4,
+    pub color: Color,
+}
+
+impl Instance {
+    pub fn new(transform: Matrix4) -> Instance {
+        Instance {
+            transform: transform,
+            color: Color::white(),
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Instance {
+        self.color = color;
+        self
+    }
+}
+
+#[derive(Debug,Clone)]
 pub struct Mesh {
     pub name: String,
-    pub vertices: Vec<Vector3>,
-    pub faces: Vec<Face>,
-    pub position: Vector3,
-    pub rotation: Vector3,
-    pub scale: Vector3,
+    pub vertices: Vec<Vertex>,
+    // Triangles of indices into vertices.
+    pub indices: Indices,
+    pub transform: Transform,
+    pub material: Material,
+    // Blend shapes, with a weight for each, see morph.rs.
+    pub morph_targets: Vec<MorphTarget>,
+    pub morph_weights: Vec<f64>,
+    // Deformations applied when drawn, see modifier.rs.
+    pub modifiers: Vec<Modifier>,
 }
 
 impl Mesh {
+    pub fn new(name: &str) -> Mesh {
+        Mesh::from_positions(name, &[], Vec::new())
+    }
+
+    // Vertices at the positions, without any other attributes.
+    pub fn from_positions(name: &str, positions: &[Vector3], faces: Vec<Face>) -> Mesh {
+        Mesh {
+            name: name.to_string(),
+            vertices: positions.iter().map(|&p| Vertex::new(p)).collect(),
+            indices: Indices::from_faces(&faces),
+            transform: Transform::identity(),
+            material: Material::new(),
+            morph_targets: Vec::new(),
+            morph_weights: Vec::new(),
+            modifiers: Vec::new(),
+        }
+    }
+
+    // The vertex's index, for add_face. Past what 16 bits can index the
+    // indices are promoted to 32 bits.
+    pub fn add_vertex(&mut self, vertex: Vertex) -> u32 {
+        self.vertices.push(vertex);
+        if self.vertices.len() > u16::MAX as usize + 1 {
+            self.indices.promote();
+        }
+        self.vertices.len() as u32 - 1
+    }
+
+    pub fn add_face(&mut self, a: u32, b: u32, c: u32) {
+        for &i in &[a, b, c] {
+            self.indices.push(i);
+        }
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.indices.face_count()
+    }
+
+    pub fn face(&self, i: usize) -> Face {
+        self.indices.face(i)
+    }
+
+    pub fn faces<'a>(&'a self) -> impl Iterator<Item = Face> + 'a {
+        (0..self.face_count()).map(move |i| self.face(i))
+    }
+
+    // Err for the first face referring to a vertex past the vertices. Such
+    // faces are skipped when drawn, so check meshes built by hand here.
+    pub fn validate(&self) -> Result<()> {
+        let count = self.vertices.len();
+        for (i, face) in self.faces().enumerate() {
+            if let Some(&index) = [face.a, face.b, face.c].iter().find(|&&v| v as usize >= count) {
+                return Err(SwrError::InvalidIndex {
+                    mesh: self.name.clone(),
+                    face: i,
+                    index: index,
+                    vertices: count,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn positions(&self) -> Vec<Vector3> {
+        self.vertices.iter().map(|v| v.position).collect()
+    }
+
+    // Whether any vertex has the attribute, as opposed to its default.
+    pub fn has_normals(&self) -> bool {
+        self.vertices.iter().any(|v| v.normal != Vector3::zero())
+    }
+
+    pub fn has_uvs(&self) -> bool {
+        self.vertices.iter().any(|v| v.uv != Vector2::zero())
+    }
+
+    pub fn has_tangents(&self) -> bool {
+        self.vertices.iter().any(|v| v.tangent != Vector4::zero())
+    }
+
+    pub fn has_colors(&self) -> bool {
+        self.vertices.iter().any(|v| v.color != Color::white())
+    }
+
     pub fn bounds(&self) -> (Vector3, Vector3) {
         if self.vertices.len() > 0 {
-            let mut min = *self.vertices.first().unwrap();
-            let mut max = *self.vertices.first().unwrap();
+            let mut min = self.vertices[0].position;
+            let mut max = self.vertices[0].position;
             for vert in &self.vertices {
-                min = min.min(*vert);
-                max = max.max(*vert);
+                min = min.min(vert.position);
+                max = max.max(vert.position);
             }
             (min, max)
         } else {
@@ -41,46 +294,176 @@ impl Mesh {
         }
     }
 
-    pub fn triangle() -> Mesh {
-        Mesh {
-            name: "Triangle".to_string(),
-            vertices: vec![Vector3::new(-1.0, -1.0, -1.0),
-                           Vector3::new(1.0, -1.0, -1.0),
-                           Vector3::new(1.0, 1.0, -1.0)],
-            faces: vec![Face::new(0, 1, 2)],
-            position: Vector3::zero(),
-            rotation: Vector3::zero(),
-            scale: Vector3::one(),
+    // The ray is in world space and the mesh is placed by its transform.
+    pub fn raycast(&self, ray: &Ray) -> Option<RayHit> {
+        self.raycast_with_parent(ray, &Matrix4::identity())
+    }
+
+    // Nearest hit with the mesh placed by its transform followed by parent,
+    // as it is drawn by Device::render_scene.
+    pub fn raycast_with_parent(&self, ray: &Ray, parent: &Matrix4) -> Option<RayHit> {
+        let world = self.transform.matrix() * *parent;
+        let mut nearest: Option<RayHit> = None;
+        for (i, face) in self.faces().enumerate() {
+            let corner = |i: u32| {
+                Vector3::transform_coordinate(&self.vertices[i as usize].position, &world)
+            };
+            let (v0, v1, v2) = (corner(face.a), corner(face.b), corner(face.c));
+            if let Some((distance, barycentric)) = ray.intersect_triangle(v0, v1, v2) {
+                if nearest.is_none_or(|hit| distance < hit.distance) {
+                    nearest = Some(RayHit {
+                        distance: distance,
+                        face: i,
+                        barycentric: barycentric,
+                    });
+                }
+            }
         }
+        nearest
+    }
+
+    // Smooth vertex normals, averaging the normals of the faces around each
+    // vertex weighted by face area. Faces are expected to be wound
+    // consistently.
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![Vector3::zero(); self.vertices.len()];
+        for face in self.faces() {
+            let (a, b, c) = (face.a as usize, face.b as usize, face.c as usize);
+            let p = self.vertices[a].position;
+            let normal = (self.vertices[b].position - p).cross(self.vertices[c].position - p);
+            normals[a] = normals[a] + normal;
+            normals[b] = normals[b] + normal;
+            normals[c] = normals[c] + normal;
+        }
+        for (vertex, n) in self.vertices.iter_mut().zip(normals) {
+            vertex.normal = if n.length_sqr() > 0.0 { n.normalize() } else { n };
+        }
+    }
+
+    // Tangents for normal mapping from the texture coordinates of the faces
+    // around each vertex, made perpendicular to its normal. Zero for
+    // vertices without a normal or where the texture does not vary.
+    pub fn compute_tangents(&mut self) {
+        let count = self.vertices.len();
+        let mut tangents = vec![Vector3::zero(); count];
+        let mut bitangents = vec![Vector3::zero(); count];
+        for face in self.faces() {
+            let (a, b, c) = (face.a as usize, face.b as usize, face.c as usize);
+            let (va, vb, vc) = (self.vertices[a], self.vertices[b], self.vertices[c]);
+            let (e1, e2) = (vb.position - va.position, vc.position - va.position);
+            let (d1, d2) = (vb.uv - va.uv, vc.uv - va.uv);
+            let r = d1.x * d2.y - d2.x * d1.y;
+            if r == 0.0 {
+                continue;
+            }
+            let tangent = (e1 * d2.y - e2 * d1.y) * (1.0 / r);
+            // Up the texture, against v
+            let bitangent = (e1 * d2.x - e2 * d1.x) * (1.0 / r);
+            for &i in &[a, b, c] {
+                tangents[i] = tangents[i] + tangent;
+                bitangents[i] = bitangents[i] + bitangent;
+            }
+        }
+        for (i, vertex) in self.vertices.iter_mut().enumerate() {
+            let n = vertex.normal;
+            let t = tangents[i] - n * n.dot(tangents[i]);
+            vertex.tangent = if n.length_sqr() > 0.0 && t.length_sqr() > 0.0 {
+                let t = t.normalize();
+                let w = if n.cross(t).dot(bitangents[i]) < 0.0 { -1.0 } else { 1.0 };
+                Vector4::new(t.x, t.y, t.z, w)
+            } else {
+                Vector4::zero()
+            };
+        }
+    }
+
+    pub fn triangle() -> Mesh {
+        Mesh::from_positions("Triangle",
+                             &[Vector3::new(-1.0, -1.0, -1.0),
+                               Vector3::new(1.0, -1.0, -1.0),
+                               Vector3::new(1.0, 1.0, -1.0)],
+                             vec![Face::new(0, 1, 2)])
     }
 
     pub fn cube() -> Mesh {
-        Mesh {
-            name: "Cube".to_string(),
-            vertices: vec![Vector3::new(-1.0, -1.0, -1.0),
-                           Vector3::new(1.0, -1.0, -1.0),
-                           Vector3::new(1.0, 1.0, -1.0),
-                           Vector3::new(-1.0, 1.0, -1.0),
-                           Vector3::new(-1.0, -1.0, 1.0),
-                           Vector3::new(1.0, -1.0, 1.0),
-                           Vector3::new(1.0, 1.0, 1.0),
-                           Vector3::new(-1.0, 1.0, 1.0)],
-            faces: vec![Face::new(0, 1, 2),
-                        Face::new(2, 3, 0),
-                        Face::new(1, 5, 6),
-                        Face::new(6, 2, 1),
-                        Face::new(4, 7, 6),
-                        Face::new(6, 5, 4),
-                        Face::new(0, 3, 7),
-                        Face::new(7, 4, 0),
-                        Face::new(5, 1, 0),
-                        Face::new(0, 4, 5),
-                        Face::new(2, 6, 7),
-                        Face::new(7, 3, 2)],
-            position: Vector3::zero(),
-            rotation: Vector3::zero(),
-            scale: Vector3::one(),
+        Mesh::from_positions("Cube",
+                             &[Vector3::new(-1.0, -1.0, -1.0),
+                               Vector3::new(1.0, -1.0, -1.0),
+                               Vector3::new(1.0, 1.0, -1.0),
+                               Vector3::new(-1.0, 1.0, -1.0),
+                               Vector3::new(-1.0, -1.0, 1.0),
+                               Vector3::new(1.0, -1.0, 1.0),
+                               Vector3::new(1.0, 1.0, 1.0),
+                               Vector3::new(-1.0, 1.0, 1.0)],
+                             vec![Face::new(0, 1, 2),
+                                  Face::new(2, 3, 0),
+                                  Face::new(1, 5, 6),
+                                  Face::new(6, 2, 1),
+                                  Face::new(4, 7, 6),
+                                  Face::new(6, 5, 4),
+                                  Face::new(0, 3, 7),
+                                  Face::new(7, 4, 0),
+                                  Face::new(5, 1, 0),
+                                  Face::new(0, 4, 5),
+                                  Face::new(2, 6, 7),
+                                  Face::new(7, 3, 2)])
+    }
+
+    // A surface through the points of f over u and v from 0 to 1, split
+    // into steps of each, with u and v as its texture coordinates. It faces
+    // the way of the change in f along u crossed with the change along v.
+    // Wrapping makes f at 1 meet f at 0, the normals smooth across the seam.
+    pub fn from_parametric<F>(f: F,
+                              u_steps: usize,
+                              v_steps: usize,
+                              wrap_u: bool,
+                              wrap_v: bool)
+                              -> Mesh
+        where F: Fn(f64, f64) -> Vector3
+    {
+        let (u_steps, v_steps) = (u_steps.max(1), v_steps.max(1));
+        let columns = u_steps + 1;
+        let mut mesh = Mesh::new("Parametric");
+        for j in 0..v_steps + 1 {
+            for i in 0..columns {
+                let uv = Vector2::new(i as f64 / u_steps as f64, j as f64 / v_steps as f64);
+                mesh.add_vertex(Vertex::new(f(uv.x, uv.y)).with_uv(uv));
+            }
         }
+        for j in 0..v_steps {
+            for i in 0..u_steps {
+                let lt = (i + j * columns) as u32;
+                let rt = lt + 1;
+                let lb = lt + columns as u32;
+                let rb = lb + 1;
+                mesh.add_face(lt, rt, lb);
+                mesh.add_face(rt, rb, lb);
+            }
+        }
+
+        // Summed over the faces around each point, the ends of a wrapped
+        // direction being the same point
+        let point = |k: usize| {
+            let (i, j) = (k % columns, k / columns);
+            let i = if wrap_u && i == u_steps { 0 } else { i };
+            let j = if wrap_v && j == v_steps { 0 } else { j };
+            i + j * columns
+        };
+        let mut normals = vec![Vector3::zero(); mesh.vertices.len()];
+        for face in mesh.faces() {
+            let (a, b, c) = (face.a as usize, face.b as usize, face.c as usize);
+            let p = mesh.vertices[a].position;
+            let normal = (mesh.vertices[b].position - p).cross(mesh.vertices[c].position - p);
+            for &k in &[a, b, c] {
+                normals[point(k)] = normals[point(k)] + normal;
+            }
+        }
+        for k in 0..mesh.vertices.len() {
+            let n = normals[point(k)];
+            mesh.vertices[k].normal = if n.length_sqr() > 0.0 { n.normalize() } else { n };
+        }
+        mesh.compute_tangents();
+        mesh
     }
 
     pub fn shell(inner_radius: f64,
@@ -90,215 +473,742 @@ impl Mesh {
                  slices: usize,
                  stacks: usize)
                  -> Mesh {
-        let vertices_per_row = slices + 1;
-        let vertices_per_col = stacks + 1;
-
-        let vertex_count = vertices_per_row * vertices_per_col;
-        let mut vertices = Vec::with_capacity(vertex_count as usize);
+        let n = number_of_spirals as f64;
+        let (a, b, c) = (final_shell_radius, height, inner_radius);
+        let surface = |u: f64, v: f64| {
+            let s = u * f64::consts::PI * 2.0;
+            let t = v * f64::consts::PI * 2.0;
+            let (sin_nt, cos_nt) = (n * t).sin_cos();
+            let (sin_s, cos_s) = s.sin_cos();
+            let r = a * (1.0 - v) * (1.0 + cos_s) + c;
+            Vector3::new(r * cos_nt, r * sin_nt, b * v + a * (1.0 - v) * sin_s)
+        };
+        let mut mesh = Mesh::from_parametric(surface, slices, stacks, true, false);
+        mesh.name = "Shell".to_string();
+        mesh
+    }
 
-        let vertical_angle = (f64::consts::PI * 2.0) / slices as f64;
-        let horizontal_angle = (f64::consts::PI * 2.0) / stacks as f64;
+    pub fn torus(radius: f64, ring_radius: f64, sides: u32, rings: u32) -> Mesh {
+        let surface = |u: f64, v: f64| {
+            let phi = u * f64::consts::PI * 2.0;
+            let theta = v * f64::consts::PI * 2.0;
+            let r = radius + ring_radius * phi.cos();
+            Vector3::new(theta.cos() * r, theta.sin() * r, ring_radius * phi.sin())
+        };
+        let mut mesh = Mesh::from_parametric(surface, sides as usize, rings as usize, true, true);
+        mesh.name = "Torus".to_string();
+        mesh
+    }
 
-        let n = number_of_spirals as f64;
-        let a = final_shell_radius as f64;
-        let b = height as f64;
-        let c = inner_radius as f64;
+    // A tube around a curve winding p times around the z axis and q times
+    // through the hole of a torus of the radius, in the xy plane like
+    // Mesh::torus. The curve is split into segments and the tube into
+    // sides. Coprime p and q make a single knot, like 2 and 3 for a
+    // trefoil.
+    pub fn torus_knot(p: u32,
+                      q: u32,
+                      radius: f64,
+                      tube_radius: f64,
+                      segments: usize,
+                      sides: usize)
+                      -> Mesh {
+        let (segments, sides) = (segments.max(3), sides.max(3));
+        let curve = |t: f64| {
+            let (u, w) = (p as f64 * t, q as f64 * t);
+            let r = radius * (2.0 + w.cos()) * 0.5;
+            Vector3::new(r * u.cos(), r * u.sin(), radius * w.sin() * 0.5)
+        };
+        let mut mesh = Mesh::new("Torus Knot");
+        for i in 0..segments + 1 {
+            let t = i as f64 / segments as f64 * 2.0 * f64::consts::PI;
+            // A frame following the curve, turned towards its middle
+            let center = curve(t);
+            let next = curve(t + 0.01);
+            let tangent = next - center;
+            let bitangent = tangent.cross(next + center).normalize();
+            let normal = bitangent.cross(tangent).normalize();
+            for j in 0..sides + 1 {
+                let (sin, cos) = (j as f64 / sides as f64 * 2.0 * f64::consts::PI).sin_cos();
+                let out = normal * -cos + bitangent * sin;
+                let uv = Vector2::new(i as f64 / segments as f64, j as f64 / sides as f64);
+                mesh.add_vertex(Vertex::new(center + out * tube_radius)
+                    .with_normal(out)
+                    .with_uv(uv));
+            }
+        }
+        let columns = sides as u32 + 1;
+        for i in 0..segments as u32 {
+            for j in 0..sides as u32 {
+                let (a, b) = (i * columns + j, (i + 1) * columns + j);
+                mesh.add_face(a, b, a + 1);
+                mesh.add_face(b, b + 1, a + 1);
+            }
+        }
+        mesh.compute_tangents();
+        mesh
+    }
 
-        for v in 0..vertices_per_col {
-            let t = vertical_angle * v as f64;
+    // The platonic solids have their vertices radius from the origin and
+    // faces wound outwards. Without normals they shade flat.
 
-            for h in 0..vertices_per_row {
-                let s = horizontal_angle * h as f64;
+    // Standing on a face with a vertex up along z.
+    pub fn tetrahedron(radius: f64) -> Mesh {
+        let angle = 2.0 * f64::consts::PI / 3.0;
+        let base = radius * 8f64.sqrt() / 3.0;
+        let mut vertices = Vec::new();
+        for i in 0..3 {
+            let t = i as f64 * angle;
+            vertices.push(Vector3::new(base * t.cos(), base * t.sin(), -radius / 3.0))
+        }
+        vertices.push(Vector3::new(0.0, 0.0, radius));
+        let faces = vec![
+            Face::new(0, 2, 1),
+            Face::new(0, 1, 3),
+            Face::new(1, 2, 3),
+            Face::new(2, 0, 3),
+        ];
+        Mesh::from_positions("Tetrahedron", &vertices, faces)
+    }
 
-                let t2pi = t / (f64::consts::PI * 2.0);
-                let cos_nt = (n * t).cos();
-                let cos_s = s.cos();
-                let sin_nt = (n * t).sin();
-                let sin_s = s.sin();
+    pub fn octahedron(radius: f64) -> Mesh {
+        let angle = f64::consts::PI / 2.0;
+        let mut vertices = Vec::new();
+        for i in 0..4 {
+            let t = i as f64 * angle;
+            vertices.push(Vector3::new(radius * t.cos(), radius * t.sin(), 0.0))
+        }
+        let top = Vector3::new(0.0, 0.0, radius);
+        vertices.push(top);
+        let bot = Vector3::new(0.0, 0.0, -radius);
+        vertices.push(bot);
+        let faces = vec![
+            Face::new(0, 1, 4),
+            Face::new(1, 2, 4),
+            Face::new(2, 3, 4),
+            Face::new(3, 0, 4),
+            Face::new(1, 0, 5),
+            Face::new(2, 1, 5),
+            Face::new(3, 2, 5),
+            Face::new(0, 3, 5),
+        ];
+        Mesh::from_positions("Octahedron", &vertices, faces)
+    }
 
-                let x = a * (1.0 - t2pi) * cos_nt * (1.0 + cos_s) + c * cos_nt;
-                let y = a * (1.0 - t2pi) * sin_nt * (1.0 + cos_s) + c * sin_nt;
-                let z = b * t2pi + a * (1.0 - t2pi) * sin_s;
+    pub fn icosahedron(radius: f64) -> Mesh {
+        let (positions, faces) = icosahedron();
+        let positions: Vec<Vector3> = positions.iter().map(|&p| p * radius).collect();
+        let faces = faces.iter().map(|f| Face::new(f[0], f[1], f[2])).collect();
+        Mesh::from_positions("Icosahedron", &positions, faces)
+    }
 
-                vertices.push(Vector3::new(x, y, z))
+    // The dual of the icosahedron, a vertex over each of its faces, with
+    // each pentagon split into three triangles.
+    pub fn dodecahedron(radius: f64) -> Mesh {
+        let (corners, triangles) = icosahedron();
+        let positions: Vec<Vector3> = triangles.iter()
+            .map(|t| {
+                let center = corners[t[0] as usize] + corners[t[1] as usize] +
+                             corners[t[2] as usize];
+                center.normalize() * radius
+            })
+            .collect();
+        let mut faces = Vec::new();
+        for corner in 0..corners.len() as u32 {
+            // The triangles around the corner, turned to start there, in
+            // order around it: each shares its last edge with the next
+            let around: Vec<(usize, [u32; 3])> = triangles.iter()
+                .enumerate()
+                .filter_map(|(i, t)| {
+                    t.iter().position(|&c| c == corner).map(|k| {
+                        (i, [t[k], t[(k + 1) % 3], t[(k + 2) % 3]])
+                    })
+                })
+                .collect();
+            let mut pentagon = vec![around[0]];
+            while pentagon.len() < around.len() {
+                let last = pentagon[pentagon.len() - 1].1[2];
+                pentagon.push(*around.iter().find(|&&(_, t)| t[1] == last).unwrap());
+            }
+            for i in 2..pentagon.len() {
+                faces.push(Face::new(pentagon[0].0 as u32,
+                                     pentagon[i - 1].0 as u32,
+                                     pentagon[i].0 as u32));
             }
         }
+        Mesh::from_positions("Dodecahedron", &positions, faces)
+    }
 
-        let face_count = slices * stacks * 6;
-        let mut faces = Vec::with_capacity(face_count as usize);
+    pub fn sphere(pivot: Vector3, radius: f64, slices: usize, stacks: usize) -> Mesh {
+        let surface = |u: f64, v: f64| {
+            let u = u * 2.0 * f64::consts::PI;
+            let v = (v - 0.5) * f64::consts::PI;
+            pivot + Vector3::new(v.cos() * u.cos(), v.cos() * u.sin(), v.sin()) * radius
+        };
+        let mut mesh = Mesh::from_parametric(surface, slices, stacks, true, false);
+        mesh.name = "Sphere".to_string();
+        mesh
+    }
 
-        for v in 0..slices {
-            for h in 0..stacks {
-                let lt = (h + v * vertices_per_row) as u32;
-                let rt = ((h + 1) + v * vertices_per_row) as u32;
-                let lb = (h + (v + 1) * vertices_per_row) as u32;
-                let rb = ((h + 1) + (v + 1) * vertices_per_row) as u32;
+    // In the xz plane centered on the origin, facing +y, split into quads.
+    // The texture is stretched over it once, with v along +z.
+    pub fn plane(width: f64, depth: f64, subdivisions_x: usize, subdivisions_z: usize) -> Mesh {
+        let (columns, rows) = (subdivisions_x.max(1), subdivisions_z.max(1));
+        let mut mesh = Mesh::new("Plane");
+        for j in 0..rows + 1 {
+            for i in 0..columns + 1 {
+                let uv = Vector2::new(i as f64 / columns as f64, j as f64 / rows as f64);
+                let position = Vector3::new((uv.x - 0.5) * width, 0.0, (uv.y - 0.5) * depth);
+                mesh.add_vertex(Vertex::new(position)
+                    .with_normal(Vector3::unit_y())
+                    .with_uv(uv));
+            }
+        }
+        for j in 0..rows {
+            for i in 0..columns {
+                let lt = (i + j * (columns + 1)) as u32;
+                let rt = lt + 1;
+                let lb = lt + columns as u32 + 1;
+                let rb = lb + 1;
+                mesh.add_face(lt, lb, rt);
+                mesh.add_face(rt, lb, rb);
+            }
+        }
+        mesh.compute_tangents();
+        mesh
+    }
 
-                faces.push(Face::new(lt, rt, lb));
-                faces.push(Face::new(rt, rb, lb))
+    // Floor of lines like DebugDraw::draw_grid, size units across in the xz
+    // plane, made of flat strips of the thickness facing +y so it renders
+    // with the other meshes.
+    pub fn grid(size: f64, divisions: usize, thickness: f64) -> Mesh {
+        let divisions = divisions.max(1);
+        let (half, width) = (size * 0.5, thickness * 0.5);
+        let mut mesh = Mesh::new("Grid");
+        let mut strip = |min: Vector3, max: Vector3| {
+            let corners = [Vector3::new(min.x, 0.0, min.z),
+                           Vector3::new(max.x, 0.0, min.z),
+                           Vector3::new(min.x, 0.0, max.z),
+                           Vector3::new(max.x, 0.0, max.z)];
+            let first = mesh.vertices.len() as u32;
+            for &corner in &corners {
+                mesh.add_vertex(Vertex::new(corner).with_normal(Vector3::unit_y()));
             }
+            mesh.add_face(first, first + 2, first + 1);
+            mesh.add_face(first + 1, first + 2, first + 3);
+        };
+        for i in 0..divisions + 1 {
+            let t = -half + size * i as f64 / divisions as f64;
+            strip(Vector3::new(t - width, 0.0, -half - width),
+                  Vector3::new(t + width, 0.0, half + width));
+            strip(Vector3::new(-half - width, 0.0, t - width),
+                  Vector3::new(half + width, 0.0, t + width));
         }
+        mesh
+    }
 
-        Mesh {
-            name: "Shell".to_string(),
-            vertices: vertices,
-            faces: faces,
-            position: Vector3::zero(),
-            rotation: Vector3::zero(),
-            scale: Vector3::one(),
+    // Cylinders, cones and capsules stand on the y axis, centered on the
+    // origin, with the segments around it. The texture wraps around the
+    // side once from top to bottom, and is laid flat over the caps.
+
+    pub fn cylinder(radius: f64, height: f64, segments: usize) -> Mesh {
+        let half = height * 0.5;
+        let mut mesh = Mesh::new("Cylinder");
+        revolve(&mut mesh,
+                &[(Vector2::new(radius, half), Vector2::new(1.0, 0.0), 0.0),
+                  (Vector2::new(radius, -half), Vector2::new(1.0, 0.0), 1.0)],
+                segments);
+        cap(&mut mesh, radius, half, segments);
+        cap(&mut mesh, radius, -half, segments);
+        mesh.compute_tangents();
+        mesh
+    }
+
+    // Pointing up, with its base capped.
+    pub fn cone(radius: f64, height: f64, segments: usize) -> Mesh {
+        let half = height * 0.5;
+        let slant = Vector2::new(height, radius);
+        let normal = slant * (1.0 / slant.length());
+        let mut mesh = Mesh::new("Cone");
+        revolve(&mut mesh,
+                &[(Vector2::new(0.0, half), normal, 0.0),
+                  (Vector2::new(radius, -half), normal, 1.0)],
+                segments);
+        cap(&mut mesh, radius, -half, segments);
+        mesh.compute_tangents();
+        mesh
+    }
+
+    // A cylinder rounded off by half spheres, height tall in all.
+    pub fn capsule(radius: f64, height: f64, segments: usize) -> Mesh {
+        let half = (height * 0.5 - radius).max(0.0);
+        let rings = (segments / 4).max(1);
+        // From the top pole down to the bottom one, v following the length
+        let length = f64::consts::PI * radius + 2.0 * half;
+        let mut profile = Vec::with_capacity(2 * rings + 2);
+        for i in 0..rings + 1 {
+            let angle = f64::consts::FRAC_PI_2 * i as f64 / rings as f64;
+            let normal = Vector2::new(angle.sin(), angle.cos());
+            let v = radius * angle / length;
+            profile.push((normal * radius + Vector2::new(0.0, half), normal, v));
+        }
+        for i in 0..rings + 1 {
+            let angle = f64::consts::FRAC_PI_2 * (1.0 + i as f64 / rings as f64);
+            let normal = Vector2::new(angle.sin(), angle.cos());
+            let v = (radius * angle + 2.0 * half) / length;
+            profile.push((normal * radius - Vector2::new(0.0, half), normal, v));
         }
+        let mut mesh = Mesh::new("Capsule");
+        revolve(&mut mesh, &profile, segments);
+        mesh.compute_tangents();
+        mesh
     }
 
-    pub fn torus(radius: f64, ring_radius: f64, sides: u32, rings: u32) -> Mesh {
-        let vertices_per_row = sides + 1;
-        let vertices_per_col = rings + 1;
-
-        let vertex_count = vertices_per_row * vertices_per_col;
-        let mut vertices = Vec::with_capacity(vertex_count as usize);
-
-        let vertical_angle = (f64::consts::PI * 2.0) / rings as f64;
-        let horizontal_angle = (f64::consts::PI * 2.0) / sides as f64;
-
-        for v in 0..vertices_per_col {
-            let theta = vertical_angle * v as f64;
-            for h in 0..vertices_per_row {
-                let phi = horizontal_angle * h as f64;
-                let x = theta.cos() * (radius + ring_radius * phi.cos());
-                let y = theta.sin() * (radius + ring_radius * phi.cos());
-                let z = ring_radius * phi.sin();
-                vertices.push(Vector3::new(x, y, z))
+    // Revolves the profile, points as distance from the y axis and height,
+    // around the axis in the segments. Listed from the top down along the
+    // outside, like a vase from its rim to its foot, the surface faces away
+    // from the axis. The normals are smooth, but for a sharp corner at a
+    // repeated point. The texture wraps around once, with v following the
+    // length of the profile.
+    pub fn lathe(profile: &[Vector2], segments: usize) -> Mesh {
+        let length: f64 = profile.windows(2).map(|w| (w[1] - w[0]).length()).sum();
+        let mut rows = Vec::with_capacity(profile.len());
+        let mut along = 0.0;
+        for (i, &point) in profile.iter().enumerate() {
+            if i > 0 {
+                along += (point - profile[i - 1]).length();
             }
+            // Turned right from the segments on either side
+            let side = |d: Vector2| {
+                let n = Vector2::new(-d.y, d.x);
+                if n.length_sqr() > 0.0 { n * (1.0 / n.length()) } else { n }
+            };
+            let before = if i > 0 { side(point - profile[i - 1]) } else { Vector2::zero() };
+            let after = profile.get(i + 1).map_or(Vector2::zero(), |&next| side(next - point));
+            let normal = if before.length_sqr() == 0.0 || after.length_sqr() == 0.0 {
+                before + after
+            } else {
+                let n = before + after;
+                n * (1.0 / n.length())
+            };
+            rows.push((point, normal, if length > 0.0 { along / length } else { 0.0 }));
         }
+        let mut mesh = Mesh::new("Lathe");
+        revolve(&mut mesh, &rows, segments);
+        mesh.compute_tangents();
+        mesh
+    }
 
-        let face_count = sides * rings * 6;
-        let mut faces = Vec::with_capacity(face_count as usize);
-
-        for v in 0..rings {
-            for h in 0..sides {
-                let lt = h + v * vertices_per_row;
-                let rt = (h + 1) + v * vertices_per_row;
-                let lb = h + (v + 1) * vertices_per_row;
-                let rb = (h + 1) + (v + 1) * vertices_per_row;
+    // A sphere of near equal triangles, from an icosahedron with each face
+    // split in four the number of times, the new vertices pushed out onto
+    // the sphere. Textured like Mesh::sphere but around the y axis, with v
+    // down from the top.
+    pub fn icosphere(radius: f64, subdivisions: usize) -> Mesh {
+        let (mut positions, mut faces) = icosahedron();
+        for _ in 0..subdivisions {
+            let mut midpoints = HashMap::new();
+            let mut split = Vec::with_capacity(faces.len() * 4);
+            for &[a, b, c] in &faces {
+                let mut midpoint = |a: u32, b: u32| {
+                    *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                        let p = (positions[a as usize] + positions[b as usize]).normalize();
+                        positions.push(p);
+                        positions.len() as u32 - 1
+                    })
+                };
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                split.extend_from_slice(&[[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]);
+            }
+            faces = split;
+        }
 
-                faces.push(Face::new(lt, rt, lb));
-                faces.push(Face::new(rt, rb, lb))
+        let mut mesh = Mesh::new("Icosphere");
+        for &p in &positions {
+            let u = p.z.atan2(p.x) / (2.0 * f64::consts::PI);
+            let uv = Vector2::new(if u < 0.0 { u + 1.0 } else { u }, p.y.acos() / f64::consts::PI);
+            mesh.add_vertex(Vertex::new(p * radius).with_normal(p).with_uv(uv));
+        }
+        // Faces across the seam where u wraps around reach past 1 instead,
+        // on copies of the vertices at the start. A pole has every u, each
+        // face taking the one between its other corners.
+        let mut wrapped = HashMap::new();
+        for face in &mut faces {
+            let us: Vec<f64> = face.iter().map(|&i| mesh.vertices[i as usize].uv.x).collect();
+            let seam = us.iter().cloned().fold(0.0, f64::max) -
+                       us.iter().cloned().fold(1.0, f64::min) > 0.5;
+            for index in face.iter_mut() {
+                let vertex = mesh.vertices[*index as usize];
+                if seam && vertex.uv.x < 0.5 && !is_pole(vertex.normal) {
+                    *index = *wrapped.entry(*index).or_insert_with(|| {
+                        let uv = Vector2::new(vertex.uv.x + 1.0, vertex.uv.y);
+                        mesh.add_vertex(vertex.with_uv(uv))
+                    });
+                }
+            }
+            for i in 0..3 {
+                let vertex = mesh.vertices[face[i] as usize];
+                if is_pole(vertex.normal) {
+                    let (b, c) = (face[(i + 1) % 3] as usize, face[(i + 2) % 3] as usize);
+                    let u = (mesh.vertices[b].uv.x + mesh.vertices[c].uv.x) * 0.5;
+                    face[i] = mesh.add_vertex(vertex.with_uv(Vector2::new(u, vertex.uv.y)));
+                }
             }
         }
+        for &[a, b, c] in &faces {
+            mesh.add_face(a, b, c);
+        }
+        mesh.compute_tangents();
+        mesh
+    }
+}
 
-        Mesh {
-            name: "Torus".to_string(),
-            vertices: vertices,
-            faces: faces,
-            position: Vector3::zero(),
-            rotation: Vector3::zero(),
-            scale: Vector3::one(),
+// Twelve unit vertices and twenty faces wound outwards.
+fn icosahedron() -> (Vec<Vector3>, Vec<[u32; 3]>) {
+    let t = (1.0 + 5f64.sqrt()) * 0.5;
+    let positions = [(-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+                     (0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+                     (t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0)]
+        .iter()
+        .map(|&(x, y, z)| Vector3::new(x, y, z).normalize())
+        .collect();
+    let faces = vec![[0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+                     [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+                     [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+                     [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1]];
+    (positions, faces)
+}
+
+// On the y axis, where the direction has no longitude.
+fn is_pole(direction: Vector3) -> bool {
+    direction.x.abs() < 1e-9 && direction.z.abs() < 1e-9
+}
+
+// Sweeps the profile, points and normals given as distance from the y axis
+// and height, around the axis. Rows of the profile go from the top down,
+// with their v texture coordinate, and u goes once around. The first and
+// last column meet at a seam.
+fn revolve(mesh: &mut Mesh, profile: &[(Vector2, Vector2, f64)], segments: usize) {
+    let segments = segments.max(3);
+    let first = mesh.vertices.len() as u32;
+    for &(point, normal, v) in profile {
+        for i in 0..segments + 1 {
+            let u = i as f64 / segments as f64;
+            let (sin, cos) = (u * 2.0 * f64::consts::PI).sin_cos();
+            mesh.add_vertex(Vertex::new(Vector3::new(point.x * cos, point.y, point.x * sin))
+                .with_normal(Vector3::new(normal.x * cos, normal.y, normal.x * sin))
+                .with_uv(Vector2::new(u, v)));
+        }
+    }
+    let columns = segments as u32 + 1;
+    for row in 0..profile.len().saturating_sub(1) as u32 {
+        for i in 0..segments as u32 {
+            let top = first + row * columns + i;
+            let bottom = top + columns;
+            mesh.add_face(bottom, top, bottom + 1);
+            mesh.add_face(bottom + 1, top, top + 1);
         }
     }
+}
 
-    pub fn tetrahedron(radius: f64) -> Mesh {
-        let angle = 2.0 * f64::consts::PI / 3.0;
-        let peak = (2.0 * radius).sqrt() / 2.0;
-        let mut vertices = Vec::new();
-        for i in 0..3 {
-            let t = radius * (i as f64 * angle);
-            let x = t.cos();
-            let y = t.sin();
-            vertices.push(Vector3::new(x, y, -peak))
+// A disc closing a revolved surface at the height, facing away from the
+// origin.
+fn cap(mesh: &mut Mesh, radius: f64, y: f64, segments: usize) {
+    let segments = segments.max(3);
+    let normal = Vector3::new(0.0, y.signum(), 0.0);
+    let center = mesh.add_vertex(Vertex::new(Vector3::new(0.0, y, 0.0))
+        .with_normal(normal)
+        .with_uv(Vector2::new(0.5, 0.5)));
+    for i in 0..segments {
+        let (sin, cos) = (i as f64 / segments as f64 * 2.0 * f64::consts::PI).sin_cos();
+        mesh.add_vertex(Vertex::new(Vector3::new(radius * cos, y, radius * sin))
+            .with_normal(normal)
+            .with_uv(Vector2::new(0.5 + cos * 0.5, 0.5 + sin * 0.5)));
+    }
+    for i in 0..segments as u32 {
+        let (a, b) = (center + 1 + i, center + 1 + (i + 1) % segments as u32);
+        if y < 0.0 {
+            mesh.add_face(center, a, b);
+        } else {
+            mesh.add_face(center, b, a);
         }
-        vertices.push(Vector3::new(0.0, 0.0, peak));
-        Mesh {
-            name: "Tetrahedron".to_string(),
-            vertices: vertices,
-            faces: vec![
-                Face::new(0, 1, 2),
-                Face::new(0, 1, 3),
-                Face::new(1, 2, 3),
-                Face::new(2, 0, 3),
-            ],
-            position: Vector3::zero(),
-            rotation: Vector3::zero(),
-            scale: Vector3::one(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::{Vector2, Vector3};
+    use render::color::Color;
+    use super::{Indices, Mesh, Vertex};
+
+    #[test]
+    fn indices() {
+        let mut mesh = Mesh::cube();
+        assert_eq!(12, mesh.face_count());
+        assert_eq!(36 * 2, mesh.indices.size_in_bytes());
+        assert_eq!((6, 2, 1), (mesh.face(3).a, mesh.face(3).b, mesh.face(3).c));
+
+        // Promoted once there are more vertices than 16 bits index
+        while mesh.vertices.len() <= 65536 {
+            mesh.add_vertex(Vertex::new(Vector3::zero()));
         }
+        assert_eq!(36 * 4, mesh.indices.size_in_bytes());
+        assert_eq!((6, 2, 1), (mesh.face(3).a, mesh.face(3).b, mesh.face(3).c));
+        mesh.add_face(0, 1, 65536);
+        assert_eq!(65536, mesh.face(12).c);
+
+        let mut indices = Indices::from_u32(vec![0, 1, 2]);
+        assert_eq!(Indices::U16(vec![0, 1, 2]), indices);
+        indices.push(70000);
+        assert_eq!(Indices::U32(vec![0, 1, 2, 70000]), indices);
     }
 
-    pub fn octahedron(radius: f64) -> Mesh {
-        let angle = f64::consts::PI / 2.0;
-        let mut vertices = Vec::new();
-        for i in 0..4 {
-            let t = radius * (i as f64 * angle);
-            let x = t.cos();
-            let y = t.sin();
-            vertices.push(Vector3::new(x, y, 0.0))
+    #[test]
+    fn build() {
+        let mut mesh = Mesh::new("Quad");
+        let red = Color::rgb(1.0, 0.0, 0.0);
+        for &(x, y) in &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+            let vertex = Vertex::new(Vector3::new(x, y, 0.0)).with_uv(Vector2::new(x, 1.0 - y));
+            mesh.add_vertex(if x > 0.0 { vertex.with_color(red) } else { vertex });
         }
-        let top = Vector3::new(0.0, 0.0, radius);
-        vertices.push(top);
-        let bot = Vector3::new(0.0, 0.0, -radius);
-        vertices.push(bot);
-        Mesh {
-            name: "Octahedron".to_string(),
-            vertices: vertices,
-            faces: vec![
-                Face::new(0, 1, 4),
-                Face::new(1, 2, 4),
-                Face::new(2, 3, 4),
-                Face::new(3, 0, 4),
-                Face::new(0, 1, 5),
-                Face::new(1, 2, 5),
-                Face::new(2, 3, 5),
-                Face::new(3, 0, 5),
-            ],
-            position: Vector3::zero(),
-            rotation: Vector3::zero(),
-            scale: Vector3::one(),
+        mesh.add_face(0, 1, 2);
+        mesh.add_face(2, 3, 0);
+        assert_eq!(4, mesh.vertices.len());
+        assert!(mesh.has_uvs() && mesh.has_colors() && !mesh.has_normals());
+        assert_eq!((Vector3::zero(), Vector3::new(1.0, 1.0, 0.0)), mesh.bounds());
+
+        mesh.compute_normals();
+        mesh.compute_tangents();
+        assert!(mesh.vertices.iter().all(|v| v.normal == Vector3::unit_z()));
+        assert!(mesh.has_tangents());
+    }
+
+    #[test]
+    fn plane() {
+        let plane = Mesh::plane(4.0, 2.0, 4, 2);
+        assert_eq!(15, plane.vertices.len());
+        assert_eq!(16, plane.face_count());
+        assert_eq!((Vector3::new(-2.0, 0.0, -1.0), Vector3::new(2.0, 0.0, 1.0)), plane.bounds());
+        let last = plane.vertices[14];
+        assert_eq!((Vector3::new(2.0, 0.0, 1.0), Vector2::new(1.0, 1.0)), (last.position, last.uv));
+        // Wound to face the way the normals point
+        let mut computed = Mesh::plane(4.0, 2.0, 4, 2);
+        computed.compute_normals();
+        for (v, c) in plane.vertices.iter().zip(&computed.vertices) {
+            assert_eq!(Vector3::unit_y(), v.normal);
+            assert_eq!(v.normal, c.normal);
+            assert_eq!(Vector3::unit_x(), v.tangent.xyz());
         }
+
+        let grid = Mesh::grid(10.0, 5, 0.1);
+        // A strip each way for each of the 6 lines
+        assert_eq!(24, grid.face_count());
+        assert_eq!((Vector3::new(-5.05, 0.0, -5.05), Vector3::new(5.05, 0.0, 5.05)), grid.bounds());
     }
 
-    pub fn sphere(pivot: Vector3, radius: f64, slices: usize, stacks: usize) -> Mesh {
+    #[test]
+    fn revolved() {
+        // Wound to face the way the normals point, away from the axis
+        let outward = |mesh: &Mesh| {
+            for face in mesh.faces() {
+                let (a, b, c) = (face.a as usize, face.b as usize, face.c as usize);
+                let p = mesh.vertices[a].position;
+                let normal = (mesh.vertices[b].position - p).cross(mesh.vertices[c].position - p);
+                if normal.length_sqr() > 1e-12 {
+                    let center = (p + mesh.vertices[b].position + mesh.vertices[c].position) *
+                                 (1.0 / 3.0);
+                    assert!(normal.dot(center) > 0.0, "{} {:?}", mesh.name, face);
+                    assert!(normal.dot(mesh.vertices[a].normal) > 0.0, "{}", mesh.name);
+                }
+            }
+        };
 
-        let hori_vertex_count = slices + 1;
-        let vert_vertex_count = stacks + 1;
+        let cylinder = Mesh::cylinder(1.0, 2.0, 8);
+        outward(&cylinder);
+        // The side, then the caps
+        assert_eq!(8 * 2 + 8 * 2, cylinder.face_count());
+        let (min, max) = cylinder.bounds();
+        assert!((min - Vector3::new(-1.0, -1.0, -1.0)).length() < 1e-9);
+        assert!((max - Vector3::new(1.0, 1.0, 1.0)).length() < 1e-9);
 
-        let body_vertex_count = hori_vertex_count * vert_vertex_count;
-        let body_face_count = slices * stacks * 2;
+        let cone = Mesh::cone(1.0, 2.0, 8);
+        outward(&cone);
+        let slant = Vector3::new(2.0, 1.0, 0.0).normalize();
+        assert!((cone.vertices[0].normal - slant).length() < 1e-9);
 
-        let mut vertices: Vec<Vector3> = Vec::with_capacity(body_vertex_count);
-        let mut faces: Vec<Face> = Vec::with_capacity(body_face_count);
+        let capsule = Mesh::capsule(0.5, 3.0, 16);
+        outward(&capsule);
+        let (min, max) = capsule.bounds();
+        assert!((min.y + 1.5).abs() < 1e-9 && (max.y - 1.5).abs() < 1e-9);
+        for v in &capsule.vertices {
+            assert!((v.normal.length() - 1.0).abs() < 1e-9);
+            // On the rounded ends the normals point from the centers
+            let center = Vector3::new(0.0, v.position.y.clamp(-1.0, 1.0), 0.0);
+            assert!((center + v.normal * 0.5 - v.position).length() < 1e-9);
+        }
+        assert_eq!(0.0, capsule.vertices[0].uv.y);
+        assert_eq!(1.0, capsule.vertices.last().unwrap().uv.y);
+    }
 
-        for j in 0..vert_vertex_count {
-            for i in 0..hori_vertex_count {
+    #[test]
+    fn platonic_solids() {
+        let solids = [(Mesh::tetrahedron(2.0), 4, 4),
+                      (Mesh::octahedron(2.0), 6, 8),
+                      (Mesh::icosahedron(2.0), 12, 20),
+                      (Mesh::dodecahedron(2.0), 20, 36)];
+        for &(ref mesh, vertices, faces) in &solids {
+            assert_eq!((vertices, faces), (mesh.vertices.len(), mesh.face_count()));
+            for v in &mesh.vertices {
+                assert!((v.position.length() - 2.0).abs() < 1e-9, "{}", mesh.name);
+            }
+            // Every edge is as long as the others
+            let edge = |a: u32, b: u32| {
+                (mesh.vertices[a as usize].position - mesh.vertices[b as usize].position).length()
+            };
+            let shortest = mesh.faces()
+                .map(|f| edge(f.a, f.b).min(edge(f.b, f.c)).min(edge(f.c, f.a)))
+                .fold(f64::INFINITY, f64::min);
+            for face in mesh.faces() {
+                let (a, b, c) = (mesh.vertices[face.a as usize].position,
+                                 mesh.vertices[face.b as usize].position,
+                                 mesh.vertices[face.c as usize].position);
+                assert!((b - a).cross(c - a).dot(a + b + c) > 0.0, "{} {:?}", mesh.name, face);
+                if mesh.name != "Dodecahedron" {
+                    assert!((edge(face.a, face.b) - shortest).abs() < 1e-9, "{}", mesh.name);
+                }
+            }
+        }
+    }
 
-                let mut u = i as f64 / slices as f64;
-                let mut v = j as f64 / stacks as f64;
+    #[test]
+    fn torus_knot() {
+        let knot = Mesh::torus_knot(2, 3, 2.0, 0.25, 64, 8);
+        assert_eq!(2 * 64 * 8, knot.face_count());
+        // Closed, the last ring over the first
+        let (first, last) = (knot.vertices[0], knot.vertices[64 * 9]);
+        assert!((first.position - last.position).length() < 1e-9);
+        for face in knot.faces() {
+            let (a, b, c) = (knot.vertices[face.a as usize],
+                             knot.vertices[face.b as usize],
+                             knot.vertices[face.c as usize]);
+            let normal = (b.position - a.position).cross(c.position - a.position);
+            assert!(normal.dot(a.normal) > 0.0);
+            assert!((a.normal.length() - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn lathe() {
+        // A cup, its side meeting the bottom at a sharp corner
+        let profile = [Vector2::new(1.0, 1.0),
+                       Vector2::new(1.0, 0.0),
+                       Vector2::new(1.0, 0.0),
+                       Vector2::new(0.0, 0.0)];
+        let cup = Mesh::lathe(&profile, 8);
+        assert_eq!(4 * 9, cup.vertices.len());
+        assert_eq!(3 * 8 * 2, cup.face_count());
+        assert_eq!((Vector3::unit_x(), 0.0), (cup.vertices[0].normal, cup.vertices[0].uv.y));
+        assert_eq!(Vector3::unit_x(), cup.vertices[9].normal);
+        assert_eq!(-1.0, cup.vertices[18].normal.y);
+        assert_eq!((0.5, 1.0), (cup.vertices[18].uv.y, cup.vertices[27].uv.y));
+        for face in cup.faces() {
+            let (a, b, c) = (cup.vertices[face.a as usize],
+                             cup.vertices[face.b as usize],
+                             cup.vertices[face.c as usize]);
+            let normal = (b.position - a.position).cross(c.position - a.position);
+            assert!(normal.length_sqr() < 1e-12 || normal.dot(c.normal) > 0.0);
+        }
 
-                u *= 2.0 * f64::consts::PI;
-                v = v * f64::consts::PI - f64::consts::PI * 0.5;
+        // Smooth over a bend, halfway between the sides
+        let bowl = Mesh::lathe(&[Vector2::new(1.0, 1.0),
+                                 Vector2::new(1.0, 0.0),
+                                 Vector2::new(0.0, -1.0)],
+                               4);
+        let halfway = (Vector3::unit_x() + Vector3::new(1.0, -1.0, 0.0).normalize()).normalize();
+        assert!((bowl.vertices[5].normal - halfway).length() < 1e-9);
+    }
+
+    #[test]
+    fn icosphere() {
+        let sphere = Mesh::icosphere(2.0, 2);
+        assert_eq!(20 * 16, sphere.face_count());
+        for v in &sphere.vertices {
+            assert!((v.position.length() - 2.0).abs() < 1e-9);
+            assert!((v.position * 0.5 - v.normal).length() < 1e-9);
+        }
+        // Each triangle is wound outwards and spans little of the texture,
+        // the seam and poles included
+        for face in sphere.faces() {
+            let (a, b, c) = (sphere.vertices[face.a as usize],
+                             sphere.vertices[face.b as usize],
+                             sphere.vertices[face.c as usize]);
+            let normal = (b.position - a.position).cross(c.position - a.position);
+            assert!(normal.dot(a.position) > 0.0);
+            for &(p, q) in &[(a.uv, b.uv), (b.uv, c.uv), (c.uv, a.uv)] {
+                assert!((p - q).length() < 0.3, "{:?} {:?}", p, q);
+            }
+        }
+    }
 
-                vertices.push(pivot +
-                              Vector3::new(v.cos() * u.cos() * radius,
-                                           v.cos() * u.sin() * radius,
-                                           v.sin() * radius));
+    #[test]
+    fn from_parametric() {
+        let sphere = Mesh::sphere(Vector3::zero(), 2.0, 16, 8);
+        assert_eq!((17 * 9, 16 * 8 * 2), (sphere.vertices.len(), sphere.face_count()));
+        // Smooth across the seam, pointing along the radius but at the poles
+        for v in &sphere.vertices {
+            if v.position.z.abs() < 1.9 {
+                assert!(v.normal.dot(v.position.normalize()) > 0.99);
             }
         }
+        assert_eq!(sphere.vertices[16 + 17 * 4].normal, sphere.vertices[17 * 4].normal);
 
-        for i in 0..slices {
-            for j in 0..stacks {
-                faces.push(Face::new(((i + j * hori_vertex_count) + 0) as u32,
-                                     ((i + j * hori_vertex_count) + 1) as u32,
-                                     ((i + (j + 1) * hori_vertex_count) + 0) as u32));
+        // Wrapped both ways, like a torus
+        let torus = Mesh::torus(2.0, 0.5, 8, 16);
+        let (first, last) = (torus.vertices[0], torus.vertices[9 * 17 - 1]);
+        assert!((first.position - last.position).length() < 1e-9);
+        assert_eq!(first.normal, last.normal);
+        assert_eq!((Vector2::zero(), Vector2::new(1.0, 1.0)), (first.uv, last.uv));
+        assert!(first.normal.cross(Vector3::unit_x()).length() < 1e-9);
+    }
 
-                faces.push(Face::new(((i + j * hori_vertex_count) + 1) as u32,
-                                     ((i + (j + 1) * hori_vertex_count) + 1) as u32,
-                                     ((i + (j + 1) * hori_vertex_count) + 0) as u32));
+    #[test]
+    fn compute_normals() {
+        let mut mesh = Mesh::sphere(Vector3::zero(), 2.0, 16, 8);
+        mesh.compute_normals();
+        assert!(mesh.has_normals() && mesh.has_uvs());
+        // Smooth normals of a sphere point along the radius, away from the
+        // poles where the faces are degenerate. The duplicated vertices along
+        // the seam only see the faces on one side and lean a little.
+        for v in &mesh.vertices {
+            let (p, n) = (v.position, v.normal);
+            assert!((n.length() - 1.0).abs() < 1e-9);
+            if p.z.abs() < 1.9 {
+                assert!(n.dot(p.normalize()).abs() > 0.95);
             }
         }
+    }
+    #[test]
+    fn compute_tangents() {
+        // Facing +z with u to the right and v down
+        let mut mesh = Mesh::triangle();
+        let uvs = [Vector2::new(0.0, 1.0), Vector2::new(1.0, 1.0), Vector2::new(1.0, 0.0)];
+        for (v, &uv) in mesh.vertices.iter_mut().zip(&uvs) {
+            v.uv = uv;
+        }
+        mesh.compute_tangents();
+        assert!(!mesh.has_tangents());
+        mesh.compute_normals();
+        mesh.compute_tangents();
+        for v in &mesh.vertices {
+            assert_eq!(Vector3::unit_x(), v.tangent.xyz());
+            assert_eq!(1.0, v.tangent.w);
+        }
 
-        Mesh {
-            name: "Sphere".to_string(),
-            vertices: vertices,
-            faces: faces,
-            position: Vector3::zero(),
-            rotation: Vector3::zero(),
-            scale: Vector3::one(),
+        // Mirrored texture coordinates flip the handedness
+        for v in &mut mesh.vertices {
+            v.uv.y = 1.0 - v.uv.y;
         }
+        mesh.compute_tangents();
+        let t = |v: &Vertex| v.tangent;
+        assert!(mesh.vertices.iter().map(t).all(|t| t.xyz() == Vector3::unit_x() && t.w == -1.0));
     }
 }
@@ -1 +1,19 @@
+pub mod decimate;
+pub mod extrude;
+pub mod gltf;
+pub mod half_edge;
+pub mod hull;
+pub mod lod;
+pub mod marching_cubes;
+pub mod merge;
 pub mod mesh;
+pub mod modifier;
+pub mod morph;
+pub mod obj;
+pub mod ply;
+pub mod point_cloud;
+pub mod ray;
+pub mod stl;
+pub mod subdivide;
+pub mod voxel;
+pub mod weld;
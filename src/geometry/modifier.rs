@@ -0,0 +1,237 @@
+
+use std::f64;
+
+use super::super::math::vector::{Vector3, Vector4};
+use super::mesh::{Mesh, Vertex};
+
+// Deformations of a mesh's vertices in its own space, applied in order
+// when it is drawn, after any blend shapes and before its transform. The
+// vertices themselves are left as they are, so changing a modifier from
+// frame to frame animates it, e.g. the phase of a wave.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Modifier {
+    // Turns each point around the y axis by angle radians for each unit of
+    // its height.
+    Twist { angle: f64 },
+    // Curls the x axis up into a circle, turning angle radians for each unit
+    // along it, with the origin staying put.
+    Bend { angle: f64 },
+    // Raises points by a sine wave running along the direction, its crests
+    // wavelength apart. Moving the phase makes the waves travel.
+    Wave {
+        amplitude: f64,
+        wavelength: f64,
+        direction: Vector3,
+        phase: f64,
+    },
+    // Pushes points along their normals by smooth noise, up to amplitude
+    // either way, with features 1 / frequency apart. Points without a normal
+    // go away from the origin instead. Vertices only share a position
+    // afterwards if they share a normal, so hard edges split.
+    Noise {
+        amplitude: f64,
+        frequency: f64,
+        seed: u32,
+    },
+}
+
+impl Modifier {
+    pub fn deform(&self, position: Vector3, normal: Vector3) -> Vector3 {
+        match *self {
+            Modifier::Twist { angle } => {
+                let (sin, cos) = (angle * position.y).sin_cos();
+                Vector3::new(position.x * cos + position.z * sin,
+                             position.y,
+                             position.z * cos - position.x * sin)
+            }
+            Modifier::Bend { angle } => {
+                if angle == 0.0 {
+                    return position;
+                }
+                // Around a center a radius above the origin
+                let radius = 1.0 / angle;
+                let (sin, cos) = (angle * position.x).sin_cos();
+                let r = radius - position.y;
+                Vector3::new(r * sin, radius - r * cos, position.z)
+            }
+            Modifier::Wave { amplitude, wavelength, direction, phase } => {
+                let d = position.dot(direction) / wavelength.max(1e-9);
+                let y = amplitude * (d * 2.0 * f64::consts::PI + phase).sin();
+                position + Vector3::new(0.0, y, 0.0)
+            }
+            Modifier::Noise { amplitude, frequency, seed } => {
+                let n = if normal.length_sqr() > 0.0 { normal } else { position };
+                if n.length_sqr() == 0.0 {
+                    return position;
+                }
+                position + n.normalize() * (amplitude * noise(position * frequency, seed))
+            }
+        }
+    }
+
+    // Moves the vertex, turning its normal and tangent with the surface
+    // around it, taken from how the modifier moves nearby points.
+    pub fn apply(&self, vertex: &mut Vertex) {
+        let p = vertex.position;
+        let normal = vertex.normal;
+        let h = 1e-4 * p.length().max(1.0);
+        let axis = |d: Vector3| {
+            (self.deform(p + d, normal) - self.deform(p - d, normal)) * (0.5 / h)
+        };
+        let (dx, dy, dz) = (axis(Vector3::new(h, 0.0, 0.0)),
+                            axis(Vector3::new(0.0, h, 0.0)),
+                            axis(Vector3::new(0.0, 0.0, h)));
+        vertex.position = self.deform(p, normal);
+        // Normals by the inverse transpose of those derivatives, up to scale
+        let direction = |v: Vector3| if v.length_sqr() > 0.0 { v.normalize() } else { v };
+        vertex.normal = direction(dy.cross(dz) * normal.x + dz.cross(dx) * normal.y +
+                                  dx.cross(dy) * normal.z);
+        if vertex.tangent != Vector4::zero() {
+            let t = vertex.tangent;
+            let tangent = direction(dx * t.x + dy * t.y + dz * t.z);
+            vertex.tangent = Vector4::new(tangent.x, tangent.y, tangent.z, t.w);
+        }
+    }
+}
+
+fn hash(x: i64, y: i64, z: i64, seed: u32) -> f64 {
+    let mut h = seed as u64 ^ 0x9e37_79b9_7f4a_7c15;
+    for &v in &[x, y, z] {
+        h ^= v as u64;
+        h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        h ^= h >> 33;
+    }
+    (h >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+}
+
+// Value noise in [-1, 1], random at whole coordinates and smoothly
+// interpolated between them.
+fn noise(p: Vector3, seed: u32) -> f64 {
+    let (x0, y0, z0) = (p.x.floor(), p.y.floor(), p.z.floor());
+    let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+    let (tx, ty, tz) = (smooth(p.x - x0), smooth(p.y - y0), smooth(p.z - z0));
+    let (x0, y0, z0) = (x0 as i64, y0 as i64, z0 as i64);
+    let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+    let corner = |dx: i64, dy: i64, dz: i64| hash(x0 + dx, y0 + dy, z0 + dz, seed);
+    let plane = |dz: i64| {
+        lerp(lerp(corner(0, 0, dz), corner(1, 0, dz), tx),
+             lerp(corner(0, 1, dz), corner(1, 1, dz), tx),
+             ty)
+    };
+    lerp(plane(0), plane(1), tz)
+}
+
+impl Mesh {
+    // Whether drawing the mesh changes its vertices first, by blend shapes
+    // or modifiers.
+    pub fn is_deformed(&self) -> bool {
+        self.is_morphed() || !self.modifiers.is_empty()
+    }
+
+    // The vertices as they are drawn, blended and then modified.
+    pub fn deformed_vertices(&self) -> Vec<Vertex> {
+        let mut vertices = Vec::new();
+        self.deform_into(&mut vertices);
+        vertices
+    }
+
+    // Like deformed_vertices, into the buffer in place of what it held.
+    pub fn deform_into(&self, vertices: &mut Vec<Vertex>) {
+        if self.is_morphed() {
+            self.morph_into(vertices)
+        } else {
+            vertices.clear();
+            vertices.extend_from_slice(&self.vertices);
+        }
+        for modifier in &self.modifiers {
+            for vertex in vertices.iter_mut() {
+                modifier.apply(vertex)
+            }
+        }
+    }
+
+    // Makes the modified vertices the mesh's own and drops the modifiers.
+    pub fn apply_modifiers(&mut self) {
+        let modifiers = self.modifiers.split_off(0);
+        for modifier in modifiers {
+            for vertex in &mut self.vertices {
+                modifier.apply(vertex)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::f64;
+    use math::vector::Vector3;
+    use geometry::mesh::Mesh;
+    use super::Modifier;
+
+    fn check_normals(mesh: &Mesh) {
+        // Smooth normals of the modified shape, recomputed from its faces
+        let mut modified = mesh.clone();
+        modified.vertices = mesh.deformed_vertices();
+        let mut recomputed = modified.clone();
+        recomputed.compute_normals();
+        for (v, r) in modified.vertices.iter().zip(recomputed.vertices.iter()) {
+            // Not on any face
+            if r.normal == Vector3::zero() {
+                continue;
+            }
+            assert!(v.normal.dot(r.normal) > 0.95);
+        }
+    }
+
+    #[test]
+    fn deform() {
+        let twist = Modifier::Twist { angle: f64::consts::PI / 2.0 };
+        let p = twist.deform(Vector3::new(1.0, 1.0, 0.0), Vector3::zero());
+        assert!((p - Vector3::new(0.0, 1.0, -1.0)).length() < 1e-12);
+
+        // A half turn puts the end of a line over its start
+        let bend = Modifier::Bend { angle: f64::consts::PI / 2.0 };
+        let p = bend.deform(Vector3::new(2.0, 0.0, 0.0), Vector3::zero());
+        assert!((p - Vector3::new(0.0, 4.0 / f64::consts::PI, 0.0)).length() < 1e-12);
+        assert_eq!(Vector3::zero(), bend.deform(Vector3::zero(), Vector3::zero()));
+
+        let wave = Modifier::Wave {
+            amplitude: 0.5,
+            wavelength: 4.0,
+            direction: Vector3::unit_x(),
+            phase: 0.0,
+        };
+        let crest = wave.deform(Vector3::new(1.0, 0.0, 3.0), Vector3::unit_y());
+        assert!((crest - Vector3::new(1.0, 0.5, 3.0)).length() < 1e-12);
+
+        let noise = Modifier::Noise { amplitude: 0.2, frequency: 3.0, seed: 7 };
+        let p = noise.deform(Vector3::new(0.0, 2.0, 0.0), Vector3::zero());
+        assert!(p.x == 0.0 && p.z == 0.0 && (p.y - 2.0).abs() <= 0.2);
+    }
+
+    #[test]
+    fn modified_normals() {
+        let mut water = Mesh::plane(8.0, 8.0, 32, 32);
+        water.modifiers.push(Modifier::Wave {
+            amplitude: 0.3,
+            wavelength: 3.0,
+            direction: Vector3::new(1.0, 0.0, 1.0).normalize(),
+            phase: 0.5,
+        });
+        assert!(water.is_deformed());
+        check_normals(&water);
+
+        let mut rock = Mesh::icosphere(1.0, 3);
+        rock.compute_normals();
+        rock.modifiers = vec![Modifier::Noise { amplitude: 0.1, frequency: 2.0, seed: 1 },
+                              Modifier::Twist { angle: 0.5 }];
+        check_normals(&rock);
+
+        // Baked, the same as drawn
+        let deformed = rock.deformed_vertices();
+        rock.apply_modifiers();
+        assert!(!rock.is_deformed());
+        assert_eq!(deformed, rock.vertices);
+    }
+}
@@ -0,0 +1,142 @@
+
+use super::super::math::vector::Vector3;
+use super::mesh::{Mesh, Vertex};
+
+// A shape the mesh can blend towards, as offsets from its vertices, e.g. a
+// smile on a face. Normal offsets are optional and left empty for a shape
+// whose lighting can stay as it is.
+#[derive(Debug,Clone,PartialEq)]
+pub struct MorphTarget {
+    pub name: String,
+    pub positions: Vec<Vector3>,
+    pub normals: Vec<Vector3>,
+}
+
+impl MorphTarget {
+    pub fn new(name: &str, positions: Vec<Vector3>) -> MorphTarget {
+        MorphTarget {
+            name: name.to_string(),
+            positions: positions,
+            normals: Vec::new(),
+        }
+    }
+
+    // The offsets from the base's vertices to the shape's, which has them
+    // in the same order, e.g. a copy of the base that has been deformed.
+    pub fn from_shape(name: &str, base: &Mesh, shape: &Mesh) -> MorphTarget {
+        let offsets = |f: &dyn Fn(&Vertex) -> Vector3| {
+            base.vertices.iter().zip(shape.vertices.iter()).map(|(b, s)| f(s) - f(b)).collect()
+        };
+        MorphTarget {
+            name: name.to_string(),
+            positions: offsets(&|v| v.position),
+            normals: if base.has_normals() { offsets(&|v| v.normal) } else { Vec::new() },
+        }
+    }
+}
+
+// Blend shapes: each target is added to the vertices by its weight in
+// morph_weights when the mesh is drawn, leaving the vertices themselves as
+// they are. Targets made before the vertices changed in number, e.g. by
+// weld or subdivide, no longer line up and are skipped.
+
+impl Mesh {
+    // The target's index, for its weight. Starts at a weight of 0.
+    pub fn add_morph_target(&mut self, target: MorphTarget) -> usize {
+        self.morph_targets.push(target);
+        self.morph_weights.resize(self.morph_targets.len(), 0.0);
+        self.morph_targets.len() - 1
+    }
+
+    pub fn morph_target_index(&self, name: &str) -> Option<usize> {
+        self.morph_targets.iter().position(|t| t.name == name)
+    }
+
+    // Whether any target has a weight, so drawing blends the vertices.
+    pub fn is_morphed(&self) -> bool {
+        self.morph_weights
+            .iter()
+            .zip(self.morph_targets.iter())
+            .any(|(&w, t)| w != 0.0 && t.positions.len() == self.vertices.len())
+    }
+
+    // The vertices with the targets added by their weights. Blended normals
+    // are normalized again, and the other attributes are left.
+    pub fn morphed_vertices(&self) -> Vec<Vertex> {
+        let mut vertices = Vec::new();
+        self.morph_into(&mut vertices);
+        vertices
+    }
+
+    // Like morphed_vertices, into the buffer in place of what it held, so
+    // one kept between frames is only allocated once.
+    pub fn morph_into(&self, vertices: &mut Vec<Vertex>) {
+        vertices.clear();
+        vertices.extend_from_slice(&self.vertices);
+        for (target, &weight) in self.morph_targets.iter().zip(self.morph_weights.iter()) {
+            if weight == 0.0 || target.positions.len() != vertices.len() {
+                continue;
+            }
+            for (vertex, &offset) in vertices.iter_mut().zip(target.positions.iter()) {
+                vertex.position = vertex.position + offset * weight;
+            }
+            for (vertex, &offset) in vertices.iter_mut().zip(target.normals.iter()) {
+                vertex.normal = vertex.normal + offset * weight;
+            }
+        }
+        for (vertex, base) in vertices.iter_mut().zip(self.vertices.iter()) {
+            if base.normal != Vector3::zero() && vertex.normal.length_sqr() > 0.0 {
+                vertex.normal = vertex.normal.normalize();
+            }
+        }
+    }
+
+    // Makes the current blend the mesh's vertices and drops the targets.
+    pub fn apply_morph(&mut self) {
+        self.vertices = self.morphed_vertices();
+        self.morph_targets.clear();
+        self.morph_weights.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use geometry::mesh::Mesh;
+    use super::MorphTarget;
+
+    #[test]
+    fn blend() {
+        let mut mesh = Mesh::plane(2.0, 2.0, 1, 1);
+        let mut raised = mesh.clone();
+        for v in &mut raised.vertices {
+            v.position.y += 1.0;
+        }
+        let mut tilted = mesh.clone();
+        tilted.vertices[0].position.x += 2.0;
+        tilted.vertices[0].normal = Vector3::new(1.0, 1.0, 0.0).normalize();
+        let up = mesh.add_morph_target(MorphTarget::from_shape("Up", &mesh, &raised));
+        let tilt = mesh.add_morph_target(MorphTarget::from_shape("Tilt", &mesh, &tilted));
+        assert_eq!((Some(1), None), (mesh.morph_target_index("Tilt"), mesh.morph_target_index("?")));
+        assert!(!mesh.is_morphed());
+        assert_eq!(mesh.vertices, mesh.morphed_vertices());
+
+        mesh.morph_weights[up] = 0.5;
+        mesh.morph_weights[tilt] = 1.0;
+        assert!(mesh.is_morphed());
+        let morphed = mesh.morphed_vertices();
+        let base = mesh.vertices[0].position;
+        assert_eq!(base + Vector3::new(2.0, 0.5, 0.0), morphed[0].position);
+        assert!((tilted.vertices[0].normal - morphed[0].normal).length() < 1e-12);
+        assert_eq!(mesh.vertices[1].position + Vector3::new(0.0, 0.5, 0.0), morphed[1].position);
+
+        // Baked, and skipped once the vertices no longer match
+        let mut baked = mesh.clone();
+        baked.apply_morph();
+        assert_eq!((morphed, 0), (baked.vertices, baked.morph_targets.len()));
+        mesh.morph_targets[up].positions.pop();
+        mesh.morph_weights[tilt] = 0.0;
+        assert!(!mesh.is_morphed());
+    }
+}
@@ -0,0 +1,230 @@
+
+use super::super::error::{Result, SwrError};
+use super::super::math::vector::{Vector2, Vector3};
+use super::super::render::color::Color;
+use super::mesh::{Mesh, Vertex};
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+// Wavefront OBJ, readable by nearly every modeling tool. Vertices are
+// written in the mesh's own space, without its transform, along with the
+// normals, texture coordinates and colors it has. Colors follow the
+// position of each vertex, an extension Blender and MeshLab read.
+//
+// Reading makes a vertex for each different combination of position,
+// texture coordinate and normal the faces use, and splits polygons into
+// fans of triangles. Every object and group in the file goes into the one
+// mesh, and materials, lines and curves are skipped.
+
+impl Mesh {
+    // Named after the file.
+    pub fn from_obj<P: AsRef<Path>>(path: P) -> Result<Mesh> {
+        let path = path.as_ref();
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        let name = path.file_stem().map_or(String::new(), |s| s.to_string_lossy().into_owned());
+        Mesh::read_obj(&text, &name)
+    }
+
+    pub fn read_obj(text: &str, name: &str) -> Result<Mesh> {
+        let mut mesh = Mesh::new(name);
+        let (mut positions, mut colors) = (Vec::new(), Vec::new());
+        let (mut uvs, mut normals) = (Vec::new(), Vec::new());
+        let mut indices: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+        let mut has_normals = false;
+
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+            let keyword = words.next();
+            let numbers = |words: ::std::str::SplitWhitespace| -> Result<Vec<f64>> {
+                words.map(|w| w.parse().map_err(|_| invalid("OBJ number expected"))).collect()
+            };
+            match keyword {
+                Some("v") => {
+                    let v = numbers(words)?;
+                    if v.len() < 3 {
+                        return Err(invalid("OBJ vertex without a position"));
+                    }
+                    positions.push(Vector3::new(v[0], v[1], v[2]));
+                    colors.push(if v.len() >= 6 {
+                        Color::rgb(v[3], v[4], v[5])
+                    } else {
+                        Color::white()
+                    });
+                }
+                Some("vt") => {
+                    let v = numbers(words)?;
+                    // Up the texture, where v is down here
+                    uvs.push(Vector2::new(v.first().cloned().unwrap_or(0.0),
+                                          1.0 - v.get(1).cloned().unwrap_or(0.0)));
+                }
+                Some("vn") => {
+                    let v = numbers(words)?;
+                    if v.len() < 3 {
+                        return Err(invalid("OBJ normal with too few values"));
+                    }
+                    normals.push(Vector3::new(v[0], v[1], v[2]));
+                }
+                Some("f") => {
+                    let mut polygon = Vec::new();
+                    for word in words {
+                        let mut parts = word.split('/');
+                        let p = parts.next().map_or(Ok(None), |p| index(p, positions.len()))?
+                            .ok_or_else(|| invalid("OBJ face without positions"))?;
+                        let t = parts.next().map_or(Ok(None), |t| index(t, uvs.len()))?;
+                        let n = parts.next().map_or(Ok(None), |n| index(n, normals.len()))?;
+                        has_normals |= n.is_some();
+                        let key = (p, t, n);
+                        let i = match indices.get(&key) {
+                            Some(&i) => i,
+                            None => {
+                                let mut vertex = Vertex::new(positions[p]);
+                                vertex.color = colors[p];
+                                if let Some(t) = t {
+                                    vertex.uv = uvs[t];
+                                }
+                                if let Some(n) = n {
+                                    vertex.normal = normals[n];
+                                }
+                                let i = mesh.add_vertex(vertex);
+                                indices.insert(key, i);
+                                i
+                            }
+                        };
+                        polygon.push(i);
+                    }
+                    for i in 2..polygon.len() {
+                        mesh.add_face(polygon[0], polygon[i - 1], polygon[i]);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if !has_normals {
+            mesh.compute_normals();
+        }
+        mesh.compute_tangents();
+        Ok(mesh)
+    }
+
+    pub fn save_obj<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_obj(&mut w)?;
+        w.flush()
+    }
+
+    pub fn write_obj<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let (has_normals, has_uvs) = (self.has_normals(), self.has_uvs());
+        let has_colors = self.has_colors();
+
+        writeln!(w, "o {}", self.name)?;
+        for v in &self.vertices {
+            let p = v.position;
+            write!(w, "v {} {} {}", p.x, p.y, p.z)?;
+            if has_colors {
+                write!(w, " {} {} {}", v.color.r, v.color.g, v.color.b)?;
+            }
+            writeln!(w)?;
+        }
+        if has_uvs {
+            // Up the texture, where v is down here
+            for v in &self.vertices {
+                writeln!(w, "vt {} {}", v.uv.x, 1.0 - v.uv.y)?;
+            }
+        }
+        if has_normals {
+            for v in &self.vertices {
+                writeln!(w, "vn {} {} {}", v.normal.x, v.normal.y, v.normal.z)?;
+            }
+        }
+        for face in self.faces() {
+            write!(w, "f")?;
+            for &i in &[face.a, face.b, face.c] {
+                // Indices count from 1
+                let i = i + 1;
+                match (has_uvs, has_normals) {
+                    (true, true) => write!(w, " {}/{}/{}", i, i, i)?,
+                    (true, false) => write!(w, " {}/{}", i, i)?,
+                    (false, true) => write!(w, " {}//{}", i, i)?,
+                    (false, false) => write!(w, " {}", i)?,
+                }
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+}
+
+fn invalid(message: &str) -> SwrError {
+    SwrError::format(message)
+}
+
+// Zero based, from one counting from 1, or back from the last of the count
+// read so far when negative. None for an empty one, as in "1//3".
+fn index(word: &str, count: usize) -> Result<Option<usize>> {
+    if word.is_empty() {
+        return Ok(None);
+    }
+    let i: i64 = word.parse().map_err(|_| invalid("OBJ index expected"))?;
+    let i = if i < 0 { count as i64 + i } else { i - 1 };
+    if i < 0 || i >= count as i64 {
+        return Err(invalid("OBJ index past the vertices"));
+    }
+    Ok(Some(i as usize))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::{Vector2, Vector3};
+    use geometry::mesh::Mesh;
+    use render::color::Color;
+
+    #[test]
+    fn write_obj() {
+        let mut triangle = Mesh::triangle();
+        let mut obj = Vec::new();
+        triangle.write_obj(&mut obj).unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+        assert_eq!("o Triangle\nv -1 -1 -1\nv 1 -1 -1\nv 1 1 -1\nf 1 2 3\n", obj);
+
+        triangle.compute_normals();
+        let uvs = [Vector2::new(0.0, 1.0), Vector2::new(1.0, 1.0), Vector2::new(1.0, 0.0)];
+        for (v, &uv) in triangle.vertices.iter_mut().zip(&uvs) {
+            v.uv = uv;
+        }
+        let mut obj = Vec::new();
+        triangle.write_obj(&mut obj).unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+        assert!(obj.contains("\nvt 1 1\nvn 0 0 1\n"));
+        assert!(obj.ends_with("\nf 1/1/1 2/2/2 3/3/3\n"));
+
+        let read = Mesh::read_obj(&obj, "Triangle").unwrap();
+        assert_eq!(triangle.vertices.len(), read.vertices.len());
+        for (a, b) in triangle.vertices.iter().zip(read.vertices.iter()) {
+            assert_eq!((a.position, a.normal, a.uv), (b.position, b.normal, b.uv));
+        }
+    }
+
+    #[test]
+    fn read_obj() {
+        // A quad sharing positions between two texture seams, indexed from
+        // the end and with a color
+        let obj = "# quad\no Quad\nv 0 0 0 1 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                   vt 0 0\nvt 1 1\nf -4/1 -3/1 -2/2 -1/2\nf 1/2 2/2 3/2\n";
+        let mesh = Mesh::read_obj(obj, "Quad").unwrap();
+        assert_eq!((6, 3), (mesh.vertices.len(), mesh.face_count()));
+        assert_eq!(Color::rgb(1.0, 0.0, 0.0), mesh.vertices[0].color);
+        assert_eq!(Vector2::new(1.0, 0.0), mesh.vertices[2].uv);
+        // Normals worked out from the faces
+        assert_eq!(Vector3::unit_z(), mesh.vertices[0].normal);
+
+        assert!(Mesh::read_obj("f 1 2 3\n", "").is_err());
+        assert!(Mesh::read_obj("v 0 0\n", "").is_err());
+    }
+}
@@ -0,0 +1,462 @@
+
+use super::super::error::{Result, SwrError};
+use super::super::math::vector::{Vector2, Vector3};
+use super::super::render::color::Color;
+use super::mesh::{Mesh, Vertex};
+
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+use std::str::SplitAsciiWhitespace;
+
+// PLY files, as written by 3D scanners and most modeling tools, in ASCII or
+// either binary byte order. Vertices are read with their normals, colors and
+// texture coordinates where the file has them, and polygons are split into
+// fans of triangles. Elements other than vertices and faces are skipped.
+// Meshes are saved as little endian binary, in their own space.
+
+impl Mesh {
+    // Named after the file.
+    pub fn from_ply<P: AsRef<Path>>(path: P) -> Result<Mesh> {
+        let path = path.as_ref();
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        let name = path.file_stem().map_or(String::new(), |s| s.to_string_lossy().into_owned());
+        Mesh::read_ply(&bytes, &name)
+    }
+
+    pub fn read_ply(bytes: &[u8], name: &str) -> Result<Mesh> {
+        let (elements, mut body) = read_header(bytes)?;
+        let mut mesh = Mesh::new(name);
+        for element in &elements {
+            let find = |names: &[&str]| {
+                element.properties.iter().position(|p| names.contains(&p.name.as_str()))
+            };
+            let attribute = |names: &[&[&str]]| -> Option<Vec<usize>> {
+                names.iter().map(|&n| find(n)).collect()
+            };
+            let position = attribute(&[&["x"], &["y"], &["z"]]);
+            let normal = attribute(&[&["nx"], &["ny"], &["nz"]]);
+            let color = attribute(&[&["red", "diffuse_red"],
+                                    &["green", "diffuse_green"],
+                                    &["blue", "diffuse_blue"]]);
+            let alpha = find(&["alpha"]);
+            let uv = attribute(&[&["u", "s", "texture_u", "texture_s"],
+                                 &["v", "t", "texture_v", "texture_t"]]);
+            let indices = find(&["vertex_indices", "vertex_index"]);
+
+            for _ in 0..element.count {
+                // Lists are kept apart, in their place a 0
+                let mut values = Vec::with_capacity(element.properties.len());
+                let mut lists = Vec::new();
+                for property in &element.properties {
+                    match property.list {
+                        Some(count) => {
+                            let n = body.read(count)? as usize;
+                            let list = (0..n).map(|_| body.read(property.scalar))
+                                .collect::<Result<Vec<f64>>>()?;
+                            lists.push(list);
+                            values.push(0.0);
+                        }
+                        None => values.push(body.read(property.scalar)?),
+                    }
+                }
+                let value = |i: usize| values[i];
+                // Colors of integer types are fractions of their largest value
+                let fraction = |i: usize| values[i] / element.properties[i].scalar.max();
+
+                match element.name.as_str() {
+                    "vertex" => {
+                        let p = position.as_ref()
+                            .ok_or_else(|| invalid("PLY vertices without positions"))?;
+                        let mut vertex =
+                            Vertex::new(Vector3::new(value(p[0]), value(p[1]), value(p[2])));
+                        if let Some(ref n) = normal {
+                            vertex.normal = Vector3::new(value(n[0]), value(n[1]), value(n[2]));
+                        }
+                        if let Some(ref c) = color {
+                            let a = alpha.map_or(1.0, &fraction);
+                            vertex.color =
+                                Color::new(fraction(c[0]), fraction(c[1]), fraction(c[2]), a);
+                        }
+                        // Up the texture, as in OpenGL, where v is down here
+                        if let Some(ref t) = uv {
+                            vertex.uv = Vector2::new(value(t[0]), 1.0 - value(t[1]));
+                        }
+                        mesh.add_vertex(vertex);
+                    }
+                    "face" if indices.is_some() => {
+                        let list = indices.and_then(|i| {
+                            let nth = element.properties[..i].iter().filter(|p| p.list.is_some());
+                            lists.get(nth.count())
+                        });
+                        let polygon: Vec<u32> = list.map_or(Vec::new(), |l| {
+                            l.iter().map(|&i| i as u32).collect()
+                        });
+                        for i in 2..polygon.len() {
+                            mesh.add_face(polygon[0], polygon[i - 1], polygon[i]);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        mesh.validate()?;
+        if !mesh.has_normals() {
+            mesh.compute_normals();
+        }
+        mesh.compute_tangents();
+        Ok(mesh)
+    }
+
+    pub fn save_ply<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_ply(&mut w)?;
+        w.flush()
+    }
+
+    pub fn write_ply<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let count = self.vertices.len();
+        let (has_normals, has_uvs) = (self.has_normals(), self.has_uvs());
+        let has_colors = self.has_colors();
+
+        writeln!(w, "ply\nformat binary_little_endian 1.0\ncomment {}", self.name)?;
+        writeln!(w, "element vertex {}", count)?;
+        for name in &["x", "y", "z"] {
+            writeln!(w, "property float {}", name)?;
+        }
+        if has_normals {
+            for name in &["nx", "ny", "nz"] {
+                writeln!(w, "property float {}", name)?;
+            }
+        }
+        if has_uvs {
+            writeln!(w, "property float s\nproperty float t")?;
+        }
+        if has_colors {
+            for name in &["red", "green", "blue", "alpha"] {
+                writeln!(w, "property uchar {}", name)?;
+            }
+        }
+        writeln!(w, "element face {}", self.face_count())?;
+        writeln!(w, "property list uchar uint vertex_indices\nend_header")?;
+
+        let float = |w: &mut W, v: f64| w.write_all(&(v as f32).to_le_bytes());
+        for v in &self.vertices {
+            let p = v.position;
+            for &c in &[p.x, p.y, p.z] {
+                float(&mut w, c)?;
+            }
+            if has_normals {
+                for &c in &[v.normal.x, v.normal.y, v.normal.z] {
+                    float(&mut w, c)?;
+                }
+            }
+            if has_uvs {
+                float(&mut w, v.uv.x)?;
+                float(&mut w, 1.0 - v.uv.y)?;
+            }
+            if has_colors {
+                let (r, g, b, a) = v.color.to_u8();
+                w.write_all(&[r, g, b, a])?;
+            }
+        }
+        for face in self.faces() {
+            w.write_all(&[3])?;
+            for &i in &[face.a, face.b, face.c] {
+                w.write_all(&i.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalid(message: &str) -> SwrError {
+    SwrError::format(message)
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Scalar> {
+        match name {
+            "char" | "int8" => Ok(Scalar::I8),
+            "uchar" | "uint8" => Ok(Scalar::U8),
+            "short" | "int16" => Ok(Scalar::I16),
+            "ushort" | "uint16" => Ok(Scalar::U16),
+            "int" | "int32" => Ok(Scalar::I32),
+            "uint" | "uint32" => Ok(Scalar::U32),
+            "float" | "float32" => Ok(Scalar::F32),
+            "double" | "float64" => Ok(Scalar::F64),
+            _ => Err(invalid("unknown PLY property type")),
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    // Largest value of integer types, 1 for floats.
+    fn max(self) -> f64 {
+        match self {
+            Scalar::I8 => i8::MAX as f64,
+            Scalar::U8 => u8::MAX as f64,
+            Scalar::I16 => i16::MAX as f64,
+            Scalar::U16 => u16::MAX as f64,
+            Scalar::I32 => i32::MAX as f64,
+            Scalar::U32 => u32::MAX as f64,
+            Scalar::F32 | Scalar::F64 => 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    scalar: Scalar,
+    // Type of the length of list properties, scalar being that of the items.
+    list: Option<Scalar>,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// Values following the header.
+enum Body<'a> {
+    Ascii(SplitAsciiWhitespace<'a>),
+    Binary {
+        bytes: &'a [u8],
+        offset: usize,
+        big_endian: bool,
+    },
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64> {
+        match *self {
+            Body::Ascii(ref mut tokens) => {
+                tokens.next()
+                    .and_then(|t| t.parse().ok())
+                    .ok_or_else(|| invalid("malformed number in PLY"))
+            }
+            Body::Binary { bytes, ref mut offset, big_endian } => {
+                let size = scalar.size();
+                let b = bytes.get(*offset..*offset + size)
+                    .ok_or_else(|| invalid("truncated PLY"))?;
+                *offset += size;
+                let mut a = [0u8; 8];
+                a[..size].copy_from_slice(b);
+                if big_endian {
+                    a[..size].reverse();
+                }
+                Ok(match scalar {
+                    Scalar::I8 => a[0] as i8 as f64,
+                    Scalar::U8 => a[0] as f64,
+                    Scalar::I16 => i16::from_le_bytes([a[0], a[1]]) as f64,
+                    Scalar::U16 => u16::from_le_bytes([a[0], a[1]]) as f64,
+                    Scalar::I32 => i32::from_le_bytes([a[0], a[1], a[2], a[3]]) as f64,
+                    Scalar::U32 => u32::from_le_bytes([a[0], a[1], a[2], a[3]]) as f64,
+                    Scalar::F32 => f32::from_le_bytes([a[0], a[1], a[2], a[3]]) as f64,
+                    Scalar::F64 => f64::from_le_bytes(a),
+                })
+            }
+        }
+    }
+}
+
+fn read_header(bytes: &[u8]) -> Result<(Vec<Element>, Body<'_>)> {
+    if !bytes.starts_with(b"ply") {
+        return Err(invalid("not a PLY file"));
+    }
+    let mut elements: Vec<Element> = Vec::new();
+    let mut format = None;
+    let mut offset = 0;
+    loop {
+        let end = bytes[offset..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| invalid("PLY header without an end"))?;
+        let line = String::from_utf8_lossy(&bytes[offset..offset + end]);
+        offset += end + 1;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["end_header"] => break,
+            ["format", kind, _] => format = Some(kind.to_string()),
+            ["element", name, count] => {
+                elements.push(Element {
+                    name: name.to_string(),
+                    count: count.parse().map_err(|_| invalid("malformed PLY element count"))?,
+                    properties: Vec::new(),
+                })
+            }
+            ["property", "list", count, scalar, name] => {
+                let element = elements.last_mut().ok_or_else(|| invalid("PLY property first"))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    scalar: Scalar::parse(scalar)?,
+                    list: Some(Scalar::parse(count)?),
+                })
+            }
+            ["property", scalar, name] => {
+                let element = elements.last_mut().ok_or_else(|| invalid("PLY property first"))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    scalar: Scalar::parse(scalar)?,
+                    list: None,
+                })
+            }
+            _ => {}
+        }
+    }
+
+    let data = &bytes[offset..];
+    let body = match format.as_deref() {
+        Some("ascii") => {
+            let text = ::std::str::from_utf8(data).map_err(|_| invalid("PLY text is not UTF-8"))?;
+            Body::Ascii(text.split_ascii_whitespace())
+        }
+        Some("binary_little_endian") => {
+            Body::Binary {
+                bytes: data,
+                offset: 0,
+                big_endian: false,
+            }
+        }
+        Some("binary_big_endian") => {
+            Body::Binary {
+                bytes: data,
+                offset: 0,
+                big_endian: true,
+            }
+        }
+        _ => return Err(invalid("unknown PLY format")),
+    };
+    Ok((elements, body))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::{Vector2, Vector3};
+    use geometry::mesh::Mesh;
+    use render::color::Color;
+
+    #[test]
+    fn ascii() {
+        let ply = "ply
+format ascii 1.0
+comment a colored quad
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+property float s
+property float t
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0 0 0
+1 0 0 0 255 0 1 0
+1 1 0 0 0 255 1 1
+0 1 0 255 255 255 0 1
+4 0 1 2 3
+";
+        let mesh = Mesh::read_ply(ply.as_bytes(), "quad").unwrap();
+        assert_eq!(4, mesh.vertices.len());
+        assert_eq!(Vector3::new(1.0, 1.0, 0.0), mesh.vertices[2].position);
+        assert_eq!(Color::rgb(0.0, 1.0, 0.0), mesh.vertices[1].color);
+        // Flipped to v down
+        assert_eq!(Vector2::new(0.0, 0.0), mesh.vertices[3].uv);
+        // Split in two, facing +z
+        assert_eq!(2, mesh.face_count());
+        assert_eq!((0, 2, 3), (mesh.face(1).a, mesh.face(1).b, mesh.face(1).c));
+        assert_eq!(Vector3::unit_z(), mesh.vertices[0].normal);
+        assert!(mesh.has_tangents());
+
+        assert!(Mesh::read_ply(b"ply\nformat ascii 1.0\nelement vertex 1\n", "x").is_err());
+    }
+
+    #[test]
+    fn binary() {
+        let mut ply = b"ply
+format binary_big_endian 1.0
+element vertex 3
+property double x
+property double y
+property double z
+property float nx
+property float ny
+property float nz
+element edge 1
+property int vertex1
+property int vertex2
+element face 1
+property list uchar ushort vertex_index
+end_header
+"
+            .to_vec();
+        for v in &[[0.0f64, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]] {
+            for &c in v.iter() {
+                ply.extend_from_slice(&c.to_be_bytes());
+            }
+            for &n in &[0.0f32, 0.0, -1.0] {
+                ply.extend_from_slice(&n.to_be_bytes());
+            }
+        }
+        ply.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        ply.extend_from_slice(&[3, 0, 2, 0, 1, 0, 0]);
+
+        let mesh = Mesh::read_ply(&ply, "triangle").unwrap();
+        assert_eq!(Vector3::unit_y(), mesh.vertices[2].position);
+        // Normals from the file, no colors or texture coordinates
+        assert_eq!(Vector3::new(0.0, 0.0, -1.0), mesh.vertices[1].normal);
+        assert!(!mesh.has_colors() && !mesh.has_uvs());
+        assert_eq!((2, 1, 0), (mesh.face(0).a, mesh.face(0).b, mesh.face(0).c));
+
+        ply.truncate(ply.len() - 1);
+        assert!(Mesh::read_ply(&ply, "triangle").is_err());
+    }
+
+    #[test]
+    fn write_ply() {
+        let mut triangle = Mesh::triangle();
+        let uvs = [Vector2::new(0.0, 1.0), Vector2::new(1.0, 1.0), Vector2::new(1.0, 0.0)];
+        for (v, &uv) in triangle.vertices.iter_mut().zip(&uvs) {
+            v.uv = uv;
+            v.color = Color::rgb(1.0, 0.0, 0.0);
+        }
+        triangle.compute_normals();
+        triangle.compute_tangents();
+        let mut ply = Vec::new();
+        triangle.write_ply(&mut ply).unwrap();
+
+        // Read back the same
+        let read = Mesh::read_ply(&ply, "triangle").unwrap();
+        assert_eq!(triangle.vertices, read.vertices);
+        assert_eq!((0, 1, 2), (read.face(0).a, read.face(0).b, read.face(0).c));
+    }
+}
@@ -0,0 +1,94 @@
+
+use super::super::math::vector::Vector3;
+use super::super::math::transform::Transform;
+use super::super::render::color::Color;
+use super::mesh::Mesh;
+
+// Colored points drawn as they are by Device::render_points, without faces
+// between them, e.g. a scan read with Mesh::from_ply.
+#[derive(Debug,Clone)]
+pub struct PointCloud {
+    pub name: String,
+    pub positions: Vec<Vector3>,
+    // One for each position.
+    pub colors: Vec<Color>,
+    // Width and height of the square drawn for each point, in presented
+    // pixels whatever the distance.
+    pub point_size: f64,
+    pub transform: Transform,
+}
+
+impl PointCloud {
+    pub fn new(name: &str) -> PointCloud {
+        PointCloud {
+            name: name.to_string(),
+            positions: Vec::new(),
+            colors: Vec::new(),
+            point_size: 1.0,
+            transform: Transform::identity(),
+        }
+    }
+
+    // The mesh's vertices with their colors, placed like the mesh.
+    pub fn from_mesh(mesh: &Mesh) -> PointCloud {
+        PointCloud {
+            positions: mesh.positions(),
+            colors: mesh.vertices.iter().map(|v| v.color).collect(),
+            transform: mesh.transform,
+            ..PointCloud::new(&mesh.name)
+        }
+    }
+
+    pub fn with_point_size(mut self, point_size: f64) -> PointCloud {
+        self.point_size = point_size;
+        self
+    }
+
+    pub fn add(&mut self, position: Vector3, color: Color) {
+        self.positions.push(position);
+        self.colors.push(color);
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    // Minimum and maximum corners of the axis aligned box around the
+    // points, before the transform.
+    pub fn bounds(&self) -> (Vector3, Vector3) {
+        let first = self.positions.first().cloned().unwrap_or_else(Vector3::zero);
+        self.positions
+            .iter()
+            .fold((first, first), |(min, max), &p| (min.min(p), max.max(p)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use geometry::mesh::Mesh;
+    use render::color::Color;
+    use super::PointCloud;
+
+    #[test]
+    fn from_mesh() {
+        let mut cube = Mesh::cube();
+        cube.vertices[0].color = Color::rgb(1.0, 0.0, 0.0);
+        cube.transform.position = Vector3::new(1.0, 2.0, 3.0);
+        let cloud = PointCloud::from_mesh(&cube).with_point_size(3.0);
+        assert_eq!((8, 8), (cloud.len(), cloud.colors.len()));
+        assert_eq!(Color::rgb(1.0, 0.0, 0.0), cloud.colors[0]);
+        assert_eq!(cube.bounds(), cloud.bounds());
+        assert_eq!((cube.transform, 3.0), (cloud.transform, cloud.point_size));
+
+        let mut empty = PointCloud::new("Scan");
+        assert!(empty.is_empty());
+        empty.add(Vector3::one(), Color::white());
+        assert_eq!((Vector3::one(), Vector3::one()), empty.bounds());
+    }
+}
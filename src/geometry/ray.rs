@@ -0,0 +1,117 @@
+
+use super::super::math::vector::Vector3;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Ray {
+    pub origin: Vector3,
+    pub direction: Vector3,
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct RayHit {
+    // Along the ray, in units of the ray direction's length.
+    pub distance: f64,
+    pub face: usize,
+    // Weights of the face's a, b and c vertices at the hit point.
+    pub barycentric: Vector3,
+}
+
+impl Ray {
+    pub fn new(origin: Vector3, direction: Vector3) -> Ray {
+        Ray {
+            origin: origin,
+            direction: direction,
+        }
+    }
+
+    pub fn point_at(&self, distance: f64) -> Vector3 {
+        self.origin + self.direction * distance
+    }
+
+    // The ray through two points, e.g. the unprojected near and far points
+    // of a pixel. The direction is normalized.
+    pub fn between(from: Vector3, to: Vector3) -> Ray {
+        Ray::new(from, (to - from).normalize())
+    }
+
+    // Möller–Trumbore. Triangles are hit from both sides, like the
+    // rasterizer draws them. Returns the distance and the weights of v0, v1
+    // and v2.
+    pub fn intersect_triangle(&self, v0: Vector3, v1: Vector3, v2: Vector3) -> Option<(f64, Vector3)> {
+        let edge1 = v1 - v0;
+        let edge2 = v2 - v0;
+        let p = self.direction.cross(edge2);
+        let det = edge1.dot(p);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+
+        let inv_det = 1.0 / det;
+        let s = self.origin - v0;
+        let u = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(edge1);
+        let v = self.direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(q) * inv_det;
+        if t < 0.0 {
+            return None;
+        }
+
+        Some((t, Vector3::new(1.0 - u - v, u, v)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use geometry::mesh::Mesh;
+    use super::Ray;
+
+    #[test]
+    fn triangle() {
+        let (v0, v1, v2) = (Vector3::new(0.0, 0.0, 0.0),
+                            Vector3::new(1.0, 0.0, 0.0),
+                            Vector3::new(0.0, 1.0, 0.0));
+
+        let ray = Ray::new(Vector3::new(0.25, 0.5, 2.0), Vector3::new(0.0, 0.0, -1.0));
+        let (t, w) = ray.intersect_triangle(v0, v1, v2).unwrap();
+        assert_eq!(2.0, t);
+        assert_eq!(Vector3::new(0.25, 0.25, 0.5), w);
+
+        // back side, outside and pointing away
+        let ray = Ray::new(Vector3::new(0.25, 0.5, -2.0), Vector3::new(0.0, 0.0, 1.0));
+        assert!(ray.intersect_triangle(v0, v1, v2).is_some());
+        let ray = Ray::new(Vector3::new(0.75, 0.5, 2.0), Vector3::new(0.0, 0.0, -1.0));
+        assert_eq!(None, ray.intersect_triangle(v0, v1, v2));
+        let ray = Ray::new(Vector3::new(0.25, 0.5, 2.0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(None, ray.intersect_triangle(v0, v1, v2));
+    }
+
+    #[test]
+    fn mesh() {
+        let mut cube = Mesh::cube();
+        cube.transform.position = Vector3::new(0.0, 0.0, -5.0);
+        cube.transform.scale = Vector3::new(2.0, 2.0, 2.0);
+
+        let ray = Ray::new(Vector3::new(0.5, 0.5, 0.0), Vector3::new(0.0, 0.0, -1.0));
+        let hit = cube.raycast(&ray).unwrap();
+        assert!((hit.distance - 3.0).abs() < 1e-9);
+        assert!(hit.face < cube.face_count());
+        let face = cube.face(hit.face);
+        let w = hit.barycentric;
+        let position = |i: u32| cube.vertices[i as usize].position;
+        let local = position(face.a) * w.x + position(face.b) * w.y + position(face.c) * w.z;
+        assert!((local.z - 1.0).abs() < 1e-9);
+
+        let ray = Ray::new(Vector3::new(3.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0));
+        assert_eq!(None, cube.raycast(&ray));
+    }
+}
@@ -0,0 +1,182 @@
+
+use super::super::error::{Result, SwrError};
+use super::super::math::vector::Vector3;
+use super::mesh::{Mesh, Vertex};
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+// STL stores each triangle on its own with a facet normal, in ASCII or
+// binary. Corners at the same position are welded into one vertex so the
+// surface is connected and shades smoothly, and the normals are computed
+// from the geometry rather than trusted from the file. A triangle wound
+// against its facet normal is turned around to match it.
+
+impl Mesh {
+    // Named after the file.
+    pub fn from_stl<P: AsRef<Path>>(path: P) -> Result<Mesh> {
+        let path = path.as_ref();
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        let name = path.file_stem().map_or(String::new(), |s| s.to_string_lossy().into_owned());
+        Mesh::read_stl(&bytes, &name)
+    }
+
+    pub fn read_stl(bytes: &[u8], name: &str) -> Result<Mesh> {
+        let triangles = if is_binary(bytes) { read_binary(bytes)? } else { read_ascii(bytes)? };
+        Ok(weld(name, &triangles))
+    }
+}
+
+// A facet normal and its corners.
+type Triangle = (Vector3, [Vector3; 3]);
+
+fn invalid(message: &str) -> SwrError {
+    SwrError::format(message)
+}
+
+// ASCII files start with "solid", but so may the 80 byte header of binary
+// ones, which are told apart by their zero bytes.
+fn is_binary(bytes: &[u8]) -> bool {
+    !bytes.starts_with(b"solid") || bytes.contains(&0)
+}
+
+fn read_binary(bytes: &[u8]) -> Result<Vec<Triangle>> {
+    if bytes.len() < 84 {
+        return Err(invalid("truncated binary STL"));
+    }
+    let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+    if bytes.len() < 84 + count * 50 {
+        return Err(invalid("truncated binary STL"));
+    }
+    let vector = |offset: usize| {
+        let f = |o: usize| {
+            let b = &bytes[offset + o..offset + o + 4];
+            f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64
+        };
+        Vector3::new(f(0), f(4), f(8))
+    };
+    Ok((0..count)
+        .map(|i| {
+            let offset = 84 + i * 50;
+            (vector(offset), [vector(offset + 12), vector(offset + 24), vector(offset + 36)])
+        })
+        .collect())
+}
+
+// Facets with more than three vertices are split into a fan.
+fn read_ascii(bytes: &[u8]) -> Result<Vec<Triangle>> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut tokens = text.split_whitespace();
+    let mut triangles = Vec::new();
+    let (mut normal, mut corners) = (Vector3::zero(), Vec::new());
+    while let Some(token) = tokens.next() {
+        match token {
+            "facet" => {
+                if tokens.next() != Some("normal") {
+                    return Err(invalid("facet without a normal in STL"));
+                }
+                normal = read_vector(&mut tokens)?;
+                corners.clear();
+            }
+            "vertex" => corners.push(read_vector(&mut tokens)?),
+            "endfacet" => {
+                for i in 2..corners.len() {
+                    triangles.push((normal, [corners[0], corners[i - 1], corners[i]]));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(triangles)
+}
+
+fn read_vector<'a, I: Iterator<Item = &'a str>>(tokens: &mut I) -> Result<Vector3> {
+    let mut v = [0.0; 3];
+    for c in &mut v {
+        *c = tokens.next()
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(|| invalid("malformed number in STL"))?;
+    }
+    Ok(Vector3::new(v[0], v[1], v[2]))
+}
+
+fn weld(name: &str, triangles: &[Triangle]) -> Mesh {
+    let mut mesh = Mesh::new(name);
+    for &(normal, corners) in triangles {
+        let mut face = [0u32; 3];
+        for (i, &corner) in corners.iter().enumerate() {
+            face[i] = mesh.add_vertex(Vertex::new(corner));
+        }
+        let wound = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+        if wound.dot(normal) < 0.0 {
+            face.swap(1, 2);
+        }
+        mesh.add_face(face[0], face[1], face[2]);
+    }
+    // Positions are read from floats, so equal ones are exactly equal
+    mesh.weld(0.0, true);
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use geometry::mesh::Mesh;
+
+    const ASCII: &str = "solid square
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 1 1 0
+    endloop
+  endfacet
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 0 1 0
+      vertex 1 1 0
+    endloop
+  endfacet
+endsolid square
+";
+
+    #[test]
+    fn ascii() {
+        let mesh = Mesh::read_stl(ASCII.as_bytes(), "square").unwrap();
+        // The shared corners are welded
+        assert_eq!(4, mesh.vertices.len());
+        assert_eq!(2, mesh.face_count());
+        // The second facet is wound against its normal and turned around
+        assert!(mesh.vertices.iter().all(|v| v.normal == Vector3::unit_z()));
+
+        assert!(Mesh::read_stl(b"solid x facet normal 0 0 one", "x").is_err());
+    }
+
+    #[test]
+    fn binary() {
+        // A header starting like an ASCII file
+        let mut bytes = b"solid but binary".to_vec();
+        bytes.resize(80, 0);
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        for triangle in &[[0.0f32, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+                          [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0]] {
+            for &v in triangle.iter() {
+                bytes.extend_from_slice(&v.to_le_bytes());
+            }
+            bytes.extend_from_slice(&[0, 0]);
+        }
+
+        let mesh = Mesh::read_stl(&bytes, "corner").unwrap();
+        assert_eq!(4, mesh.vertices.len());
+        assert_eq!(Vector3::new(0.0, 0.0, 1.0), mesh.vertices[3].position);
+        // Without facet normals the winding is kept
+        assert_eq!((1, 3, 2), (mesh.face(1).a, mesh.face(1).b, mesh.face(1).c));
+
+        bytes.truncate(120);
+        assert!(Mesh::read_stl(&bytes, "corner").is_err());
+    }
+}
@@ -0,0 +1,148 @@
+
+use super::super::math::vector::Vector3;
+use super::mesh::{Face, Indices, Mesh, Vertex};
+
+use std::collections::{BTreeMap, HashMap};
+
+// Loop subdivision splits each triangle in four and moves the vertices
+// towards a weighted average of their neighbours, rounding the surface off.
+// Edges with a face on one side only are boundaries, smoothed only along
+// themselves, so open meshes stay flat at their edges and seams stay closed
+// where both sides move alike.
+
+impl Mesh {
+    // Refines the mesh the number of times, each one quadrupling its faces,
+    // and smooths its normals. Texture coordinates and colors are blended
+    // without smoothing.
+    pub fn subdivide_loop(&mut self, iterations: usize) {
+        for _ in 0..iterations {
+            self.subdivide_once();
+        }
+        if iterations > 0 {
+            self.compute_normals();
+            if self.has_uvs() {
+                self.compute_tangents();
+            }
+        }
+    }
+
+    fn subdivide_once(&mut self) {
+        let faces: Vec<Face> = self.faces().collect();
+
+        // The corners across each edge from it, in order so the new vertices
+        // are the same each time
+        let mut edges: BTreeMap<(u32, u32), Vec<u32>> = BTreeMap::new();
+        for face in &faces {
+            for &(a, b, c) in &[(face.a, face.b, face.c),
+                                (face.b, face.c, face.a),
+                                (face.c, face.a, face.b)] {
+                edges.entry((a.min(b), a.max(b))).or_default().push(c);
+            }
+        }
+        let mut neighbours = vec![Vec::new(); self.vertices.len()];
+        let mut boundary = vec![Vec::new(); self.vertices.len()];
+        for (&(a, b), opposite) in &edges {
+            neighbours[a as usize].push(b);
+            neighbours[b as usize].push(a);
+            if opposite.len() == 1 {
+                boundary[a as usize].push(b);
+                boundary[b as usize].push(a);
+            }
+        }
+
+        let position = |i: u32| self.vertices[i as usize].position;
+        let mut vertices: Vec<Vertex> = self.vertices
+            .iter()
+            .enumerate()
+            .map(|(i, &vertex)| {
+                let (around, edge) = (&neighbours[i], &boundary[i]);
+                let p = vertex.position;
+                let moved = if edge.len() == 2 {
+                    p * 0.75 + (position(edge[0]) + position(edge[1])) * 0.125
+                } else if edge.is_empty() && !around.is_empty() {
+                    let n = around.len() as f64;
+                    let beta = if around.len() == 3 { 3.0 / 16.0 } else { 3.0 / (8.0 * n) };
+                    let sum = around.iter().fold(Vector3::zero(), |sum, &k| sum + position(k));
+                    p * (1.0 - n * beta) + sum * beta
+                } else {
+                    // Where more than two boundaries meet it stays put
+                    p
+                };
+                Vertex { position: moved, ..vertex }
+            })
+            .collect();
+
+        let mut midpoints = HashMap::with_capacity(edges.len());
+        for (&(a, b), opposite) in &edges {
+            let (va, vb) = (self.vertices[a as usize], self.vertices[b as usize]);
+            let mut vertex = va;
+            vertex.position = if opposite.len() == 2 {
+                (va.position + vb.position) * 0.375 +
+                (position(opposite[0]) + position(opposite[1])) * 0.125
+            } else {
+                (va.position + vb.position) * 0.5
+            };
+            vertex.uv = (va.uv + vb.uv) * 0.5;
+            vertex.color = (va.color + vb.color) * 0.5;
+            vertices.push(vertex);
+            midpoints.insert((a, b), vertices.len() as u32 - 1);
+        }
+
+        let mut indices = Vec::with_capacity(faces.len() * 12);
+        for face in &faces {
+            let midpoint = |a: u32, b: u32| midpoints[&(a.min(b), a.max(b))];
+            let (ab, bc, ca) = (midpoint(face.a, face.b),
+                                midpoint(face.b, face.c),
+                                midpoint(face.c, face.a));
+            indices.extend_from_slice(&[face.a, ab, ca, ab, face.b, bc, ca, bc, face.c, ab, bc, ca]);
+        }
+        self.vertices = vertices;
+        self.indices = Indices::from_u32(indices);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use geometry::mesh::Mesh;
+
+    #[test]
+    fn subdivide_cube() {
+        let mut cube = Mesh::cube();
+        cube.subdivide_loop(1);
+        // A vertex added on each of the 18 edges
+        assert_eq!((8 + 18, 12 * 4), (cube.vertices.len(), cube.face_count()));
+
+        cube.subdivide_loop(2);
+        assert_eq!(12 * 64, cube.face_count());
+        // Rounded off within the cube, wound as before
+        let (min, max) = cube.bounds();
+        assert!(min.x > -1.0 && min.y > -1.0 && min.z > -1.0);
+        assert!(max.x < 1.0 && max.y < 1.0 && max.z < 1.0);
+        let distances: Vec<f64> = cube.vertices.iter().map(|v| v.position.length()).collect();
+        let (near, far) = distances.iter()
+            .fold((f64::INFINITY, 0.0f64), |(near, far), &d| (near.min(d), far.max(d)));
+        assert!(far - near < 0.2, "{} {}", near, far);
+        for face in cube.faces() {
+            let (a, b, c) = (cube.vertices[face.a as usize].position,
+                             cube.vertices[face.b as usize].position,
+                             cube.vertices[face.c as usize].position);
+            assert!((b - a).cross(c - a).dot(a) < 0.0);
+        }
+    }
+
+    #[test]
+    fn subdivide_open() {
+        let mut plane = Mesh::plane(2.0, 2.0, 1, 1);
+        plane.subdivide_loop(2);
+        assert_eq!(2 * 16, plane.face_count());
+        // Flat and facing up still, with its outline rounded off
+        for v in &plane.vertices {
+            assert_eq!((0.0, Vector3::unit_y()), (v.position.y, v.normal));
+        }
+        let (min, max) = plane.bounds();
+        assert!(min.x > -1.0 && max.x < 1.0 && max.x > 0.9);
+        assert!(plane.vertices.iter().all(|v| v.position.x.abs() + v.position.z.abs() < 1.6));
+    }
+}
@@ -0,0 +1,227 @@
+
+use super::super::math::vector::{Vector2, Vector3};
+use super::super::render::color::Color;
+use super::mesh::{Mesh, Vertex};
+
+// A box of unit cubes, each empty or filled with a color, like a chunk of
+// blocks in a voxel game.
+#[derive(Debug,Clone)]
+pub struct VoxelGrid {
+    width: usize,
+    height: usize,
+    depth: usize,
+    // x fastest, then z, then y, so layers of the same height are together
+    voxels: Vec<Option<Color>>,
+}
+
+impl VoxelGrid {
+    pub fn new(width: usize, height: usize, depth: usize) -> VoxelGrid {
+        VoxelGrid {
+            width: width,
+            height: height,
+            depth: depth,
+            voxels: vec![None; width * height * depth],
+        }
+    }
+
+    // Width, height and depth, along x, y and z.
+    pub fn size(&self) -> (usize, usize, usize) {
+        (self.width, self.height, self.depth)
+    }
+
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        (y * self.depth + z) * self.width + x
+    }
+
+    // None outside the grid.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Option<Color> {
+        if x < self.width && y < self.height && z < self.depth {
+            self.voxels[self.index(x, y, z)]
+        } else {
+            None
+        }
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, voxel: Option<Color>) {
+        let i = self.index(x, y, z);
+        self.voxels[i] = voxel;
+    }
+
+    pub fn filled_count(&self) -> usize {
+        self.voxels.iter().filter(|v| v.is_some()).count()
+    }
+
+    // The faces of filled voxels next to empty ones, with the voxel's color
+    // as the vertex color, from the origin to the far corner of the grid.
+    // Greedy meshing merges each face into the largest rectangle of faces
+    // of the same color it can grow to, first along one axis and then the
+    // other, so flat walls take two triangles instead of two per voxel.
+    // Texture coordinates count voxels, so a repeating texture shows once
+    // on each.
+    pub fn to_mesh(&self) -> Mesh {
+        let mut mesh = Mesh::new("Voxels");
+        let size = [self.width, self.height, self.depth];
+        let voxel = |p: [i64; 3]| {
+            if p.iter().any(|&c| c < 0) {
+                None
+            } else {
+                self.get(p[0] as usize, p[1] as usize, p[2] as usize)
+            }
+        };
+
+        for d in 0..3 {
+            // The face's plane spans u and v, with u crossed with v along d
+            let (u, v) = ((d + 1) % 3, (d + 2) % 3);
+            for &facing in &[1i64, -1] {
+                let mut mask = vec![None; size[u] * size[v]];
+                for slice in 0..size[d] + 1 {
+                    // Faces in the plane at slice, of the voxels before it
+                    // facing forward or the ones after facing back
+                    for j in 0..size[v] {
+                        for i in 0..size[u] {
+                            let mut at = [0i64; 3];
+                            at[u] = i as i64;
+                            at[v] = j as i64;
+                            at[d] = slice as i64 - 1;
+                            let before = voxel(at);
+                            at[d] = slice as i64;
+                            let after = voxel(at);
+                            mask[j * size[u] + i] = match (before, after, facing) {
+                                (Some(color), None, 1) | (None, Some(color), -1) => Some(color),
+                                _ => None,
+                            };
+                        }
+                    }
+
+                    for j in 0..size[v] {
+                        let mut i = 0;
+                        while i < size[u] {
+                            let color = match mask[j * size[u] + i] {
+                                Some(color) => color,
+                                None => {
+                                    i += 1;
+                                    continue;
+                                }
+                            };
+                            let row = |j: usize, i: usize, w: usize| {
+                                (i..i + w).all(|k| mask[j * size[u] + k] == Some(color))
+                            };
+                            let mut w = 1;
+                            while i + w < size[u] && row(j, i + w, 1) {
+                                w += 1;
+                            }
+                            let mut h = 1;
+                            while j + h < size[v] && row(j + h, i, w) {
+                                h += 1;
+                            }
+                            for jj in j..j + h {
+                                for cell in &mut mask[jj * size[u] + i..jj * size[u] + i + w] {
+                                    *cell = None;
+                                }
+                            }
+
+                            let corner = |a: usize, b: usize| {
+                                let mut p = [0.0; 3];
+                                p[u] = a as f64;
+                                p[v] = b as f64;
+                                p[d] = slice as f64;
+                                Vector3::new(p[0], p[1], p[2])
+                            };
+                            let mut normal = [0.0; 3];
+                            normal[d] = facing as f64;
+                            let normal = Vector3::new(normal[0], normal[1], normal[2]);
+                            let first = mesh.vertices.len() as u32;
+                            for &(a, b) in &[(i, j), (i + w, j), (i + w, j + h), (i, j + h)] {
+                                mesh.add_vertex(Vertex::new(corner(a, b))
+                                    .with_normal(normal)
+                                    .with_uv(Vector2::new((a - i) as f64, (b - j) as f64))
+                                    .with_color(color));
+                            }
+                            if facing > 0 {
+                                mesh.add_face(first, first + 1, first + 2);
+                                mesh.add_face(first, first + 2, first + 3);
+                            } else {
+                                mesh.add_face(first, first + 2, first + 1);
+                                mesh.add_face(first, first + 3, first + 2);
+                            }
+                            i += w;
+                        }
+                    }
+                }
+            }
+        }
+        mesh.compute_tangents();
+        mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use geometry::mesh::Mesh;
+    use render::color::Color;
+    use super::VoxelGrid;
+
+    fn check_faces(mesh: &Mesh) {
+        for face in mesh.faces() {
+            let (a, b, c) = (mesh.vertices[face.a as usize],
+                             mesh.vertices[face.b as usize],
+                             mesh.vertices[face.c as usize]);
+            let normal = (b.position - a.position).cross(c.position - a.position).normalize();
+            assert_eq!(a.normal, normal);
+        }
+    }
+
+    #[test]
+    fn single_voxel() {
+        let mut grid = VoxelGrid::new(3, 3, 3);
+        assert_eq!(0, grid.to_mesh().face_count());
+        let red = Color::rgb(1.0, 0.0, 0.0);
+        grid.set(1, 2, 0, Some(red));
+        assert_eq!((Some(red), None), (grid.get(1, 2, 0), grid.get(1, 3, 0)));
+        let mesh = grid.to_mesh();
+        assert_eq!((24, 12), (mesh.vertices.len(), mesh.face_count()));
+        assert_eq!((Vector3::new(1.0, 2.0, 0.0), Vector3::new(2.0, 3.0, 1.0)), mesh.bounds());
+        assert!(mesh.vertices.iter().all(|v| v.color == red));
+        check_faces(&mesh);
+    }
+
+    #[test]
+    fn greedy_merge() {
+        // A solid block is a box of six quads, whatever its size
+        let (grass, stone) = (Color::rgb(0.2, 0.8, 0.2), Color::gray(0.5));
+        let mut grid = VoxelGrid::new(5, 4, 6);
+        for y in 0..4 {
+            for z in 0..6 {
+                for x in 0..5 {
+                    grid.set(x, y, z, Some(stone));
+                }
+            }
+        }
+        assert_eq!(120, grid.filled_count());
+        let mesh = grid.to_mesh();
+        assert_eq!(12, mesh.face_count());
+        check_faces(&mesh);
+        // Textured once per voxel
+        let top = mesh.vertices.iter().map(|v| v.uv.x.max(v.uv.y)).fold(0.0, f64::max);
+        assert_eq!(6.0, top);
+
+        // A different color on top splits the sides in two, and a hole in
+        // the top leaves the quads around it and the walls inside
+        for z in 0..6 {
+            for x in 0..5 {
+                grid.set(x, 3, z, Some(grass));
+            }
+        }
+        assert_eq!(2 + 4 * 2 * 2 + 2, grid.to_mesh().face_count());
+        grid.set(2, 3, 2, None);
+        let mesh = grid.to_mesh();
+        check_faces(&mesh);
+        let top = mesh.faces()
+            .filter(|f| mesh.vertices[f.a as usize].position.y == 4.0)
+            .count();
+        assert!((2 * 4..=2 * 4 + 2).contains(&top));
+        assert!(mesh.vertices.iter().any(|v| v.position.y == 3.0 && v.normal == Vector3::unit_y()));
+    }
+}
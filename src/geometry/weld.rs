@@ -0,0 +1,117 @@
+
+use super::super::math::vector::Vector3;
+use super::mesh::{Indices, Mesh, Vertex};
+
+use std::collections::HashMap;
+
+impl Mesh {
+    // Merges vertices within epsilon of each other into the first of them,
+    // keeping its other attributes, and drops the faces left with less than
+    // three corners. Seams, like those of Mesh::sphere, close up at the cost
+    // of the texture coordinates on one side. Smoothing the normals computes
+    // them again over the joined faces.
+    pub fn weld(&mut self, epsilon: f64, smooth_normals: bool) {
+        // Positions within epsilon are in the same or a neighbouring cell.
+        // Adding zero turns -0 into 0, the same bits for exact matches.
+        let cell = |p: Vector3| {
+            if epsilon > 0.0 {
+                ((p.x / epsilon).floor() as i64,
+                 (p.y / epsilon).floor() as i64,
+                 (p.z / epsilon).floor() as i64)
+            } else {
+                ((p.x + 0.0).to_bits() as i64,
+                 (p.y + 0.0).to_bits() as i64,
+                 (p.z + 0.0).to_bits() as i64)
+            }
+        };
+        let reach = if epsilon > 0.0 { 1 } else { 0 };
+
+        let mut cells: HashMap<(i64, i64, i64), Vec<u32>> = HashMap::new();
+        let mut kept: Vec<Vertex> = Vec::new();
+        let mut remap = Vec::with_capacity(self.vertices.len());
+        for vertex in &self.vertices {
+            let p = vertex.position;
+            let (x, y, z) = cell(p);
+            let mut found = None;
+            'search: for dx in -reach..reach + 1 {
+                for dy in -reach..reach + 1 {
+                    for dz in -reach..reach + 1 {
+                        let near = cells.get(&(x + dx, y + dy, z + dz)).into_iter().flatten();
+                        for &k in near {
+                            if (kept[k as usize].position - p).length() <= epsilon {
+                                found = Some(k);
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+            remap.push(found.unwrap_or_else(|| {
+                kept.push(*vertex);
+                let k = kept.len() as u32 - 1;
+                cells.entry((x, y, z)).or_default().push(k);
+                k
+            }));
+        }
+
+        let mut indices = Vec::with_capacity(self.indices.len());
+        for face in self.faces() {
+            let (a, b, c) = (remap[face.a as usize], remap[face.b as usize], remap[face.c as usize]);
+            if a != b && b != c && c != a {
+                indices.extend_from_slice(&[a, b, c]);
+            }
+        }
+        self.vertices = kept;
+        self.indices = Indices::from_u32(indices);
+        if smooth_normals {
+            self.compute_normals();
+            if self.has_uvs() {
+                self.compute_tangents();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use geometry::mesh::{Mesh, Vertex};
+
+    #[test]
+    fn weld_sphere() {
+        let mut sphere = Mesh::sphere(Vector3::zero(), 2.0, 16, 8);
+        sphere.weld(1e-9, true);
+        // The seam closed and each pole a single vertex, without the faces
+        // that were only a line there
+        assert_eq!(16 * 7 + 2, sphere.vertices.len());
+        assert_eq!(16 * 8 * 2 - 16 * 2, sphere.face_count());
+        // Smooth along the radius all over, the poles included
+        for v in &sphere.vertices {
+            assert!(v.normal.dot(v.position.normalize()) > 0.99);
+        }
+    }
+
+    #[test]
+    fn weld_epsilon() {
+        let mut mesh = Mesh::new("Strip");
+        let corners = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 0.0001), (1.0, 1.0), (-0.0, 1.0)];
+        for &(x, y) in &corners {
+            mesh.add_vertex(Vertex::new(Vector3::new(x, y, 0.0)));
+        }
+        mesh.add_face(0, 1, 2);
+        mesh.add_face(3, 4, 5);
+
+        let mut exact = mesh.clone();
+        exact.weld(0.0, false);
+        // Only -0 and 0 are the same
+        assert_eq!(5, exact.vertices.len());
+        assert!(!exact.has_normals());
+
+        mesh.weld(0.001, true);
+        assert_eq!(4, mesh.vertices.len());
+        let face = mesh.face(1);
+        assert_eq!((1, 3, 2), (face.a, face.b, face.c));
+        assert!(mesh.vertices.iter().all(|v| (v.normal - Vector3::unit_z()).length() < 1e-3));
+    }
+}
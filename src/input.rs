@@ -0,0 +1,135 @@
+
+use minifb::{Key, MouseButton, MouseMode, Window};
+
+fn button_index(button: MouseButton) -> usize {
+    match button {
+        MouseButton::Left => 0,
+        MouseButton::Middle => 1,
+        MouseButton::Right => 2,
+    }
+}
+
+// Snapshot of keyboard and mouse state, refreshed once per frame by update.
+// Keeping the previous snapshot around is what makes the just_pressed and
+// just_released queries possible.
+#[derive(Debug,Clone,Default)]
+pub struct Input {
+    keys: Vec<Key>,
+    previous_keys: Vec<Key>,
+    mouse_buttons: [bool; 3],
+    previous_mouse_buttons: [bool; 3],
+    mouse_position: Option<(f64, f64)>,
+    mouse_delta: (f64, f64),
+    scroll: (f64, f64),
+}
+
+impl Input {
+    pub fn new() -> Input {
+        Input::default()
+    }
+
+    pub fn update(&mut self, window: &Window) {
+        let keys = window.get_keys().unwrap_or_default();
+        let buttons = [window.get_mouse_down(MouseButton::Left),
+                       window.get_mouse_down(MouseButton::Middle),
+                       window.get_mouse_down(MouseButton::Right)];
+        let position = window.get_mouse_pos(MouseMode::Pass).map(|(x, y)| (x as f64, y as f64));
+        let scroll = window.get_scroll_wheel().map_or((0.0, 0.0), |(x, y)| (x as f64, y as f64));
+        self.set_state(keys, buttons, position, scroll);
+    }
+
+    // Advances to the next frame with explicitly provided state; update
+    // reads the same values from a window.
+    pub fn set_state(&mut self,
+                     keys: Vec<Key>,
+                     mouse_buttons: [bool; 3],
+                     mouse_position: Option<(f64, f64)>,
+                     scroll: (f64, f64)) {
+        self.previous_keys = ::std::mem::replace(&mut self.keys, keys);
+        self.previous_mouse_buttons = self.mouse_buttons;
+        self.mouse_buttons = mouse_buttons;
+        self.mouse_delta = match (mouse_position, self.mouse_position) {
+            (Some((x, y)), Some((last_x, last_y))) => (x - last_x, y - last_y),
+            _ => (0.0, 0.0),
+        };
+        self.mouse_position = mouse_position;
+        self.scroll = scroll;
+    }
+
+    pub fn is_pressed(&self, key: Key) -> bool {
+        self.keys.contains(&key)
+    }
+
+    pub fn just_pressed(&self, key: Key) -> bool {
+        self.keys.contains(&key) && !self.previous_keys.contains(&key)
+    }
+
+    pub fn just_released(&self, key: Key) -> bool {
+        !self.keys.contains(&key) && self.previous_keys.contains(&key)
+    }
+
+    pub fn is_mouse_pressed(&self, button: MouseButton) -> bool {
+        self.mouse_buttons[button_index(button)]
+    }
+
+    pub fn mouse_just_pressed(&self, button: MouseButton) -> bool {
+        let i = button_index(button);
+        self.mouse_buttons[i] && !self.previous_mouse_buttons[i]
+    }
+
+    pub fn mouse_just_released(&self, button: MouseButton) -> bool {
+        let i = button_index(button);
+        !self.mouse_buttons[i] && self.previous_mouse_buttons[i]
+    }
+
+    pub fn mouse_position(&self) -> Option<(f64, f64)> {
+        self.mouse_position
+    }
+
+    pub fn mouse_delta(&self) -> (f64, f64) {
+        self.mouse_delta
+    }
+
+    pub fn scroll(&self) -> (f64, f64) {
+        self.scroll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use minifb::{Key, MouseButton};
+    use super::Input;
+
+    #[test]
+    fn keys() {
+        let mut input = Input::new();
+        input.set_state(vec![Key::W], [false; 3], None, (0.0, 0.0));
+        assert!(input.is_pressed(Key::W));
+        assert!(input.just_pressed(Key::W));
+
+        input.set_state(vec![Key::W, Key::A], [false; 3], None, (0.0, 0.0));
+        assert!(input.is_pressed(Key::W));
+        assert!(!input.just_pressed(Key::W));
+        assert!(input.just_pressed(Key::A));
+
+        input.set_state(vec![Key::A], [false; 3], None, (0.0, 0.0));
+        assert!(!input.is_pressed(Key::W));
+        assert!(input.just_released(Key::W));
+        assert!(!input.just_released(Key::A));
+    }
+
+    #[test]
+    fn mouse() {
+        let mut input = Input::new();
+        input.set_state(vec![], [true, false, false], Some((10.0, 10.0)), (0.0, 1.0));
+        assert!(input.mouse_just_pressed(MouseButton::Left));
+        assert_eq!((0.0, 0.0), input.mouse_delta());
+        assert_eq!((0.0, 1.0), input.scroll());
+
+        input.set_state(vec![], [false, false, false], Some((15.0, 7.0)), (0.0, 0.0));
+        assert!(input.mouse_just_released(MouseButton::Left));
+        assert!(!input.is_mouse_pressed(MouseButton::Left));
+        assert_eq!((5.0, -3.0), input.mouse_delta());
+    }
+}
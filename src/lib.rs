@@ -0,0 +1,18 @@
+
+#[cfg(feature = "window")]
+extern crate minifb;
+extern crate png;
+extern crate gif;
+extern crate serde_json;
+
+#[macro_use]
+pub mod profiler;
+pub mod math;
+pub mod geometry;
+pub mod render;
+pub mod camera;
+pub mod error;
+pub mod scene;
+pub mod timing;
+#[cfg(feature = "window")]
+pub mod input;
@@ -1,33 +1,64 @@
 
 extern crate minifb;
 extern crate md3_rs;
+#[macro_use]
+extern crate swr_rs;
 
-use minifb::{Key, WindowOptions, Window};
+use minifb::{Key, MouseButton, WindowOptions, Window};
 
 const WIDTH: usize = 1200;
 const HEIGHT: usize = 720;
+const SCALE: usize = 2;
 
 use std::f64;
 
-mod math;
-mod geometry;
-
-use math::vector::{Vector2, Vector3};
-use math::matrix::Matrix4;
-use math::matrix::Matrix2;
-use math::quaternion::Quaternion;
-
-use geometry::mesh::Mesh;
-use geometry::mesh::Face;
+use swr_rs::math::vector::{Vector2, Vector3};
+use swr_rs::math::matrix::Matrix4;
+use swr_rs::math::quaternion::Quaternion;
+
+use swr_rs::error::SwrError;
+use swr_rs::geometry::mesh::Mesh;
+use swr_rs::geometry::mesh::Face;
+use swr_rs::geometry::modifier::Modifier;
+use swr_rs::geometry::morph::MorphTarget;
+use swr_rs::geometry::point_cloud::PointCloud;
+use swr_rs::geometry::voxel::VoxelGrid;
+
+use swr_rs::render::color::Color;
+use swr_rs::render::debug::{DebugDraw, DebugView};
+use swr_rs::render::light::PointLight;
+use swr_rs::render::material::{Diffuse, Material, Shading};
+use swr_rs::render::device::{Camera, Device, Handedness, Msaa, Projection, RenderMode};
+use swr_rs::render::fog::{Fog, FogMode};
+use swr_rs::render::offline::OfflineRender;
+use swr_rs::render::particles::{Emitter, ParticleSystem};
+use swr_rs::render::post::{Bloom, ChromaticAberration, Grayscale, Invert, PostChain, Vignette};
+use swr_rs::render::recorder::Recorder;
+use swr_rs::render::shadow::ShadowMap;
+use swr_rs::render::sprite::{Billboard, Sprite};
+use swr_rs::render::ssao::Ssao;
+use swr_rs::render::stats::Stats;
+use swr_rs::render::texture::{CubeMap, Environment, Texture};
+use swr_rs::render::viewport::Viewport;
+
+use swr_rs::camera::orbit::OrbitCamera;
+use swr_rs::camera::fps::FpsCamera;
+
+use swr_rs::input::Input;
+
+use swr_rs::scene;
+use swr_rs::scene::graph::{Node, Scene};
+
+use swr_rs::timing::{FixedTimestep, FramePacer, Interpolated};
 
 use md3_rs::Md3;
 
-fn md3_to_mesh(md3: &Md3) -> Mesh {
-    let surface = md3.surfaces.first().unwrap();
+fn md3_to_mesh(md3: &Md3) -> Result<Mesh, SwrError> {
+    let surface = md3.surfaces.first().ok_or_else(|| SwrError::format("MD3 without surfaces"))?;
 
     let vertices = {
         let mut vertices = Vec::new();
-        let frame = (&surface).vertices.first().unwrap();
+        let frame = surface.vertices.first().ok_or_else(|| SwrError::format("MD3 without frames"))?;
         for vert in frame {
             vertices.push(Vector3::new(vert.x as f64, vert.y as f64, vert.z as f64));
         }
@@ -44,342 +75,978 @@ fn md3_to_mesh(md3: &Md3) -> Mesh {
         faces
     };
 
-    Mesh {
-        name: md3.header.name.clone(),
-        vertices: vertices,
-        faces: faces,
-        position: Vector3::zero(),
-        rotation: Vector3::zero(),
-        scale: Vector3::one(),
-    }
+    let mesh = Mesh::from_positions(&md3.header.name, &vertices, faces);
+    mesh.validate()?;
+    Ok(mesh)
 }
 
+const USAGE: &str = "\
+Usage: swr_rs [options]
+
+  --model <file>            show a .obj, .ply, .stl or .md3 model in place of the shell
+  --primitive <name>        show a built in shape in place of the shell: cube, sphere,
+                            icosphere, plane, torus, cylinder, cone, capsule,
+                            tetrahedron, octahedron, icosahedron or triangle
+  --scene <file.json>       show a scene file, loaded again when it changes
+  --size <WIDTHxHEIGHT>     window size, or image size with --headless
+  --mode <mode>             solid, wireframe or hidden-line
+  --eye <x,y,z>             camera position
+  --target <x,y,z>          point the camera looks at
+  --fov <degrees>           vertical field of view
+  --fps <rate>              frames per second to pace to, or uncapped, 60 by default
+  --headless                render one frame without a window, saved to --output
+  --output <file.png>       where --headless saves the frame
+  --still <file.png> [WxH]  render a supersampled still, 7680x4320 by default
+  --help                    show this
+";
+
 #[derive(Debug)]
-struct Camera {
-    position: Vector3,
-    target: Vector3,
-    fov: f64,
-    zfar: f64,
-    znear: f64,
+struct Options {
+    model: Option<String>,
+    primitive: Option<String>,
+    scene: Option<String>,
+    size: Option<(usize, usize)>,
+    render_mode: RenderMode,
+    eye: Option<Vector3>,
+    target: Option<Vector3>,
+    fov: Option<f64>,
+    fps: Option<f64>,
+    headless: bool,
+    output: Option<String>,
+    still: Option<(String, Option<(usize, usize)>)>,
 }
 
-#[derive(Debug)]
-struct Device {
-    width: usize,
-    height: usize,
-    backbuffer: Box<[u32]>,
-    depthbuffer: Box<[f64]>,
+fn parse_size(size: &str) -> Result<(usize, usize), String> {
+    let mut parts = size.split('x').map(|p| p.parse::<usize>());
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(width)), Some(Ok(height)), None) if width > 0 && height > 0 => {
+            Ok((width, height))
+        }
+        _ => Err(format!("Invalid size '{}', expected WIDTHxHEIGHT", size)),
+    }
 }
 
-fn round(x: f64) -> f64 {
-    (x + 0.5).round()
+fn parse_vector(vector: &str) -> Result<Vector3, String> {
+    let v = vector.split(',')
+        .map(|c| c.trim().parse::<f64>())
+        .collect::<Result<Vec<f64>, _>>()
+        .map_err(|_| format!("Invalid vector '{}', expected x,y,z", vector))?;
+    match v.len() {
+        3 => Ok(Vector3::new(v[0], v[1], v[2])),
+        _ => Err(format!("Invalid vector '{}', expected x,y,z", vector)),
+    }
 }
 
-fn fpart(x: f64) -> f64 {
-    x.fract().abs()
+// None after --help.
+fn parse_args(args: &[String]) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        model: None,
+        primitive: None,
+        scene: None,
+        size: None,
+        render_mode: RenderMode::Solid,
+        eye: None,
+        target: None,
+        fov: None,
+        fps: Some(60.0),
+        headless: false,
+        output: None,
+        still: None,
+    };
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        if arg == "--help" || arg == "-h" {
+            return Ok(None);
+        }
+        if arg == "--headless" {
+            options.headless = true;
+            continue;
+        }
+        let takes_value = ["--model", "--primitive", "--scene", "--size", "--mode", "--eye",
+                           "--target", "--fov", "--fps", "--output", "--still"];
+        if !takes_value.contains(&arg.as_str()) {
+            return Err(format!("Unknown option '{}'", arg));
+        }
+        let value = args.next().ok_or_else(|| format!("Missing value for {}", arg))?;
+        match arg.as_str() {
+            "--model" => options.model = Some(value.clone()),
+            "--primitive" => options.primitive = Some(value.clone()),
+            "--scene" => options.scene = Some(value.clone()),
+            "--size" => options.size = Some(parse_size(value)?),
+            "--mode" => {
+                options.render_mode = match value.as_str() {
+                    "solid" => RenderMode::Solid,
+                    "wireframe" => RenderMode::SolidWireframe,
+                    "hidden-line" => RenderMode::HiddenLine,
+                    _ => return Err(format!("Unknown render mode '{}'", value)),
+                }
+            }
+            "--eye" => options.eye = Some(parse_vector(value)?),
+            "--target" => options.target = Some(parse_vector(value)?),
+            "--fov" => {
+                let fov = value.parse().map_err(|_| format!("Invalid fov '{}'", value))?;
+                options.fov = Some(fov);
+            }
+            "--fps" => {
+                options.fps = match value.parse::<f64>() {
+                    _ if value == "uncapped" => None,
+                    Ok(fps) if fps > 0.0 => Some(fps),
+                    _ => return Err(format!("Invalid fps '{}'", value)),
+                }
+            }
+            "--output" => options.output = Some(value.clone()),
+            _ => {
+                // The size may follow the path, as before the other options
+                let size = match args.peek() {
+                    Some(size) if !size.starts_with("--") => Some(parse_size(size)?),
+                    _ => None,
+                };
+                if size.is_some() {
+                    args.next();
+                }
+                options.still = Some((value.clone(), size));
+            }
+        }
+    }
+    if options.model.is_some() && options.primitive.is_some() {
+        return Err("Both --model and --primitive given".to_string());
+    }
+    if options.headless && options.output.is_none() {
+        return Err("--headless needs an --output file".to_string());
+    }
+    Ok(Some(options))
 }
 
-fn rfpart(x: f64) -> f64 {
-    1.0 - fpart(x)
+// The mesh given in place of the shell. Models are scaled to fit in the
+// view and centered.
+fn load_model(options: &Options) -> Result<Option<Mesh>, SwrError> {
+    if let Some(ref name) = options.primitive {
+        return scene::file::primitive(name).map(Some);
+    }
+    let path = match options.model {
+        Some(ref path) => path,
+        None => return Ok(None),
+    };
+    let mut mesh = if path.to_lowercase().ends_with(".md3") {
+        let md3 = Md3::from_file(path).map_err(|e| SwrError::format(format!("{}: {:?}", path, e)))?;
+        let mut mesh = md3_to_mesh(&md3)?;
+        mesh.compute_normals();
+        mesh
+    } else {
+        scene::file::load_mesh(path).map_err(|e| SwrError::format(format!("{}: {}", path, e)))?
+    };
+    let (min, max) = mesh.bounds();
+    let size = max - min;
+    let scale = 4.0 / size.x.max(size.y.max(size.z)).max(1e-9);
+    mesh.transform.scale = Vector3::one() * scale;
+    mesh.transform.position = (min + max) * (-0.5 * scale);
+    Ok(Some(mesh))
 }
 
-impl Device {
-    fn new(width: usize, height: usize) -> Device {
-        Device {
-            width: width,
-            height: height,
-            backbuffer: vec![0; width * height].into_boxed_slice(),
-            depthbuffer: vec![0.0; width * height].into_boxed_slice(),
+fn main() {
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return;
         }
+        Err(e) => {
+            eprint!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = run(options) {
+        eprintln!("{}", e);
+        std::process::exit(1);
     }
+}
+
+fn run(options: Options) -> Result<(), SwrError> {
+    let model = load_model(&options)?;
+    let (width, height) = options.size.unwrap_or((WIDTH, HEIGHT));
+
+    let mut device = Device::new(width, height);
+    device.render_mode = options.render_mode;
+
+    // let md3 = Md3::from_file(std::env::args().nth(1).unwrap()).unwrap();
+    //
+    // let mut md3_mesh = md3_to_mesh(&md3);
+
+    let mut camera = Camera {
+        position: Vector3::new(0.0, 0.0, 8.0),
+        target: Vector3::zero(),
+        fov: 45.0 * f64::consts::PI / 180.0,
+        znear: 0.01,
+        zfar: 100.0,
+        projection: Projection::Perspective,
+        handedness: Handedness::Right,
+    };
+
+    let mut sphere = Mesh::sphere(Vector3::zero(), 1.0, 16, 16);
+    let mut cube = Mesh::cube();
+    let mut octahedron = Mesh::octahedron(1.0);
+    octahedron.transform.rotation = Quaternion::from_euler_angle_degrees(Vector3::new(0.0, 45.0, -90.0));
+    octahedron.transform.scale = Vector3::new(1.0, 1.0, 2.0);
+    octahedron.transform.position = Vector3::new(2.0, 0.0, 0.0);
+
+    let mut tetrahedron = Mesh::tetrahedron(1.0);
+    tetrahedron.transform.rotation = Quaternion::from_euler_angle_degrees(Vector3::new(0.0, 45.0, -90.0));
+    tetrahedron.transform.position = Vector3::new(-2.0, 0.0, 0.0);
+
+    let mut triangle = Mesh::triangle();
+
+    let mut torus = Mesh::torus(1.0, 0.5, 32, 32);
+
+    let mut shell = model.unwrap_or_else(|| Mesh::shell(0.1, 0.5, 3.0, 3, 32, 32));
+
+    // Rows of bumps for the shell's normal map, the slopes of
+    // sin(u) * sin(v) encoded as tangent space normals
+    let bumps = {
+        let (size, bumps, height) = (64, 12.0, 0.4);
+        let k = f64::consts::PI * 2.0 * bumps / size as f64;
+        let texels = (0..size * size)
+            .map(|i| {
+                let (u, v) = ((i % size) as f64 * k, (i / size) as f64 * k);
+                let n = Vector3::new(-height * u.cos() * v.sin(), height * u.sin() * v.cos(), 1.0)
+                    .normalize();
+                Color::rgb(n.x * 0.5 + 0.5, n.y * 0.5 + 0.5, n.z * 0.5 + 0.5)
+            })
+            .collect();
+        Texture::new(size, size, texels)
+    };
 
-    fn clear(&mut self, clear_color: u32) {
-        for i in 0..self.backbuffer.len() {
-            self.backbuffer[i] = clear_color
+    let mut solar_system = Scene::new();
+    solar_system.add(Node::with_mesh(Mesh::sphere(Vector3::zero(), 1.0, 16, 16)), None);
+    let planet_orbit = solar_system.add(Node::new("Planet orbit"), None);
+    let mut planet = Node::with_mesh(Mesh::sphere(Vector3::zero(), 0.5, 16, 16));
+    planet.transform.position = Vector3::new(4.0, 0.0, 0.0);
+    let planet = solar_system.add(planet, Some(planet_orbit));
+    let mut moon = Node::with_mesh(Mesh::sphere(Vector3::zero(), 0.2, 8, 8));
+    moon.transform.position = Vector3::new(1.0, 0.0, 0.0);
+    solar_system.add(moon, Some(planet));
+    let mut show_solar_system = false;
+
+    // Picture-in-picture view from the side, toggled with P
+    let side_camera = Camera {
+        position: Vector3::new(12.0, 4.0, 0.0),
+        target: Vector3::zero(),
+        fov: 45.0 * f64::consts::PI / 180.0,
+        znear: 0.01,
+        zfar: 100.0,
+        projection: Projection::Perspective,
+        handedness: Handedness::Right,
+    };
+    let mut picture_in_picture = false;
+
+    // The cube casting a shadow on the ground, toggled with K
+    let mut ground = Mesh::cube();
+    ground.transform.scale = Vector3::new(6.0, 0.05, 6.0);
+    ground.transform.position = Vector3::new(0.0, -2.0, 0.0);
+    let light = Camera {
+        position: Vector3::new(-4.0, 8.0, -4.0),
+        target: Vector3::zero(),
+        fov: 0.0,
+        znear: 0.1,
+        zfar: 30.0,
+        projection: Projection::Orthographic { height: 14.0 },
+        handedness: Handedness::Right,
+    };
+    let mut show_shadows = false;
+
+    // A chunk of blocks on rolling hills, grass over dirt over stone,
+    // shown in place of the shell with 2
+    let chunk = {
+        let (grass, dirt, stone) = (Color::from_argb8888(0xff5fa84a),
+                                    Color::from_argb8888(0xff8b5a2b),
+                                    Color::from_argb8888(0xff7f7f7f));
+        let mut grid = VoxelGrid::new(32, 16, 32);
+        for z in 0..32 {
+            for x in 0..32 {
+                let (fx, fz) = (x as f64, z as f64);
+                let hill = 7.0 + 3.0 * (fx * 0.3).sin() * (fz * 0.25).cos() +
+                           2.0 * (fz * 0.12).sin();
+                let top = hill.round() as usize;
+                for y in 0..top {
+                    let block = if y + 1 == top {
+                        grass
+                    } else if y + 4 >= top {
+                        dirt
+                    } else {
+                        stone
+                    };
+                    grid.set(x, y, z, Some(block));
+                }
+            }
+        }
+        let mut mesh = grid.to_mesh();
+        mesh.transform.scale = Vector3::one() * 0.2;
+        mesh.transform.position = Vector3::new(-3.2, -2.0, -3.2);
+        mesh
+    };
+    let mut show_chunk = false;
+
+    // The shell's vertices as a scan, tinted by height, shown in its place
+    // with 3
+    let scan = {
+        let mut cloud = PointCloud::from_mesh(&shell).with_point_size(2.0);
+        let (min, max) = cloud.bounds();
+        for (color, p) in cloud.colors.iter_mut().zip(cloud.positions.iter()) {
+            let t = (p.y - min.y) / (max.y - min.y).max(1e-9);
+            *color = Color::rgb(0.2, 0.4, 1.0).lerp(Color::rgb(1.0, 0.8, 0.2), t);
         }
-        for i in 0..self.depthbuffer.len() {
-            self.depthbuffer[i] = 0.0
+        cloud
+    };
+    let mut show_scan = false;
+
+    // A fountain of sparks from the top of the shell, toggled with 4
+    let mut sparks = ParticleSystem::new();
+    sparks.add_emitter(Emitter {
+        rate: 200.0,
+        lifetime: 1.5,
+        velocity: Vector3::new(0.0, 5.0, 0.0),
+        spread: 1.5,
+        size: 0.08,
+        start_color: Color::from_argb8888(0xffffd040),
+        end_color: Color::new(1.0, 0.2, 0.0, 0.0),
+        ..Emitter::new(Vector3::new(0.0, 1.0, 0.0))
+    });
+    let mut show_sparks = false;
+
+    // A soft round glow, shown at each light facing the camera and as an
+    // icon in the corner while markers are on with 5
+    let glow = {
+        let size = 32;
+        let texels = (0..size * size)
+            .map(|i| {
+                let (x, y) = ((i % size) as f64 + 0.5, (i / size) as f64 + 0.5);
+                let r = Vector2::new(x / size as f64 - 0.5, y / size as f64 - 0.5).length() * 2.0;
+                Color::new(1.0, 1.0, 1.0, (1.0 - r).max(0.0).powf(1.5))
+            })
+            .collect();
+        Texture::new(size, size, texels)
+    };
+    let mut show_markers = false;
+
+    // A ball blending into a rounded box and back, shown in place of the
+    // shell with 6
+    let mut blob = Mesh::sphere(Vector3::zero(), 2.0, 32, 32);
+    let boxy = {
+        let mut shape = blob.clone();
+        for v in &mut shape.vertices {
+            let p = v.position;
+            let k = 2.0 / p.x.abs().max(p.y.abs()).max(p.z.abs()).max(1e-9);
+            v.position = p.lerp(p * k, 0.8);
         }
+        shape.compute_normals();
+        shape
+    };
+    let box_shape = blob.add_morph_target(MorphTarget::from_shape("Box", &blob, &boxy));
+    let mut show_blob = false;
+
+    // Rolling water around a lumpy rock, shown in place of the shell with 7
+    let mut water = Mesh::plane(10.0, 10.0, 48, 48);
+    water.material.color = Color::from_argb8888(0xff3070c0);
+    water.transform.position = Vector3::new(0.0, -1.0, 0.0);
+    let mut rock = Mesh::icosphere(1.5, 3);
+    rock.compute_normals();
+    rock.material.color = Color::from_argb8888(0xff8a7f70);
+    rock.modifiers.push(Modifier::Noise {
+        amplitude: 0.25,
+        frequency: 1.5,
+        seed: 3,
+    });
+    let mut show_water = false;
+
+    // Sky fading from blue overhead to haze at the horizon and dark ground
+    // below, shown in place of the flat background until Y is pressed
+    let sky = CubeMap::from_fn(64, |direction| {
+        let zenith = Color::from_argb8888(0xff3a6ea5).to_linear();
+        let horizon = Color::from_argb8888(0xffc8d6e5).to_linear();
+        let ground = Color::from_argb8888(0xff2a2a2a).to_linear();
+        if direction.y > 0.0 {
+            horizon.lerp(zenith, direction.y.sqrt())
+        } else {
+            horizon.lerp(ground, (-direction.y * 8.0).min(1.0))
+        }
+    });
+    let mut show_sky = true;
+    device.environment = Some(Environment::Cube(sky.clone()));
+
+    // Loaded again when it or its files change
+    let scene_path = options.scene.clone();
+    let mut scene_file = match scene_path {
+        Some(ref path) => {
+            let mut file = scene::file::load(path)?;
+            if let Some(c) = file.camera.take() {
+                camera = c;
+            }
+            file.apply(&mut device);
+            Some(file)
+        }
+        None => None,
+    };
+    let mut scene_watcher = scene_file.as_ref().map(|file| file.watcher());
+
+    if let Some(eye) = options.eye {
+        camera.position = eye;
+    }
+    if let Some(target) = options.target {
+        camera.target = target;
+    }
+    if let Some(fov) = options.fov {
+        camera.fov = fov.to_radians();
     }
 
-    fn put_pixel(&mut self, x: u32, y: u32, color: u32) {
-        let offset = (y as usize * self.width) + x as usize;
-        self.backbuffer[offset] = color
+    let background = scene_file.as_ref()
+        .and_then(|file| file.background)
+        .unwrap_or_else(|| Color::from_argb8888(0xff222222));
+
+    // Rendered in tiles by devices of its own, set up as this one
+    if let Some((ref path, size)) = options.still {
+        let (width, height) = size.or(options.size).unwrap_or((7680, 4320));
+        let mut offline = OfflineRender::new(width, height);
+        offline.clear_color = background;
+        offline.save_png_with(path, |tile, viewport| {
+            tile.render_mode = device.render_mode;
+            match scene_file {
+                Some(ref file) => {
+                    file.apply(tile);
+                    tile.render_scene(&camera, viewport, &file.scene)
+                }
+                None => tile.render(&camera, viewport, &[&shell]),
+            }
+        })?;
+        return Ok(());
     }
 
-    fn draw_point(&mut self, point: Vector2) {
-        if point.x >= 0.0 && point.y >= 0.0 && point.x < self.width as f64 &&
-           point.y < self.height as f64 {
-            self.put_pixel(point.x as u32, point.y as u32, 0xffff2222)
+    if options.headless {
+        let viewport = device.viewport();
+        device.clear(background);
+        match scene_file {
+            Some(ref file) => device.render_scene(&camera, &viewport, &file.scene),
+            None => device.render(&camera, &viewport, &[&shell]),
         }
+        device.resolve();
+        let output = options.output.as_ref().unwrap();
+        device.save_png(output)?;
+        return Ok(());
     }
 
-    fn plot(&mut self, x: i32, y: i32, c: f64) {
+    let mut window = Window::new("SWR_RS",
+                                 width,
+                                 height,
+                                 WindowOptions {
+                                     scale: minifb::Scale::X2,
+                                     resize: true,
+                                     ..Default::default()
+                                 })?;
 
-        let c = (255.0 * c) as u32;
-        // let c = 255 - c;
-        let color = (0xff << 24) | (c << 16) | (c << 8) | (c);
+    let start = std::time::Instant::now();
 
-        if x >= 0 && y >= 0 && x < self.width as i32 && y < self.height as i32 {
-            self.put_pixel(x as u32, y as u32, color)
+    // let (min, max) = md3_mesh.bounds();
+    //
+    // let size = max - min;
+    // let scale = Vector3::one() * (4.0 / size.x.max(size.y.max(size.z)));
+    //
+    // md3_mesh.transform.scale = scale;
+    // md3_mesh.transform.position = Vector3::new(0.0, 0.0, 0.0);
+    // md3_mesh.transform.rotation = Quaternion::from_euler_angle_degrees(Vector3::new(0.0, 0.0, -90.0));
+
+    let mut recorder: Option<Recorder> = None;
+
+    let mut orbit = OrbitCamera::new(camera.target, (camera.position - camera.target).length());
+    let mut fly_camera = FpsCamera::new(camera.position);
+    let mut flying = false;
+    let mut pacer = FramePacer::new(options.fps);
+    let mut stats = Stats::new();
+    let mut show_stats = true;
+    let mut debug = DebugDraw::new();
+    let mut show_debug = false;
+    let mut post_effect = 0;
+
+    let mut input = Input::new();
+
+    // Motion runs in fixed steps, drawn blended between the last two
+    let mut timestep = FixedTimestep::new(1.0 / 60.0);
+    let mut clock = Interpolated::new(0.0);
+    let mut shell_motion = Interpolated::new(shell.transform);
+    let mut cube_motion = Interpolated::new(cube.transform);
+    let mut planet_orbit_motion = Interpolated::new(solar_system.node(planet_orbit).transform);
+    let mut planet_motion = Interpolated::new(solar_system.node(planet).transform);
+
+    while window.is_open() {
+        input.update(&window);
+        if input.is_pressed(Key::Escape) {
+            break;
         }
-    }
 
-    fn draw_line(&mut self, p1: Vector2, p2: Vector2) {
-        let len = (p1 - p2).length().abs();
+        let now = std::time::Instant::now();
+
+        let elapsed = (now - start).subsec_nanos() as f64 * 1e-9 + (now - start).as_secs() as f64;
+
+        let dt = pacer.begin_frame();
 
-        for i in 0..len as u32 {
-            self.draw_point(Vector2::lerp(p1, p2, i as f64 / len));
+        if input.just_pressed(Key::Tab) {
+            show_solar_system = !show_solar_system;
         }
-    }
 
-    fn draw_line_aa(&mut self, p1: Vector3, p2: Vector3) {
-        let x0 = p1.x;
-        let x1 = p2.x;
-        let y0 = p1.y;
-        let y1 = p2.y;
+        if input.just_pressed(Key::P) {
+            picture_in_picture = !picture_in_picture;
+        }
 
-        let steep = {
-            (y1 - y0).abs() > (x1 - x0).abs()
-        };
+        if input.just_pressed(Key::F) {
+            flying = !flying;
+            if flying {
+                fly_camera.position = camera.position;
+                fly_camera.look_at(camera.target);
+            }
+        }
 
-        let (x0, y0, x1, y1) = if steep {
-            (y0, x0, y1, x1)
-        } else if x0 > x1 {
-            (x1, y1, x0, y0)
+        let (position, target) = (camera.position, camera.target);
+        if flying {
+            fly_camera.update(&input, dt);
+            fly_camera.apply(&mut camera);
         } else {
-            (x0, y0, x1, y1)
-        };
-
-        let dx = x1 - x0;
-        let dy = y1 - y0;
-
-        let slope = dy / dx;
-        let xend = round(x0);
-        let yend = y0 + slope * (xend - x0);
-        let xgap = rfpart(x0 + 0.5);
-        let xpxl1 = xend as i32;
-        let ypxl1 = yend as i32;
-
-        if steep {
-            self.plot(ypxl1, xpxl1, rfpart(yend) * xgap);
-            self.plot(ypxl1 + 1, xpxl1, fpart(yend) * xgap);
-        } else {
-            self.plot(xpxl1, ypxl1, rfpart(yend) * xgap);
-            self.plot(xpxl1, ypxl1 + 1, fpart(yend) * xgap);
+            orbit.update(&input);
+            orbit.apply(&mut camera);
+        }
+        // Accumulated frames only add up while the view stays the same
+        if camera.position != position || camera.target != target {
+            device.reset_accumulation();
         }
 
-        let mut intery = yend + slope;
-
-        let xend = round(x1);
-        let yend = y1 + slope * (xend - x1);
-        let xgap = fpart(x1 + 0.5);
-        let xpxl2 = xend as i32;
-        let ypxl2 = yend as i32;
+        let (window_width, window_height) = window.get_size();
+        if window_width >= SCALE && window_height >= SCALE {
+            device.resize(window_width / SCALE, window_height / SCALE);
+        }
 
-        if steep {
-            self.plot(ypxl2, xpxl2, rfpart(yend) * xgap);
-            self.plot(ypxl2 + 1, xpxl2, fpart(yend) * xgap);
-        } else {
-            self.plot(xpxl2, ypxl2, rfpart(yend) * xgap);
-            self.plot(xpxl2, ypxl2 + 1, fpart(yend) * xgap);
+        // The camera stays where it was moved to
+        if let (Some(path), Some(watcher)) = (scene_path.as_ref(), scene_watcher.as_mut()) {
+            if !watcher.poll().is_empty() {
+                match scene::file::load(path) {
+                    Ok(file) => {
+                        println!("Reloaded {}", path);
+                        file.apply(&mut device);
+                        *watcher = file.watcher();
+                        scene_file = Some(file);
+                        device.reset_accumulation();
+                    }
+                    Err(e) => println!("Reloading {} failed: {}", path, e),
+                }
+            }
         }
 
-        if steep {
-            for x in (xpxl1 + 1)..(xpxl2 - 1) {
-                self.plot(intery as i32, x, rfpart(intery));
-                self.plot(intery as i32 + 1, x, fpart(intery));
-                intery = intery + slope
+        let spin = |x, y, z| Quaternion::from_euler_angle_degrees(Vector3::new(x, y, z));
+        for _ in 0..timestep.advance(dt) {
+            clock.step();
+            clock.current += timestep.step;
+            shell_motion.step();
+            cube_motion.step();
+            planet_orbit_motion.step();
+            planet_motion.step();
+            if !device.is_accumulating() {
+                shell_motion.current.rotate(spin(0.0, 1.0, 1.0));
+                planet_orbit_motion.current.rotate(spin(0.0, 0.5, 0.0));
+                planet_motion.current.rotate(spin(0.0, 3.0, 0.0));
+                cube_motion.current.rotate(spin(0.0, 1.0, 0.5));
             }
-        } else {
-            for x in (xpxl1 + 1)..(xpxl2 - 1) {
-                self.plot(x, intery as i32, rfpart(intery));
-                self.plot(x, intery as i32 + 1, fpart(intery));
-                intery = intery + slope
+            if show_sparks {
+                sparks.update(timestep.step);
             }
         }
+        let alpha = timestep.alpha();
+        shell.transform = shell_motion.get(alpha);
+        cube.transform = cube_motion.get(alpha);
+        solar_system.node_mut(planet_orbit).transform = planet_orbit_motion.get(alpha);
+        solar_system.node_mut(planet).transform = planet_motion.get(alpha);
+
+        let time = clock.get(alpha);
+        blob.morph_weights[box_shape] = time.sin() * 0.5 + 0.5;
+        water.modifiers = vec![Modifier::Wave {
+                                   amplitude: 0.2,
+                                   wavelength: 3.0,
+                                   direction: Vector3::new(1.0, 0.0, 0.5).normalize(),
+                                   phase: -time * 2.0,
+                               }];
 
-    }
+        {
+            let meshes = if show_chunk {
+                vec![&chunk]
+            } else if show_scan {
+                vec![]
+            } else if show_blob {
+                vec![&blob]
+            } else if show_water {
+                vec![&water, &rock]
+            } else if show_shadows {
+                vec![&cube, &ground]
+            } else {
+                vec![&shell]
+            };
+            // let meshes = vec![&octahedron,&tetrahedron];
+            // let meshes = vec![&cube, &sphere];
+            // let meshes = vec![&triangle];
+            let viewport = device.viewport();
+            let inset = Viewport::new(viewport.width * 0.68,
+                                      viewport.height * 0.02,
+                                      viewport.width * 0.3,
+                                      viewport.height * 0.3);
+
+            if show_shadows {
+                if show_solar_system {
+                    device.render_scene_shadows(&light, &solar_system);
+                } else {
+                    device.render_shadows(&light, &meshes);
+                }
+            }
+            let background = scene_file.as_ref()
+                .and_then(|file| file.background)
+                .unwrap_or_else(|| Color::from_argb8888(0xff222222));
+            device.clear(background);
+            if let Some(ref file) = scene_file {
+                device.render_scene(&camera, &viewport, &file.scene);
+            } else if show_solar_system {
+                device.render_scene(&camera, &viewport, &solar_system);
+            } else {
+                device.render(&camera, &viewport, &meshes);
+            }
+            if show_scan {
+                device.render_points(&camera, &viewport, &[&scan]);
+            }
+            if show_sparks {
+                device.render_particles(&camera, &viewport, &sparks);
+            }
+            if show_sky {
+                device.render_skybox(&camera, &viewport, &sky);
+            }
+            if show_markers {
+                let markers: Vec<Billboard> = device.lights
+                    .iter()
+                    .map(|light| {
+                        Billboard::new(light.position, Vector2::new(0.6, 0.6))
+                            .with_color(light.color)
+                    })
+                    .collect();
+                device.render_billboards(&camera, &viewport, &markers, Some(&glow));
+                let icon = Sprite::new(Vector2::new(viewport.width - 20.0, 4.0),
+                                       Vector2::new(16.0, 16.0));
+                device.draw_sprites(&[icon], Some(&glow));
+            }
+            if show_debug {
+                debug.clear();
+                debug.draw_grid(20.0, 20, Color::from_argb8888(0xff666666));
+                let instances = if show_solar_system {
+                    solar_system.meshes()
+                } else {
+                    meshes.iter().map(|mesh| (*mesh, Matrix4::identity())).collect()
+                };
+                for (mesh, parent) in instances {
+                    let world = mesh.transform.matrix() * parent;
+                    let (min, max) = mesh.bounds();
+                    let center = Vector3::transform_coordinate(&((min + max) * 0.5), &world);
+                    let corner = Vector3::transform_coordinate(&max, &world);
+                    debug.draw_axes(&world, 1.0);
+                    debug.draw_sphere_wire(center,
+                                           (corner - center).length(),
+                                           Color::from_argb8888(0xffffff00));
+                }
+                device.render_debug(&camera, &viewport, &debug);
+            }
+            if picture_in_picture {
+                device.clear_viewport(Color::from_argb8888(0xff444444), &inset);
+                if show_solar_system {
+                    device.render_scene(&side_camera, &inset, &solar_system);
+                } else {
+                    device.render(&side_camera, &inset, &meshes);
+                }
+            }
+            device.resolve();
+            stats.busy_time = pacer.busy_time();
+            stats.target_fps = pacer.target_fps;
+            stats.end_frame(dt, &mut device);
+            if show_stats {
+                stats.draw(&mut device);
+            }
+            if device.debug_view != DebugView::Off {
+                let label = format!("{:?} view", device.debug_view);
+                let y = device.height() as i32 - 12;
+                device.draw_text(4, y, &label, Color::white());
+            }
+        }
 
-    fn project(&mut self, coord: &Vector3, trans: &Matrix4) -> Vector3 {
-        let point = Vector3::transform_coordinate(coord, trans);
+        // Middle click prints what is under the cursor
+        if input.mouse_just_pressed(MouseButton::Middle) && show_solar_system {
+            if let Some((x, y)) = input.mouse_position() {
+                let (x, y) = (x / SCALE as f64, y / SCALE as f64);
+                match solar_system.pick(&camera, &device.viewport(), x, y) {
+                    Some((id, hit)) => {
+                        println!("Picked {} (face {})", solar_system.node(id).name, hit.face)
+                    }
+                    None => println!("Picked nothing"),
+                }
+            }
+        }
 
-        let x = point.x * self.width as f64 + self.width as f64 / 2.0;
-        let y = -point.y * self.height as f64 + self.height as f64 / 2.0;
+        if input.just_pressed(Key::R) && recorder.is_none() {
+            recorder = Some(Recorder::gif("recording.gif", 60, 5.0));
+        }
 
-        Vector3::new(x, y, point.z)
-    }
+        if let Some(mut rec) = recorder.take() {
+            match rec.capture(&device) {
+                Ok(()) if rec.is_finished() => {
+                    if let Err(e) = rec.finish() {
+                        println!("Failed to save recording: {}", e);
+                    }
+                }
+                Ok(()) => recorder = Some(rec),
+                Err(e) => println!("Failed to record frame: {}", e),
+            }
+        }
 
-    fn render_pixel(&mut self, x: u32, y: u32, w: Vector3) {
-        let a = Vector3::new(0.75, 0.75, 0.75).clamp(Vector3::zero(), Vector3::one());
-        let b = Vector3::new(0.5, 0.5, 0.5).clamp(Vector3::zero(), Vector3::one());
-        let c = Vector3::new(0.0, 0.0, 0.0).clamp(Vector3::zero(), Vector3::one());
+        if input.just_pressed(Key::L) {
+            device.render_mode = match device.render_mode {
+                RenderMode::Solid => RenderMode::SolidWireframe,
+                RenderMode::SolidWireframe => RenderMode::HiddenLine,
+                RenderMode::HiddenLine => RenderMode::Solid,
+            };
+        }
 
-        let color = a * w.x + b * w.y + c * w.z;
+        if input.just_pressed(Key::B) {
+            show_debug = !show_debug;
+        }
 
-        let color = color * 255.0;
+        if input.just_pressed(Key::C) {
+            post_effect = (post_effect + 1) % 6;
+            device.post = match post_effect {
+                1 => PostChain::new().then(Vignette { radius: 0.4, strength: 0.8 }),
+                2 => PostChain::new().then(ChromaticAberration { offset: 3.0 }),
+                3 => PostChain::new().then(Grayscale),
+                4 => PostChain::new().then(Invert),
+                // Needs HDR to have colors past the threshold
+                5 => {
+                    PostChain::new().then(Bloom {
+                        threshold: 1.0,
+                        intensity: 0.5,
+                        radius: 6.0,
+                    })
+                }
+                _ => PostChain::new(),
+            };
+        }
 
-        let r = color.x as u8 as u32;
-        let g = color.y as u8 as u32;
-        let b = color.z as u8 as u32;
+        if input.just_pressed(Key::X) {
+            device.fxaa = !device.fxaa;
+        }
 
-        let c = (0xff << 24) | (r << 16) | (g << 8) | b;
+        if input.just_pressed(Key::M) {
+            let msaa = match device.msaa() {
+                Msaa::Off => Msaa::X2,
+                Msaa::X2 => Msaa::X4,
+                Msaa::X4 => Msaa::Off,
+            };
+            device.set_msaa(msaa);
+        }
 
-        self.put_pixel(x, y, c)
-    }
+        if input.just_pressed(Key::F5) {
+            let factor = match device.supersampling() {
+                1 => 2,
+                2 => 4,
+                _ => 1,
+            };
+            device.set_supersampling(factor);
+        }
 
-    fn draw_triangle(&mut self, v0: Vector3, v1: Vector3, v2: Vector3) {
-        let screen_max = Vector2::new(self.width as f64, self.height as f64);
-        let max = v0.max(v1).max(v2).xy().min(screen_max);
-        let min = v0.min(v1).min(v2).xy().max(Vector2::zero());
+        // Clears only the tiles drawn to, compare the clear stage with F3
+        if input.just_pressed(Key::F6) {
+            let partial = !device.is_partial_clear();
+            device.set_partial_clear(partial);
+        }
 
-        for y in min.y as u32..max.y as u32 {
-            for x in min.x as u32..max.x as u32 {
+        // Still mode, the scene stops and converges to a smooth image
+        if input.just_pressed(Key::T) {
+            let accumulate = !device.is_accumulating();
+            device.set_accumulation(accumulate);
+        }
 
-                let a = edge_func(v0.xy(), v1.xy(), v2.xy());
-                let w0 = edge_func(v1.xy(), v2.xy(), Vector2::new(x as f64, y as f64)) / a;
-                let w1 = edge_func(v2.xy(), v0.xy(), Vector2::new(x as f64, y as f64)) / a;
-                let w2 = edge_func(v0.xy(), v1.xy(), Vector2::new(x as f64, y as f64)) / a;
+        // Deferred shading, lit by a ring of colored lights
+        if input.just_pressed(Key::N) {
+            let deferred = !device.is_deferred();
+            device.set_deferred(deferred);
+            device.lights = if deferred {
+                let colors = [0xffff4040, 0xff40ff40, 0xff4040ff,
+                              0xffffff40, 0xff40ffff, 0xffff40ff];
+                colors.iter()
+                    .enumerate()
+                    .map(|(i, &color)| {
+                        let angle = i as f64 * f64::consts::PI * 2.0 / colors.len() as f64;
+                        let position = Vector3::new(angle.cos() * 4.0, 1.0, angle.sin() * 4.0);
+                        PointLight::new(position, Color::from_argb8888(color), 6.0)
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+        }
 
-                let w = Vector3::new(w0, w1, w2);
+        if input.just_pressed(Key::Y) {
+            show_sky = !show_sky;
+        }
 
-                if w0 >= 0.0 && w1 >= 0.0 && w2 >= 0.0 {
+        if input.just_pressed(Key::I) {
+            shell.material.normal_map = match shell.material.normal_map {
+                Some(_) => None,
+                None => Some(bumps.clone()),
+            };
+        }
 
-                    let z = v0.z * w0 + v1.z * w1 + v2.z * w2;
-                    let offset = y as usize * self.width + x as usize;
-                    if self.depthbuffer[offset] < z {
-                        self.depthbuffer[offset] = z;
-                        self.render_pixel(x, y, w)
+        // Shading models on the shell: polished plastic, clay and brushed
+        // gold, physically based, then toon shaded with an outline. All but
+        // the first need lights to show.
+        if input.just_pressed(Key::Key1) {
+            let next = match shell.material.shading {
+                Shading::Basic => Material::pbr(Color::white(), 0.0, 0.3),
+                Shading::Pbr(Diffuse::Lambert) if shell.material.metallic == 0.0 => {
+                    Material {
+                        shading: Shading::Pbr(Diffuse::OrenNayar),
+                        ..Material::pbr(Color::white(), 0.0, 0.9)
                     }
-
                 }
-
-            }
+                Shading::Pbr(Diffuse::OrenNayar) => {
+                    Material::pbr(Color::from_argb8888(0xffffd27f).to_linear(), 1.0, 0.4)
+                }
+                Shading::Pbr(_) => Material::toon(Color::from_argb8888(0xffe07040).to_linear(), 3),
+                Shading::Toon { .. } => Material::new(),
+            };
+            shell.material = Material {
+                normal_map: shell.material.normal_map.take(),
+                reflectivity: shell.material.reflectivity,
+                ..next
+            };
         }
 
-    }
-
-    fn render(&mut self, camera: &Camera, meshes: &Vec<&Mesh>) {
-        let view_mat = Matrix4::look_at_lh(camera.position, camera.target, Vector3::unit_y());
-        let projection_mat = Matrix4::perspective_rh(camera.fov,
-                                                     self.width as f64 / self.height as f64,
-                                                     camera.znear,
-                                                     camera.zfar);
-        for mesh in meshes {
-
-
-            let world_mat = Matrix4::scale(mesh.scale) *
-                            Matrix4::rotation(Quaternion::from_euler_angle_degrees(mesh.rotation)) *
-                            Matrix4::translation(mesh.position);
-            let transform_mat = world_mat * view_mat * projection_mat;
-
-            for face in &mesh.faces {
-                let v0 = self.project(&mesh.vertices[face.a as usize], &transform_mat);
-                let v1 = self.project(&mesh.vertices[face.b as usize], &transform_mat);
-                let v2 = self.project(&mesh.vertices[face.c as usize], &transform_mat);
-                self.draw_triangle(v0, v1, v2);
-                // self.draw_line_aa(v0, v1);
-                // self.draw_line_aa(v1, v2);
-                // self.draw_line_aa(v2, v0);
-            }
-
+        if input.just_pressed(Key::Key2) {
+            show_chunk = !show_chunk;
         }
 
-    }
-}
+        if input.just_pressed(Key::Key3) {
+            show_scan = !show_scan;
+        }
 
-fn edge_func(v0: Vector2, v1: Vector2, p: Vector2) -> f64 {
-    (v0.y - v1.y) * p.x + (v1.x - v0.x) * p.y + (v0.x * v1.y - v0.y * v1.x)
-}
+        if input.just_pressed(Key::Key4) {
+            show_sparks = !show_sparks;
+            sparks.clear();
+        }
 
-fn main() {
+        if input.just_pressed(Key::Key5) {
+            show_markers = !show_markers;
+        }
 
-    let mut device = Device::new(WIDTH, HEIGHT);
+        if input.just_pressed(Key::Key6) {
+            show_blob = !show_blob;
+        }
 
-    let mut window = Window::new("SWR_RS",
-                                 WIDTH,
-                                 HEIGHT,
-                                 WindowOptions { scale: minifb::Scale::X2, ..Default::default() })
-        .unwrap_or_else(|e| {
-            panic!("{}", e);
-        });
+        if input.just_pressed(Key::Key7) {
+            show_water = !show_water;
+        }
 
-    // let md3 = Md3::from_file(std::env::args().nth(1).unwrap()).unwrap();
-    //
-    // let mut md3_mesh = md3_to_mesh(&md3);
+        // Mirrors the sky on the shell
+        if input.just_pressed(Key::J) {
+            shell.material.reflectivity = if shell.material.reflectivity > 0.0 { 0.0 } else { 0.6 };
+        }
 
-    let camera = Camera {
-        position: Vector3::new(0.0, 0.0, 15.0),
-        target: Vector3::zero(),
-        fov: 45.0 * f64::consts::PI / 180.0,
-        znear: 0.01,
-        zfar: 100.0,
-    };
+        // Fades into the clear color, cycling through the falloffs
+        if input.just_pressed(Key::Z) {
+            let mode = match device.fog.map(|fog| fog.mode) {
+                None => Some(FogMode::Linear { start: 6.0, end: 14.0 }),
+                Some(FogMode::Linear { .. }) => Some(FogMode::Exponential { density: 0.12 }),
+                Some(FogMode::Exponential { .. }) => {
+                    Some(FogMode::ExponentialSquared { density: 0.1 })
+                }
+                Some(FogMode::ExponentialSquared { .. }) => None,
+            };
+            device.fog = mode.map(|mode| Fog::new(Color::from_argb8888(0xff222222), mode));
+        }
 
-    let mut sphere = Mesh::sphere(Vector3::zero(), 1.0, 16, 16);
-    let mut cube = Mesh::cube();
-    let mut octahedron = Mesh::octahedron(1.0);
-    octahedron.rotation = Vector3::new(0.0, 45.0, -90.0);
-    octahedron.scale = Vector3::new(1.0, 1.0, 2.0);
-    octahedron.position = Vector3::new(2.0, 0.0, 0.0);
+        // Ambient occlusion, seen with deferred shading
+        if input.just_pressed(Key::U) {
+            device.ssao = match device.ssao {
+                Some(_) => None,
+                None => Some(Ssao::new()),
+            };
+        }
 
-    let mut tetrahedron = Mesh::tetrahedron(1.0);
-    tetrahedron.rotation = Vector3::new(0.0, 45.0, -90.0);
-    tetrahedron.position = Vector3::new(-2.0, 0.0, 0.0);
+        if input.just_pressed(Key::K) {
+            show_shadows = !show_shadows;
+            device.shadow = if show_shadows { Some(ShadowMap::new(512)) } else { None };
+        }
 
-    let mut triangle = Mesh::triangle();
+        if input.just_pressed(Key::F3) {
+            show_stats = !show_stats;
+        }
 
-    let mut torus = Mesh::torus(1.0, 0.5, 32, 32);
+        // Uncapped to see how fast frames render, and back
+        if input.just_pressed(Key::F4) {
+            pacer.target_fps = match pacer.target_fps {
+                Some(_) => None,
+                None => options.fps.or(Some(60.0)),
+            };
+        }
 
-    let mut shell = Mesh::shell(0.1, 0.5, 3.0, 3, 32, 32);
+        if input.just_pressed(Key::F12) {
+            if let Err(e) = device.save_png("screenshot.png") {
+                println!("Failed to save screenshot: {}", e);
+            }
+        }
 
-    let start = std::time::Instant::now();
+        // For a look at the generated shell in a modeling tool
+        if input.just_pressed(Key::F11) {
+            if let Err(e) = shell.save_obj("shell.obj") {
+                println!("Failed to save mesh: {}", e);
+            }
+        }
 
-    // let (min, max) = md3_mesh.bounds();
-    //
-    // let size = max - min;
-    // let scale = Vector3::one() * (4.0 / size.x.max(size.y.max(size.z)));
-    //
-    // md3_mesh.scale = scale;
-    // md3_mesh.position = Vector3::new(0.0, 0.0, 0.0);
-    // md3_mesh.rotation = Vector3::new(0.0, 0.0, -90.0);
+        if input.just_pressed(Key::O) {
+            camera.projection = match camera.projection {
+                Projection::Perspective => Projection::Orthographic { height: 8.0 },
+                Projection::Orthographic { .. } => Projection::Perspective,
+            };
+        }
 
-    let sleep_time = std::time::Duration::from_millis(16);
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        let now = std::time::Instant::now();
+        if input.just_pressed(Key::V) {
+            device.debug_view = device.debug_view.next();
+        }
 
-        let elapsed = (now - start).subsec_nanos() as f64 * 1e-9 + (now - start).as_secs() as f64;
+        if input.just_pressed(Key::G) {
+            device.gamma_correct = !device.gamma_correct;
+        }
 
-        {
-            let meshes = vec![&shell];
-            // let meshes = vec![&octahedron,&tetrahedron];
-            // let meshes = vec![&cube, &sphere];
-            // let meshes = vec![&triangle];
-            device.clear(0xff222222);
-            device.render(&camera, &meshes);
+        if input.just_pressed(Key::H) {
+            let hdr = !device.is_hdr();
+            device.set_hdr(hdr);
         }
 
         let r = elapsed.sin().abs();
         let r = Vector3::new(r, r, r);
 
-        shell.rotation = shell.rotation + Vector3::new(0.0, 1.0, 1.0);
-        // octahedron.rotation = octahedron.rotation + Vector3::new(0.0, 1.0, 0.0);
-        // tetrahedron.rotation = tetrahedron.rotation + Vector3::new(1.0, 1.0, 1.0);
-        // octahedron.scale = Vector3::one() + r;
-        // sphere.rotation = sphere.rotation + Vector3::new(1.0, 1.0, 1.0);
-        // sphere.scale = Vector3::one() + r;
-        // cube.rotation = cube.rotation + Vector3::new(1.0, 1.0, 1.0);
-        // cube.scale = Vector3::one() + r;
+        // octahedron.transform.rotate(spin(0.0, 1.0, 0.0));
+        // tetrahedron.transform.rotate(spin(1.0, 1.0, 1.0));
+        // octahedron.transform.scale = Vector3::one() + r;
+        // sphere.transform.rotate(spin(1.0, 1.0, 1.0));
+        // sphere.transform.scale = Vector3::one() + r;
+        // cube.transform.rotate(spin(1.0, 1.0, 1.0));
+        // cube.transform.scale = Vector3::one() + r;
 
-        // md3_mesh.rotation = md3_mesh.rotation + Vector3::new(0.0, 0.3, 0.0);
+        // md3_mesh.transform.rotate(spin(0.0, 0.3, 0.0));
 
-        window.update_with_buffer(&device.backbuffer);
-
-        let elapsed = now.elapsed();
-        if sleep_time > elapsed {
-            let sleep = sleep_time - elapsed;
-            std::thread::sleep(sleep)
+        {
+            profile_scope!("present");
+            window.update_with_buffer(device.backbuffer())?;
         }
 
+        pacer.end_frame();
+
     }
+    Ok(())
 }
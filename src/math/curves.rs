@@ -0,0 +1,156 @@
+use super::vector::Vector2;
+
+pub fn quadratic(p0: Vector2, p1: Vector2, p2: Vector2, t: f64) -> Vector2 {
+    let s = 1.0 - t;
+    p0 * (s * s) + p1 * (2.0 * s * t) + p2 * (t * t)
+}
+
+pub fn cubic(p0: Vector2, p1: Vector2, p2: Vector2, p3: Vector2, t: f64) -> Vector2 {
+    let s = 1.0 - t;
+    p0 * (s * s * s) + p1 * (3.0 * s * s * t) + p2 * (3.0 * s * t * t) + p3 * (t * t * t)
+}
+
+// Uniform Catmull-Rom segment from p1 at t = 0 to p2 at t = 1, with p0 and
+// p3 shaping the tangents.
+pub fn catmull_rom(p0: Vector2, p1: Vector2, p2: Vector2, p3: Vector2, t: f64) -> Vector2 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0 + (p2 - p0) * t + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2 +
+     (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3) * 0.5
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum Curve {
+    Quadratic(Vector2, Vector2, Vector2),
+    Cubic(Vector2, Vector2, Vector2, Vector2),
+    // Passes through all of the points, the end points repeated for the
+    // tangents of the first and last segments. Needs at least one point.
+    CatmullRom(Vec<Vector2>),
+}
+
+impl Curve {
+    // Point at t in [0, 1]. A spline spends an equal range of t on each of
+    // its segments, see ArcLength for an even speed.
+    pub fn point(&self, t: f64) -> Vector2 {
+        match *self {
+            Curve::Quadratic(p0, p1, p2) => quadratic(p0, p1, p2, t),
+            Curve::Cubic(p0, p1, p2, p3) => cubic(p0, p1, p2, p3, t),
+            Curve::CatmullRom(ref points) => {
+                let n = points.len();
+                if n < 2 {
+                    return points[0];
+                }
+                let s = t.clamp(0.0, 1.0) * (n - 1) as f64;
+                let i = (s as usize).min(n - 2);
+                catmull_rom(points[i.saturating_sub(1)],
+                            points[i],
+                            points[i + 1],
+                            points[(i + 2).min(n - 1)],
+                            s - i as f64)
+            }
+        }
+    }
+
+    pub fn arc_length(&self, samples: usize) -> ArcLength {
+        ArcLength::new(self, samples)
+    }
+}
+
+// Cumulative length of a curve sampled at evenly spaced t, for mapping
+// distances along the curve back to t.
+#[derive(Debug,Clone,PartialEq)]
+pub struct ArcLength {
+    lengths: Vec<f64>,
+}
+
+impl ArcLength {
+    pub fn new(curve: &Curve, samples: usize) -> ArcLength {
+        let samples = samples.max(1);
+        let mut lengths = Vec::with_capacity(samples + 1);
+        let mut length = 0.0;
+        let mut prev = curve.point(0.0);
+        lengths.push(0.0);
+        for i in 1..samples + 1 {
+            let p = curve.point(i as f64 / samples as f64);
+            length += (p - prev).length();
+            lengths.push(length);
+            prev = p;
+        }
+        ArcLength { lengths: lengths }
+    }
+
+    pub fn length(&self) -> f64 {
+        self.lengths[self.lengths.len() - 1]
+    }
+
+    // The t at the distance along the curve, clamped to its ends.
+    pub fn parameter(&self, distance: f64) -> f64 {
+        let samples = (self.lengths.len() - 1) as f64;
+        if distance <= 0.0 {
+            return 0.0;
+        }
+        if distance >= self.length() {
+            return 1.0;
+        }
+        // First sample past the distance, the one before it is not
+        let i = match self.lengths.binary_search_by(|l| l.partial_cmp(&distance).unwrap()) {
+            Ok(i) => return i as f64 / samples,
+            Err(i) => i,
+        };
+        let (l0, l1) = (self.lengths[i - 1], self.lengths[i]);
+        (i as f64 - 1.0 + (distance - l0) / (l1 - l0)) / samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector2;
+    use super::Curve;
+
+    fn close(a: Vector2, b: Vector2) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn evaluate() {
+        let (a, b, c, d) = (Vector2::new(0.0, 0.0),
+                            Vector2::new(0.0, 2.0),
+                            Vector2::new(2.0, 2.0),
+                            Vector2::new(2.0, 0.0));
+
+        let quadratic = Curve::Quadratic(a, b, c);
+        assert!(close(a, quadratic.point(0.0)));
+        assert!(close(c, quadratic.point(1.0)));
+        assert!(close(Vector2::new(0.5, 1.5), quadratic.point(0.5)));
+
+        let cubic = Curve::Cubic(a, b, c, d);
+        assert!(close(d, cubic.point(1.0)));
+        assert!(close(Vector2::new(1.0, 1.5), cubic.point(0.5)));
+
+        // Passes through every point
+        let spline = Curve::CatmullRom(vec![a, b, c, d]);
+        assert!(close(a, spline.point(0.0)));
+        assert!(close(b, spline.point(1.0 / 3.0)));
+        assert!(close(c, spline.point(2.0 / 3.0)));
+        assert!(close(d, spline.point(1.0)));
+        assert!(close(a, Curve::CatmullRom(vec![a]).point(0.5)));
+    }
+
+    #[test]
+    fn arc_length() {
+        // Control points spaced unevenly, so t is not proportional to distance
+        let line = Curve::Cubic(Vector2::new(0.0, 0.0),
+                                Vector2::new(1.0, 0.0),
+                                Vector2::new(2.0, 0.0),
+                                Vector2::new(9.0, 0.0));
+        let table = line.arc_length(256);
+        assert!((table.length() - 9.0).abs() < 1e-9);
+        assert_eq!(0.0, table.parameter(-1.0));
+        assert_eq!(1.0, table.parameter(10.0));
+        for &distance in &[1.0, 4.5, 8.0] {
+            let x = line.point(table.parameter(distance)).x;
+            assert!((x - distance).abs() < 1e-3, "{} at {}", x, distance);
+        }
+    }
+}
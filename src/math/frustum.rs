@@ -0,0 +1,107 @@
+
+use super::vector::Vector3;
+use super::matrix::Matrix4;
+
+// Points p with normal.dot(p) + d >= 0 are on the inner side.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Plane {
+    pub normal: Vector3,
+    pub d: f64,
+}
+
+impl Plane {
+    pub fn new(normal: Vector3, d: f64) -> Plane {
+        Plane {
+            normal: normal,
+            d: d,
+        }
+    }
+
+    pub fn normalize(self) -> Plane {
+        let length = self.normal.length();
+        Plane::new(self.normal / length, self.d / length)
+    }
+
+    pub fn distance(&self, point: Vector3) -> f64 {
+        self.normal.dot(point) + self.d
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Frustum {
+    // left, right, bottom, top, near, far
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    // Extracts the clip planes of a view-projection matrix, following the
+    // NDC conventions of the Matrix4 projections. The planes end up in the
+    // space the matrix transforms from, so passing world * view * projection
+    // gives planes in object space.
+    pub fn from_matrix(m: &Matrix4) -> Frustum {
+        let x = Plane::new(Vector3::new(m.m11, m.m21, m.m31), m.m41);
+        let y = Plane::new(Vector3::new(m.m12, m.m22, m.m32), m.m42);
+        let z = Plane::new(Vector3::new(m.m13, m.m23, m.m33), m.m43);
+        let w = Plane::new(Vector3::new(m.m14, m.m24, m.m34), m.m44);
+        let add = |a: Plane, b: Plane| Plane::new(a.normal + b.normal, a.d + b.d).normalize();
+        let sub = |a: Plane, b: Plane| Plane::new(a.normal - b.normal, a.d - b.d).normalize();
+
+        // Depth is reversed, so near is z <= w and far is z >= 0
+        Frustum { planes: [add(w, x), sub(w, x), add(w, y), sub(w, y), sub(w, z), z.normalize()] }
+    }
+
+    // Conservative: boxes near a corner of the frustum may be reported as
+    // intersecting even though they are outside.
+    pub fn intersects_aabb(&self, min: Vector3, max: Vector3) -> bool {
+        self.planes.iter().all(|plane| {
+            let n = plane.normal;
+            let farthest = Vector3::new(if n.x >= 0.0 { max.x } else { min.x },
+                                        if n.y >= 0.0 { max.y } else { min.y },
+                                        if n.z >= 0.0 { max.z } else { min.z });
+            plane.distance(farthest) >= 0.0
+        })
+    }
+
+    pub fn intersects_sphere(&self, center: Vector3, radius: f64) -> bool {
+        self.planes.iter().all(|plane| plane.distance(center) >= -radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::f64;
+    use math::vector::Vector3;
+    use math::matrix::Matrix4;
+    use super::Frustum;
+
+    fn frustum() -> Frustum {
+        let view = Matrix4::look_at_rh(Vector3::new(0.0, 0.0, 10.0),
+                                       Vector3::zero(),
+                                       Vector3::unit_y());
+        let projection = Matrix4::perspective_rh(f64::consts::PI / 2.0, 1.0, 1.0, 20.0);
+        Frustum::from_matrix(&(view * projection))
+    }
+
+    #[test]
+    fn aabb() {
+        let f = frustum();
+        let one = Vector3::one();
+        assert!(f.intersects_aabb(one * -1.0, one));
+        // behind the camera, beyond the far plane and off to the side
+        assert!(!f.intersects_aabb(Vector3::new(-1.0, -1.0, 11.0), Vector3::new(1.0, 1.0, 12.0)));
+        assert!(!f.intersects_aabb(Vector3::new(-1.0, -1.0, -12.0), Vector3::new(1.0, 1.0, -11.0)));
+        assert!(!f.intersects_aabb(Vector3::new(11.0, -1.0, -1.0), Vector3::new(12.0, 1.0, 1.0)));
+        // straddling the left plane
+        assert!(f.intersects_aabb(Vector3::new(-11.0, -1.0, -1.0), Vector3::new(-9.0, 1.0, 1.0)));
+    }
+
+    #[test]
+    fn sphere() {
+        let f = frustum();
+        assert!(f.intersects_sphere(Vector3::zero(), 1.0));
+        assert!(f.intersects_sphere(Vector3::new(0.0, 0.0, 9.5), 1.0));
+        assert!(!f.intersects_sphere(Vector3::new(0.0, 0.0, 9.6), 0.4));
+        assert!(!f.intersects_sphere(Vector3::new(0.0, 12.0, 0.0), 1.0));
+    }
+}
@@ -27,6 +27,113 @@ impl Matrix2 {
     }
 }
 
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Matrix3 {
+    pub m11: f64,
+    pub m12: f64,
+    pub m13: f64,
+    pub m21: f64,
+    pub m22: f64,
+    pub m23: f64,
+    pub m31: f64,
+    pub m32: f64,
+    pub m33: f64,
+}
+
+impl Matrix3 {
+    // From its rows, top first.
+    pub fn new(row1: Vector3, row2: Vector3, row3: Vector3) -> Matrix3 {
+        Matrix3 {
+            m11: row1.x,
+            m12: row1.y,
+            m13: row1.z,
+            m21: row2.x,
+            m22: row2.y,
+            m23: row2.z,
+            m31: row3.x,
+            m32: row3.y,
+            m33: row3.z,
+        }
+    }
+
+    pub fn identity() -> Matrix3 {
+        Matrix3::new(Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z())
+    }
+
+    // Upper left 3x3 part, i.e. the rotation and scale without translation.
+    pub fn from_matrix4(m: &Matrix4) -> Matrix3 {
+        Matrix3::new(Vector3::new(m.m11, m.m12, m.m13),
+                     Vector3::new(m.m21, m.m22, m.m23),
+                     Vector3::new(m.m31, m.m32, m.m33))
+    }
+
+    // Inverse transpose of the upper 3x3 part of m. Normals transformed by
+    // this stay perpendicular to their surface under non-uniform scaling,
+    // but have to be renormalized.
+    pub fn normal_matrix(m: &Matrix4) -> Matrix3 {
+        let m = Matrix3::from_matrix4(m);
+        m.inverse().map_or(m, |inv| inv.transpose())
+    }
+
+    pub fn transpose(self) -> Matrix3 {
+        Matrix3::new(Vector3::new(self.m11, self.m21, self.m31),
+                     Vector3::new(self.m12, self.m22, self.m32),
+                     Vector3::new(self.m13, self.m23, self.m33))
+    }
+
+    pub fn determinant(self) -> f64 {
+        self.m11 * (self.m22 * self.m33 - self.m23 * self.m32) -
+        self.m12 * (self.m21 * self.m33 - self.m23 * self.m31) +
+        self.m13 * (self.m21 * self.m32 - self.m22 * self.m31)
+    }
+
+    pub fn inverse(self) -> Option<Matrix3> {
+        let det = self.determinant();
+        if det.abs() <= f64::EPSILON {
+            return None;
+        }
+
+        let adjugate = Matrix3::new(Vector3::new(self.m22 * self.m33 - self.m23 * self.m32,
+                                                 self.m13 * self.m32 - self.m12 * self.m33,
+                                                 self.m12 * self.m23 - self.m13 * self.m22),
+                                    Vector3::new(self.m23 * self.m31 - self.m21 * self.m33,
+                                                 self.m11 * self.m33 - self.m13 * self.m31,
+                                                 self.m13 * self.m21 - self.m11 * self.m23),
+                                    Vector3::new(self.m21 * self.m32 - self.m22 * self.m31,
+                                                 self.m12 * self.m31 - self.m11 * self.m32,
+                                                 self.m11 * self.m22 - self.m12 * self.m21));
+        Some(adjugate * (1.0 / det))
+    }
+
+    // Row vector times matrix, same convention as Vector3::transform_coordinate.
+    pub fn transform(self, v: Vector3) -> Vector3 {
+        Vector3::new(v.x * self.m11 + v.y * self.m21 + v.z * self.m31,
+                     v.x * self.m12 + v.y * self.m22 + v.z * self.m32,
+                     v.x * self.m13 + v.y * self.m23 + v.z * self.m33)
+    }
+}
+
+impl Mul<f64> for Matrix3 {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Matrix3 {
+        Matrix3::new(Vector3::new(self.m11, self.m12, self.m13) * rhs,
+                     Vector3::new(self.m21, self.m22, self.m23) * rhs,
+                     Vector3::new(self.m31, self.m32, self.m33) * rhs)
+    }
+}
+
+impl Mul for Matrix3 {
+    type Output = Self;
+
+    fn mul(self, rhs: Matrix3) -> Matrix3 {
+        // Each row of the product is that row of self transformed by rhs
+        Matrix3::new(rhs.transform(Vector3::new(self.m11, self.m12, self.m13)),
+                     rhs.transform(Vector3::new(self.m21, self.m22, self.m23)),
+                     rhs.transform(Vector3::new(self.m31, self.m32, self.m33)))
+    }
+}
+
 #[derive(Debug,Clone,Copy,PartialEq)]
 pub struct Matrix4 {
     pub m11: f64,
@@ -106,8 +213,64 @@ impl Matrix4 {
         }
     }
 
+    // Cofactor expansion using the 2x2 sub-determinants of the top and
+    // bottom row pairs. Returns None for singular matrices.
+    pub fn inverse(self) -> Option<Matrix4> {
+        let m = self;
+        let s0 = m.m11 * m.m22 - m.m21 * m.m12;
+        let s1 = m.m11 * m.m23 - m.m21 * m.m13;
+        let s2 = m.m11 * m.m24 - m.m21 * m.m14;
+        let s3 = m.m12 * m.m23 - m.m22 * m.m13;
+        let s4 = m.m12 * m.m24 - m.m22 * m.m14;
+        let s5 = m.m13 * m.m24 - m.m23 * m.m14;
+
+        let c5 = m.m33 * m.m44 - m.m43 * m.m34;
+        let c4 = m.m32 * m.m44 - m.m42 * m.m34;
+        let c3 = m.m32 * m.m43 - m.m42 * m.m33;
+        let c2 = m.m31 * m.m44 - m.m41 * m.m34;
+        let c1 = m.m31 * m.m43 - m.m41 * m.m33;
+        let c0 = m.m31 * m.m42 - m.m41 * m.m32;
+
+        let det = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if det.abs() <= f64::EPSILON {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        Some(Matrix4::new((m.m22 * c5 - m.m23 * c4 + m.m24 * c3) * inv_det,
+                          (-m.m12 * c5 + m.m13 * c4 - m.m14 * c3) * inv_det,
+                          (m.m42 * s5 - m.m43 * s4 + m.m44 * s3) * inv_det,
+                          (-m.m32 * s5 + m.m33 * s4 - m.m34 * s3) * inv_det,
+                          (-m.m21 * c5 + m.m23 * c2 - m.m24 * c1) * inv_det,
+                          (m.m11 * c5 - m.m13 * c2 + m.m14 * c1) * inv_det,
+                          (-m.m41 * s5 + m.m43 * s2 - m.m44 * s1) * inv_det,
+                          (m.m31 * s5 - m.m33 * s2 + m.m34 * s1) * inv_det,
+                          (m.m21 * c4 - m.m22 * c2 + m.m24 * c0) * inv_det,
+                          (-m.m11 * c4 + m.m12 * c2 - m.m14 * c0) * inv_det,
+                          (m.m41 * s4 - m.m42 * s2 + m.m44 * s0) * inv_det,
+                          (-m.m31 * s4 + m.m32 * s2 - m.m34 * s0) * inv_det,
+                          (-m.m21 * c3 + m.m22 * c1 - m.m23 * c0) * inv_det,
+                          (m.m11 * c3 - m.m12 * c1 + m.m13 * c0) * inv_det,
+                          (-m.m41 * s3 + m.m42 * s1 - m.m43 * s0) * inv_det,
+                          (m.m31 * s3 - m.m32 * s1 + m.m33 * s0) * inv_det))
+    }
+
+    // The projection matrices below map the view volume to NDC with x and y
+    // in [-1, 1], y up, and depth in [0, 1] reversed: znear maps to 1 and
+    // zfar to 0, so the depth buffer is cleared to 0 and keeps the largest
+    // value. The _lh variants expect a view space looking down +z, as built
+    // by look_at_lh, the _rh variants one looking down -z as built by
+    // look_at_rh.
+
     pub fn look_at_lh(eye: Vector3, target: Vector3, up: Vector3) -> Matrix4 {
-        let zaxis = (target - eye).normalize();
+        Matrix4::look_to(eye, (target - eye).normalize(), up)
+    }
+
+    pub fn look_at_rh(eye: Vector3, target: Vector3, up: Vector3) -> Matrix4 {
+        Matrix4::look_to(eye, (eye - target).normalize(), up)
+    }
+
+    fn look_to(eye: Vector3, zaxis: Vector3, up: Vector3) -> Matrix4 {
         let xaxis = up.cross(zaxis).normalize();
         let yaxis = zaxis.cross(xaxis).normalize();
 
@@ -132,25 +295,79 @@ impl Matrix4 {
         }
     }
 
+    pub fn perspective_lh(fov: f64, aspect: f64, znear: f64, zfar: f64) -> Matrix4 {
+        let y_scale = 1.0 / (fov * 0.5).tan();
+        let length = zfar - znear;
+
+        Matrix4 {
+            m11: y_scale / aspect,
+            m22: y_scale,
+            m33: -znear / length,
+            m43: znear * zfar / length,
+            m44: 0.0,
+            m34: 1.0,
+            ..Matrix4::identity()
+        }
+    }
+
     pub fn perspective_rh(fov: f64, aspect: f64, znear: f64, zfar: f64) -> Matrix4 {
-        let y_half_scale = 0.5 / (fov * 0.5).tan();
-        let x_half_scale = y_half_scale / aspect;
-        let width = znear / x_half_scale;
-        let height = znear / y_half_scale;
+        let y_scale = 1.0 / (fov * 0.5).tan();
         let length = zfar - znear;
-        let znear_doubled = znear * 2.0;
 
         Matrix4 {
-            m11: znear_doubled / width,
-            m22: znear_doubled / height,
-            m33: (-zfar - znear) / length,
-            m43: (-znear_doubled * zfar) / length,
+            m11: y_scale / aspect,
+            m22: y_scale,
+            m33: znear / length,
+            m43: znear * zfar / length,
             m44: 0.0,
             m34: -1.0,
             ..Matrix4::identity()
         }
     }
 
+    pub fn orthographic_lh(left: f64,
+                           right: f64,
+                           bottom: f64,
+                           top: f64,
+                           znear: f64,
+                           zfar: f64)
+                           -> Matrix4 {
+        let depth = zfar - znear;
+        Matrix4 {
+            m33: -1.0 / depth,
+            m43: zfar / depth,
+            ..Matrix4::orthographic_xy(left, right, bottom, top)
+        }
+    }
+
+    pub fn orthographic_rh(left: f64,
+                           right: f64,
+                           bottom: f64,
+                           top: f64,
+                           znear: f64,
+                           zfar: f64)
+                           -> Matrix4 {
+        let depth = zfar - znear;
+        Matrix4 {
+            m33: 1.0 / depth,
+            m43: zfar / depth,
+            ..Matrix4::orthographic_xy(left, right, bottom, top)
+        }
+    }
+
+    fn orthographic_xy(left: f64, right: f64, bottom: f64, top: f64) -> Matrix4 {
+        let width = right - left;
+        let height = top - bottom;
+
+        Matrix4 {
+            m11: 2.0 / width,
+            m22: 2.0 / height,
+            m41: -(right + left) / width,
+            m42: -(top + bottom) / height,
+            ..Matrix4::identity()
+        }
+    }
+
     pub fn scale(s: Vector3) -> Matrix4 {
         Matrix4 {
             m11: s.x,
@@ -369,3 +586,80 @@ fn matrix_mul() {
     assert_eq!(expected, result);
 
 }
+
+#[test]
+fn matrix3_inverse() {
+    let m = Matrix3::new(Vector3::new(2.0, 0.0, 1.0),
+                         Vector3::new(1.0, 3.0, 0.0),
+                         Vector3::new(0.0, 1.0, 4.0));
+    let p = m * m.inverse().unwrap();
+    let values = [p.m11 - 1.0, p.m12, p.m13, p.m21, p.m22 - 1.0, p.m23, p.m31, p.m32, p.m33 - 1.0];
+    assert!(values.iter().all(|v| v.abs() < 1e-12));
+    let singular = Matrix3::new(Vector3::new(1.0, 2.0, 3.0),
+                                Vector3::new(2.0, 4.0, 6.0),
+                                Vector3::unit_z());
+    assert_eq!(None, singular.inverse());
+}
+
+#[test]
+fn normal_matrix() {
+    let world = Matrix4::scale(Vector3::new(1.0, 4.0, 1.0)) *
+                Matrix4::rotation(Quaternion::from_euler_angle_degrees(Vector3::new(0.0, 30.0, 0.0)));
+    let tangent = Vector3::new(1.0, -1.0, 0.0);
+    let normal = Vector3::new(1.0, 1.0, 0.0);
+
+    let tangent = Matrix3::from_matrix4(&world).transform(tangent);
+    let normal = Matrix3::normal_matrix(&world).transform(normal);
+    assert!(tangent.dot(normal).abs() < 1e-12);
+}
+
+#[test]
+fn orthographic() {
+    let m = Matrix4::orthographic_lh(-4.0, 4.0, -1.0, 3.0, 1.0, 11.0);
+    let near = Vector3::transform_coordinate(&Vector3::new(-4.0, 3.0, 1.0), &m);
+    let far = Vector3::transform_coordinate(&Vector3::new(4.0, -1.0, 11.0), &m);
+    assert_eq!(Vector3::new(-1.0, 1.0, 1.0), near);
+    assert_eq!(Vector3::new(1.0, -1.0, 0.0), far);
+
+    let m = Matrix4::orthographic_rh(-4.0, 4.0, -1.0, 3.0, 1.0, 11.0);
+    let near = Vector3::transform_coordinate(&Vector3::new(-4.0, 3.0, -1.0), &m);
+    let far = Vector3::transform_coordinate(&Vector3::new(4.0, -1.0, -11.0), &m);
+    assert_eq!(Vector3::new(-1.0, 1.0, 1.0), near);
+    assert_eq!(Vector3::new(1.0, -1.0, 0.0), far);
+}
+
+#[test]
+fn perspective_depth_range() {
+    let fov = 90f64.to_radians();
+    let cases = [(Matrix4::perspective_lh(fov, 2.0, 1.0, 10.0), 1.0),
+                 (Matrix4::perspective_rh(fov, 2.0, 1.0, 10.0), -1.0)];
+    for &(m, forward) in &cases {
+        let near = Vector3::transform_coordinate(&Vector3::new(2.0, 1.0, forward), &m);
+        let far = Vector3::transform_coordinate(&Vector3::new(-20.0, -10.0, forward * 10.0), &m);
+        assert!((Vector3::new(1.0, 1.0, 1.0) - near).length() < 1e-12);
+        assert!((Vector3::new(-1.0, -1.0, 0.0) - far).length() < 1e-12);
+    }
+}
+
+#[test]
+fn look_at_handedness() {
+    let eye = Vector3::new(0.0, 0.0, 5.0);
+    let lh = Matrix4::look_at_lh(eye, Vector3::zero(), Vector3::unit_y());
+    let rh = Matrix4::look_at_rh(eye, Vector3::zero(), Vector3::unit_y());
+    let p = Vector3::new(1.0, 2.0, 0.0);
+    assert_eq!(Vector3::new(-1.0, 2.0, 5.0), Vector3::transform_coordinate(&p, &lh));
+    assert_eq!(Vector3::new(1.0, 2.0, -5.0), Vector3::transform_coordinate(&p, &rh));
+}
+
+#[test]
+fn matrix_inverse() {
+    let m = Matrix4::scale(Vector3::new(1.0, 2.0, 3.0)) *
+            Matrix4::rotation(Quaternion::from_euler_angle_degrees(Vector3::new(10.0, 20.0, 30.0))) *
+            Matrix4::translation(Vector3::new(4.0, 5.0, 6.0)) *
+            Matrix4::perspective_rh(1.0, 1.5, 0.1, 50.0);
+    let d = m * m.inverse().unwrap() - Matrix4::identity();
+    let values = [d.m11, d.m12, d.m13, d.m14, d.m21, d.m22, d.m23, d.m24, d.m31, d.m32, d.m33,
+                  d.m34, d.m41, d.m42, d.m43, d.m44];
+    assert!(values.iter().all(|v| v.abs() < 1e-9));
+    assert_eq!(None, Matrix4::scale(Vector3::new(1.0, 0.0, 1.0)).inverse());
+}
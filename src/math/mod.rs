@@ -1,3 +1,7 @@
 pub mod vector;
 pub mod matrix;
 pub mod quaternion;
+pub mod transform;
+pub mod frustum;
+pub mod curves;
+pub mod single;
@@ -1,10 +1,166 @@
+use std::f64;
+use std::ops::{Add, Mul};
 
 use super::vector::Vector3;
-use super::vector::Vector4;
+use super::matrix::Matrix4;
 
-pub type Quaternion = Vector4;
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Quaternion {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub w: f64,
+}
 
 impl Quaternion {
+    pub fn new(x: f64, y: f64, z: f64, w: f64) -> Quaternion {
+        Quaternion {
+            x: x,
+            y: y,
+            z: z,
+            w: w,
+        }
+    }
+
+    pub fn identity() -> Quaternion {
+        Quaternion::new(0.0, 0.0, 0.0, 1.0)
+    }
+
+    pub fn xyz(self) -> Vector3 {
+        Vector3::new(self.x, self.y, self.z)
+    }
+
+    pub fn dot(self, rhs: Quaternion) -> f64 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z + self.w * rhs.w
+    }
+
+    pub fn length_sqr(self) -> f64 {
+        self.dot(self)
+    }
+
+    pub fn length(self) -> f64 {
+        self.length_sqr().sqrt()
+    }
+
+    pub fn normalize(self) -> Quaternion {
+        let length = self.length();
+        if length <= f64::EPSILON {
+            return Quaternion::identity();
+        }
+        self * (1.0 / length)
+    }
+
+    pub fn conjugate(self) -> Quaternion {
+        Quaternion::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    pub fn inverse(self) -> Quaternion {
+        let n = self.length_sqr();
+        if n <= f64::EPSILON {
+            return Quaternion::identity();
+        }
+        self.conjugate() * (1.0 / n)
+    }
+
+    // Same handedness as from_euler_angle: from_axis_angle(Vector3::unit_z(), a)
+    // equals from_euler_angle(Vector3::new(0.0, a, 0.0)). The angle is in
+    // radians.
+    pub fn from_axis_angle(axis: Vector3, angle: f64) -> Quaternion {
+        let axis = axis.normalize();
+        let (sin, cos) = (angle * 0.5).sin_cos();
+        Quaternion::new(axis.x * sin, axis.y * sin, axis.z * sin, cos)
+    }
+
+    // Rotation that turns +Z towards forward and +Y as close to up as
+    // possible, matching the view space of Matrix4::look_at_lh.
+    pub fn look_rotation(forward: Vector3, up: Vector3) -> Quaternion {
+        let z = forward.normalize();
+        let mut x = up.cross(z);
+        if x.length_sqr() <= f64::EPSILON {
+            // forward and up are parallel, any perpendicular axis will do
+            let up = if z.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+            x = up.cross(z);
+        }
+        let x = x.normalize();
+        let y = z.cross(x);
+
+        Quaternion::from_matrix(&Matrix4::new(x.x, x.y, x.z, 0.0,
+                                              y.x, y.y, y.z, 0.0,
+                                              z.x, z.y, z.z, 0.0,
+                                              0.0, 0.0, 0.0, 1.0))
+    }
+
+    // Normalized linear interpolation. Cheaper than slerp and good enough
+    // when a and b are close, but the angular speed is not constant.
+    pub fn nlerp(a: Quaternion, b: Quaternion, t: f64) -> Quaternion {
+        let b = if a.dot(b) < 0.0 { b * -1.0 } else { b };
+        (a * (1.0 - t) + b * t).normalize()
+    }
+
+    // Spherical linear interpolation along the shortest arc, at constant
+    // angular speed. Falls back to nlerp when a and b are nearly parallel.
+    pub fn slerp(a: Quaternion, b: Quaternion, t: f64) -> Quaternion {
+        let mut cos = a.dot(b);
+        let b = if cos < 0.0 {
+            cos = -cos;
+            b * -1.0
+        } else {
+            b
+        };
+
+        if cos > 0.9995 {
+            return Quaternion::nlerp(a, b, t);
+        }
+
+        let theta = cos.acos();
+        let sin = theta.sin();
+        a * (((1.0 - t) * theta).sin() / sin) + b * ((t * theta).sin() / sin)
+    }
+
+    // Rotates v the same way as transforming it by Matrix4::rotation(self).
+    // Assumes a unit quaternion.
+    pub fn rotate(self, v: Vector3) -> Vector3 {
+        let q = self.conjugate();
+        let u = q.xyz();
+        let s = q.w;
+        u * (2.0 * u.dot(v)) + v * (s * s - u.dot(u)) + u.cross(v) * (2.0 * s)
+    }
+
+    pub fn to_matrix(self) -> Matrix4 {
+        Matrix4::rotation(self)
+    }
+
+    // Inverse of Matrix4::rotation. Only the upper 3x3 part is read, and it
+    // is expected to be a pure rotation.
+    pub fn from_matrix(m: &Matrix4) -> Quaternion {
+        let trace = m.m11 + m.m22 + m.m33;
+        if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion::new((m.m32 - m.m23) / s,
+                            (m.m13 - m.m31) / s,
+                            (m.m21 - m.m12) / s,
+                            0.25 * s)
+        } else if m.m11 > m.m22 && m.m11 > m.m33 {
+            let s = (1.0 + m.m11 - m.m22 - m.m33).sqrt() * 2.0;
+            Quaternion::new(0.25 * s,
+                            (m.m12 + m.m21) / s,
+                            (m.m13 + m.m31) / s,
+                            (m.m32 - m.m23) / s)
+        } else if m.m22 > m.m33 {
+            let s = (1.0 + m.m22 - m.m11 - m.m33).sqrt() * 2.0;
+            Quaternion::new((m.m12 + m.m21) / s,
+                            0.25 * s,
+                            (m.m23 + m.m32) / s,
+                            (m.m13 - m.m31) / s)
+        } else {
+            let s = (1.0 + m.m33 - m.m11 - m.m22).sqrt() * 2.0;
+            Quaternion::new((m.m13 + m.m31) / s,
+                            (m.m23 + m.m32) / s,
+                            0.25 * s,
+                            (m.m21 - m.m12) / s)
+        }
+    }
+
     pub fn from_euler_angle_degrees(euler_angle_degrees: Vector3) -> Quaternion {
         let v = euler_angle_degrees;
         Quaternion::from_euler_angle(Vector3::new(v.x.to_radians(),
@@ -33,13 +189,14 @@ impl Quaternion {
         }
     }
 
-    pub fn to_euler_angle(quaternion: Quaternion) -> Vector3 {
-        let q = quaternion;
+    // Inverse of from_euler_angle, in radians.
+    pub fn to_euler_angle(self) -> Vector3 {
+        let q = self;
         let ysqr = q.y * q.y;
         let t0 = -2.0 * (ysqr + q.z * q.z) + 1.0;
-        let t1 = 2.0 * (q.x * q.y - q.w * q.z);
+        let t1 = 2.0 * (q.x * q.y + q.w * q.z);
         let t2 = -2.0 * (q.x * q.z - q.w * q.y);
-        let t3 = 2.0 * (q.y * q.z - q.w * q.x);
+        let t3 = 2.0 * (q.y * q.z + q.w * q.x);
         let t4 = -2.0 * (q.x * q.x + ysqr) + 1.0;
 
         let t2 = if t2 > 1.0 { 1.0 } else { t2 };
@@ -52,3 +209,139 @@ impl Quaternion {
         Vector3::new(pitch, yaw, roll)
     }
 }
+
+impl Add for Quaternion {
+    type Output = Self;
+
+    fn add(self, rhs: Quaternion) -> Quaternion {
+        Quaternion::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z, self.w + rhs.w)
+    }
+}
+
+// Hamilton product. Matrix4::rotation(a) * Matrix4::rotation(b) is the same
+// as Matrix4::rotation(a * b).
+impl Mul for Quaternion {
+    type Output = Self;
+
+    fn mul(self, rhs: Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            x: self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            y: self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            z: self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        }
+    }
+}
+
+impl Mul<f64> for Quaternion {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Quaternion {
+        Quaternion::new(self.x * rhs, self.y * rhs, self.z * rhs, self.w * rhs)
+    }
+}
+
+impl Mul<Vector3> for Quaternion {
+    type Output = Vector3;
+
+    fn mul(self, rhs: Vector3) -> Vector3 {
+        self.rotate(rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use math::matrix::Matrix4;
+    use super::Quaternion;
+
+    fn assert_near(expected: Quaternion, actual: Quaternion) {
+        // q and -q describe the same rotation
+        let d = expected.dot(actual).abs();
+        assert!((d - 1.0).abs() < 1e-9, "{:?} != {:?}", expected, actual);
+    }
+
+    fn euler(x: f64, y: f64, z: f64) -> Quaternion {
+        Quaternion::from_euler_angle_degrees(Vector3::new(x, y, z))
+    }
+
+    #[test]
+    fn multiply_matches_matrix() {
+        let (a, b) = (euler(10.0, 20.0, 30.0), euler(-45.0, 60.0, 5.0));
+        let expected = Matrix4::rotation(a) * Matrix4::rotation(b);
+        assert_near(Quaternion::from_matrix(&expected), a * b);
+    }
+
+    #[test]
+    fn rotate_matches_matrix() {
+        let q = euler(10.0, 20.0, 30.0);
+        let v = Vector3::new(1.0, -2.0, 3.0);
+        let expected = Vector3::transform_coordinate(&v, &q.to_matrix());
+        assert!((expected - q * v).length() < 1e-9);
+    }
+
+    #[test]
+    fn inverse() {
+        let q = euler(10.0, 20.0, 30.0) * 2.0;
+        assert_near(Quaternion::identity(), q * q.inverse());
+        assert!((q.normalize().length() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        for &q in &[euler(10.0, 20.0, 30.0),
+                    euler(0.0, 180.0, 0.0),
+                    euler(180.0, 0.0, 0.0),
+                    euler(0.0, 0.0, 180.0),
+                    euler(170.0, -100.0, 20.0)] {
+            assert_near(q, Quaternion::from_matrix(&q.to_matrix()));
+        }
+    }
+
+    #[test]
+    fn axis_angle() {
+        let q = Quaternion::from_axis_angle(Vector3::new(0.0, 0.0, 2.0), 90f64.to_radians());
+        assert_near(euler(0.0, 90.0, 0.0), q);
+        let q = Quaternion::from_axis_angle(Vector3::unit_x(), 30f64.to_radians());
+        assert_near(euler(0.0, 0.0, 30.0), q);
+    }
+
+    #[test]
+    fn look_rotation() {
+        let forward = Vector3::new(1.0, 2.0, -3.0).normalize();
+        let q = Quaternion::look_rotation(forward, Vector3::unit_y());
+        assert!((forward - q * Vector3::unit_z()).length() < 1e-9);
+        assert!((q * Vector3::unit_x()).y.abs() < 1e-9);
+        assert!((q * Vector3::unit_y()).y > 0.0);
+
+        let q = Quaternion::look_rotation(Vector3::unit_y(), Vector3::unit_y());
+        assert!((Vector3::unit_y() - q * Vector3::unit_z()).length() < 1e-9);
+    }
+
+    #[test]
+    fn slerp() {
+        let a = euler(0.0, 0.0, 0.0);
+        let b = euler(0.0, 90.0, 0.0);
+        assert_near(a, Quaternion::slerp(a, b, 0.0));
+        assert_near(b, Quaternion::slerp(a, b, 1.0));
+        assert_near(euler(0.0, 30.0, 0.0), Quaternion::slerp(a, b, 1.0 / 3.0));
+        // -b is the same rotation, the shorter arc must still be taken
+        assert_near(euler(0.0, 30.0, 0.0), Quaternion::slerp(a, b * -1.0, 1.0 / 3.0));
+    }
+
+    #[test]
+    fn nlerp() {
+        let a = euler(0.0, 0.0, 0.0);
+        let b = euler(0.0, 90.0, 0.0);
+        assert_near(euler(0.0, 45.0, 0.0), Quaternion::nlerp(a, b, 0.5));
+        assert!((Quaternion::nlerp(a, b, 0.3).length() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn euler_round_trip() {
+        let angles = Vector3::new(0.3, -0.5, 1.2);
+        let q = Quaternion::from_euler_angle(angles);
+        assert!((angles - q.to_euler_angle()).length() < 1e-9);
+    }
+}
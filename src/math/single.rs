@@ -0,0 +1,246 @@
+
+use super::matrix::Matrix4;
+use super::vector::{Vector2, Vector3, Vector4};
+
+use std::ops::{Add, Sub, Mul};
+
+// Single precision copies of the vector and matrix types, for the parts of
+// the pipeline that run per vertex or per pixel, where f32 packs twice the
+// lanes and moves half the memory. Convert to and from the f64 types with
+// From at the edges.
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Vector2f {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vector2f {
+    pub fn new(x: f32, y: f32) -> Vector2f {
+        Vector2f { x: x, y: y }
+    }
+
+    pub fn cross(self, rhs: Vector2f) -> f32 {
+        self.x * rhs.y - self.y * rhs.x
+    }
+
+    pub fn dot(self, rhs: Vector2f) -> f32 {
+        self.x * rhs.x + self.y * rhs.y
+    }
+}
+
+impl Add for Vector2f {
+    type Output = Self;
+
+    fn add(self, rhs: Vector2f) -> Vector2f {
+        Vector2f::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl Sub for Vector2f {
+    type Output = Self;
+
+    fn sub(self, rhs: Vector2f) -> Vector2f {
+        Vector2f::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl Mul<f32> for Vector2f {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Vector2f {
+        Vector2f::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Vector3f {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vector3f {
+    pub fn new(x: f32, y: f32, z: f32) -> Vector3f {
+        Vector3f { x: x, y: y, z: z }
+    }
+
+    pub fn dot(self, rhs: Vector3f) -> f32 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    pub fn cross(self, rhs: Vector3f) -> Vector3f {
+        Vector3f::new(self.y * rhs.z - self.z * rhs.y,
+                      self.z * rhs.x - self.x * rhs.z,
+                      self.x * rhs.y - self.y * rhs.x)
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn xy(self) -> Vector2f {
+        Vector2f::new(self.x, self.y)
+    }
+
+    pub fn transform(vec: &Vector3f, mat: &Matrix4f) -> Vector4f {
+        let m = &mat.m;
+        Vector4f::new(vec.x * m[0][0] + vec.y * m[1][0] + vec.z * m[2][0] + m[3][0],
+                      vec.x * m[0][1] + vec.y * m[1][1] + vec.z * m[2][1] + m[3][1],
+                      vec.x * m[0][2] + vec.y * m[1][2] + vec.z * m[2][2] + m[3][2],
+                      vec.x * m[0][3] + vec.y * m[1][3] + vec.z * m[2][3] + m[3][3])
+    }
+}
+
+impl Add for Vector3f {
+    type Output = Self;
+
+    fn add(self, rhs: Vector3f) -> Vector3f {
+        Vector3f::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl Sub for Vector3f {
+    type Output = Self;
+
+    fn sub(self, rhs: Vector3f) -> Vector3f {
+        Vector3f::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl Mul<f32> for Vector3f {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Vector3f {
+        Vector3f::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Vector4f {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Vector4f {
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Vector4f {
+        Vector4f {
+            x: x,
+            y: y,
+            z: z,
+            w: w,
+        }
+    }
+
+    pub fn xyz(self) -> Vector3f {
+        Vector3f::new(self.x, self.y, self.z)
+    }
+}
+
+impl Add for Vector4f {
+    type Output = Self;
+
+    fn add(self, rhs: Vector4f) -> Vector4f {
+        Vector4f::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z, self.w + rhs.w)
+    }
+}
+
+impl Mul<f32> for Vector4f {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Vector4f {
+        Vector4f::new(self.x * rhs, self.y * rhs, self.z * rhs, self.w * rhs)
+    }
+}
+
+// Rows as in Matrix4, so m[3] holds the translation.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Matrix4f {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Matrix4f {
+    pub fn identity() -> Matrix4f {
+        Matrix4f::from(Matrix4::identity())
+    }
+}
+
+impl From<Vector2> for Vector2f {
+    fn from(v: Vector2) -> Vector2f {
+        Vector2f::new(v.x as f32, v.y as f32)
+    }
+}
+
+impl From<Vector2f> for Vector2 {
+    fn from(v: Vector2f) -> Vector2 {
+        Vector2::new(v.x as f64, v.y as f64)
+    }
+}
+
+impl From<Vector3> for Vector3f {
+    fn from(v: Vector3) -> Vector3f {
+        Vector3f::new(v.x as f32, v.y as f32, v.z as f32)
+    }
+}
+
+impl From<Vector3f> for Vector3 {
+    fn from(v: Vector3f) -> Vector3 {
+        Vector3::new(v.x as f64, v.y as f64, v.z as f64)
+    }
+}
+
+impl From<Vector4> for Vector4f {
+    fn from(v: Vector4) -> Vector4f {
+        Vector4f::new(v.x as f32, v.y as f32, v.z as f32, v.w as f32)
+    }
+}
+
+impl From<Vector4f> for Vector4 {
+    fn from(v: Vector4f) -> Vector4 {
+        Vector4::new(v.x as f64, v.y as f64, v.z as f64, v.w as f64)
+    }
+}
+
+impl From<Matrix4> for Matrix4f {
+    fn from(m: Matrix4) -> Matrix4f {
+        let row = |a: f64, b: f64, c: f64, d: f64| [a as f32, b as f32, c as f32, d as f32];
+        Matrix4f {
+            m: [row(m.m11, m.m12, m.m13, m.m14),
+                row(m.m21, m.m22, m.m23, m.m24),
+                row(m.m31, m.m32, m.m33, m.m34),
+                row(m.m41, m.m42, m.m43, m.m44)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::matrix::Matrix4;
+    use math::quaternion::Quaternion;
+    use math::vector::{Vector2, Vector3};
+    use super::{Matrix4f, Vector2f, Vector3f};
+
+    #[test]
+    fn matches_double() {
+        let m = Matrix4::rotation(Quaternion::from_euler_angle_degrees(Vector3::new(30.0,
+                                                                                    45.0,
+                                                                                    0.0))) *
+                Matrix4::perspective_rh(1.0, 1.5, 0.1, 100.0);
+        let v = Vector3::new(1.0, -2.0, -5.0);
+        let single = Vector3f::transform(&Vector3f::from(v), &Matrix4f::from(m));
+        let double = Vector3::transform(&v, &m);
+        for &(a, b) in &[(single.x, double.x), (single.y, double.y),
+                         (single.z, double.z), (single.w, double.w)] {
+            assert!((a as f64 - b).abs() < 1e-5);
+        }
+        assert_eq!(Vector3f::transform(&Vector3f::from(v), &Matrix4f::identity()).xyz(),
+                   Vector3f::from(v));
+
+        let (a, b) = (Vector2f::new(3.0, 1.0), Vector2f::new(1.0, 2.0));
+        assert_eq!((5.0, 5.0), (a.cross(b), a.dot(b)));
+        assert_eq!(Vector2::new(4.0, 3.0), Vector2::from(a + b));
+    }
+}
@@ -0,0 +1,139 @@
+
+use std::ops::Mul;
+
+use super::vector::Vector3;
+use super::matrix::Matrix4;
+use super::quaternion::Quaternion;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Transform {
+    pub position: Vector3,
+    pub rotation: Quaternion,
+    pub scale: Vector3,
+}
+
+impl Transform {
+    pub fn new(position: Vector3, rotation: Quaternion, scale: Vector3) -> Transform {
+        Transform {
+            position: position,
+            rotation: rotation,
+            scale: scale,
+        }
+    }
+
+    pub fn identity() -> Transform {
+        Transform::new(Vector3::zero(), Quaternion::identity(), Vector3::one())
+    }
+
+    pub fn from_position(position: Vector3) -> Transform {
+        Transform { position: position, ..Transform::identity() }
+    }
+
+    // Applies rotation in local space, before the current rotation.
+    pub fn rotate(&mut self, rotation: Quaternion) {
+        self.rotation = rotation * self.rotation
+    }
+
+    // Scale, then rotate, then translate.
+    pub fn matrix(&self) -> Matrix4 {
+        Matrix4::scale(self.scale) * Matrix4::rotation(self.rotation) *
+        Matrix4::translation(self.position)
+    }
+
+    pub fn transform_point(&self, point: Vector3) -> Vector3 {
+        self.rotation.rotate(point * self.scale) + self.position
+    }
+
+    // Positions and scales blended linearly, rotations by slerp.
+    pub fn lerp(self, b: Transform, t: f64) -> Transform {
+        Transform::new(self.position.lerp(b.position, t),
+                       Quaternion::slerp(self.rotation, b.rotation, t),
+                       self.scale.lerp(b.scale, t))
+    }
+
+    // Exact for uniform scale. With non-uniform scale and rotation combined
+    // the inverse is not representable as a Transform.
+    pub fn inverse(&self) -> Transform {
+        let rotation = self.rotation.conjugate();
+        let scale = Vector3::new(1.0 / self.scale.x, 1.0 / self.scale.y, 1.0 / self.scale.z);
+        let position = rotation.rotate(self.position) * scale * -1.0;
+        Transform::new(position, rotation, scale)
+    }
+}
+
+// a * b applies a first, then b, matching Matrix4 multiplication order:
+// (a * b).matrix() == a.matrix() * b.matrix() as long as b has uniform
+// scale.
+impl Mul for Transform {
+    type Output = Self;
+
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform::new(rhs.transform_point(self.position),
+                       self.rotation * rhs.rotation,
+                       self.scale * rhs.scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use math::matrix::Matrix4;
+    use math::quaternion::Quaternion;
+    use super::Transform;
+
+    fn assert_matrix_near(expected: Matrix4, actual: Matrix4) {
+        let d = expected - actual;
+        let values = [d.m11, d.m12, d.m13, d.m14, d.m21, d.m22, d.m23, d.m24, d.m31, d.m32,
+                      d.m33, d.m34, d.m41, d.m42, d.m43, d.m44];
+        assert!(values.iter().all(|v| v.abs() < 1e-9),
+                "{:?} != {:?}",
+                expected,
+                actual);
+    }
+
+    fn transform_a() -> Transform {
+        Transform::new(Vector3::new(1.0, 2.0, 3.0),
+                       Quaternion::from_euler_angle_degrees(Vector3::new(10.0, 20.0, 30.0)),
+                       Vector3::new(1.0, 2.0, 3.0))
+    }
+
+    fn transform_b() -> Transform {
+        Transform::new(Vector3::new(-4.0, 0.5, 2.0),
+                       Quaternion::from_euler_angle_degrees(Vector3::new(45.0, -60.0, 5.0)),
+                       Vector3::new(2.0, 2.0, 2.0))
+    }
+
+    #[test]
+    fn transform_point() {
+        let t = transform_a();
+        let p = Vector3::new(0.5, -1.0, 2.0);
+        let expected = Vector3::transform_coordinate(&p, &t.matrix());
+        assert!((expected - t.transform_point(p)).length() < 1e-9);
+    }
+
+    #[test]
+    fn compose() {
+        let (a, b) = (transform_a(), transform_b());
+        assert_matrix_near(a.matrix() * b.matrix(), (a * b).matrix());
+    }
+
+    #[test]
+    fn lerp() {
+        let (a, b) = (transform_a(), transform_b());
+        assert_eq!(a, a.lerp(b, 0.0));
+        assert_matrix_near(b.matrix(), a.lerp(b, 1.0).matrix());
+        let half = a.lerp(b, 0.5);
+        assert_eq!(Vector3::new(-1.5, 1.25, 2.5), half.position);
+        let angle = |q: Quaternion| q.w.abs().min(1.0).acos() * 2.0;
+        let turn = half.rotation * a.rotation.conjugate();
+        assert!((angle(turn) * 2.0 - angle(b.rotation * a.rotation.conjugate())).abs() < 1e-9);
+    }
+
+    #[test]
+    fn inverse() {
+        let b = transform_b();
+        assert_matrix_near(Matrix4::identity(), b.matrix() * b.inverse().matrix());
+        assert_matrix_near(Matrix4::identity(), (b * b.inverse()).matrix());
+    }
+}
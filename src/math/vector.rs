@@ -145,6 +145,12 @@ impl Vector3 {
         self + (b - self) * t
     }
 
+    // Mirrored by the plane through the origin with the unit normal, e.g. a
+    // view direction bouncing off a surface.
+    pub fn reflect(self, normal: Vector3) -> Vector3 {
+        self - normal * (2.0 * self.dot(normal))
+    }
+
     pub fn length_sqr(self) -> f64 {
         self.x * self.x + self.y * self.y + self.z * self.z
     }
@@ -263,6 +269,10 @@ impl Vector4 {
     pub fn xyz(self) -> Vector3 {
         Vector3::new(self.x, self.y, self.z)
     }
+
+    pub fn lerp(self, b: Vector4, t: f64) -> Self {
+        self + (b - self) * t
+    }
 }
 
 impl Div<f64> for Vector4 {
@@ -304,6 +314,19 @@ impl Sub for Vector4 {
     }
 }
 
+impl Mul<f64> for Vector4 {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Vector4 {
+        Vector4 {
+            x: self.x * rhs,
+            y: self.y * rhs,
+            z: self.z * rhs,
+            w: self.w * rhs,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -356,6 +379,13 @@ mod tests {
         assert_eq!(122.0, c);
     }
 
+    #[test]
+    fn reflect() {
+        let a = Vector3::new(1.0, -1.0, 0.0);
+        let b = a.reflect(Vector3::unit_y());
+        assert_eq!(Vector3::new(1.0, 1.0, 0.0), b);
+    }
+
     #[test]
     fn length_sqr() {
         let a = Vector3::new(2.0, 3.0, 4.0);
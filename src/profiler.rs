@@ -0,0 +1,152 @@
+
+use std::cell::{Cell, RefCell};
+use std::time::Instant;
+
+// Times named stages of a frame, e.g. clearing or rasterizing, by scopes:
+//
+//     {
+//         profile_scope!("raster");
+//         ...
+//     }
+//
+// adds the time until the end of the block to the stage. A stage timed
+// more than once in a frame adds up, and scopes inside others count in
+// both. The profile is kept per thread, and end_frame starts the next.
+
+// A stage's total over a frame.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct StageTime {
+    pub name: &'static str,
+    pub seconds: f64,
+    // Scopes that added to it.
+    pub calls: usize,
+}
+
+#[derive(Debug,Default)]
+struct Profile {
+    // In the order stages were first timed
+    current: Vec<StageTime>,
+    last: Vec<StageTime>,
+}
+
+thread_local! {
+    static PROFILE: RefCell<Profile> = RefCell::new(Profile::default());
+    static ENABLED: Cell<bool> = const { Cell::new(true) };
+}
+
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope = $crate::profiler::Scope::new($name);
+    };
+}
+
+// Adds the time it is alive to a stage, see profile_scope.
+#[derive(Debug)]
+pub struct Scope {
+    name: &'static str,
+    // None while profiling is off
+    start: Option<Instant>,
+}
+
+impl Scope {
+    pub fn new(name: &'static str) -> Scope {
+        Scope {
+            name: name,
+            start: if is_enabled() { Some(Instant::now()) } else { None },
+        }
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        if let Some(start) = self.start {
+            let elapsed = start.elapsed();
+            record(self.name, elapsed.subsec_nanos() as f64 * 1e-9 + elapsed.as_secs() as f64);
+        }
+    }
+}
+
+// Profiling is on to begin with. Off, scopes cost next to nothing.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.with(|e| e.set(enabled))
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.with(|e| e.get())
+}
+
+// Adds time measured some other way to a stage.
+pub fn record(name: &'static str, seconds: f64) {
+    PROFILE.with(|profile| {
+        let current = &mut profile.borrow_mut().current;
+        match current.iter_mut().find(|s| s.name == name) {
+            Some(stage) => {
+                stage.seconds += seconds;
+                stage.calls += 1;
+            }
+            None => {
+                current.push(StageTime {
+                    name: name,
+                    seconds: seconds,
+                    calls: 1,
+                })
+            }
+        }
+    })
+}
+
+// Ends the frame, returning its stages, and starts the next. Call once per
+// frame, e.g. through Stats::end_frame.
+pub fn end_frame() -> Vec<StageTime> {
+    PROFILE.with(|profile| {
+        let mut profile = profile.borrow_mut();
+        let frame = profile.current.split_off(0);
+        profile.last = frame.clone();
+        frame
+    })
+}
+
+// The stages of the last ended frame.
+pub fn last_frame() -> Vec<StageTime> {
+    PROFILE.with(|profile| profile.borrow().last.clone())
+}
+
+// Seconds the stage took in the last ended frame, 0 if it didn't run.
+pub fn stage_time(name: &str) -> f64 {
+    PROFILE.with(|profile| {
+        profile.borrow().last.iter().find(|s| s.name == name).map_or(0.0, |s| s.seconds)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::thread;
+    use std::time::Duration;
+    use super::{end_frame, last_frame, record, set_enabled, stage_time};
+
+    #[test]
+    fn scopes() {
+        end_frame();
+        for _ in 0..2 {
+            profile_scope!("sleep");
+            thread::sleep(Duration::from_millis(2));
+        }
+        record("other", 0.5);
+        let frame = end_frame();
+        assert_eq!(vec!["sleep", "other"], frame.iter().map(|s| s.name).collect::<Vec<_>>());
+        assert_eq!(2, frame[0].calls);
+        assert!(frame[0].seconds >= 0.004);
+        assert_eq!((frame.clone(), 0.5), (last_frame(), stage_time("other")));
+
+        // Nothing carried over, or timed while off
+        set_enabled(false);
+        {
+            profile_scope!("sleep");
+        }
+        set_enabled(true);
+        assert!(end_frame().is_empty());
+        assert_eq!(0.0, stage_time("sleep"));
+    }
+}
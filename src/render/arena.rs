@@ -0,0 +1,106 @@
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+
+// Scratch buffers for what a frame makes and throws away, such as the
+// transformed triangles and deformed vertices of each mesh. A buffer is
+// taken empty and given back when done with, keeping its capacity for the
+// next take, so once the sizes a frame needs settle, rendering allocates
+// nothing. The device resets it at the start of each frame.
+#[derive(Default)]
+pub struct FrameArena {
+    // Free buffers of each element type, a Vec<Vec<T>> for each T
+    pools: HashMap<TypeId, Box<dyn Any>>,
+    // Capacity of the free buffers
+    bytes: usize,
+    // Buffers made since the last reset, for want of a free one
+    allocations: usize,
+}
+
+impl FrameArena {
+    pub fn new() -> FrameArena {
+        FrameArena::default()
+    }
+
+    fn pool<T: 'static>(&mut self) -> &mut Vec<Vec<T>> {
+        self.pools
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Vec::<Vec<T>>::new()))
+            .downcast_mut()
+            .expect("pools are keyed by their element type")
+    }
+
+    // An empty buffer, one given back before if there is one.
+    pub fn take<T: 'static>(&mut self) -> Vec<T> {
+        match self.pool::<T>().pop() {
+            Some(buffer) => {
+                self.bytes -= buffer.capacity() * mem::size_of::<T>();
+                buffer
+            }
+            None => {
+                self.allocations += 1;
+                Vec::new()
+            }
+        }
+    }
+
+    // Empties the buffer and keeps it for a later take.
+    pub fn recycle<T: 'static>(&mut self, mut buffer: Vec<T>) {
+        buffer.clear();
+        self.bytes += buffer.capacity() * mem::size_of::<T>();
+        self.pool::<T>().push(buffer)
+    }
+
+    // Starts counting the allocations of the next frame.
+    pub fn reset(&mut self) {
+        self.allocations = 0
+    }
+
+    pub fn allocations(&self) -> usize {
+        self.allocations
+    }
+
+    // Held by the free buffers, so with all given back what the frames
+    // have needed at most.
+    pub fn size_in_bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl fmt::Debug for FrameArena {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FrameArena")
+            .field("types", &self.pools.len())
+            .field("bytes", &self.bytes)
+            .field("allocations", &self.allocations)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::FrameArena;
+
+    #[test]
+    fn reuse() {
+        let mut arena = FrameArena::new();
+        let mut a: Vec<u64> = arena.take();
+        a.extend(0..100);
+        let b: Vec<u8> = arena.take();
+        assert_eq!(2, arena.allocations());
+        let capacity = a.capacity();
+        arena.recycle(a);
+        arena.recycle(b);
+        assert_eq!(capacity * 8, arena.size_in_bytes());
+
+        // The next frame gets the same buffers back, emptied
+        arena.reset();
+        let a: Vec<u64> = arena.take();
+        assert!(a.is_empty() && a.capacity() == capacity);
+        assert_eq!((0, 0), (arena.allocations(), arena.size_in_bytes()));
+        arena.recycle(a);
+    }
+}
@@ -0,0 +1,172 @@
+
+use math::vector::{Vector2, Vector3, Vector4};
+
+// Liang-Barsky clipping of the segment from p1 to p2 against the rectangle
+// from min to max. Returns the parameters along the segment where its
+// visible part starts and ends, or None when it lies entirely outside.
+pub fn clip_line(p1: Vector2, p2: Vector2, min: Vector2, max: Vector2) -> Option<(f64, f64)> {
+    let d = p2 - p1;
+    let mut t0 = 0.0;
+    let mut t1 = 1.0;
+
+    // Inside each edge where p * t <= q
+    let edges = [(-d.x, p1.x - min.x),
+                 (d.x, max.x - p1.x),
+                 (-d.y, p1.y - min.y),
+                 (d.y, max.y - p1.y)];
+    for &(p, q) in &edges {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                if t > t1 {
+                    return None;
+                }
+                t0 = t.max(t0);
+            } else {
+                if t < t0 {
+                    return None;
+                }
+                t1 = t.min(t1);
+            }
+        }
+    }
+
+    Some((t0, t1))
+}
+
+// Clips a clip space segment against the near plane, in front of which
+// z <= w. Returns the parameters of the part in front, as for clip_line.
+pub fn clip_line_near(c1: Vector4, c2: Vector4) -> Option<(f64, f64)> {
+    let d1 = c1.w - c1.z;
+    let d2 = c2.w - c2.z;
+    if d1 < 0.0 && d2 < 0.0 {
+        None
+    } else if d1 >= 0.0 && d2 >= 0.0 {
+        Some((0.0, 1.0))
+    } else {
+        let t = d1 / (d1 - d2);
+        if d1 < 0.0 {
+            Some((t, 1.0))
+        } else {
+            Some((0.0, t))
+        }
+    }
+}
+
+// Whether the clip space point is in front of the near plane and within
+// guard_band times the view volume across, beyond which the rasterizer
+// should not be left to scissor triangles.
+pub fn in_guard_band(c: Vector4, guard_band: f64) -> bool {
+    let extent = c.w * guard_band;
+    c.z <= c.w && c.x.abs() <= extent && c.y.abs() <= extent
+}
+
+// Clips a clip space triangle to the part in_guard_band, a convex polygon
+// whose corners go into out as weights of the triangle's, each of which
+// gives their position and attributes alike. Empty when nothing is left.
+pub fn clip_triangle(c: &[Vector4; 3], guard_band: f64, out: &mut Vec<Vector3>) {
+    out.clear();
+    out.extend_from_slice(&[Vector3::new(1.0, 0.0, 0.0),
+                            Vector3::new(0.0, 1.0, 0.0),
+                            Vector3::new(0.0, 0.0, 1.0)]);
+    // Inside each plane where its distance is positive
+    let planes: [&dyn Fn(Vector4) -> f64; 5] = [&|p| p.w - p.z,
+                                                &|p| p.w * guard_band + p.x,
+                                                &|p| p.w * guard_band - p.x,
+                                                &|p| p.w * guard_band + p.y,
+                                                &|p| p.w * guard_band - p.y];
+    let position = |w: Vector3| c[0] * w.x + c[1] * w.y + c[2] * w.z;
+    // Each plane adds at most one corner, so there are never more than 8
+    let mut polygon = [Vector3::zero(); 8];
+    for plane in &planes {
+        let mut len = 0;
+        for (i, &a) in out.iter().enumerate() {
+            let b = out[(i + 1) % out.len()];
+            let (da, db) = (plane(position(a)), plane(position(b)));
+            if da >= 0.0 {
+                polygon[len] = a;
+                len += 1;
+            }
+            if (da >= 0.0) != (db >= 0.0) {
+                polygon[len] = a + (b - a) * (da / (da - db));
+                len += 1;
+            }
+        }
+        out.clear();
+        out.extend_from_slice(&polygon[..len]);
+        if out.is_empty() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::{Vector2, Vector3, Vector4};
+    use super::{clip_line, clip_line_near, clip_triangle, in_guard_band};
+
+    #[test]
+    fn rectangle() {
+        let min = Vector2::zero();
+        let max = Vector2::new(10.0, 10.0);
+        let clip = |x0, y0, x1, y1| {
+            clip_line(Vector2::new(x0, y0), Vector2::new(x1, y1), min, max)
+        };
+
+        assert_eq!(Some((0.0, 1.0)), clip(1.0, 1.0, 9.0, 9.0));
+        assert_eq!(Some((0.25, 0.75)), clip(-5.0, 5.0, 15.0, 5.0));
+        assert_eq!(Some((0.0, 0.5)), clip(5.0, 5.0, 5.0, 15.0));
+        assert_eq!(None, clip(-5.0, -1.0, 15.0, -1.0));
+        assert_eq!(None, clip(-5.0, 5.0, 5.0, 20.0));
+        assert_eq!(None, clip(11.0, 0.0, 11.0, 10.0));
+    }
+
+    #[test]
+    fn near() {
+        let front = Vector4::new(0.0, 0.0, 0.5, 1.0);
+        let behind = Vector4::new(0.0, 0.0, 1.5, 1.0);
+        assert_eq!(Some((0.0, 1.0)), clip_line_near(front, front));
+        assert_eq!(None, clip_line_near(behind, behind));
+        assert_eq!(Some((0.0, 0.5)), clip_line_near(front, behind));
+        assert_eq!(Some((0.5, 1.0)), clip_line_near(behind, front));
+    }
+
+    #[test]
+    fn triangle() {
+        let point = |x, y, z| Vector4::new(x, y, z, 1.0);
+        let mut out = Vec::new();
+
+        // Off screen but within the guard band, left as it is
+        let inside = [point(0.0, 0.0, 0.5), point(1.5, 0.0, 0.5), point(0.0, 1.5, 0.5)];
+        assert!(inside.iter().all(|&p| in_guard_band(p, 2.0)));
+        clip_triangle(&inside, 2.0, &mut out);
+        assert_eq!(vec![Vector3::new(1.0, 0.0, 0.0),
+                        Vector3::new(0.0, 1.0, 0.0),
+                        Vector3::new(0.0, 0.0, 1.0)],
+                   out);
+
+        // Half behind the near plane, a corner cut off
+        let near = [point(0.0, 0.0, 0.5), point(1.0, 0.0, 1.5), point(0.0, 1.0, 0.5)];
+        assert!(!in_guard_band(near[1], 2.0));
+        clip_triangle(&near, 2.0, &mut out);
+        assert_eq!(4, out.len());
+        assert!(out.contains(&Vector3::new(0.5, 0.5, 0.0)));
+        assert!(out.iter().all(|w| (w.x + w.y + w.z - 1.0).abs() < 1e-12));
+
+        // Past the guard band, cut where it ends
+        let wide = [point(0.0, 0.0, 0.5), point(4.0, 0.0, 0.5), point(0.0, 1.0, 0.5)];
+        clip_triangle(&wide, 2.0, &mut out);
+        assert_eq!(4, out.len());
+        assert!(out.contains(&Vector3::new(0.5, 0.5, 0.0)));
+
+        clip_triangle(&[point(3.0, 0.0, 0.5), point(4.0, 0.0, 0.5), point(3.0, 1.0, 0.5)],
+                      2.0,
+                      &mut out);
+        assert!(out.is_empty());
+    }
+}
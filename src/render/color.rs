@@ -0,0 +1,183 @@
+
+use std::ops::{Add, Sub, Mul};
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Color {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64,
+}
+
+fn to_u8(c: f64) -> u8 {
+    (c.max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+// https://en.wikipedia.org/wiki/SRGB
+fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f64) -> f64 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl Color {
+    pub fn new(r: f64, g: f64, b: f64, a: f64) -> Color {
+        Color {
+            r: r,
+            g: g,
+            b: b,
+            a: a,
+        }
+    }
+
+    pub fn rgb(r: f64, g: f64, b: f64) -> Color {
+        Color::new(r, g, b, 1.0)
+    }
+
+    pub fn gray(v: f64) -> Color {
+        Color::rgb(v, v, v)
+    }
+
+    pub fn black() -> Color {
+        Color::rgb(0.0, 0.0, 0.0)
+    }
+
+    pub fn white() -> Color {
+        Color::rgb(1.0, 1.0, 1.0)
+    }
+
+    pub fn from_u8(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color::new(r as f64 / 255.0,
+                   g as f64 / 255.0,
+                   b as f64 / 255.0,
+                   a as f64 / 255.0)
+    }
+
+    pub fn from_argb8888(argb: u32) -> Color {
+        Color::from_u8((argb >> 16) as u8,
+                       (argb >> 8) as u8,
+                       argb as u8,
+                       (argb >> 24) as u8)
+    }
+
+    pub fn to_u8(self) -> (u8, u8, u8, u8) {
+        (to_u8(self.r), to_u8(self.g), to_u8(self.b), to_u8(self.a))
+    }
+
+    pub fn to_argb8888(self) -> u32 {
+        let (r, g, b, a) = self.to_u8();
+        ((a as u32) << 24) | ((r as u32) << 16) | ((g as u32) << 8) | (b as u32)
+    }
+
+    pub fn clamp(self) -> Color {
+        Color::new(self.r.max(0.0).min(1.0),
+                   self.g.max(0.0).min(1.0),
+                   self.b.max(0.0).min(1.0),
+                   self.a.max(0.0).min(1.0))
+    }
+
+    pub fn lerp(self, b: Color, t: f64) -> Color {
+        self + (b - self) * t
+    }
+
+    pub fn to_linear(self) -> Color {
+        Color::new(srgb_to_linear(self.r),
+                   srgb_to_linear(self.g),
+                   srgb_to_linear(self.b),
+                   self.a)
+    }
+
+    pub fn to_srgb(self) -> Color {
+        Color::new(linear_to_srgb(self.r),
+                   linear_to_srgb(self.g),
+                   linear_to_srgb(self.b),
+                   self.a)
+    }
+}
+
+impl Add for Color {
+    type Output = Self;
+
+    fn add(self, rhs: Color) -> Color {
+        Color::new(self.r + rhs.r, self.g + rhs.g, self.b + rhs.b, self.a + rhs.a)
+    }
+}
+
+impl Sub for Color {
+    type Output = Self;
+
+    fn sub(self, rhs: Color) -> Color {
+        Color::new(self.r - rhs.r, self.g - rhs.g, self.b - rhs.b, self.a - rhs.a)
+    }
+}
+
+impl Mul for Color {
+    type Output = Self;
+
+    fn mul(self, rhs: Color) -> Color {
+        Color::new(self.r * rhs.r, self.g * rhs.g, self.b * rhs.b, self.a * rhs.a)
+    }
+}
+
+impl Mul<f64> for Color {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Color {
+        Color::new(self.r * rhs, self.g * rhs, self.b * rhs, self.a * rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::Color;
+
+    #[test]
+    fn argb8888_round_trip() {
+        let argb = 0xff224466;
+        assert_eq!(argb, Color::from_argb8888(argb).to_argb8888());
+    }
+
+    #[test]
+    fn to_argb8888_clamps() {
+        let c = Color::new(2.0, -1.0, 0.5, 1.0);
+        assert_eq!(0xffff0080, c.to_argb8888());
+    }
+
+    #[test]
+    fn lerp() {
+        let a = Color::black();
+        let b = Color::white();
+        assert_eq!(Color::gray(0.5), a.lerp(b, 0.5));
+    }
+
+    #[test]
+    fn mul() {
+        let a = Color::rgb(0.5, 1.0, 0.25);
+        let b = Color::rgb(0.5, 0.5, 4.0);
+        assert_eq!(Color::rgb(0.25, 0.5, 1.0), a * b);
+    }
+
+    #[test]
+    fn srgb_round_trip() {
+        let argb = 0xff224466;
+        let c = Color::from_argb8888(argb);
+        assert_eq!(argb, c.to_linear().to_srgb().to_argb8888());
+    }
+
+    #[test]
+    fn linear_mid_gray_to_srgb() {
+        let c = Color::gray(0.5).to_srgb();
+        assert_eq!(0xffbcbcbc, c.to_argb8888());
+    }
+}
@@ -0,0 +1,208 @@
+
+use std::f64;
+
+use math::vector::Vector3;
+use math::matrix::Matrix4;
+use geometry::ray::Ray;
+
+use super::color::Color;
+use super::device::LineStyle;
+
+// Debug visualizations. Depth and Overdraw replace the shaded image with a
+// view of the device's internal buffers when it is resolved, the others
+// replace the shading of each pixel with an interpolated vertex attribute.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum DebugView {
+    Off,
+    // Nearest covered pixel white, farthest black.
+    Depth,
+    // Number of writes per pixel since the last clear, see heat_color.
+    Overdraw,
+    // World space normal, each axis mapped from [-1,1] to [0,1] in rgb.
+    Normals,
+    // Texture coordinates as red and green, repeating outside [0,1].
+    Uv,
+    // An 8x8 checker pattern per unit of texture coordinates.
+    Checker,
+}
+
+impl DebugView {
+    // Cycles through all views, e.g. to switch with a single key.
+    pub fn next(self) -> DebugView {
+        match self {
+            DebugView::Off => DebugView::Depth,
+            DebugView::Depth => DebugView::Overdraw,
+            DebugView::Overdraw => DebugView::Normals,
+            DebugView::Normals => DebugView::Uv,
+            DebugView::Uv => DebugView::Checker,
+            DebugView::Checker => DebugView::Off,
+        }
+    }
+
+    // Views shading meshes by their attributes, rather than showing one of
+    // the device's buffers.
+    pub fn shows_attributes(self) -> bool {
+        matches!(self, DebugView::Normals | DebugView::Uv | DebugView::Checker)
+    }
+}
+
+// Black for untouched pixels, then blue, cyan, green, yellow and red for one
+// to five writes, and white for anything above.
+pub fn heat_color(count: u32) -> Color {
+    let stops = [0xff000000,
+                 0xff0000ff,
+                 0xff00ffff,
+                 0xff00ff00,
+                 0xffffff00,
+                 0xffff0000,
+                 0xffffffff];
+    Color::from_argb8888(stops[(count as usize).min(stops.len() - 1)])
+}
+
+// A world space line segment queued by DebugDraw.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct DebugLine {
+    pub from: Vector3,
+    pub to: Vector3,
+    pub color: Color,
+}
+
+// Immediate mode line primitives for visualizing bounds, normals and
+// directions while developing. Queue them during the frame and draw them
+// after the main pass with Device::render_debug, then clear the queue.
+#[derive(Debug,Clone,Default)]
+pub struct DebugDraw {
+    lines: Vec<DebugLine>,
+    pub style: LineStyle,
+}
+
+impl DebugDraw {
+    pub fn new() -> DebugDraw {
+        DebugDraw {
+            lines: Vec::new(),
+            style: LineStyle::solid(),
+        }
+    }
+
+    pub fn lines(&self) -> &[DebugLine] {
+        &self.lines
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear()
+    }
+
+    pub fn draw_line(&mut self, from: Vector3, to: Vector3, color: Color) {
+        self.lines.push(DebugLine {
+            from: from,
+            to: to,
+            color: color,
+        })
+    }
+
+    pub fn draw_aabb(&mut self, min: Vector3, max: Vector3, color: Color) {
+        let corner = |i: usize| {
+            Vector3::new(if i & 1 == 0 { min.x } else { max.x },
+                         if i & 2 == 0 { min.y } else { max.y },
+                         if i & 4 == 0 { min.z } else { max.z })
+        };
+        // Corners differing in a single bit share an edge
+        for i in 0..8 {
+            for bit in &[1, 2, 4] {
+                if i & bit == 0 {
+                    self.draw_line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+
+    // Three circles around the x, y and z axes.
+    pub fn draw_sphere_wire(&mut self, center: Vector3, radius: f64, color: Color) {
+        let segments = 32;
+        let point = |axis: usize, i: usize| {
+            let angle = i as f64 / segments as f64 * 2.0 * f64::consts::PI;
+            let (s, c) = (angle.sin() * radius, angle.cos() * radius);
+            center +
+            match axis {
+                0 => Vector3::new(0.0, c, s),
+                1 => Vector3::new(s, 0.0, c),
+                _ => Vector3::new(c, s, 0.0),
+            }
+        };
+        for axis in 0..3 {
+            for i in 0..segments {
+                self.draw_line(point(axis, i), point(axis, i + 1), color);
+            }
+        }
+    }
+
+    // The x, y and z axes of the transform in red, green and blue.
+    pub fn draw_axes(&mut self, transform: &Matrix4, size: f64) {
+        let origin = Vector3::transform_coordinate(&Vector3::zero(), transform);
+        let axes = [(Vector3::unit_x(), Color::rgb(1.0, 0.0, 0.0)),
+                    (Vector3::unit_y(), Color::rgb(0.0, 1.0, 0.0)),
+                    (Vector3::unit_z(), Color::rgb(0.0, 0.0, 1.0))];
+        for &(axis, color) in &axes {
+            let end = Vector3::transform_coordinate(&(axis * size), transform);
+            self.draw_line(origin, end, color);
+        }
+    }
+
+    pub fn draw_ray(&mut self, ray: &Ray, length: f64, color: Color) {
+        self.draw_line(ray.origin, ray.point_at(length), color)
+    }
+
+    // Grid in the xz plane centered on the origin, size units across.
+    pub fn draw_grid(&mut self, size: f64, divisions: usize, color: Color) {
+        let half = size * 0.5;
+        for i in 0..divisions + 1 {
+            let t = -half + size * i as f64 / divisions as f64;
+            self.draw_line(Vector3::new(t, 0.0, -half), Vector3::new(t, 0.0, half), color);
+            self.draw_line(Vector3::new(-half, 0.0, t), Vector3::new(half, 0.0, t), color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use math::matrix::Matrix4;
+    use geometry::ray::Ray;
+    use super::super::color::Color;
+    use super::{heat_color, DebugDraw};
+
+    #[test]
+    fn heat() {
+        assert_eq!(Color::black(), heat_color(0));
+        assert_eq!(Color::rgb(0.0, 0.0, 1.0), heat_color(1));
+        assert_eq!(Color::rgb(1.0, 0.0, 0.0), heat_color(5));
+        assert_eq!(Color::white(), heat_color(100));
+    }
+
+    #[test]
+    fn debug_draw() {
+        let mut debug = DebugDraw::new();
+        debug.draw_aabb(Vector3::one() * -1.0, Vector3::one(), Color::white());
+        assert_eq!(12, debug.lines().len());
+        assert!(debug.lines()
+            .iter()
+            .all(|line| (line.to - line.from).length() == 2.0));
+
+        debug.clear();
+        debug.draw_axes(&Matrix4::translation(Vector3::new(1.0, 2.0, 3.0)), 2.0);
+        assert_eq!(3, debug.lines().len());
+        assert_eq!(Vector3::new(3.0, 2.0, 3.0), debug.lines()[0].to);
+        assert_eq!(Vector3::new(1.0, 2.0, 5.0), debug.lines()[2].to);
+
+        debug.clear();
+        debug.draw_sphere_wire(Vector3::zero(), 2.0, Color::white());
+        assert!(debug.lines().iter().all(|line| (line.from.length() - 2.0).abs() < 1e-9));
+
+        debug.clear();
+        debug.draw_grid(10.0, 4, Color::white());
+        debug.draw_ray(&Ray::new(Vector3::zero(), Vector3::unit_y()), 3.0, Color::white());
+        assert_eq!(11, debug.lines().len());
+        assert_eq!(Vector3::new(0.0, 3.0, 0.0), debug.lines()[10].to);
+    }
+}
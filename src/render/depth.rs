@@ -0,0 +1,295 @@
+
+use std::ops::Range;
+
+use math::vector::Vector3;
+
+// How the depth buffer stores depth, trading memory for precision. Depth
+// runs from 0 at the far plane, and where nothing was drawn, up to 1 at the
+// near plane. The normalized formats spread their whole range evenly over
+// that, so with a perspective camera, whose depth bunches up towards 0,
+// U16 suits only near and far planes close together.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum DepthFormat {
+    F64,
+    F32,
+    U16,
+    U32,
+}
+
+impl DepthFormat {
+    pub fn bytes_per_value(self) -> usize {
+        match self {
+            DepthFormat::F64 => 8,
+            DepthFormat::F32 | DepthFormat::U32 => 4,
+            DepthFormat::U16 => 2,
+        }
+    }
+}
+
+// Depth added to a triangle's to pull it towards the camera, e.g. so a decal
+// wins the depth test against the surface it lies on: the constant plus the
+// slope times the steepest the triangle's depth changes from one pixel to
+// the next, which grows as it turns edge on. Depth is bunched up towards 0
+// with a perspective camera, so a constant that separates near surfaces can
+// be too much far away.
+#[derive(Debug,Clone,Copy,Default,PartialEq)]
+pub struct DepthBias {
+    pub constant: f64,
+    pub slope: f64,
+}
+
+impl DepthBias {
+    pub fn new(constant: f64, slope: f64) -> DepthBias {
+        DepthBias {
+            constant: constant,
+            slope: slope,
+        }
+    }
+
+    // The bias for the triangle, its vertices in pixels and depth.
+    pub fn offset(&self, v: &[Vector3; 3]) -> f64 {
+        let (e1, e2) = (v[1] - v[0], v[2] - v[0]);
+        let area = e1.x * e2.y - e2.x * e1.y;
+        if self.slope == 0.0 || area == 0.0 {
+            return self.constant;
+        }
+        let dz_dx = (e1.z * e2.y - e2.z * e1.y) / area;
+        let dz_dy = (e1.x * e2.z - e2.x * e1.z) / area;
+        self.constant + self.slope * dz_dx.abs().max(dz_dy.abs())
+    }
+}
+
+// A value depth can be stored as. Stored values order the same as the
+// depths they were made from.
+trait Storage: Copy + PartialOrd {
+    fn encode(z: f64) -> Self;
+    fn decode(self) -> f64;
+}
+
+impl Storage for f64 {
+    fn encode(z: f64) -> f64 {
+        z
+    }
+
+    fn decode(self) -> f64 {
+        self
+    }
+}
+
+impl Storage for f32 {
+    fn encode(z: f64) -> f32 {
+        z as f32
+    }
+
+    fn decode(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Storage for u16 {
+    fn encode(z: f64) -> u16 {
+        (z.max(0.0).min(1.0) * u16::MAX as f64).round() as u16
+    }
+
+    fn decode(self) -> f64 {
+        self as f64 / u16::MAX as f64
+    }
+}
+
+impl Storage for u32 {
+    fn encode(z: f64) -> u32 {
+        (z.max(0.0).min(1.0) * u32::MAX as f64).round() as u32
+    }
+
+    fn decode(self) -> f64 {
+        self as f64 / u32::MAX as f64
+    }
+}
+
+fn test<T: Storage>(values: &[T], i: usize, z: f64) -> bool {
+    values[i] < T::encode(z)
+}
+
+fn test_and_set<T: Storage>(values: &mut [T], i: usize, z: f64) -> bool {
+    let value = T::encode(z);
+    if values[i] < value {
+        values[i] = value;
+        true
+    } else {
+        false
+    }
+}
+
+fn fill<T: Storage>(values: &mut [T], z: f64) {
+    let value = T::encode(z);
+    for v in values.iter_mut() {
+        *v = value
+    }
+}
+
+#[derive(Debug,Clone)]
+enum Values {
+    F64(Box<[f64]>),
+    F32(Box<[f32]>),
+    U16(Box<[u16]>),
+    U32(Box<[u32]>),
+}
+
+// Depth per pixel or sample in one of the formats, read and written as
+// f64 whatever it is stored as.
+#[derive(Debug,Clone)]
+pub struct DepthBuffer {
+    values: Values,
+}
+
+impl DepthBuffer {
+    // Cleared to 0.
+    pub fn new(format: DepthFormat, len: usize) -> DepthBuffer {
+        let values = match format {
+            DepthFormat::F64 => Values::F64(vec![0.0; len].into_boxed_slice()),
+            DepthFormat::F32 => Values::F32(vec![0.0; len].into_boxed_slice()),
+            DepthFormat::U16 => Values::U16(vec![0; len].into_boxed_slice()),
+            DepthFormat::U32 => Values::U32(vec![0; len].into_boxed_slice()),
+        };
+        DepthBuffer { values: values }
+    }
+
+    pub fn format(&self) -> DepthFormat {
+        match self.values {
+            Values::F64(_) => DepthFormat::F64,
+            Values::F32(_) => DepthFormat::F32,
+            Values::U16(_) => DepthFormat::U16,
+            Values::U32(_) => DepthFormat::U32,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self.values {
+            Values::F64(ref values) => values.len(),
+            Values::F32(ref values) => values.len(),
+            Values::U16(ref values) => values.len(),
+            Values::U32(ref values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.len() * self.format().bytes_per_value()
+    }
+
+    // The depth as stored, so rounded to the format.
+    pub fn get(&self, i: usize) -> f64 {
+        match self.values {
+            Values::F64(ref values) => values[i],
+            Values::F32(ref values) => values[i].decode(),
+            Values::U16(ref values) => values[i].decode(),
+            Values::U32(ref values) => values[i].decode(),
+        }
+    }
+
+    pub fn set(&mut self, i: usize, z: f64) {
+        match self.values {
+            Values::F64(ref mut values) => values[i] = z,
+            Values::F32(ref mut values) => values[i] = Storage::encode(z),
+            Values::U16(ref mut values) => values[i] = Storage::encode(z),
+            Values::U32(ref mut values) => values[i] = Storage::encode(z),
+        }
+    }
+
+    // Whether z is nearer than what is stored, as test_and_set finds it.
+    pub fn test(&self, i: usize, z: f64) -> bool {
+        match self.values {
+            Values::F64(ref values) => test(values, i, z),
+            Values::F32(ref values) => test(values, i, z),
+            Values::U16(ref values) => test(values, i, z),
+            Values::U32(ref values) => test(values, i, z),
+        }
+    }
+
+    // The depth test: writes z and returns true if it is nearer than what
+    // is stored, compared once rounded to the format, so depths too close
+    // for it keep the first drawn.
+    pub fn test_and_set(&mut self, i: usize, z: f64) -> bool {
+        match self.values {
+            Values::F64(ref mut values) => test_and_set(values, i, z),
+            Values::F32(ref mut values) => test_and_set(values, i, z),
+            Values::U16(ref mut values) => test_and_set(values, i, z),
+            Values::U32(ref mut values) => test_and_set(values, i, z),
+        }
+    }
+
+    pub fn fill(&mut self, z: f64) {
+        match self.values {
+            Values::F64(ref mut values) => fill(values, z),
+            Values::F32(ref mut values) => fill(values, z),
+            Values::U16(ref mut values) => fill(values, z),
+            Values::U32(ref mut values) => fill(values, z),
+        }
+    }
+
+    pub fn fill_range(&mut self, range: Range<usize>, z: f64) {
+        match self.values {
+            Values::F64(ref mut values) => fill(&mut values[range], z),
+            Values::F32(ref mut values) => fill(&mut values[range], z),
+            Values::U16(ref mut values) => fill(&mut values[range], z),
+            Values::U32(ref mut values) => fill(&mut values[range], z),
+        }
+    }
+
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = f64> + 'a {
+        (0..self.len()).map(move |i| self.get(i))
+    }
+
+    pub fn to_vec(&self) -> Vec<f64> {
+        self.iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use super::{DepthBias, DepthBuffer, DepthFormat};
+
+    #[test]
+    fn formats() {
+        for &format in &[DepthFormat::F64, DepthFormat::F32, DepthFormat::U16, DepthFormat::U32] {
+            let mut depth = DepthBuffer::new(format, 4);
+            assert_eq!((format, 4 * format.bytes_per_value()),
+                       (depth.format(), depth.size_in_bytes()));
+            assert!(depth.test_and_set(1, 0.5));
+            assert!(!depth.test(1, 0.25) && depth.test(1, 0.6));
+            assert!(!depth.test_and_set(1, 0.25));
+            assert!(depth.test_and_set(1, 0.75));
+            assert!((depth.get(1) - 0.75).abs() < 1e-4);
+            depth.fill_range(2..4, 1.0);
+            assert_eq!(1.0, depth.get(3));
+            depth.fill(0.0);
+            assert_eq!(vec![0.0; 4], depth.to_vec());
+        }
+
+        // Too close for 16 bits, the first drawn stays
+        let mut depth = DepthBuffer::new(DepthFormat::U16, 1);
+        assert!(depth.test_and_set(0, 0.5));
+        assert!(!depth.test_and_set(0, 0.500001));
+        let mut depth = DepthBuffer::new(DepthFormat::U32, 1);
+        assert!(depth.test_and_set(0, 0.5));
+        assert!(depth.test_and_set(0, 0.500001));
+    }
+
+    #[test]
+    fn bias() {
+        // Depth rising by 0.01 a pixel along x and 0.02 along y
+        let triangle = [Vector3::new(0.0, 0.0, 0.5),
+                        Vector3::new(10.0, 0.0, 0.6),
+                        Vector3::new(0.0, 10.0, 0.7)];
+        assert_eq!(0.0, DepthBias::default().offset(&triangle));
+        assert_eq!(0.25, DepthBias::new(0.25, 0.0).offset(&triangle));
+        assert!((DepthBias::new(0.25, 2.0).offset(&triangle) - 0.29).abs() < 1e-12);
+        let flat = [triangle[0], triangle[1], Vector3::new(20.0, 0.0, 0.7)];
+        assert_eq!(0.25, DepthBias::new(0.25, 2.0).offset(&flat));
+    }
+}
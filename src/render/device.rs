@@ -0,0 +1,4654 @@
+
+use std::f64;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::mem;
+use std::ops::Range;
+use std::path::Path;
+use std::time::Instant;
+
+use math::vector::{Vector2, Vector3, Vector4};
+use math::matrix::{Matrix3, Matrix4};
+use math::single::{Matrix4f, Vector2f, Vector3f, Vector4f};
+use math::frustum::Frustum;
+use math::curves::Curve;
+
+use geometry::lod::Lod;
+use geometry::mesh::{Instance, Mesh, Vertex};
+use geometry::point_cloud::PointCloud;
+use geometry::ray::Ray;
+use scene::graph::{NodeId, Scene};
+
+use super::arena::FrameArena;
+use super::color::Color;
+use super::tonemap::ToneMapping;
+use super::viewport::{Rect, Viewport};
+use super::clip;
+use super::debug;
+use super::debug::{DebugDraw, DebugView};
+use super::depth::{DepthBias, DepthBuffer, DepthFormat};
+use super::font;
+use super::fog::Fog;
+use super::fxaa;
+use super::gbuffer::GBuffer;
+use super::hiz::{HiZ, BLOCK_SIZE};
+use super::image;
+use super::light;
+use super::light::PointLight;
+use super::material::{Material, Outline, Shading, Surface};
+use super::occlusion::OcclusionBuffer;
+use super::particles::{Particle, ParticleStyle, ParticleSystem};
+use super::post::{Frame, PostChain};
+use super::queue;
+use super::queue::RenderQueue;
+use super::raymarch;
+use super::shadow::ShadowMap;
+use super::sprite::{Billboard, Sprite};
+use super::ssao::Ssao;
+use super::stats::{Benchmark, RenderStats};
+use super::target::RenderTarget;
+use super::texture::{CubeMap, Environment, Texture};
+use super::tiles::DirtyTiles;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Projection {
+    // Uses the camera fov as the vertical field of view.
+    Perspective,
+    // Parallel projection showing height world units vertically, centered
+    // on the view direction. The width follows the aspect ratio.
+    Orthographic { height: f64 },
+}
+
+// Selects between the matching _lh and _rh view and projection matrices.
+// With Right, the default for the camera controllers, +x is right, +y is up
+// and the camera looks down -z.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Handedness {
+    Left,
+    Right,
+}
+
+// How Device::render draws the faces of meshes.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum RenderMode {
+    Solid,
+    // Solid faces with their edges drawn on top, hidden edges removed by
+    // the depth test.
+    SolidWireframe,
+    // Only the visible edges, over the cleared background. Faces are drawn
+    // to the depth buffer alone.
+    HiddenLine,
+}
+
+// Floating point precision of the transform and raster stages of render:
+// vertex positions, clipping, edge functions, weights and depth. The
+// attributes and shading stay in f64 either way.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Precision {
+    Double,
+    // Positions go from the transform to the depth test as Vector4f and
+    // Vector3f, see math::single, and depth is stored as F32. Only triangles
+    // clipped at the guard band are cut in f64. Far surfaces close together
+    // fight sooner.
+    Single,
+}
+
+// Accumulated frames after which the jitter pattern repeats.
+const JITTER_FRAMES: usize = 64;
+
+// Samples per pixel for multisample antialiasing of triangle edges. Each
+// sample has its own coverage and depth, while shading still runs once
+// per pixel.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Msaa {
+    Off,
+    X2,
+    X4,
+}
+
+impl Msaa {
+    // Sample positions relative to the pixel, in the standard patterns.
+    fn offsets(self) -> &'static [(f64, f64)] {
+        match self {
+            Msaa::Off => &[(0.0, 0.0)],
+            Msaa::X2 => &[(0.25, 0.25), (-0.25, -0.25)],
+            Msaa::X4 => &[(-0.125, -0.375), (0.375, -0.125), (-0.375, 0.125), (0.125, 0.375)],
+        }
+    }
+
+    pub fn samples(self) -> usize {
+        self.offsets().len()
+    }
+}
+
+// Fraction of its depth a pixel must be behind an outlined one to be drawn
+// in the outline, so faces do not outline themselves.
+const OUTLINE_DEPTH_RATIO: f64 = 0.01;
+
+// Relative depth bias pulling wireframe edges towards the camera, so they
+// win the depth test against the faces they lie on, on top of
+// Device::wireframe_bias.
+const HIDDEN_LINE_BIAS: f64 = 1e-3;
+
+// Depth a triangle must be nearer than the farthest pixel of a block before
+// the block is rasterized, for rounding in the edge functions.
+const HIZ_TOLERANCE: f64 = 1e-6;
+
+// Pixels a tessellated curve may stray from the true curve.
+const CURVE_TOLERANCE: f64 = 0.25;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+enum Pass {
+    Shade,
+    Depth,
+    Edges,
+}
+
+// The meshes a render call draws, each with the instance it is drawn with,
+// looked up in place rather than collected into a list for every call.
+#[derive(Clone,Copy)]
+enum Draws<'a> {
+    Meshes(&'a [&'a Mesh]),
+    Instanced(&'a Mesh, &'a [Instance]),
+    Lods(&'a [&'a Lod], &'a Camera),
+    Paired(&'a [(&'a Mesh, Instance)]),
+    // Nodes of the scene with a mesh, with their world matrices.
+    Nodes(&'a Scene, &'a [(NodeId, Instance)]),
+}
+
+impl<'a> Draws<'a> {
+    fn len(&self) -> usize {
+        match *self {
+            Draws::Meshes(meshes) => meshes.len(),
+            Draws::Instanced(_, instances) => instances.len(),
+            Draws::Lods(lods, _) => lods.len(),
+            Draws::Paired(draws) => draws.len(),
+            Draws::Nodes(_, nodes) => nodes.len(),
+        }
+    }
+
+    fn get(&self, i: usize) -> (&'a Mesh, Instance) {
+        match *self {
+            Draws::Meshes(meshes) => (meshes[i], Instance::new(Matrix4::identity())),
+            Draws::Instanced(mesh, instances) => (mesh, instances[i]),
+            Draws::Lods(lods, camera) => {
+                let (mesh, transform) = lods[i].selected(camera);
+                (mesh, Instance::new(transform))
+            }
+            Draws::Paired(draws) => draws[i],
+            Draws::Nodes(scene, nodes) => {
+                let (id, instance) = nodes[i];
+                let mesh = scene.node(id).mesh.as_ref().expect("only nodes with a mesh are drawn");
+                (mesh, instance)
+            }
+        }
+    }
+}
+
+// Appearance of lines drawn with Device::draw_line_styled.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct LineStyle {
+    // Width in pixels, lines up to one pixel wide use Wu's algorithm.
+    pub thickness: f64,
+    // Lengths in pixels of the drawn and skipped parts of a dashed line.
+    pub dash: Option<(f64, f64)>,
+    // Tests against and writes the depth buffer, with the depth of the
+    // endpoints interpolated along the line.
+    pub depth_test: bool,
+}
+
+impl LineStyle {
+    pub fn solid() -> LineStyle {
+        LineStyle {
+            thickness: 1.0,
+            dash: None,
+            depth_test: true,
+        }
+    }
+}
+
+impl Default for LineStyle {
+    fn default() -> LineStyle {
+        LineStyle::solid()
+    }
+}
+
+// How Device::draw_circle, draw_ellipse and draw_arc draw their shapes.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct ShapeStyle {
+    pub filled: bool,
+    // Width in pixels of the outline, ignored when filled.
+    pub thickness: f64,
+    // Fades the edge over a pixel instead of covering pixels fully or not.
+    pub antialias: bool,
+}
+
+impl ShapeStyle {
+    pub fn outline() -> ShapeStyle {
+        ShapeStyle {
+            filled: false,
+            thickness: 1.0,
+            antialias: true,
+        }
+    }
+
+    pub fn fill() -> ShapeStyle {
+        ShapeStyle {
+            filled: true,
+            thickness: 1.0,
+            antialias: true,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Camera {
+    pub position: Vector3,
+    pub target: Vector3,
+    pub fov: f64,
+    pub zfar: f64,
+    pub znear: f64,
+    pub projection: Projection,
+    pub handedness: Handedness,
+}
+
+impl Camera {
+    pub fn view_matrix(&self) -> Matrix4 {
+        let look_at = match self.handedness {
+            Handedness::Left => Matrix4::look_at_lh,
+            Handedness::Right => Matrix4::look_at_rh,
+        };
+        look_at(self.position, self.target, Vector3::unit_y())
+    }
+
+    // Direction the camera looks along to see the world space point, not
+    // normalized. The same everywhere for an orthographic camera.
+    pub fn view_direction(&self, point: Vector3) -> Vector3 {
+        match self.projection {
+            Projection::Perspective => point - self.position,
+            Projection::Orthographic { .. } => self.target - self.position,
+        }
+    }
+
+    // Ray from the near towards the far plane through the given point of
+    // the viewport, in world space.
+    pub fn screen_ray(&self, viewport: &Viewport, x: f64, y: f64) -> Ray {
+        let view_projection = self.view_matrix() * self.projection_matrix(viewport.aspect());
+        let inverse = view_projection.inverse().unwrap_or_else(Matrix4::identity);
+        let ndc = viewport.unproject(Vector3::new(x, y, 0.0));
+        let near = Vector3::transform_coordinate(&Vector3::new(ndc.x, ndc.y, 1.0), &inverse);
+        let far = Vector3::transform_coordinate(&Vector3::new(ndc.x, ndc.y, 0.0), &inverse);
+        Ray::between(near, far)
+    }
+
+    pub fn projection_matrix(&self, aspect: f64) -> Matrix4 {
+        match self.projection {
+            Projection::Perspective => {
+                let perspective = match self.handedness {
+                    Handedness::Left => Matrix4::perspective_lh,
+                    Handedness::Right => Matrix4::perspective_rh,
+                };
+                perspective(self.fov, aspect, self.znear, self.zfar)
+            }
+            Projection::Orthographic { height } => {
+                let orthographic = match self.handedness {
+                    Handedness::Left => Matrix4::orthographic_lh,
+                    Handedness::Right => Matrix4::orthographic_rh,
+                };
+                let half_height = height * 0.5;
+                let half_width = half_height * aspect;
+                orthographic(-half_width,
+                             half_width,
+                             -half_height,
+                             half_height,
+                             self.znear,
+                             self.zfar)
+            }
+        }
+    }
+}
+
+// Per-vertex attributes interpolated across a triangle. inv_w is the
+// reciprocal clip space w, used for perspective correct interpolation.
+#[derive(Debug,Clone,Copy)]
+struct Varying {
+    inv_w: f64,
+    // World space, for looking up shadows.
+    position: Vector3,
+    // View space depth, for fog.
+    depth: f64,
+    // World space direction from the eye, for reflections.
+    view: Vector3,
+    normal: Vector3,
+    // World space, with the handedness in w as for Mesh::tangents.
+    tangent: Vector4,
+    uv: Vector2,
+    // The mesh's vertex color, linearized as needed.
+    color: Color,
+}
+
+// Attributes at the barycentric weights w, corrected for perspective so they
+// stay fixed to the surface rather than the screen.
+fn interpolate(w: Vector3, varyings: &[Varying; 3]) -> Varying {
+    let w = Vector3::new(w.x * varyings[0].inv_w,
+                         w.y * varyings[1].inv_w,
+                         w.z * varyings[2].inv_w);
+    blend(w * (1.0 / (w.x + w.y + w.z)), varyings)
+}
+
+// Attributes at the weights w as they are, e.g. along the triangle in clip
+// space. The result has no inv_w of its own.
+fn blend(w: Vector3, varyings: &[Varying; 3]) -> Varying {
+    let [a, b, c] = *varyings;
+    // Exact where the corners agree, as without vertex colors
+    let vertex_color = if a.color == b.color && b.color == c.color {
+        a.color
+    } else {
+        a.color * w.x + b.color * w.y + c.color * w.z
+    };
+    Varying {
+        inv_w: 1.0,
+        position: a.position * w.x + b.position * w.y + c.position * w.z,
+        depth: a.depth * w.x + b.depth * w.y + c.depth * w.z,
+        view: a.view * w.x + b.view * w.y + c.view * w.z,
+        normal: a.normal * w.x + b.normal * w.y + c.normal * w.z,
+        tangent: a.tangent * w.x + b.tangent * w.y + c.tangent * w.z,
+        uv: a.uv * w.x + b.uv * w.y + c.uv * w.z,
+        color: vertex_color,
+    }
+}
+
+// Unit shading normal at the attributes, bent by the material's normal map
+// where the mesh has tangents.
+fn surface_normal(varying: &Varying, material: &Material) -> Vector3 {
+    let normal = varying.normal;
+    if normal.length_sqr() == 0.0 {
+        return normal;
+    }
+    let normal = normal.normalize();
+    let map = match material.normal_map {
+        Some(ref map) => map,
+        None => return normal,
+    };
+    let tangent = varying.tangent.xyz();
+    let tangent = tangent - normal * normal.dot(tangent);
+    if tangent.length_sqr() == 0.0 {
+        return normal;
+    }
+    let tangent = tangent.normalize();
+    let bitangent = normal.cross(tangent) * if varying.tangent.w < 0.0 { -1.0 } else { 1.0 };
+    let c = map.sample(varying.uv);
+    let bent = tangent * (c.r * 2.0 - 1.0) + bitangent * (c.g * 2.0 - 1.0) +
+               normal * (c.b * 2.0 - 1.0);
+    if bent.length_sqr() > 0.0 { bent.normalize() } else { normal }
+}
+
+impl Varying {
+    fn flat() -> Varying {
+        Varying {
+            inv_w: 1.0,
+            position: Vector3::zero(),
+            depth: 0.0,
+            view: Vector3::zero(),
+            normal: Vector3::unit_z(),
+            tangent: Vector4::zero(),
+            uv: Vector2::zero(),
+            color: Color::white(),
+        }
+    }
+}
+
+// Everything the device draws into, sized to it. Device::render_to_target
+// swaps in another one for the target.
+#[derive(Debug)]
+struct Framebuffer {
+    width: usize,
+    height: usize,
+    backbuffer: Box<[u32]>,
+    depthbuffer: DepthBuffer,
+    // Farthest depth of each block of the depth buffer, see hierarchical_z.
+    hiz: HiZ,
+    // Number of writes to each pixel since the last clear.
+    overdraw: Box<[u32]>,
+    hdrbuffer: Option<Box<[Color]>>,
+    msaa: Msaa,
+    // Pixels rendered along each axis per pixel presented. With more than
+    // one, the buffers above are that much larger and resolve box filters
+    // them down into presented.
+    supersampling: usize,
+    presented: Option<Box<[u32]>>,
+    // Sum of the frames resolved since accumulation was last reset, each
+    // rendered with a different subpixel jitter.
+    accumulation: Option<Box<[Color]>>,
+    accumulated_frames: usize,
+    // Color and depth of each sample with multisampling, the samples of a
+    // pixel next to each other. Empty without it.
+    sample_color: Box<[Color]>,
+    sample_depth: DepthBuffer,
+    gbuffer: Option<GBuffer>,
+    // Tiles drawn to since the last clear, the only ones the next clear
+    // resets. None with partial clears off.
+    dirty: Option<DirtyTiles>,
+    // The backbuffer and linear colors of the last clear.
+    last_clear: Option<(u32, Color)>,
+    // Presented pixels drawing is restricted to, None for all of them.
+    scissor: Option<Rect>,
+}
+
+impl Framebuffer {
+    fn new(width: usize, height: usize, depthbuffer: DepthBuffer) -> Framebuffer {
+        Framebuffer {
+            width: width,
+            height: height,
+            backbuffer: vec![0; width * height].into_boxed_slice(),
+            depthbuffer: depthbuffer,
+            hiz: HiZ::new(width, height),
+            overdraw: vec![0; width * height].into_boxed_slice(),
+            hdrbuffer: None,
+            msaa: Msaa::Off,
+            supersampling: 1,
+            presented: None,
+            accumulation: None,
+            accumulated_frames: 0,
+            sample_color: Vec::new().into_boxed_slice(),
+            sample_depth: DepthBuffer::new(DepthFormat::F64, 0),
+            gbuffer: None,
+            dirty: None,
+            last_clear: None,
+            scissor: None,
+        }
+    }
+
+    // Pixels rendered, more than presented when supersampling.
+    fn len(&self) -> usize {
+        self.width * self.height
+    }
+}
+
+#[derive(Debug)]
+pub struct Device {
+    framebuffer: Framebuffer,
+    depth_format: DepthFormat,
+    // Outline of the material last shaded at each pixel, while meshes with
+    // outlines are being rendered. Empty otherwise.
+    outlines: Vec<Option<Outline>>,
+    pub tone_mapping: ToneMapping,
+    pub exposure: f64,
+    pub gamma_correct: bool,
+    pub debug_view: DebugView,
+    pub render_mode: RenderMode,
+    precision: Precision,
+    // Rejects blocks of triangles behind everything drawn there before
+    // testing their pixels, see render::hiz. Without multisampling only.
+    pub hierarchical_z: bool,
+    // How far from the center of the viewport, in its half sizes, triangles
+    // are left whole for the rasterizer to scissor. Only those reaching
+    // farther, or behind the near plane, are clipped into smaller ones. At
+    // least 1.
+    pub guard_band: f64,
+    // Pulls the edges of wireframes towards the camera, so they are not
+    // hidden by the faces they lie on, even turned edge on.
+    pub wireframe_bias: DepthBias,
+    // Applied to meshes by their texture coordinates in place of the
+    // default shading, e.g. the texture of a RenderTarget.
+    pub texture: Option<Texture>,
+    // Full screen passes run by resolve, before tone mapping. Without HDR
+    // they run on the backbuffer itself, so resolve only once per frame.
+    pub post: PostChain,
+    // Antialiases the backbuffer at the end of resolve. Like post, it runs
+    // on the backbuffer itself without HDR.
+    pub fxaa: bool,
+    // Darkens meshes where the light does not reach them, once filled by
+    // render_shadows.
+    pub shadow: Option<ShadowMap>,
+    // Skips meshes hidden behind the occluders, once filled by
+    // render_occluders.
+    pub occlusion: Option<OcclusionBuffer>,
+    // Lights meshes are shaded with, in addition to the ambient light. They
+    // are left unlit without any.
+    pub lights: Vec<PointLight>,
+    pub ambient: Color,
+    // Darkens the ambient light in creases, with deferred shading only.
+    pub ssao: Option<Ssao>,
+    // Fades meshes into the fog color with their distance from the camera.
+    pub fog: Option<Fog>,
+    // Reflected by meshes with a reflective material.
+    pub environment: Option<Environment>,
+    stats: RenderStats,
+    // Buffers render reuses from frame to frame, reset by clear.
+    arena: FrameArena,
+}
+
+fn round(x: f64) -> f64 {
+    (x + 0.5).round()
+}
+
+fn fpart(x: f64) -> f64 {
+    x.fract().abs()
+}
+
+fn rfpart(x: f64) -> f64 {
+    1.0 - fpart(x)
+}
+
+// Pushes the points after t0 up to t1. Splits at least a few times, as the
+// midpoint of an S shaped span can lie on its chord.
+fn tessellate(curve: &Curve, t0: f64, t1: f64, depth: u32, points: &mut Vec<Vector2>) {
+    let a = curve.point(t0);
+    let b = curve.point(t1);
+    let tm = (t0 + t1) * 0.5;
+    let m = curve.point(tm);
+    let chord = b - a;
+    let distance = if chord.length() > 0.0 {
+        chord.cross(m - a).abs() / chord.length()
+    } else {
+        (m - a).length()
+    };
+    if depth >= 16 || (depth >= 3 && distance <= CURVE_TOLERANCE) {
+        points.push(b);
+    } else {
+        tessellate(curve, t0, tm, depth + 1, points);
+        tessellate(curve, tm, t1, depth + 1, points);
+    }
+}
+
+impl Device {
+    pub fn new(width: usize, height: usize) -> Device {
+        Device {
+            framebuffer: Framebuffer::new(width,
+                                          height,
+                                          DepthBuffer::new(DepthFormat::F64, width * height)),
+            depth_format: DepthFormat::F64,
+            outlines: Vec::new(),
+            tone_mapping: ToneMapping::Reinhard,
+            exposure: 1.0,
+            gamma_correct: true,
+            debug_view: DebugView::Off,
+            render_mode: RenderMode::Solid,
+            precision: Precision::Double,
+            hierarchical_z: true,
+            guard_band: 4.0,
+            wireframe_bias: DepthBias::new(0.0, 1.0),
+            texture: None,
+            post: PostChain::new(),
+            fxaa: false,
+            shadow: None,
+            occlusion: None,
+            lights: Vec::new(),
+            ambient: Color::gray(0.1),
+            ssao: None,
+            fog: None,
+            environment: None,
+            stats: RenderStats::default(),
+            arena: FrameArena::new(),
+        }
+    }
+
+    // Size of the presented frame. Coordinates passed to the device are in
+    // its pixels, whatever the supersampling.
+    pub fn width(&self) -> usize {
+        self.framebuffer.width / self.framebuffer.supersampling
+    }
+
+    pub fn height(&self) -> usize {
+        self.framebuffer.height / self.framebuffer.supersampling
+    }
+
+    // Viewport covering the whole device.
+    pub fn viewport(&self) -> Viewport {
+        Viewport::new(0.0, 0.0, self.width() as f64, self.height() as f64)
+    }
+
+    // The resolved frame, downsampled when supersampling.
+    pub fn backbuffer(&self) -> &[u32] {
+        match self.framebuffer.presented {
+            Some(ref presented) => presented,
+            None => &self.framebuffer.backbuffer,
+        }
+    }
+
+    // Direct access to the resolved ARGB8888 pixels, e.g. for overlays.
+    pub fn backbuffer_mut(&mut self) -> &mut [u32] {
+        // Whatever is written, the next clear resets it all
+        if self.framebuffer.presented.is_none() {
+            self.invalidate()
+        }
+        self.frame_mut()
+    }
+
+    fn frame_mut(&mut self) -> &mut [u32] {
+        match self.framebuffer.presented {
+            Some(ref mut presented) => presented,
+            None => &mut self.framebuffer.backbuffer,
+        }
+    }
+
+    // Draws text in the embedded 8x8 font with its top left corner at
+    // (x, y), each line break starting a new row. Text goes straight to the
+    // backbuffer, so draw it after resolve.
+    pub fn draw_text(&mut self, x: i32, y: i32, text: &str, color: Color) {
+        let argb = color.to_argb8888();
+        let width = self.width();
+        let clip = Rect::new(0, 0, width, self.height());
+        let clip = self.framebuffer.scissor.map_or(clip, |scissor| scissor.intersect(&clip));
+        if self.framebuffer.presented.is_none() {
+            let (w, h) = font::text_size(text);
+            let (x0, y0) = (x.max(0) as usize, y.max(0) as usize);
+            let (x1, y1) = ((x + w as i32).max(0) as usize, (y + h as i32).max(0) as usize);
+            self.mark_dirty(x0..x1, y0..y1)
+        }
+        for (i, line) in text.lines().enumerate() {
+            font::draw_text(self.frame_mut(),
+                            width,
+                            &clip,
+                            x,
+                            y + (i * font::GLYPH_HEIGHT) as i32,
+                            line,
+                            argb);
+        }
+    }
+
+    // Counters accumulated since the last reset_stats.
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = RenderStats::default()
+    }
+
+    // Returns the backbuffer as tightly packed RGBA8 rows, top row first.
+    pub fn read_pixels(&self) -> Vec<u8> {
+        image::argb_to_rgba(self.backbuffer())
+    }
+
+    // Returns the depth buffer as grayscale RGBA8, nearest covered pixel
+    // white and farthest black. Uncovered pixels are black as well.
+    pub fn read_depth_pixels(&self) -> Vec<u8> {
+        let depth = self.normalized_depth();
+        let ss = self.framebuffer.supersampling;
+        let mut pixels = Vec::with_capacity(self.width() * self.height() * 4);
+        // Point sampled when supersampling, as depth doesn't average well
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let v = depth[y * ss * self.framebuffer.width + x * ss];
+                let (r, g, b, a) = Color::gray(v).to_u8();
+                pixels.push(r);
+                pixels.push(g);
+                pixels.push(b);
+                pixels.push(a);
+            }
+        }
+        pixels
+    }
+
+    // Depth remapped so the covered range spans [0, 1], far to near.
+    fn normalized_depth(&self) -> Vec<f64> {
+        let mut min = f64::MAX;
+        let mut max = f64::MIN;
+        for z in self.framebuffer.depthbuffer.iter() {
+            if z != 0.0 {
+                min = min.min(z);
+                max = max.max(z);
+            }
+        }
+        let range = if max > min { max - min } else { 1.0 };
+
+        self.framebuffer.depthbuffer
+            .iter()
+            .map(|z| if z != 0.0 { (z - min) / range } else { 0.0 })
+            .collect()
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        image::write_png(file, self.width(), self.height(), &self.read_pixels())
+    }
+
+    pub fn save_ppm<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        image::write_ppm(file, self.width(), self.height(), &self.read_pixels())
+    }
+
+    pub fn save_depth_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        image::write_png(file, self.width(), self.height(), &self.read_depth_pixels())
+    }
+
+    pub fn save_depth_ppm<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        image::write_ppm(file, self.width(), self.height(), &self.read_depth_pixels())
+    }
+
+    // Runs draw with the device rendering into the target instead, sized as
+    // the target, in HDR and without multi- or supersampling, accumulation
+    // or deferred shading. Resolve and draw_text go to a scratch backbuffer
+    // that is dropped afterwards. Should draw resize the device or turn HDR
+    // off, the target is left cleared.
+    pub fn render_to_target<F>(&mut self, target: &mut RenderTarget, draw: F)
+        where F: FnOnce(&mut Device)
+    {
+        let (color, depth) = target.take();
+        let mut framebuffer = Framebuffer::new(target.width(), target.height(), depth);
+        framebuffer.hdrbuffer = Some(color);
+        mem::swap(&mut self.framebuffer, &mut framebuffer);
+
+        draw(self);
+
+        mem::swap(&mut self.framebuffer, &mut framebuffer);
+        let size = target.width() * target.height();
+        let color = match framebuffer.hdrbuffer {
+            Some(color) if color.len() == size => color,
+            _ => vec![Color::black(); size].into_boxed_slice(),
+        };
+        let depth = if framebuffer.depthbuffer.len() == size {
+            framebuffer.depthbuffer
+        } else {
+            DepthBuffer::new(DepthFormat::F64, size)
+        };
+        target.put_back(color, depth)
+    }
+
+    pub fn is_hdr(&self) -> bool {
+        self.framebuffer.hdrbuffer.is_some()
+    }
+
+    // Reallocates all buffers for the new size. Their contents are
+    // undefined until the next clear.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        if width == self.width() && height == self.height() {
+            return;
+        }
+        self.allocate(width, height)
+    }
+
+    fn allocate(&mut self, width: usize, height: usize) {
+        self.framebuffer.presented = if self.framebuffer.supersampling > 1 {
+            Some(vec![0; width * height].into_boxed_slice())
+        } else {
+            None
+        };
+        let ss = self.framebuffer.supersampling;
+        let (width, height) = (width * ss, height * ss);
+        self.framebuffer.width = width;
+        self.framebuffer.height = height;
+        self.framebuffer.backbuffer = vec![0; width * height].into_boxed_slice();
+        self.framebuffer.depthbuffer = DepthBuffer::new(self.depth_format, width * height);
+        self.framebuffer.hiz = HiZ::new(width, height);
+        self.framebuffer.overdraw = vec![0; width * height].into_boxed_slice();
+        if self.is_partial_clear() {
+            self.framebuffer.dirty = Some(DirtyTiles::new(width, height))
+        }
+        let hdr = self.is_hdr();
+        self.set_hdr(hdr);
+        let msaa = self.framebuffer.msaa;
+        self.set_msaa(msaa);
+        let accumulate = self.is_accumulating();
+        self.set_accumulation(accumulate);
+        let deferred = self.is_deferred();
+        self.set_deferred(deferred);
+    }
+
+    // When enabled, pixels are written as linear floating-point colors and
+    // converted to the backbuffer by resolve.
+    pub fn set_hdr(&mut self, enabled: bool) {
+        self.framebuffer.hdrbuffer = if enabled {
+            Some(vec![Color::black(); self.framebuffer.len()].into_boxed_slice())
+        } else {
+            None
+        };
+        self.invalidate()
+    }
+
+    pub fn supersampling(&self) -> usize {
+        self.framebuffer.supersampling
+    }
+
+    // Renders factor x factor pixels for each one presented, e.g. 2 or 4,
+    // averaged by resolve, in linear space with gamma_correct. Reallocates
+    // all buffers like resize.
+    pub fn set_supersampling(&mut self, factor: usize) {
+        assert!(factor >= 1);
+        let (width, height) = (self.width(), self.height());
+        self.framebuffer.supersampling = factor;
+        self.allocate(width, height)
+    }
+
+    pub fn is_accumulating(&self) -> bool {
+        self.framebuffer.accumulation.is_some()
+    }
+
+    // For static scenes: each frame is rendered with a different subpixel
+    // offset and resolve shows the average of the frames so far, converging
+    // to an antialiased image. Call reset_accumulation when the camera or
+    // scene changes, and resolve only once per frame.
+    pub fn set_accumulation(&mut self, enabled: bool) {
+        self.framebuffer.accumulation = if enabled {
+            Some(vec![Color::new(0.0, 0.0, 0.0, 0.0); self.framebuffer.len()].into_boxed_slice())
+        } else {
+            None
+        };
+        self.framebuffer.accumulated_frames = 0;
+    }
+
+    pub fn reset_accumulation(&mut self) {
+        let enabled = self.is_accumulating();
+        self.set_accumulation(enabled)
+    }
+
+    pub fn accumulated_frames(&self) -> usize {
+        self.framebuffer.accumulated_frames
+    }
+
+    // Offset of projected geometry for the next accumulated frame, spread
+    // evenly over the pixel by a Halton sequence.
+    fn jitter(&self) -> Vector3 {
+        if !self.is_accumulating() {
+            return Vector3::zero();
+        }
+        let index = self.framebuffer.accumulated_frames % JITTER_FRAMES + 1;
+        let ss = self.framebuffer.supersampling as f64;
+        Vector3::new((halton(index, 2) - 0.5) * ss, (halton(index, 3) - 0.5) * ss, 0.0)
+    }
+
+    pub fn is_deferred(&self) -> bool {
+        self.framebuffer.gbuffer.is_some()
+    }
+
+    // Meshes are rasterized into a G-buffer and lit by one pass over the
+    // screen at the end of each render call, so each light is evaluated once
+    // per pixel on screen rather than for every pixel of every triangle.
+    // Multisampled edges are lost in the lighting pass.
+    pub fn set_deferred(&mut self, enabled: bool) {
+        self.framebuffer.gbuffer = if enabled {
+            Some(GBuffer::new(self.framebuffer.width, self.framebuffer.height))
+        } else {
+            None
+        };
+    }
+
+    pub fn gbuffer(&self) -> Option<&GBuffer> {
+        self.framebuffer.gbuffer.as_ref()
+    }
+
+    pub fn msaa(&self) -> Msaa {
+        self.framebuffer.msaa
+    }
+
+    // Allocates the sample buffers, undefined until the next clear. Lines,
+    // shapes and text are not multisampled, they set all the samples of the
+    // pixels they cover.
+    pub fn set_msaa(&mut self, msaa: Msaa) {
+        self.framebuffer.msaa = msaa;
+        let size = if msaa == Msaa::Off { 0 } else { self.framebuffer.len() * msaa.samples() };
+        self.framebuffer.sample_color = vec![Color::black(); size].into_boxed_slice();
+        self.framebuffer.sample_depth = DepthBuffer::new(self.depth_format, size);
+        self.invalidate()
+    }
+
+    pub fn depth_format(&self) -> DepthFormat {
+        self.depth_format
+    }
+
+    pub fn precision(&self) -> Precision {
+        self.precision
+    }
+
+    // Also sets the depth format to F32 for Single and F64 for Double, see
+    // set_depth_format.
+    pub fn set_precision(&mut self, precision: Precision) {
+        self.precision = precision;
+        self.set_depth_format(match precision {
+            Precision::Double => DepthFormat::F64,
+            Precision::Single => DepthFormat::F32,
+        })
+    }
+
+    // Reallocates the depth and sample depth buffers, undefined until the
+    // next clear. Render targets keep their own format.
+    pub fn set_depth_format(&mut self, format: DepthFormat) {
+        self.depth_format = format;
+        self.framebuffer.depthbuffer = DepthBuffer::new(format, self.framebuffer.len());
+        self.framebuffer.hiz.invalidate();
+        let msaa = self.framebuffer.msaa;
+        self.set_msaa(msaa);
+    }
+
+    pub fn is_partial_clear(&self) -> bool {
+        self.framebuffer.dirty.is_some()
+    }
+
+    // Keeps track of the tiles drawn to, so clear resets only those and
+    // frames the meshes don't fill cost less memory bandwidth. A resolve
+    // that rewrites the whole backbuffer, as with multisampling, HDR, post
+    // passes, FXAA or accumulation, leaves the next clear a full one.
+    pub fn set_partial_clear(&mut self, enabled: bool) {
+        self.framebuffer.dirty = if enabled {
+            Some(DirtyTiles::new(self.framebuffer.width, self.framebuffer.height))
+        } else {
+            None
+        }
+    }
+
+    pub fn dirty_tiles(&self) -> Option<&DirtyTiles> {
+        self.framebuffer.dirty.as_ref()
+    }
+
+    pub fn scissor(&self) -> Option<Rect> {
+        self.framebuffer.scissor
+    }
+
+    // Restricts whatever is drawn from then on, clears included, to the
+    // rectangle of presented pixels, e.g. for one view of a split screen or
+    // a scrolling panel. None lifts it. Resolve and post passes still
+    // cover the whole frame.
+    pub fn set_scissor(&mut self, scissor: Option<Rect>) {
+        self.framebuffer.scissor = scissor
+    }
+
+    // The pixels drawing may write, the scissor's scaled to the rendered
+    // pixels and kept inside the frame.
+    fn scissor_rect(&self) -> Rect {
+        let frame = Rect::new(0, 0, self.framebuffer.width, self.framebuffer.height);
+        match self.framebuffer.scissor {
+            Some(scissor) => {
+                let ss = self.framebuffer.supersampling;
+                Rect::new(scissor.x * ss, scissor.y * ss, scissor.width * ss, scissor.height * ss)
+                    .intersect(&frame)
+            }
+            None => frame,
+        }
+    }
+
+    // Marks the pixels in the ranges for the next partial clear.
+    fn mark_dirty(&mut self, x: Range<usize>, y: Range<usize>) {
+        if let Some(ref mut dirty) = self.framebuffer.dirty {
+            dirty.mark_rect(x, y)
+        }
+    }
+
+    // For buffers whose contents are unknown, so the next clear resets
+    // them all.
+    fn invalidate(&mut self) {
+        if let Some(ref mut dirty) = self.framebuffer.dirty {
+            dirty.mark_all()
+        }
+    }
+
+    // With partial clears, only the tiles drawn to since the last clear,
+    // if it was to the same color.
+    pub fn clear(&mut self, clear_color: Color) {
+        profile_scope!("clear");
+        self.arena.reset();
+        if self.framebuffer.scissor.is_some() {
+            let viewport = self.viewport();
+            self.clear_viewport(clear_color, &viewport);
+            return;
+        }
+        let hdr_clear_color = self.linearize(clear_color);
+        let clear_color = clear_color.to_argb8888();
+        if self.framebuffer.last_clear != Some((clear_color, hdr_clear_color)) {
+            self.framebuffer.last_clear = Some((clear_color, hdr_clear_color));
+            self.invalidate();
+        }
+        let mut spans: Vec<Range<usize>> = self.arena.take();
+        match self.framebuffer.dirty {
+            Some(ref mut dirty) => dirty.take_spans(&mut spans),
+            None => spans.push(0..self.framebuffer.len()),
+        }
+        for span in &spans {
+            if let Some(ref mut hdrbuffer) = self.framebuffer.hdrbuffer {
+                for c in hdrbuffer[span.clone()].iter_mut() {
+                    *c = hdr_clear_color
+                }
+            }
+            for pixel in self.framebuffer.backbuffer[span.clone()].iter_mut() {
+                *pixel = clear_color
+            }
+            self.framebuffer.depthbuffer.fill_range(span.clone(), 0.0);
+            for count in self.framebuffer.overdraw[span.clone()].iter_mut() {
+                *count = 0
+            }
+            let samples = self.samples(span.start).start..self.samples(span.end).start;
+            self.framebuffer.sample_depth.fill_range(samples.clone(), 0.0);
+            for c in self.framebuffer.sample_color[samples].iter_mut() {
+                *c = hdr_clear_color
+            }
+            if let Some(ref mut gbuffer) = self.framebuffer.gbuffer {
+                gbuffer.clear_range(span.clone())
+            }
+        }
+        self.arena.recycle(spans);
+        // What the partial clear skipped was still clear
+        self.framebuffer.hiz.clear();
+    }
+
+    // Clears color and depth inside the viewport only, e.g. before drawing
+    // a picture-in-picture view over an already rendered frame.
+    pub fn clear_viewport(&mut self, clear_color: Color, viewport: &Viewport) {
+        let (min, max) = self.clip_rect(&self.scale_viewport(viewport));
+        let hdr_clear_color = self.linearize(clear_color);
+        let clear_color = clear_color.to_argb8888();
+        self.framebuffer.hiz.invalidate();
+        // Not the last clear's color, unless it happens to be
+        self.mark_dirty(min.x as usize..max.x as usize, min.y as usize..max.y as usize);
+        for y in min.y as usize..max.y as usize {
+            for x in min.x as usize..max.x as usize {
+                let offset = y * self.framebuffer.width + x;
+                if let Some(ref mut hdrbuffer) = self.framebuffer.hdrbuffer {
+                    hdrbuffer[offset] = hdr_clear_color
+                }
+                self.framebuffer.backbuffer[offset] = clear_color;
+                self.framebuffer.depthbuffer.set(offset, 0.0);
+                self.framebuffer.overdraw[offset] = 0;
+                let samples = self.samples(offset);
+                for i in samples {
+                    self.framebuffer.sample_color[i] = hdr_clear_color;
+                    self.framebuffer.sample_depth.set(i, 0.0)
+                }
+                if let Some(ref mut gbuffer) = self.framebuffer.gbuffer {
+                    gbuffer.uncover(offset)
+                }
+            }
+        }
+    }
+
+    // Part of the viewport that lies inside the device and the scissor, in
+    // whole pixels.
+    fn clip_rect(&self, viewport: &Viewport) -> (Vector2, Vector2) {
+        let scissor = self.scissor_rect();
+        let min = Vector2::new(viewport.x, viewport.y)
+            .max(Vector2::new(scissor.x as f64, scissor.y as f64));
+        let max = Vector2::new(viewport.x + viewport.width, viewport.y + viewport.height)
+            .min(Vector2::new(scissor.right() as f64, scissor.bottom() as f64));
+        // A pixel belongs to the viewport when its corner does, so adjacent
+        // viewports neither overlap nor leave gaps
+        let min = Vector2::new(min.x.ceil(), min.y.ceil());
+        let max = Vector2::new(max.x.ceil(), max.y.ceil());
+        (min, max.max(min))
+    }
+
+    // From presented pixels to the pixels rendered when supersampling.
+    fn scale_viewport(&self, viewport: &Viewport) -> Viewport {
+        let ss = self.framebuffer.supersampling as f64;
+        Viewport {
+            x: viewport.x * ss,
+            y: viewport.y * ss,
+            width: viewport.width * ss,
+            height: viewport.height * ss,
+            ..*viewport
+        }
+    }
+
+    // Pixels are sampled at whole coordinates, so the center of the pixels
+    // rendered for a presented pixel lies this far past its scaled position.
+    fn sample_shift(&self) -> f64 {
+        (self.framebuffer.supersampling as f64 - 1.0) * 0.5
+    }
+
+    fn scale_point(&self, p: Vector3) -> Vector3 {
+        let ss = self.framebuffer.supersampling as f64;
+        let shift = self.sample_shift();
+        Vector3::new(p.x * ss + shift, p.y * ss + shift, p.z)
+    }
+
+    fn scale_style(&self, style: &LineStyle) -> LineStyle {
+        let ss = self.framebuffer.supersampling as f64;
+        LineStyle {
+            thickness: style.thickness * ss,
+            dash: style.dash.map(|(on, off)| (on * ss, off * ss)),
+            ..*style
+        }
+    }
+
+    // Colors are authored in sRGB. With gamma correction enabled shading
+    // happens in linear space and is encoded back to sRGB in write_pixel.
+    fn linearize(&self, color: Color) -> Color {
+        if self.gamma_correct {
+            color.to_linear()
+        } else {
+            color
+        }
+    }
+
+    fn encode(&self, color: Color) -> Color {
+        if self.gamma_correct {
+            color.to_srgb()
+        } else {
+            color
+        }
+    }
+
+    // Indices of the samples of the pixel at offset, none without
+    // multisampling.
+    fn samples(&self, offset: usize) -> Range<usize> {
+        let msaa = self.framebuffer.msaa;
+        let n = if msaa == Msaa::Off { 0 } else { msaa.samples() };
+        offset * n..(offset + 1) * n
+    }
+
+    // Color of the pixel before resolve, in the space shading happens in.
+    fn pixel(&self, offset: usize) -> Color {
+        if self.framebuffer.msaa != Msaa::Off {
+            let samples = self.samples(offset);
+            let weight = 1.0 / samples.len() as f64;
+            return self.framebuffer.sample_color[samples]
+                .iter()
+                .fold(Color::new(0.0, 0.0, 0.0, 0.0), |sum, &c| sum + c * weight);
+        }
+        match self.framebuffer.hdrbuffer {
+            Some(ref hdrbuffer) => hdrbuffer[offset],
+            None => self.linearize(Color::from_argb8888(self.framebuffer.backbuffer[offset])),
+        }
+    }
+
+    // Sets the pixels covering the presented pixel at (x, y) to the color,
+    // in the space shading happens in.
+    pub fn put_pixel(&mut self, x: u32, y: u32, color: Color) {
+        let ss = self.framebuffer.supersampling as u32;
+        let scissor = self.scissor_rect();
+        for sy in y * ss..(y + 1) * ss {
+            for sx in x * ss..(x + 1) * ss {
+                if scissor.contains(sx as usize, sy as usize) {
+                    self.write_pixel(sx, sy, color)
+                }
+            }
+        }
+    }
+
+    // With multisampling, sets all the samples of the pixel.
+    fn write_pixel(&mut self, x: u32, y: u32, color: Color) {
+        let offset = (y as usize * self.framebuffer.width) + x as usize;
+        self.framebuffer.overdraw[offset] += 1;
+        if let Some(ref mut gbuffer) = self.framebuffer.gbuffer {
+            gbuffer.uncover(offset)
+        }
+        self.store_pixel(offset, color)
+    }
+
+    fn store_pixel(&mut self, offset: usize, color: Color) {
+        if let Some(ref mut dirty) = self.framebuffer.dirty {
+            dirty.mark(offset)
+        }
+        if self.framebuffer.msaa != Msaa::Off {
+            for i in self.samples(offset) {
+                self.framebuffer.sample_color[i] = color
+            }
+            return;
+        }
+        match self.framebuffer.hdrbuffer {
+            Some(ref mut hdrbuffer) => hdrbuffer[offset] = color,
+            None => self.framebuffer.backbuffer[offset] = self.encode(color).to_argb8888(),
+        }
+    }
+
+    pub fn resolve(&mut self) {
+        profile_scope!("resolve");
+        self.resolve_samples();
+        self.resolve_color();
+        // Views of the buffers are shown as they are
+        let buffer_view = matches!(self.debug_view, DebugView::Depth | DebugView::Overdraw);
+        if self.fxaa && !buffer_view {
+            self.invalidate();
+            let framebuffer = &mut self.framebuffer;
+            fxaa::fxaa(&mut framebuffer.backbuffer, framebuffer.width, framebuffer.height)
+        }
+        if let Some(ref mut presented) = self.framebuffer.presented {
+            let frame = image::downsample(&self.framebuffer.backbuffer,
+                                          self.framebuffer.width,
+                                          self.framebuffer.height,
+                                          self.framebuffer.supersampling,
+                                          self.gamma_correct);
+            presented.copy_from_slice(&frame);
+        }
+    }
+
+    // Averages the samples of each pixel into the HDR buffer or backbuffer.
+    fn resolve_samples(&mut self) {
+        if self.framebuffer.msaa == Msaa::Off {
+            return;
+        }
+        self.invalidate();
+        for offset in 0..self.framebuffer.len() {
+            let color = self.pixel(offset);
+            match self.framebuffer.hdrbuffer {
+                Some(ref mut hdrbuffer) => hdrbuffer[offset] = color,
+                None => self.framebuffer.backbuffer[offset] = self.encode(color).to_argb8888(),
+            }
+        }
+    }
+
+    fn resolve_color(&mut self) {
+        match self.debug_view {
+            DebugView::Off | DebugView::Normals | DebugView::Uv | DebugView::Checker => {}
+            DebugView::Depth => {
+                self.invalidate();
+                let depth = self.normalized_depth();
+                for (pixel, &v) in self.framebuffer.backbuffer.iter_mut().zip(depth.iter()) {
+                    *pixel = Color::gray(v).to_argb8888()
+                }
+                return;
+            }
+            DebugView::Overdraw => {
+                self.invalidate();
+                let Framebuffer { ref mut backbuffer, ref overdraw, .. } = self.framebuffer;
+                for (pixel, &count) in backbuffer.iter_mut().zip(overdraw.iter()) {
+                    *pixel = debug::heat_color(count).to_argb8888()
+                }
+                return;
+            }
+        }
+
+        let hdr = self.is_hdr();
+        if !hdr && self.post.is_empty() && !self.is_accumulating() {
+            return;
+        }
+        self.invalidate();
+
+        // A copy, so the HDR buffer is left for resolving again
+        let mut color: Vec<Color> = match self.framebuffer.hdrbuffer {
+            Some(ref hdrbuffer) => hdrbuffer.to_vec(),
+            None => {
+                self.framebuffer.backbuffer
+                    .iter()
+                    .map(|&pixel| self.linearize(Color::from_argb8888(pixel)))
+                    .collect()
+            }
+        };
+        if let Some(ref mut accumulation) = self.framebuffer.accumulation {
+            self.framebuffer.accumulated_frames += 1;
+            let weight = 1.0 / self.framebuffer.accumulated_frames as f64;
+            for (sum, c) in accumulation.iter_mut().zip(color.iter_mut()) {
+                *sum = *sum + *c;
+                *c = *sum * weight
+            }
+        }
+        self.post.apply(&mut Frame {
+            width: self.framebuffer.width,
+            height: self.framebuffer.height,
+            color: &mut color,
+            depth: &self.framebuffer.depthbuffer,
+        });
+
+        for (i, &c) in color.iter().enumerate() {
+            let c = if hdr { self.tone_mapping.apply(c, self.exposure) } else { c };
+            self.framebuffer.backbuffer[i] = self.encode(c).to_argb8888()
+        }
+    }
+
+    pub fn draw_point(&mut self, point: Vector2) {
+        if point.x >= 0.0 && point.y >= 0.0 && point.x < self.width() as f64 &&
+           point.y < self.height() as f64 {
+            let color = self.linearize(Color::from_argb8888(0xffff2222));
+            self.put_pixel(point.x as u32, point.y as u32, color)
+        }
+    }
+
+    // Blends color over the pixel by coverage c. With a depth, the pixel is
+    // depth tested and the depth written like for triangles.
+    fn plot(&mut self, x: i32, y: i32, z: Option<f64>, color: Color, c: f64) {
+        if x >= 0 && y >= 0 && self.scissor_rect().contains(x as usize, y as usize) {
+            let offset = y as usize * self.framebuffer.width + x as usize;
+            if let Some(z) = z {
+                if self.framebuffer.depthbuffer.get(offset) > z {
+                    return;
+                }
+                self.framebuffer.depthbuffer.set(offset, z);
+            }
+            let dst = self.pixel(offset);
+            self.write_pixel(x as u32, y as u32, dst.lerp(color, c.max(0.0).min(1.0)))
+        }
+    }
+
+    pub fn draw_line(&mut self, p1: Vector2, p2: Vector2) {
+        let len = (p1 - p2).length().abs();
+
+        for i in 0..len as u32 {
+            self.draw_point(Vector2::lerp(p1, p2, i as f64 / len));
+        }
+    }
+
+    pub fn draw_line_aa(&mut self, p1: Vector3, p2: Vector3) {
+        self.draw_line_styled(p1, Color::white(), p2, Color::white(), &LineStyle::solid())
+    }
+
+    // Antialiased line with its color interpolated from c1 at p1 to c2 at p2.
+    pub fn draw_line_styled(&mut self,
+                            p1: Vector3,
+                            c1: Color,
+                            p2: Vector3,
+                            c2: Color,
+                            style: &LineStyle) {
+        let (p1, p2) = (self.scale_point(p1), self.scale_point(p2));
+        let style = self.scale_style(style);
+        self.line_styled(p1, c1, p2, c2, &style)
+    }
+
+    fn line_styled(&mut self, p1: Vector3, c1: Color, p2: Vector3, c2: Color, style: &LineStyle) {
+        let c1 = self.linearize(c1);
+        let c2 = self.linearize(c2);
+        if style.thickness <= 1.0 && style.dash.is_none() {
+            // Clipped with a margin for the antialiased edges, and the ends,
+            // which are rounded up. Wide lines only visit the pixels of their
+            // clipped bounds anyway.
+            let scissor = self.scissor_rect();
+            let min = Vector2::new(scissor.x as f64 - 2.0, scissor.y as f64 - 2.0);
+            let max = Vector2::new(scissor.right() as f64, scissor.bottom() as f64);
+            if let Some((t0, t1)) = clip::clip_line(p1.xy(), p2.xy(), min, max) {
+                self.line_aa(p1.lerp(p2, t0),
+                             p1.lerp(p2, t1),
+                             c1.lerp(c2, t0),
+                             c1.lerp(c2, t1),
+                             style.depth_test)
+            }
+        } else {
+            self.line_wide(p1, p2, c1, c2, style)
+        }
+    }
+
+    // Xiaolin Wu's antialiased line, in linear color.
+    fn line_aa(&mut self, p1: Vector3, p2: Vector3, c1: Color, c2: Color, depth_test: bool) {
+        let x0 = p1.x;
+        let x1 = p2.x;
+        let y0 = p1.y;
+        let y1 = p2.y;
+
+        let steep = {
+            (y1 - y0).abs() > (x1 - x0).abs()
+        };
+
+        let (x0, y0, x1, y1) = if steep {
+            (y0, x0, y1, x1)
+        } else {
+            (x0, y0, x1, y1)
+        };
+
+        let (x0, y0, x1, y1, c1, c2, z1, z2) = if x0 > x1 {
+            (x1, y1, x0, y0, c2, c1, p2.z, p1.z)
+        } else {
+            (x0, y0, x1, y1, c1, c2, p1.z, p2.z)
+        };
+
+        let dx = x1 - x0;
+        let dy = y1 - y0;
+
+        // Color and depth at x along the major axis
+        let at = |x: i32| {
+            let t = if dx > 0.0 { (x as f64 - x0) / dx } else { 0.0 };
+            let t = t.max(0.0).min(1.0);
+            let z = if depth_test { Some(z1 + (z2 - z1) * t) } else { None };
+            (c1.lerp(c2, t), z)
+        };
+
+        let slope = dy / dx;
+        let xend = round(x0);
+        let yend = y0 + slope * (xend - x0);
+        let xgap = rfpart(x0 + 0.5);
+        let xpxl1 = xend as i32;
+        let ypxl1 = yend as i32;
+
+        let (color, z) = at(xpxl1);
+        if steep {
+            self.plot(ypxl1, xpxl1, z, color, rfpart(yend) * xgap);
+            self.plot(ypxl1 + 1, xpxl1, z, color, fpart(yend) * xgap);
+        } else {
+            self.plot(xpxl1, ypxl1, z, color, rfpart(yend) * xgap);
+            self.plot(xpxl1, ypxl1 + 1, z, color, fpart(yend) * xgap);
+        }
+
+        let mut intery = yend + slope;
+
+        let xend = round(x1);
+        let yend = y1 + slope * (xend - x1);
+        let xgap = fpart(x1 + 0.5);
+        let xpxl2 = xend as i32;
+        let ypxl2 = yend as i32;
+
+        let (color, z) = at(xpxl2);
+        if steep {
+            self.plot(ypxl2, xpxl2, z, color, rfpart(yend) * xgap);
+            self.plot(ypxl2 + 1, xpxl2, z, color, fpart(yend) * xgap);
+        } else {
+            self.plot(xpxl2, ypxl2, z, color, rfpart(yend) * xgap);
+            self.plot(xpxl2, ypxl2 + 1, z, color, fpart(yend) * xgap);
+        }
+
+        if steep {
+            for x in (xpxl1 + 1)..xpxl2 {
+                let (color, z) = at(x);
+                self.plot(intery as i32, x, z, color, rfpart(intery));
+                self.plot(intery as i32 + 1, x, z, color, fpart(intery));
+                intery = intery + slope
+            }
+        } else {
+            for x in (xpxl1 + 1)..xpxl2 {
+                let (color, z) = at(x);
+                self.plot(x, intery as i32, z, color, rfpart(intery));
+                self.plot(x, intery as i32 + 1, z, color, fpart(intery));
+                intery = intery + slope
+            }
+        }
+
+    }
+
+    pub fn draw_circle(&mut self, center: Vector2, radius: f64, color: Color, style: &ShapeStyle) {
+        self.draw_ellipse(center, Vector2::new(radius, radius), color, style)
+    }
+
+    pub fn draw_ellipse(&mut self,
+                        center: Vector2,
+                        radii: Vector2,
+                        color: Color,
+                        style: &ShapeStyle) {
+        self.shape(center, radii, None, color, style)
+    }
+
+    // Draws the edges of a mesh's triangle, given in rendered pixels, pulled
+    // in front of its face by the wireframe's and the material's depth
+    // bias, and a little more the nearer they are.
+    fn edges(&mut self, vertices: &[Vector3; 3], material: &Material) {
+        let offset = self.wireframe_bias.offset(vertices) + material.depth_bias.offset(vertices);
+        let bias = |v: Vector3| Vector3::new(v.x, v.y, v.z * (1.0 + HIDDEN_LINE_BIAS) + offset);
+        let (v0, v1, v2) = (bias(vertices[0]), bias(vertices[1]), bias(vertices[2]));
+        self.edge(v0, v1);
+        self.edge(v1, v2);
+        self.edge(v2, v0);
+    }
+
+    // Draws the solid white edge of a mesh, given in rendered pixels.
+    fn edge(&mut self, p1: Vector3, p2: Vector3) {
+        let style = self.scale_style(&LineStyle::solid());
+        self.line_styled(p1, Color::white(), p2, Color::white(), &style)
+    }
+
+    // Part of the ellipse between the angles in radians, measured from +x
+    // towards +y, i.e. clockwise on screen. Filled arcs are pie slices.
+    pub fn draw_arc(&mut self,
+                    center: Vector2,
+                    radii: Vector2,
+                    start: f64,
+                    end: f64,
+                    color: Color,
+                    style: &ShapeStyle) {
+        self.shape(center, radii, Some((start, end)), color, style)
+    }
+
+    // Antialiased curve in screen space, split into lines until they stay
+    // within CURVE_TOLERANCE pixels of it. Not depth tested.
+    pub fn draw_curve(&mut self, curve: &Curve, color: Color) {
+        let style = LineStyle { depth_test: false, ..LineStyle::solid() };
+        let mut points = vec![curve.point(0.0)];
+        tessellate(curve, 0.0, 1.0, 0, &mut points);
+        for pair in points.windows(2) {
+            let p1 = Vector3::new(pair[0].x, pair[0].y, 0.0);
+            let p2 = Vector3::new(pair[1].x, pair[1].y, 0.0);
+            self.draw_line_styled(p1, color, p2, color, &style)
+        }
+    }
+
+    // Draws the sprites in screen space in order, each over the ones before,
+    // with the texture or plain and blended by alpha. Like the other shapes
+    // they ignore depth, so draw them after the 3D pass and before resolve.
+    pub fn draw_sprites(&mut self, sprites: &[Sprite], texture: Option<&Texture>) {
+        let ss = self.framebuffer.supersampling as f64;
+        let scissor = self.scissor_rect();
+        let screen = (Vector2::new(scissor.x as f64, scissor.y as f64),
+                      Vector2::new(scissor.right() as f64, scissor.bottom() as f64));
+        for sprite in sprites {
+            let color = self.linearize(sprite.color);
+            self.textured_rect(sprite.position * ss,
+                               (sprite.position + sprite.size) * ss,
+                               screen,
+                               None,
+                               texture.map(|t| (t, sprite.uv_min, sprite.uv_max)),
+                               color)
+        }
+    }
+
+    // Fills the polygon with the even-odd rule, so self intersecting outlines
+    // leave holes where they overlap. Pixels are covered when their corner
+    // is inside, like for triangles.
+    pub fn fill_polygon(&mut self, points: &[Vector2], color: Color) {
+        if points.len() < 3 {
+            return;
+        }
+        let color = self.linearize(color);
+        let points: Vec<Vector2> = points.iter()
+            .map(|&p| self.scale_point(Vector3::new(p.x, p.y, 0.0)).xy())
+            .collect();
+        let scissor = self.scissor_rect();
+        let min_y = points.iter().fold(f64::MAX, |min, p| min.min(p.y)).max(scissor.y as f64);
+        let max_y = points.iter()
+            .fold(f64::MIN, |max, p| max.max(p.y))
+            .min(scissor.bottom() as f64);
+
+        let mut crossings = Vec::new();
+        for y in min_y.ceil() as u32..max_y.ceil() as u32 {
+            let sy = y as f64;
+            crossings.clear();
+            for (i, &p0) in points.iter().enumerate() {
+                let p1 = points[(i + 1) % points.len()];
+                // Half open, so a vertex on the scanline counts once
+                if (p0.y <= sy) != (p1.y <= sy) {
+                    crossings.push(p0.x + (sy - p0.y) * (p1.x - p0.x) / (p1.y - p0.y));
+                }
+            }
+            crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            for span in crossings.chunks(2) {
+                if span.len() < 2 {
+                    continue;
+                }
+                let start = span[0].max(scissor.x as f64).ceil() as u32;
+                let end = span[1].min(scissor.right() as f64).ceil() as u32;
+                for x in start..end {
+                    self.write_pixel(x, y, color)
+                }
+            }
+        }
+    }
+
+    fn shape(&mut self,
+             center: Vector2,
+             radii: Vector2,
+             angles: Option<(f64, f64)>,
+             color: Color,
+             style: &ShapeStyle) {
+        let ss = self.framebuffer.supersampling as f64;
+        let center = self.scale_point(Vector3::new(center.x, center.y, 0.0)).xy();
+        let radii = radii * ss;
+        let style = &ShapeStyle { thickness: style.thickness * ss, ..*style };
+        let color = self.linearize(color);
+        let half = if style.filled { 0.0 } else { style.thickness.max(1.0) * 0.5 };
+        let extent = radii + Vector2::new(half + 1.0, half + 1.0);
+        let min = (center - extent).max(Vector2::zero());
+        let size = Vector2::new(self.framebuffer.width as f64, self.framebuffer.height as f64);
+        let max = (center + extent).min(size);
+        let tau = 2.0 * f64::consts::PI;
+
+        for y in min.y.ceil() as i32..max.y.ceil() as i32 {
+            for x in min.x.ceil() as i32..max.x.ceil() as i32 {
+                let p = Vector2::new(x as f64, y as f64) - center;
+
+                if let Some((start, end)) = angles {
+                    let angle = (p.y.atan2(p.x) - start).rem_euclid(tau);
+                    if angle > end - start {
+                        continue;
+                    }
+                }
+
+                // Distance to the ellipse, from its implicit function scaled
+                // by the length of its gradient
+                let k = ((p.x / radii.x).powi(2) + (p.y / radii.y).powi(2)).sqrt();
+                let gradient = Vector2::new(p.x / (radii.x * radii.x),
+                                            p.y / (radii.y * radii.y))
+                    .length();
+                let distance = if k > 0.0 && gradient > 0.0 {
+                    (k - 1.0) * k / gradient
+                } else {
+                    -radii.x.min(radii.y)
+                };
+
+                let distance = if style.filled { distance } else { distance.abs() - half };
+                let coverage = if style.antialias {
+                    0.5 - distance
+                } else if distance <= 0.0 {
+                    1.0
+                } else {
+                    0.0
+                };
+                if coverage > 0.0 {
+                    self.plot(x, y, None, color, coverage);
+                }
+            }
+        }
+    }
+
+    // Covers the pixels within half the thickness of the segment, fading
+    // out over the last pixel, and leaves the gaps of the dash pattern.
+    fn line_wide(&mut self, p1: Vector3, p2: Vector3, c1: Color, c2: Color, style: &LineStyle) {
+        let (a, b) = (p1.xy(), p2.xy());
+        let ab = b - a;
+        let length = ab.length();
+        let half = style.thickness.max(1.0) * 0.5;
+
+        let extent = Vector2::new(half + 1.0, half + 1.0);
+        let min = (a.min(b) - extent).max(Vector2::zero());
+        let size = Vector2::new(self.framebuffer.width as f64, self.framebuffer.height as f64);
+        let max = (a.max(b) + extent).min(size);
+
+        for y in min.y as i32..max.y as i32 {
+            for x in min.x as i32..max.x as i32 {
+                let p = Vector2::new(x as f64, y as f64);
+                let t = if length > 0.0 {
+                    ((p - a).dot(ab) / (length * length)).max(0.0).min(1.0)
+                } else {
+                    0.0
+                };
+                if let Some((on, off)) = style.dash {
+                    if (t * length) % (on + off) >= on {
+                        continue;
+                    }
+                }
+                let distance = (p - (a + ab * t)).length();
+                let coverage = half + 0.5 - distance;
+                if coverage > 0.0 {
+                    let z = if style.depth_test { Some(p1.z + (p2.z - p1.z) * t) } else { None };
+                    self.plot(x, y, z, c1.lerp(c2, t), coverage);
+                }
+            }
+        }
+    }
+
+    // Whether pixels of the material blend instead of writing depth, which
+    // the G-buffer has no room for.
+    fn is_translucent(&self, material: &Material) -> bool {
+        material.translucent && (!self.is_deferred() || self.debug_view.shows_attributes())
+    }
+
+    fn render_pixel(&mut self,
+                    x: u32,
+                    y: u32,
+                    w: Vector3,
+                    varyings: &[Varying; 3],
+                    material: &Material) {
+        let color = self.shade(w, varyings, material);
+        let offset = y as usize * self.framebuffer.width + x as usize;
+        self.mark_outline(offset, material);
+        if self.is_deferred() && !self.debug_view.shows_attributes() {
+            self.framebuffer.overdraw[offset] += 1;
+            self.write_gbuffer(offset, color, w, varyings, material);
+            return;
+        }
+        if material.translucent {
+            let dst = self.pixel(offset);
+            self.write_pixel(x, y, dst.lerp(color, color.a.max(0.0).min(1.0)));
+            return;
+        }
+        self.write_pixel(x, y, color)
+    }
+
+    fn write_gbuffer(&mut self,
+                     offset: usize,
+                     albedo: Color,
+                     w: Vector3,
+                     varyings: &[Varying; 3],
+                     material: &Material) {
+        let varying = interpolate(w, varyings);
+        let normal = surface_normal(&varying, material);
+        if let Some(ref mut gbuffer) = self.framebuffer.gbuffer {
+            gbuffer.write(offset,
+                          albedo,
+                          normal,
+                          varying.position,
+                          material.surface(varying.uv))
+        }
+    }
+
+    // Albedo lit by the device's lights as the surface is shaded, or as it
+    // is without any, in which case it counts as ambient. The surface's
+    // occlusion scales the ambient light. View is the direction from the eye.
+    fn light(&self,
+             albedo: Color,
+             position: Vector3,
+             normal: Vector3,
+             view: Vector3,
+             surface: &Surface)
+             -> Color {
+        let occlusion = surface.occlusion;
+        if self.lights.is_empty() {
+            return Color::new(albedo.r * occlusion,
+                              albedo.g * occlusion,
+                              albedo.b * occlusion,
+                              albedo.a);
+        }
+        let (ambient, lights) = (self.ambient * occlusion, &self.lights);
+        match surface.shading {
+            Shading::Basic => light::illuminate(albedo, position, normal, ambient, lights),
+            Shading::Pbr(_) => {
+                light::illuminate_pbr(albedo, surface, position, normal, view, ambient, lights)
+            }
+            Shading::Toon { bands } => {
+                light::illuminate_toon(albedo, position, normal, ambient, lights, bands)
+            }
+        }
+    }
+
+    fn mark_outline(&mut self, offset: usize, material: &Material) {
+        if material.outline.is_none() && self.outlines.is_empty() {
+            return;
+        }
+        let size = self.framebuffer.len();
+        if self.outlines.len() != size {
+            self.outlines = vec![None; size];
+        }
+        self.outlines[offset] = material.outline
+    }
+
+    // Draws the outlines of the pixels marked since the last time over the
+    // pixels around them that are further away or empty, taking the depth of
+    // the outlined pixel.
+    fn outline_pass(&mut self) {
+        if self.outlines.is_empty() {
+            return;
+        }
+        let outlines = &self.outlines;
+        let scale = self.framebuffer.supersampling as f64;
+        let reach = outlines.iter()
+            .filter_map(|o| o.map(|o| (o.width * scale).ceil() as i64))
+            .max()
+            .unwrap_or(0);
+        let (width, height) = (self.framebuffer.width as i64, self.framebuffer.height as i64);
+        let mut painted = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let depth = self.framebuffer.depthbuffer.get((y * width + x) as usize);
+                let mut nearest: Option<(f64, Color)> = None;
+                for sy in (y - reach).max(0)..(y + reach + 1).min(height) {
+                    for sx in (x - reach).max(0)..(x + reach + 1).min(width) {
+                        let o = (sy * width + sx) as usize;
+                        let outline = match outlines[o] {
+                            Some(outline) => outline,
+                            None => continue,
+                        };
+                        let (dx, dy) = ((sx - x) as f64, (sy - y) as f64);
+                        let radius = outline.width * scale;
+                        let d = self.framebuffer.depthbuffer.get(o);
+                        if dx * dx + dy * dy <= radius * radius &&
+                           d > depth * (1.0 + OUTLINE_DEPTH_RATIO) &&
+                           d > nearest.map_or(0.0, |(n, _)| n) {
+                            nearest = Some((d, outline.color));
+                        }
+                    }
+                }
+                if let Some((d, color)) = nearest {
+                    painted.push(((y * width + x) as usize, d, color));
+                }
+            }
+        }
+        self.outlines.clear();
+        let scissor = self.scissor_rect();
+        for (offset, depth, color) in painted {
+            if !scissor.contains(offset % self.framebuffer.width, offset / self.framebuffer.width) {
+                continue;
+            }
+            self.framebuffer.depthbuffer.set(offset, depth);
+            for i in self.samples(offset) {
+                self.framebuffer.sample_depth.set(i, depth)
+            }
+            let color = self.linearize(color);
+            self.framebuffer.overdraw[offset] += 1;
+            self.store_pixel(offset, color)
+        }
+    }
+
+    // The color mixed with the environment mirrored about the normal, seen
+    // along the view direction.
+    fn apply_reflection(&self,
+                        color: Color,
+                        view: Vector3,
+                        normal: Vector3,
+                        reflectivity: f64)
+                        -> Color {
+        let environment = match self.environment {
+            Some(ref environment) if reflectivity > 0.0 => environment,
+            _ => return color,
+        };
+        if view.length_sqr() == 0.0 || normal.length_sqr() == 0.0 {
+            return color;
+        }
+        let (view, normal) = (view.normalize(), normal.normalize());
+        // Towards the eye, whichever way the faces are wound
+        let normal = if normal.dot(view) > 0.0 { normal * -1.0 } else { normal };
+        let c = color.lerp(environment.sample(view.reflect(normal)), reflectivity);
+        Color::new(c.r, c.g, c.b, color.a)
+    }
+
+    fn apply_fog(&self, color: Color, depth: f64) -> Color {
+        match self.fog {
+            Some(fog) => fog.apply(color, self.linearize(fog.color), depth),
+            None => color,
+        }
+    }
+
+    // Lights the pixels written to the G-buffer since the last time, seen by
+    // the camera through the viewport.
+    fn light_pass(&mut self, camera: &Camera, viewport: &Viewport) {
+        let mut gbuffer = match self.framebuffer.gbuffer.take() {
+            Some(gbuffer) => gbuffer,
+            None => return,
+        };
+        let view_mat = camera.view_matrix();
+        let occlusion = self.ssao.map(|ssao| {
+            let view_projection = camera.view_matrix() *
+                                  camera.projection_matrix(viewport.aspect());
+            // Where the vertices were projected to
+            let shift = Vector3::new(self.sample_shift(), self.sample_shift(), 0.0) +
+                        self.jitter();
+            let viewport = Viewport {
+                x: viewport.x + shift.x,
+                y: viewport.y + shift.y,
+                ..*viewport
+            };
+            ssao.occlusion(&gbuffer,
+                           &self.framebuffer.depthbuffer,
+                           camera.position,
+                           &view_projection,
+                           &viewport)
+        });
+        for offset in 0..gbuffer.covered().len() {
+            if gbuffer.covered()[offset] {
+                let position = gbuffer.position()[offset];
+                let normal = gbuffer.normal()[offset];
+                let view = camera.view_direction(position);
+                let surface = gbuffer.surface()[offset];
+                let surface = Surface {
+                    occlusion: surface.occlusion * occlusion.as_ref().map_or(1.0, |o| o[offset]),
+                    ..surface
+                };
+                let color = self.light(gbuffer.albedo()[offset], position, normal, view, &surface);
+                let color = self.apply_reflection(color, view, normal, surface.reflectivity);
+                let depth = Vector3::transform_coordinate(&position, &view_mat).z.abs();
+                let color = self.apply_fog(color, depth);
+                self.store_pixel(offset, color)
+            }
+        }
+        gbuffer.clear();
+        self.framebuffer.gbuffer = Some(gbuffer);
+    }
+
+    fn shade(&mut self, w: Vector3, varyings: &[Varying; 3], material: &Material) -> Color {
+        let color = match self.debug_view {
+            DebugView::Normals | DebugView::Uv | DebugView::Checker => {
+                let varying = interpolate(w, varyings);
+                let uv = varying.uv;
+                match self.debug_view {
+                    DebugView::Normals => {
+                        let n = surface_normal(&varying, material) * 0.5 + Vector3::one() * 0.5;
+                        self.linearize(Color::rgb(n.x, n.y, n.z))
+                    }
+                    DebugView::Uv => self.linearize(Color::rgb(fpart(uv.x), fpart(uv.y), 0.0)),
+                    _ => {
+                        let squares = 8.0;
+                        let odd = ((uv.x * squares).floor() + (uv.y * squares).floor()) % 2.0;
+                        let v = if odd.abs() < 0.5 { 0.8 } else { 0.2 };
+                        self.linearize(Color::gray(v))
+                    }
+                }
+            }
+            _ => {
+                let varying = interpolate(w, varyings);
+                let color = match material.texture.as_ref().or(self.texture.as_ref()) {
+                    // Unlit, so a screen shows the texture as it is
+                    Some(texture) => texture.sample(varying.uv),
+                    None => {
+                        let a = self.linearize(Color::gray(0.75));
+                        let b = self.linearize(Color::gray(0.5));
+                        let c = self.linearize(Color::gray(0.0));
+                        a * w.x + b * w.y + c * w.z
+                    }
+                };
+                let color = color * material.color * varying.color;
+                let color = match self.shadow {
+                    Some(ref shadow) => {
+                        let k = shadow.attenuation(varying.position);
+                        Color::new(color.r * k, color.g * k, color.b * k, color.a)
+                    }
+                    None => color,
+                };
+                // Deferred shading lights the albedo later
+                if self.is_deferred() {
+                    color
+                } else {
+                    let normal = surface_normal(&varying, material);
+                    let surface = material.surface(varying.uv);
+                    let color = self.light(color, varying.position, normal, varying.view, &surface);
+                    let color = self.apply_reflection(color,
+                                                      varying.view,
+                                                      normal,
+                                                      surface.reflectivity);
+                    self.apply_fog(color, varying.depth)
+                }
+            }
+        };
+
+        self.stats.pixels_shaded += 1;
+        color
+    }
+
+    pub fn draw_triangle(&mut self, v0: Vector3, v1: Vector3, v2: Vector3) {
+        let viewport = self.scale_viewport(&self.viewport());
+        let (v0, v1, v2) = (self.scale_point(v0), self.scale_point(v1), self.scale_point(v2));
+        let varyings = [Varying::flat(); 3];
+        self.stats.triangles_submitted += 1;
+        let material = Material::new();
+        match self.precision {
+            Precision::Double => {
+                self.rasterize::<RasterF64>(&[v0, v1, v2], &varyings, &material, &viewport, true)
+            }
+            Precision::Single => {
+                let screen = [Vector3f::from(v0), Vector3f::from(v1), Vector3f::from(v2)];
+                self.rasterize::<RasterF32>(&screen, &varyings, &material, &viewport, true)
+            }
+        }
+    }
+
+    // Draws the part of the triangle inside the viewport. Without
+    // color_write only the depth buffer is updated. Translucent materials
+    // are depth tested without writing depth, unless shading is deferred.
+    fn rasterize<R: Raster>(&mut self,
+                            screen: &[R::Screen; 3],
+                            varyings: &[Varying; 3],
+                            material: &Material,
+                            viewport: &Viewport,
+                            color_write: bool) {
+        let vertices = [R::position(screen[0]), R::position(screen[1]), R::position(screen[2])];
+        // Moved as a whole, so its slope stays
+        let bias = material.depth_bias.offset(&vertices);
+        let (v0, v1, v2) = (vertices[0], vertices[1], vertices[2]);
+        let (clip_min, clip_max) = self.clip_rect(viewport);
+        let bounds_max = v0.max(v1).max(v2).xy();
+        let bounds_min = v0.min(v1).min(v2).xy();
+        // Samples reach up to half a pixel from the pixel
+        let pad = if self.framebuffer.msaa == Msaa::Off { 0.0 } else { 0.5 };
+        let max = (bounds_max + Vector2::new(pad, pad)).min(clip_max);
+        let min = (bounds_min - Vector2::new(pad, pad)).max(clip_min);
+
+        if min.x >= max.x || min.y >= max.y {
+            self.stats.triangles_culled += 1;
+            return;
+        }
+        if bounds_min.x < clip_min.x || bounds_min.y < clip_min.y ||
+           bounds_max.x > clip_max.x || bounds_max.y > clip_max.y {
+            self.stats.triangles_clipped += 1;
+        }
+
+        let edges = R::edges(screen, bias);
+        // Rounded to a line, e.g. at the pole of a sphere
+        if edges.is_degenerate() {
+            self.stats.triangles_culled += 1;
+            return;
+        }
+        self.mark_dirty(min.x as usize..max.x as usize + 1, min.y as usize..max.y as usize + 1);
+        if self.framebuffer.msaa != Msaa::Off {
+            let end_x = (max.x.floor() + 1.0).min(clip_max.x);
+            let end_y = (max.y.floor() + 1.0).min(clip_max.y);
+            for y in min.y.ceil() as u32..end_y as u32 {
+                for x in min.x.ceil() as u32..end_x as u32 {
+                    self.rasterize_samples(x, y, &edges, varyings, material, color_write)
+                }
+            }
+            return;
+        }
+
+        // A block at a time, each tested against the hierarchical depth
+        // before its pixels are
+        let (x0, x1) = (min.x as u32, max.x as u32);
+        let (y0, y1) = (min.y as u32, max.y as u32);
+        let block = BLOCK_SIZE as u32;
+        let nearest = v0.z.max(v1.z).max(v2.z) + bias;
+        let translucent = self.is_translucent(material) && color_write;
+        // Testing costs more than it saves for triangles smaller than a block
+        let hierarchical_z = self.hierarchical_z &&
+                             ((x1 - x0) * (y1 - y0)) as usize >= BLOCK_SIZE * BLOCK_SIZE;
+        for by in y0 / block..y1.div_ceil(block) {
+            for bx in x0 / block..x1.div_ceil(block) {
+                let (bx0, bx1) = ((bx * block).max(x0), ((bx + 1) * block).min(x1));
+                let (by0, by1) = ((by * block).max(y0), ((by + 1) * block).min(y1));
+                let (column, row) = (bx as usize, by as usize);
+                if hierarchical_z {
+                    // Depth is linear over the screen, so nearest at a corner
+                    let corners = [(bx0, by0), (bx1 - 1, by0), (bx0, by1 - 1), (bx1 - 1, by1 - 1)];
+                    let z = corners.iter()
+                        .map(|&(x, y)| edges.weights(x as f64, y as f64).1)
+                        .fold(f64::MIN, f64::max)
+                        .min(nearest);
+                    let farthest = self.framebuffer
+                        .hiz
+                        .farthest(column, row, &self.framebuffer.depthbuffer);
+                    if farthest >= z + HIZ_TOLERANCE {
+                        self.stats.blocks_rejected += 1;
+                        continue;
+                    }
+                }
+
+                let mut written = false;
+                for y in by0..by1 {
+                    for x in bx0..bx1 {
+
+                        if let Some((w, z)) = edges.inside(x as f64, y as f64) {
+
+                            let offset = y as usize * self.framebuffer.width + x as usize;
+                            if translucent {
+                                if self.framebuffer.depthbuffer.test(offset, z) {
+                                    self.render_pixel(x, y, w, varyings, material)
+                                }
+                            } else if self.framebuffer.depthbuffer.test_and_set(offset, z) {
+                                written = true;
+                                if color_write {
+                                    self.render_pixel(x, y, w, varyings, material)
+                                }
+                            }
+
+                        }
+
+                    }
+                }
+                if written {
+                    self.framebuffer.hiz.mark(column, row)
+                }
+            }
+        }
+
+    }
+
+    // Coverage and depth test per sample, shading once for all the samples
+    // that pass, with the attributes at the pixel.
+    fn rasterize_samples<E: Edges>(&mut self,
+                                   x: u32,
+                                   y: u32,
+                                   edges: &E,
+                                   varyings: &[Varying; 3],
+                                   material: &Material,
+                                   color_write: bool) {
+        let offset = y as usize * self.framebuffer.width + x as usize;
+        let samples = self.samples(offset);
+        let translucent = self.is_translucent(material) && color_write;
+        let mut passed = [false; 4];
+        let mut any = false;
+        for (s, &(ox, oy)) in self.framebuffer.msaa.offsets().iter().enumerate() {
+            if let Some((_, z)) = edges.inside(x as f64 + ox, y as f64 + oy) {
+                let i = samples.start + s;
+                let pass = if translucent {
+                    self.framebuffer.sample_depth.test(i, z)
+                } else {
+                    self.framebuffer.sample_depth.test_and_set(i, z)
+                };
+                if pass {
+                    passed[s] = true;
+                    any = true;
+                }
+            }
+        }
+        if !any {
+            return;
+        }
+
+        // The nearest sample, for lines and the depth views
+        if !translucent {
+            let nearest = samples.clone()
+                .map(|i| self.framebuffer.sample_depth.get(i))
+                .fold(0.0, f64::max);
+            self.framebuffer.depthbuffer.set(offset, nearest);
+        }
+        if color_write {
+            let (w, _) = edges.weights(x as f64, y as f64);
+            let color = self.shade(w, varyings, material);
+            self.mark_outline(offset, material);
+            if self.is_deferred() && !self.debug_view.shows_attributes() {
+                self.write_gbuffer(offset, color, w, varyings, material);
+            }
+            self.framebuffer.overdraw[offset] += 1;
+            let alpha = color.a.max(0.0).min(1.0);
+            for (i, &hit) in samples.zip(passed.iter()) {
+                if hit && translucent {
+                    let behind = self.framebuffer.sample_color[i];
+                    self.framebuffer.sample_color[i] = behind.lerp(color, alpha)
+                } else if hit {
+                    self.framebuffer.sample_color[i] = color
+                }
+            }
+        }
+    }
+
+    pub fn render(&mut self, camera: &Camera, viewport: &Viewport, meshes: &[&Mesh]) {
+        let viewport = self.scale_viewport(viewport);
+        self.render_instances(camera, &viewport, Draws::Meshes(meshes))
+    }
+
+    // Draws the mesh once for each instance, all sharing its vertices.
+    pub fn render_instanced(&mut self,
+                            camera: &Camera,
+                            viewport: &Viewport,
+                            mesh: &Mesh,
+                            instances: &[Instance]) {
+        let viewport = self.scale_viewport(viewport);
+        self.render_instances(camera, &viewport, Draws::Instanced(mesh, instances))
+    }
+
+    // Draws the level of each Lod suiting its size from the camera.
+    pub fn render_lods(&mut self, camera: &Camera, viewport: &Viewport, lods: &[&Lod]) {
+        let viewport = self.scale_viewport(viewport);
+        self.render_instances(camera, &viewport, Draws::Lods(lods, camera))
+    }
+
+    // Sorts the queue for the camera and draws it in that order, see
+    // RenderQueue. The draws are kept for the next frame.
+    pub fn render_queue(&mut self, camera: &Camera, viewport: &Viewport, queue: &mut RenderQueue) {
+        let viewport = self.scale_viewport(viewport);
+        queue.sort(camera);
+        self.render_instances(camera, &viewport, Draws::Paired(queue.draws()))
+    }
+
+    // Draws the queued debug lines on top of what has been rendered, e.g.
+    // after the main pass and before resolve.
+    pub fn render_debug(&mut self, camera: &Camera, viewport: &Viewport, debug: &DebugDraw) {
+        let view_projection = camera.view_matrix() * camera.projection_matrix(viewport.aspect());
+        for line in debug.lines() {
+            let from = Vector3::transform(&line.from, &view_projection);
+            let to = Vector3::transform(&line.to, &view_projection);
+            // Lines reaching behind the camera would project inside out
+            let (t0, t1) = match clip::clip_line_near(from, to) {
+                Some(t) => t,
+                None => continue,
+            };
+            let (from, to) = (from.lerp(to, t0), from.lerp(to, t1));
+            let jitter = self.jitter() * (1.0 / self.framebuffer.supersampling as f64);
+            let from = viewport.project(from.xyz() / from.w) + jitter;
+            let to = viewport.project(to.xyz() / to.w) + jitter;
+            self.draw_line_styled(from, line.color, to, line.color, &debug.style);
+        }
+    }
+
+    // Renders the scene the number of frames, cleared and resolved like
+    // any other, timing them all, so changes to the pipeline can be
+    // measured, see benches/render.rs. Clears the stats.
+    pub fn render_benchmark(&mut self, camera: &Camera, scene: &Scene, frames: usize) -> Benchmark {
+        let viewport = self.viewport();
+        self.reset_stats();
+        let start = Instant::now();
+        for _ in 0..frames {
+            self.clear(Color::black());
+            self.render_scene(camera, &viewport, scene);
+            self.resolve();
+        }
+        let elapsed = start.elapsed();
+        let benchmark = Benchmark {
+            frames: frames,
+            seconds: elapsed.subsec_nanos() as f64 * 1e-9 + elapsed.as_secs() as f64,
+            render: self.stats,
+        };
+        self.reset_stats();
+        benchmark
+    }
+
+    pub fn render_scene(&mut self, camera: &Camera, viewport: &Viewport, scene: &Scene) {
+        let viewport = self.scale_viewport(viewport);
+        let fog = self.fog;
+        if scene.fog.is_some() {
+            self.fog = scene.fog;
+        }
+        let nodes = self.scene_nodes(scene);
+        self.render_instances(camera, &viewport, Draws::Nodes(scene, &nodes));
+        self.arena.recycle(nodes);
+        self.fog = fog;
+    }
+
+    // Fills the background inside the viewport, where nothing has been drawn
+    // at any depth, with the sky seen in each pixel's direction. Goes after
+    // the meshes and before lines that are not depth tested.
+    pub fn render_skybox(&mut self, camera: &Camera, viewport: &Viewport, sky: &CubeMap) {
+        let viewport = self.scale_viewport(viewport);
+        let (min, max) = self.clip_rect(&viewport);
+        let view_projection = camera.view_matrix() * camera.projection_matrix(viewport.aspect());
+        let inverse = view_projection.inverse().unwrap_or_else(Matrix4::identity);
+        // Where the vertices of meshes would have been projected to
+        let shift = Vector3::new(self.sample_shift(), self.sample_shift(), 0.0) + self.jitter();
+        let direction = |x: f64, y: f64| {
+            let ndc = viewport.unproject(Vector3::new(x - shift.x, y - shift.y, 0.0));
+            let near = Vector3::transform_coordinate(&Vector3::new(ndc.x, ndc.y, 1.0), &inverse);
+            let far = Vector3::transform_coordinate(&Vector3::new(ndc.x, ndc.y, 0.0), &inverse);
+            far - near
+        };
+
+        self.mark_dirty(min.x as usize..max.x as usize, min.y as usize..max.y as usize);
+        let offsets = self.framebuffer.msaa.offsets();
+        for y in min.y as usize..max.y as usize {
+            for x in min.x as usize..max.x as usize {
+                let offset = y * self.framebuffer.width + x;
+                if self.framebuffer.msaa == Msaa::Off {
+                    if self.framebuffer.depthbuffer.get(offset) == 0.0 {
+                        let color = sky.sample(direction(x as f64, y as f64));
+                        self.store_pixel(offset, color)
+                    }
+                    continue;
+                }
+                // Background samples of pixels on the edges of meshes too
+                for (i, &(ox, oy)) in self.samples(offset).zip(offsets.iter()) {
+                    if self.framebuffer.sample_depth.get(i) == 0.0 {
+                        let d = direction(x as f64 + ox, y as f64 + oy);
+                        self.framebuffer.sample_color[i] = sky.sample(d)
+                    }
+                }
+            }
+        }
+    }
+
+    // Draws the surface of a signed distance field instead of triangles, by
+    // marching a ray through each pixel inside the viewport from the near
+    // to the far plane. It is depth tested against what is already drawn
+    // and lit like a mesh, with normals from the field and the material's
+    // color. Its edges are not multisampled.
+    pub fn render_sdf<F>(&mut self,
+                         camera: &Camera,
+                         viewport: &Viewport,
+                         sdf: F,
+                         material: &Material)
+        where F: Fn(Vector3) -> f64
+    {
+        let viewport = self.scale_viewport(viewport);
+        let (min, max) = self.clip_rect(&viewport);
+        let view_mat = camera.view_matrix();
+        let view_projection = view_mat * camera.projection_matrix(viewport.aspect());
+        let inverse = view_projection.inverse().unwrap_or_else(Matrix4::identity);
+        // Where the vertices of meshes would have been projected to
+        let shift = Vector3::new(self.sample_shift(), self.sample_shift(), 0.0) + self.jitter();
+        // From the near plane, and how far it is to the far one
+        let pixel_ray = |x: f64, y: f64| {
+            let ndc = viewport.unproject(Vector3::new(x - shift.x, y - shift.y, 0.0));
+            let near = Vector3::transform_coordinate(&Vector3::new(ndc.x, ndc.y, 1.0), &inverse);
+            let far = Vector3::transform_coordinate(&Vector3::new(ndc.x, ndc.y, 0.0), &inverse);
+            (Ray::between(near, far), (far - near).length())
+        };
+        let albedo = self.linearize(material.color);
+        let surface = material.surface(Vector2::zero());
+
+        for y in min.y as u32..max.y as u32 {
+            for x in min.x as u32..max.x as u32 {
+                let (ray, length) = pixel_ray(x as f64, y as f64);
+                let position = match raymarch::march(&sdf, &ray, length) {
+                    Some(t) => ray.point_at(t),
+                    None => continue,
+                };
+                let clip = Vector3::transform(&position, &view_projection);
+                let z = viewport.project(clip.xyz() / clip.w).z;
+                let offset = y as usize * self.framebuffer.width + x as usize;
+                if !self.framebuffer.depthbuffer.test_and_set(offset, z) {
+                    continue;
+                }
+                for i in self.samples(offset) {
+                    self.framebuffer.sample_depth.set(i, z)
+                }
+
+                let normal = raymarch::normal(&sdf, position);
+                let color = if self.debug_view == DebugView::Normals {
+                    let n = normal * 0.5 + Vector3::one() * 0.5;
+                    self.linearize(Color::rgb(n.x, n.y, n.z))
+                } else {
+                    let color = match self.shadow {
+                        Some(ref shadow) => {
+                            let k = shadow.attenuation(position);
+                            Color::new(albedo.r * k, albedo.g * k, albedo.b * k, albedo.a)
+                        }
+                        None => albedo,
+                    };
+                    if self.is_deferred() {
+                        color
+                    } else {
+                        let view = camera.view_direction(position);
+                        let color = self.light(color, position, normal, view, &surface);
+                        let color = self.apply_reflection(color,
+                                                          view,
+                                                          normal,
+                                                          surface.reflectivity);
+                        let depth = Vector3::transform_coordinate(&position, &view_mat).z.abs();
+                        self.apply_fog(color, depth)
+                    }
+                };
+                self.stats.pixels_shaded += 1;
+                self.mark_outline(offset, material);
+                if self.is_deferred() && !self.debug_view.shows_attributes() {
+                    self.framebuffer.overdraw[offset] += 1;
+                    if let Some(ref mut gbuffer) = self.framebuffer.gbuffer {
+                        gbuffer.write(offset, color, normal, position, surface)
+                    }
+                } else {
+                    self.write_pixel(x, y, color)
+                }
+            }
+        }
+        self.light_pass(camera, &viewport);
+        self.outline_pass();
+    }
+
+    // Draws each point of the clouds as a square of its color, point_size
+    // presented pixels wide at any distance, depth tested against what is
+    // already drawn and hiding what comes after it. Points are not lit, only
+    // fogged, and their edges are not multisampled.
+    pub fn render_points(&mut self, camera: &Camera, viewport: &Viewport, clouds: &[&PointCloud]) {
+        let viewport = self.scale_viewport(viewport);
+        let (min, max) = self.clip_rect(&viewport);
+        let view_mat = camera.view_matrix();
+        let projection = camera.projection_matrix(viewport.aspect());
+        let shift = Vector3::new(self.sample_shift(), self.sample_shift(), 0.0) + self.jitter();
+
+        for cloud in clouds {
+            let world_view = cloud.transform.matrix() * view_mat;
+            let world_view_projection = world_view * projection;
+            let size = (cloud.point_size * self.framebuffer.supersampling as f64).max(1.0);
+            for (position, &color) in cloud.positions.iter().zip(cloud.colors.iter()) {
+                let clip = Vector3::transform(position, &world_view_projection);
+                if clip.w <= 0.0 {
+                    continue;
+                }
+                let ndc = clip.xyz() / clip.w;
+                if ndc.z < 0.0 || ndc.z > 1.0 {
+                    continue;
+                }
+                let screen = viewport.project(ndc) + shift;
+                let depth = Vector3::transform_coordinate(position, &world_view).z.abs();
+                let color = self.apply_fog(self.linearize(color), depth);
+                self.splat(screen, size, (min, max), color)
+            }
+        }
+    }
+
+    // Draws a particle system over what has been rendered, its particles
+    // sorted back to front and blended by their alpha. They are depth tested
+    // but only opaque ones write their depth. Not lit, only fogged.
+    pub fn render_particles(&mut self,
+                            camera: &Camera,
+                            viewport: &Viewport,
+                            system: &ParticleSystem) {
+        let viewport = self.scale_viewport(viewport);
+        let clip_rect = self.clip_rect(&viewport);
+        let view_mat = camera.view_matrix();
+        let projection = camera.projection_matrix(viewport.aspect());
+        let shift = Vector3::new(self.sample_shift(), self.sample_shift(), 0.0) + self.jitter();
+        let ss = self.framebuffer.supersampling as f64;
+
+        let mut particles: Vec<(Vector3, &Particle)> = system.particles()
+            .iter()
+            .map(|p| (Vector3::transform_coordinate(&p.position, &view_mat), p))
+            .collect();
+        particles.sort_by(|a, b| b.0.z.abs().partial_cmp(&a.0.z.abs()).unwrap());
+        for (view_position, particle) in particles {
+            let clip = Vector3::transform(&view_position, &projection);
+            if clip.w <= 0.0 {
+                continue;
+            }
+            let ndc = clip.xyz() / clip.w;
+            if ndc.z < 0.0 || ndc.z > 1.0 {
+                continue;
+            }
+            let center = viewport.project(ndc);
+            let size = match system.style {
+                ParticleStyle::Points(size) => size * ss,
+                ParticleStyle::Quads => {
+                    // As wide on screen as the particle across at its depth
+                    let side = view_position + Vector3::new(particle.size, 0.0, 0.0);
+                    let side = Vector3::transform(&side, &projection);
+                    (viewport.project(side.xyz() / side.w).x - center.x).abs()
+                }
+            };
+            let color = self.linearize(particle.color());
+            let color = self.apply_fog(color, view_position.z.abs());
+            self.splat(center + shift, size.max(1.0), clip_rect, color)
+        }
+    }
+
+    // Draws the billboards over what has been rendered, facing the camera
+    // and sorted back to front, with the texture or plain. Like particles
+    // they are depth tested, blended by alpha, and write their depth where
+    // opaque. Not lit, only fogged.
+    pub fn render_billboards(&mut self,
+                             camera: &Camera,
+                             viewport: &Viewport,
+                             billboards: &[Billboard],
+                             texture: Option<&Texture>) {
+        let viewport = self.scale_viewport(viewport);
+        let clip_rect = self.clip_rect(&viewport);
+        let view_mat = camera.view_matrix();
+        let projection = camera.projection_matrix(viewport.aspect());
+        let shift = Vector3::new(self.sample_shift(), self.sample_shift(), 0.0) + self.jitter();
+        let project = |p: Vector3| {
+            let clip = Vector3::transform(&p, &projection);
+            viewport.project(clip.xyz() / clip.w)
+        };
+
+        let mut billboards: Vec<(Vector3, &Billboard)> = billboards.iter()
+            .map(|b| (Vector3::transform_coordinate(&b.position, &view_mat), b))
+            .collect();
+        billboards.sort_by(|a, b| b.0.z.abs().partial_cmp(&a.0.z.abs()).unwrap());
+        for (view_position, billboard) in billboards {
+            if Vector3::transform(&view_position, &projection).w <= 0.0 {
+                continue;
+            }
+            let center = project(view_position);
+            if center.z < viewport.min_depth || center.z > viewport.max_depth {
+                continue;
+            }
+            let half = Vector3::new(billboard.size.x * 0.5, billboard.size.y * 0.5, 0.0);
+            let corner = project(view_position + half);
+            let extent = Vector2::new((corner.x - center.x).abs(), (corner.y - center.y).abs());
+            // Whole coordinates sample the middle of pixels
+            let z = center.z;
+            let center = (center + shift).xy() + Vector2::new(0.5, 0.5);
+            let color = self.linearize(billboard.color);
+            let color = self.apply_fog(color, view_position.z.abs());
+            self.textured_rect(center - extent,
+                               center + extent,
+                               clip_rect,
+                               Some(z),
+                               texture.map(|t| (t, billboard.uv_min, billboard.uv_max)),
+                               color)
+        }
+    }
+
+    // Draws the texture region stretched over the rectangle from min to max,
+    // pixel x spanning x to x + 1, within the clip rectangle. Texels are
+    // multiplied by the color and blended by their alpha. With a depth,
+    // pixels are depth tested and opaque ones write it.
+    fn textured_rect(&mut self,
+                     min: Vector2,
+                     max: Vector2,
+                     clip_rect: (Vector2, Vector2),
+                     z: Option<f64>,
+                     texture: Option<(&Texture, Vector2, Vector2)>,
+                     color: Color) {
+        let size = max - min;
+        if size.x <= 0.0 || size.y <= 0.0 {
+            return;
+        }
+        // Pixels with their middle inside
+        let start = (min - Vector2::new(0.5, 0.5)).max(clip_rect.0);
+        let end = (max - Vector2::new(0.5, 0.5)).min(clip_rect.1);
+        for y in start.y.ceil() as u32..end.y.ceil() as u32 {
+            for x in start.x.ceil() as u32..end.x.ceil() as u32 {
+                let offset = y as usize * self.framebuffer.width + x as usize;
+                if let Some(z) = z {
+                    if self.framebuffer.depthbuffer.get(offset) >= z {
+                        continue;
+                    }
+                }
+                let texel = match texture {
+                    Some((texture, uv_min, uv_max)) => {
+                        let t = Vector2::new((x as f64 + 0.5 - min.x) / size.x,
+                                             (y as f64 + 0.5 - min.y) / size.y);
+                        let uv = uv_min + Vector2::new((uv_max.x - uv_min.x) * t.x,
+                                                       (uv_max.y - uv_min.y) * t.y);
+                        texture.sample(uv)
+                    }
+                    None => Color::white(),
+                };
+                let c = Color::new(texel.r * color.r,
+                                   texel.g * color.g,
+                                   texel.b * color.b,
+                                   texel.a * color.a);
+                if c.a <= 0.0 {
+                    continue;
+                }
+                self.stats.pixels_shaded += 1;
+                if c.a < 1.0 {
+                    let dst = self.pixel(offset);
+                    self.write_pixel(x, y, dst.lerp(c, c.a));
+                    continue;
+                }
+                if let Some(z) = z {
+                    self.framebuffer.depthbuffer.set(offset, z);
+                    for i in self.samples(offset) {
+                        self.framebuffer.sample_depth.set(i, z)
+                    }
+                }
+                self.write_pixel(x, y, c)
+            }
+        }
+    }
+
+    // Fills the pixels whose centers lie in the square of the size around
+    // the screen position, within the clip rectangle and in front of what
+    // is drawn there. Opaque colors write their depth and translucent ones
+    // blend over the pixel, leaving it.
+    fn splat(&mut self, screen: Vector3, size: f64, clip_rect: (Vector2, Vector2), color: Color) {
+        let (min, max) = clip_rect;
+        let x0 = (screen.x - size * 0.5).ceil().max(min.x);
+        let y0 = (screen.y - size * 0.5).ceil().max(min.y);
+        let x1 = (screen.x + size * 0.5).ceil().min(max.x);
+        let y1 = (screen.y + size * 0.5).ceil().min(max.y);
+        let z = screen.z;
+        let opaque = color.a >= 1.0;
+        for y in y0 as u32..y1 as u32 {
+            for x in x0 as u32..x1 as u32 {
+                let offset = y as usize * self.framebuffer.width + x as usize;
+                if self.framebuffer.depthbuffer.get(offset) >= z {
+                    continue;
+                }
+                self.stats.pixels_shaded += 1;
+                if !opaque {
+                    let dst = self.pixel(offset);
+                    self.write_pixel(x, y, dst.lerp(color, color.a.max(0.0)));
+                    continue;
+                }
+                self.framebuffer.depthbuffer.set(offset, z);
+                for i in self.samples(offset) {
+                    self.framebuffer.sample_depth.set(i, z)
+                }
+                self.write_pixel(x, y, color)
+            }
+        }
+    }
+
+    // Draws the occluders into the occlusion buffer as the camera sees them
+    // through the viewport, before the frame is rendered with the same
+    // camera and viewport. Does nothing without an occlusion buffer.
+    pub fn render_occluders(&mut self, camera: &Camera, viewport: &Viewport, occluders: &[&Mesh]) {
+        let view_projection = camera.view_matrix() * camera.projection_matrix(viewport.aspect());
+        if let Some(ref mut occlusion) = self.occlusion {
+            occlusion.clear(view_projection);
+            for mesh in occluders {
+                occlusion.draw(mesh, &mesh.transform.matrix())
+            }
+        }
+    }
+
+    // Renders the depth of the meshes from the light into the shadow map,
+    // before the frame is rendered. Does nothing without a shadow map.
+    pub fn render_shadows(&mut self, light: &Camera, meshes: &[&Mesh]) {
+        self.shadow_pass(light, Draws::Meshes(meshes))
+    }
+
+    pub fn render_instanced_shadows(&mut self,
+                                    light: &Camera,
+                                    mesh: &Mesh,
+                                    instances: &[Instance]) {
+        self.shadow_pass(light, Draws::Instanced(mesh, instances))
+    }
+
+    pub fn render_scene_shadows(&mut self, light: &Camera, scene: &Scene) {
+        let nodes = self.scene_nodes(scene);
+        self.shadow_pass(light, Draws::Nodes(scene, &nodes));
+        self.arena.recycle(nodes)
+    }
+
+    // The nodes of the scene with a mesh and their world matrices, in a
+    // buffer of the arena.
+    fn scene_nodes(&mut self, scene: &Scene) -> Vec<(NodeId, Instance)> {
+        let mut nodes: Vec<(NodeId, Instance)> = self.arena.take();
+        scene.traverse(|id, node, world| {
+            if node.mesh.is_some() {
+                nodes.push((id, Instance::new(*world)))
+            }
+        });
+        nodes
+    }
+
+    fn shadow_pass(&mut self, light: &Camera, draws: Draws) {
+        let mut shadow = match self.shadow.take() {
+            Some(shadow) => shadow,
+            None => return,
+        };
+        let viewport = shadow.viewport();
+        shadow.set_view_projection(light.view_matrix() *
+                                   light.projection_matrix(viewport.aspect()));
+        self.render_to_target(shadow.target_mut(), |device| {
+            device.clear(Color::black());
+            device.render_pass(light, &viewport, draws, Pass::Depth)
+        });
+        self.shadow = Some(shadow);
+    }
+
+    // Each mesh is drawn with its own transform followed by the paired
+    // instance's.
+    fn render_instances(&mut self, camera: &Camera, viewport: &Viewport, draws: Draws) {
+        match self.render_mode {
+            RenderMode::Solid | RenderMode::SolidWireframe => {
+                self.render_pass(camera, viewport, draws, Pass::Shade);
+                self.light_pass(camera, viewport);
+                self.outline_pass();
+                // Drawn over the lit surfaces, so the lines blend with them
+                if self.render_mode == RenderMode::SolidWireframe && self.is_deferred() {
+                    self.render_pass(camera, viewport, draws, Pass::Edges)
+                }
+            }
+            RenderMode::HiddenLine => {
+                // The depth of every mesh goes first, so edges are hidden by
+                // faces of other meshes as well
+                self.render_pass(camera, viewport, draws, Pass::Depth);
+                self.render_pass(camera, viewport, draws, Pass::Edges);
+            }
+        }
+    }
+
+    fn render_pass(&mut self,
+                   camera: &Camera,
+                   viewport: &Viewport,
+                   draws: Draws,
+                   pass: Pass) {
+        match self.precision {
+            Precision::Double => self.draw_pass::<RasterF64>(camera, viewport, draws, pass),
+            Precision::Single => self.draw_pass::<RasterF32>(camera, viewport, draws, pass),
+        }
+    }
+
+    fn draw_pass<R: Raster>(&mut self,
+                            camera: &Camera,
+                            viewport: &Viewport,
+                            draws: Draws,
+                            pass: Pass) {
+        let view_mat = camera.view_matrix();
+        let projection_mat = camera.projection_matrix(viewport.aspect());
+        let view_projection = view_mat * projection_mat;
+        let mut material: Option<&Material> = None;
+        for i in 0..draws.len() {
+            let (mesh, instance) = draws.get(i);
+            let world_mat = mesh.transform.matrix() * instance.transform;
+            let transform_mat = world_mat * view_mat * projection_mat;
+            let transform_raster = R::matrix(&transform_mat);
+
+            // Counted once, by the pass rasterizing the faces
+            let counted = pass != Pass::Edges;
+            if counted {
+                self.stats.triangles_submitted += mesh.face_count();
+            }
+
+            // Blend shapes and modifiers go before anything else
+            let mut deformed: Vec<Vertex> = self.arena.take();
+            let mesh_vertices = if mesh.is_deformed() {
+                mesh.deform_into(&mut deformed);
+                &deformed
+            } else {
+                &mesh.vertices
+            };
+
+            // Skip meshes entirely outside the view, tested in object space
+            let (min, max) = if mesh.is_deformed() && !deformed.is_empty() {
+                deformed.iter().fold((deformed[0].position, deformed[0].position),
+                                     |(min, max), v| (min.min(v.position), max.max(v.position)))
+            } else {
+                mesh.bounds()
+            };
+            if !Frustum::from_matrix(&transform_mat).intersects_aabb(min, max) {
+                if counted {
+                    self.stats.triangles_culled += mesh.face_count();
+                }
+                self.arena.recycle(deformed);
+                continue;
+            }
+            // Filled for another camera, the occlusion buffer hides nothing
+            let occluded = match self.occlusion {
+                Some(ref occlusion) => {
+                    occlusion.view_projection() == view_projection &&
+                    occlusion.is_occluded(min, max, &transform_mat)
+                }
+                None => false,
+            };
+            if occluded {
+                if counted {
+                    self.stats.triangles_culled += mesh.face_count();
+                    self.stats.meshes_occluded += 1;
+                }
+                self.arena.recycle(deformed);
+                continue;
+            }
+            if counted {
+                self.stats.draw_calls += 1;
+                if !material.is_some_and(|m| queue::same_material(m, &mesh.material)) {
+                    self.stats.material_changes += 1;
+                }
+            }
+            material = Some(&mesh.material);
+
+            let normal_mat = Matrix3::normal_matrix(&world_mat);
+            // Tangents follow the surface, turning the other way in a mirror
+            let tangent_mat = Matrix3::from_matrix4(&world_mat);
+            let handedness = if tangent_mat.determinant() < 0.0 { -1.0 } else { 1.0 };
+            let world_view_mat = world_mat * view_mat;
+            let shift = Vector3::new(self.sample_shift(), self.sample_shift(), 0.0) +
+                        self.jitter();
+            let gamma_correct = self.gamma_correct;
+            let tint = if gamma_correct { instance.color.to_linear() } else { instance.color };
+            // The clip space position as well, for clipping
+            let project = |i: usize, face_normal: Vector3| {
+                let vertex = &mesh_vertices[i];
+                let clip = R::transform(vertex.position, &transform_raster);
+                let screen = R::project(clip, viewport, shift);
+                let normal = if vertex.normal == Vector3::zero() {
+                    face_normal
+                } else {
+                    vertex.normal
+                };
+                let view_position = Vector3::transform_coordinate(&vertex.position,
+                                                                  &world_view_mat);
+                let world_position = Vector3::transform_coordinate(&vertex.position, &world_mat);
+                let varying = Varying {
+                    inv_w: 1.0 / R::w(clip),
+                    position: world_position,
+                    depth: view_position.z.abs(),
+                    view: camera.view_direction(world_position),
+                    normal: normal_mat.transform(normal).normalize(),
+                    tangent: {
+                        let t = tangent_mat.transform(vertex.tangent.xyz());
+                        Vector4::new(t.x, t.y, t.z, vertex.tangent.w * handedness)
+                    },
+                    uv: vertex.uv,
+                    color: tint * if gamma_correct {
+                        vertex.color.to_linear()
+                    } else {
+                        vertex.color
+                    },
+                };
+                (clip, screen, varying)
+            };
+            // Where it is cut, a corner at the weights of the face's
+            let corner = |clip: &[R::Clip; 3], varyings: &[Varying; 3], w: Vector3| {
+                let c = R::blend(clip, w);
+                let mut varying = blend(w, varyings);
+                varying.inv_w = 1.0 / R::w(c);
+                (R::project(c, viewport, shift), varying)
+            };
+
+            // All the faces are transformed first, so the two stages can be
+            // timed apart. Those reaching past the guard band are clipped,
+            // the rest left to the rasterizer to scissor.
+            let mut triangles: Vec<([R::Screen; 3], [Varying; 3])> = self.arena.take();
+            let mut weights: Vec<Vector3> = self.arena.take();
+            let (mut split, mut clipped_away) = (0, 0);
+            {
+                profile_scope!("transform");
+                for face in mesh.faces() {
+                    let (a, b, c) = (face.a as usize, face.b as usize, face.c as usize);
+                    // Skipped rather than panicking, see Mesh::validate
+                    if a.max(b).max(c) >= mesh_vertices.len() {
+                        continue;
+                    }
+                    let p = mesh_vertices[a].position;
+                    let face_normal = (mesh_vertices[b].position - p)
+                        .cross(mesh_vertices[c].position - p);
+                    let (c0, v0, f0) = project(a, face_normal);
+                    let (c1, v1, f1) = project(b, face_normal);
+                    let (c2, v2, f2) = project(c, face_normal);
+                    let (clip, varyings) = ([c0, c1, c2], [f0, f1, f2]);
+                    if clip.iter().all(|&c| R::in_guard_band(c, self.guard_band)) {
+                        triangles.push(([v0, v1, v2], varyings));
+                        continue;
+                    }
+                    let wide = [R::widen(c0), R::widen(c1), R::widen(c2)];
+                    clip::clip_triangle(&wide, self.guard_band, &mut weights);
+                    if weights.is_empty() {
+                        clipped_away += 1;
+                        continue;
+                    }
+                    split += 1;
+                    // A fan over the polygon left
+                    let (s0, g0) = corner(&clip, &varyings, weights[0]);
+                    for pair in weights[1..].windows(2) {
+                        let (s1, g1) = corner(&clip, &varyings, pair[0]);
+                        let (s2, g2) = corner(&clip, &varyings, pair[1]);
+                        triangles.push(([s0, s1, s2], [g0, g1, g2]));
+                    }
+                }
+            }
+            self.arena.recycle(weights);
+            if counted {
+                self.stats.triangles_split += split;
+                self.stats.triangles_culled += clipped_away;
+            }
+
+            profile_scope!("raster");
+            for (screen, varyings) in &triangles {
+                let vertices = [R::position(screen[0]),
+                                R::position(screen[1]),
+                                R::position(screen[2])];
+                match pass {
+                    Pass::Shade => {
+                        self.rasterize::<R>(screen, varyings, &mesh.material, viewport, true);
+                        if self.render_mode == RenderMode::SolidWireframe && !self.is_deferred() {
+                            self.edges(&vertices, &mesh.material)
+                        }
+                    }
+                    Pass::Depth => {
+                        self.rasterize::<R>(screen, varyings, &mesh.material, viewport, false)
+                    }
+                    Pass::Edges => self.edges(&vertices, &mesh.material),
+                }
+            }
+            self.arena.recycle(triangles);
+            self.arena.recycle(deformed);
+
+        }
+
+    }
+}
+
+// The index-th element of the van der Corput sequence in the base, in
+// [0, 1). Pairs of bases give low-discrepancy points.
+fn halton(index: usize, base: usize) -> f64 {
+    let mut index = index;
+    let mut f = 1.0;
+    let mut r = 0.0;
+    while index > 0 {
+        f /= base as f64;
+        r += f * (index % base) as f64;
+        index /= base;
+    }
+    r
+}
+
+fn edge_func(v0: Vector2, v1: Vector2, p: Vector2) -> f64 {
+    (v0.y - v1.y) * p.x + (v1.x - v0.x) * p.y + (v0.x * v1.y - v0.y * v1.x)
+}
+
+// The same relative to p, which rounds to exactly the negation with v0 and
+// v1 swapped. Triangles sharing an edge then never both miss a pixel on it,
+// which f32 otherwise leaves holes for.
+fn edge_func_single(v0: Vector2f, v1: Vector2f, p: Vector2f) -> f32 {
+    (v0 - p).cross(v1 - p)
+}
+
+// The types triangles are transformed, set up and rasterized with, one for
+// each Precision. Attributes stay f64 either way.
+trait Raster {
+    type Matrix;
+    // Clip space position
+    type Clip: Copy;
+    // In pixels and depth
+    type Screen: Copy + 'static;
+    type Edges: Edges;
+
+    fn matrix(m: &Matrix4) -> Self::Matrix;
+    fn transform(position: Vector3, m: &Self::Matrix) -> Self::Clip;
+    fn w(c: Self::Clip) -> f64;
+    fn in_guard_band(c: Self::Clip, guard_band: f64) -> bool;
+    // For clip::clip_triangle, which only the few triangles reaching past
+    // the guard band go through.
+    fn widen(c: Self::Clip) -> Vector4;
+    // The position at the weights of the triangle's.
+    fn blend(c: &[Self::Clip; 3], w: Vector3) -> Self::Clip;
+    // Divided by w and mapped to the viewport, then shifted.
+    fn project(c: Self::Clip, viewport: &Viewport, shift: Vector3) -> Self::Screen;
+    fn position(v: Self::Screen) -> Vector3;
+    // Set up with the depth raised by the bias.
+    fn edges(v: &[Self::Screen; 3], bias: f64) -> Self::Edges;
+}
+
+// A triangle's screen positions with its area, set up once for the pixels
+// or samples tested against it.
+trait Edges {
+    fn is_degenerate(&self) -> bool;
+    // Barycentric weights of the point in the triangle, and the depth
+    // there.
+    fn weights(&self, x: f64, y: f64) -> (Vector3, f64);
+    // The same for points in the triangle only, tested before the weights
+    // are widened for shading.
+    fn inside(&self, x: f64, y: f64) -> Option<(Vector3, f64)>;
+}
+
+struct RasterF64;
+
+impl Raster for RasterF64 {
+    type Matrix = Matrix4;
+    type Clip = Vector4;
+    type Screen = Vector3;
+    type Edges = EdgesF64;
+
+    fn matrix(m: &Matrix4) -> Matrix4 {
+        *m
+    }
+
+    fn transform(position: Vector3, m: &Matrix4) -> Vector4 {
+        Vector3::transform(&position, m)
+    }
+
+    fn w(c: Vector4) -> f64 {
+        c.w
+    }
+
+    fn in_guard_band(c: Vector4, guard_band: f64) -> bool {
+        clip::in_guard_band(c, guard_band)
+    }
+
+    fn widen(c: Vector4) -> Vector4 {
+        c
+    }
+
+    fn blend(c: &[Vector4; 3], w: Vector3) -> Vector4 {
+        c[0] * w.x + c[1] * w.y + c[2] * w.z
+    }
+
+    fn project(c: Vector4, viewport: &Viewport, shift: Vector3) -> Vector3 {
+        viewport.project(c.xyz() / c.w) + shift
+    }
+
+    fn position(v: Vector3) -> Vector3 {
+        v
+    }
+
+    fn edges(v: &[Vector3; 3], bias: f64) -> EdgesF64 {
+        let lift = |v: Vector3| Vector3::new(v.x, v.y, v.z + bias);
+        let v = [lift(v[0]), lift(v[1]), lift(v[2])];
+        EdgesF64(v, edge_func(v[0].xy(), v[1].xy(), v[2].xy()))
+    }
+}
+
+struct EdgesF64([Vector3; 3], f64);
+
+impl Edges for EdgesF64 {
+    fn is_degenerate(&self) -> bool {
+        self.1 == 0.0
+    }
+
+    fn weights(&self, x: f64, y: f64) -> (Vector3, f64) {
+        let EdgesF64(ref v, a) = *self;
+        let p = Vector2::new(x, y);
+        let w0 = edge_func(v[1].xy(), v[2].xy(), p) / a;
+        let w1 = edge_func(v[2].xy(), v[0].xy(), p) / a;
+        let w2 = edge_func(v[0].xy(), v[1].xy(), p) / a;
+        (Vector3::new(w0, w1, w2), v[0].z * w0 + v[1].z * w1 + v[2].z * w2)
+    }
+
+    fn inside(&self, x: f64, y: f64) -> Option<(Vector3, f64)> {
+        let (w, z) = self.weights(x, y);
+        if w.x >= 0.0 && w.y >= 0.0 && w.z >= 0.0 { Some((w, z)) } else { None }
+    }
+}
+
+struct RasterF32;
+
+impl Raster for RasterF32 {
+    type Matrix = Matrix4f;
+    type Clip = Vector4f;
+    type Screen = Vector3f;
+    type Edges = EdgesF32;
+
+    fn matrix(m: &Matrix4) -> Matrix4f {
+        Matrix4f::from(*m)
+    }
+
+    fn transform(position: Vector3, m: &Matrix4f) -> Vector4f {
+        Vector3f::transform(&Vector3f::from(position), m)
+    }
+
+    fn w(c: Vector4f) -> f64 {
+        c.w as f64
+    }
+
+    fn in_guard_band(c: Vector4f, guard_band: f64) -> bool {
+        let extent = c.w * guard_band as f32;
+        c.z <= c.w && c.x.abs() <= extent && c.y.abs() <= extent
+    }
+
+    fn widen(c: Vector4f) -> Vector4 {
+        Vector4::from(c)
+    }
+
+    fn blend(c: &[Vector4f; 3], w: Vector3) -> Vector4f {
+        c[0] * w.x as f32 + c[1] * w.y as f32 + c[2] * w.z as f32
+    }
+
+    fn project(c: Vector4f, viewport: &Viewport, shift: Vector3) -> Vector3f {
+        viewport.project_single(c.xyz() * (1.0 / c.w)) + Vector3f::from(shift)
+    }
+
+    fn position(v: Vector3f) -> Vector3 {
+        Vector3::from(v)
+    }
+
+    fn edges(v: &[Vector3f; 3], bias: f64) -> EdgesF32 {
+        let bias = bias as f32;
+        let lift = |v: Vector3f| Vector3f::new(v.x, v.y, v.z + bias);
+        let v = [lift(v[0]), lift(v[1]), lift(v[2])];
+        EdgesF32(v, edge_func_single(v[0].xy(), v[1].xy(), v[2].xy()))
+    }
+}
+
+struct EdgesF32([Vector3f; 3], f32);
+
+impl EdgesF32 {
+    fn weights_single(&self, x: f64, y: f64) -> (f32, f32, f32, f32) {
+        let EdgesF32(ref v, a) = *self;
+        let p = Vector2f::new(x as f32, y as f32);
+        let w0 = edge_func_single(v[1].xy(), v[2].xy(), p) / a;
+        let w1 = edge_func_single(v[2].xy(), v[0].xy(), p) / a;
+        let w2 = edge_func_single(v[0].xy(), v[1].xy(), p) / a;
+        (w0, w1, w2, v[0].z * w0 + v[1].z * w1 + v[2].z * w2)
+    }
+}
+
+impl Edges for EdgesF32 {
+    fn is_degenerate(&self) -> bool {
+        self.1 == 0.0
+    }
+
+    fn weights(&self, x: f64, y: f64) -> (Vector3, f64) {
+        let (w0, w1, w2, z) = self.weights_single(x, y);
+        (Vector3::new(w0 as f64, w1 as f64, w2 as f64), z as f64)
+    }
+
+    fn inside(&self, x: f64, y: f64) -> Option<(Vector3, f64)> {
+        let (w0, w1, w2, z) = self.weights_single(x, y);
+        if w0 >= 0.0 && w1 >= 0.0 && w2 >= 0.0 {
+            Some((Vector3::new(w0 as f64, w1 as f64, w2 as f64), z as f64))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::f64;
+    use math::vector::{Vector2, Vector3};
+    use math::matrix::Matrix4;
+    use math::curves::Curve;
+    use math::quaternion::Quaternion;
+    use geometry::lod::{Lod, LodSelection};
+    use geometry::mesh::{Face, Instance, Mesh};
+    use geometry::modifier::Modifier;
+    use geometry::morph::MorphTarget;
+    use geometry::point_cloud::PointCloud;
+    use scene::graph::{Node, Scene};
+    use super::super::color::Color;
+    use super::super::tonemap::ToneMapping;
+    use super::super::viewport::{Rect, Viewport};
+    use super::super::debug::{heat_color, DebugDraw, DebugView};
+    use super::super::depth::{DepthBias, DepthFormat};
+    use super::super::post::{Frame, Invert, PostChain};
+    use super::super::shadow::ShadowMap;
+    use super::super::sprite::{Billboard, Sprite};
+    use super::super::light::PointLight;
+    use super::super::ssao::Ssao;
+    use super::super::fog::{Fog, FogMode};
+    use super::super::material::{Material, Outline};
+    use super::super::occlusion::OcclusionBuffer;
+    use super::super::queue::RenderQueue;
+    use super::super::particles::{Emitter, ParticleStyle, ParticleSystem};
+    use super::super::target::RenderTarget;
+    use super::super::texture::{CubeMap, Environment, Texture};
+    use super::{Camera, Device, Handedness, LineStyle, Msaa, Precision, Projection, RenderMode,
+                ShapeStyle};
+
+    // Counts the heap allocations of each thread, so a test can check what
+    // it does allocates nothing, whatever other tests run alongside it.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn heap_allocations() -> usize {
+        ALLOCATIONS.with(|count| count.get())
+    }
+
+    fn camera() -> Camera {
+        Camera {
+            position: Vector3::new(0.0, 0.0, 15.0),
+            target: Vector3::zero(),
+            fov: 45.0 * f64::consts::PI / 180.0,
+            znear: 0.01,
+            zfar: 100.0,
+            projection: Projection::Perspective,
+            handedness: Handedness::Right,
+        }
+    }
+
+    #[test]
+    fn clear() {
+        let mut device = Device::new(4, 3);
+        device.clear(Color::from_argb8888(0xff222222));
+        assert!(device.backbuffer().iter().all(|&p| p == 0xff222222));
+        assert_eq!(vec![0x22, 0x22, 0x22, 0xff], &device.read_pixels()[..4]);
+        assert_eq!(4 * 3 * 4, device.read_pixels().len());
+    }
+
+    #[test]
+    fn render_headless() {
+        let clear_color = Color::from_argb8888(0xff222222);
+        let mut device = Device::new(64, 48);
+        device.clear(clear_color);
+        device.render(&camera(), &device.viewport(), &[&Mesh::cube()]);
+        device.resolve();
+
+        let center = device.backbuffer()[24 * 64 + 32];
+        let corner = device.backbuffer()[0];
+        assert!(center != clear_color.to_argb8888());
+        assert_eq!(clear_color.to_argb8888(), corner);
+    }
+
+    #[test]
+    fn render_benchmark() {
+        let mut scene = Scene::new();
+        scene.add(Node::with_mesh(Mesh::cube()), None);
+        let mut device = Device::new(64, 48);
+        let benchmark = device.render_benchmark(&camera(), &scene, 3);
+        assert_eq!((3, 36, 0), (benchmark.frames,
+                                benchmark.render.triangles_submitted,
+                                benchmark.render.triangles_culled));
+        assert!(benchmark.pixels_per_second() > 0.0 && benchmark.triangles_per_second() > 0.0);
+        assert!(benchmark.to_string().contains(" fps, "));
+        assert_eq!(0, device.stats().pixels_shaded);
+    }
+
+    #[test]
+    fn single_precision() {
+        let render = |precision: Precision, msaa: Msaa| {
+            let mut device = Device::new(96, 64);
+            device.set_precision(precision);
+            device.set_msaa(msaa);
+            device.clear(Color::black());
+            // Off center, as edges through pixel centers can go either way
+            let camera = Camera { position: Vector3::new(0.3, 0.2, 15.0), ..camera() };
+            device.render(&camera, &device.viewport(), &[&sphere()]);
+            device.resolve();
+            (device.backbuffer().to_vec(), device.read_depth_pixels())
+        };
+        for &msaa in &[Msaa::Off, Msaa::X4] {
+            let (double, double_depth) = render(Precision::Double, msaa);
+            let (single, single_depth) = render(Precision::Single, msaa);
+            // The same but for a few pixels along the edges
+            let differing = double.iter().zip(single.iter()).filter(|&(a, b)| a != b).count();
+            assert!(differing < double.len() / 50);
+            // Where both cover the pixel
+            let depth_error = double_depth.iter()
+                .zip(single_depth.iter())
+                .filter(|&(&a, &b)| a > 0 && b > 0)
+                .map(|(&a, &b)| (a as i32 - b as i32).abs())
+                .max();
+            assert!(depth_error.unwrap() <= 1);
+        }
+        // Stored as it is computed
+        let mut device = Device::new(8, 8);
+        device.set_precision(Precision::Single);
+        assert_eq!(DepthFormat::F32, device.depth_format());
+    }
+
+    #[test]
+    fn depth_formats() {
+        let render = |format: DepthFormat| {
+            let mut device = Device::new(96, 64);
+            device.set_depth_format(format);
+            device.set_msaa(Msaa::X2);
+            device.clear(Color::black());
+            device.render(&camera(), &device.viewport(), &[&sphere()]);
+            device.resolve();
+            assert_eq!(format, device.framebuffer.depthbuffer.format());
+            (device.backbuffer().to_vec(), device.framebuffer.depthbuffer.size_in_bytes())
+        };
+        let (reference, bytes) = render(DepthFormat::F64);
+        assert_eq!(96 * 64 * 8, bytes);
+        for &format in &[DepthFormat::F32, DepthFormat::U32, DepthFormat::U16] {
+            let (image, bytes) = render(format);
+            assert_eq!(96 * 64 * format.bytes_per_value(), bytes);
+            // The same front faces win, but for rounding along the edges
+            let differing = reference.iter().zip(image.iter()).filter(|&(a, b)| a != b).count();
+            assert!(differing < reference.len() / 50);
+        }
+    }
+
+    #[test]
+    fn frame_arena() {
+        let mut device = Device::new(64, 48);
+        let mut blob = sphere();
+        blob.modifiers.push(Modifier::Twist { angle: 0.3 });
+        let cube = Mesh::cube();
+        let meshes = [&blob, &cube];
+        // Around the camera, so clipped
+        let around = [Instance::new(Matrix4::translation(Vector3::new(0.0, 0.0, 15.0)))];
+        let mut scene = Scene::new();
+        let parent = scene.add(Node::with_mesh(Mesh::cube()), None);
+        scene.add(Node::with_mesh(sphere()), Some(parent));
+        let camera = camera();
+        let frame = |device: &mut Device| {
+            let viewport = device.viewport();
+            device.clear(Color::black());
+            device.render(&camera, &viewport, &meshes);
+            device.render_instanced(&camera, &viewport, &cube, &around);
+            device.render_scene(&camera, &viewport, &scene);
+            device.resolve();
+            (device.arena.allocations(), device.arena.size_in_bytes())
+        };
+        let (allocations, bytes) = frame(&mut device);
+        assert!(allocations > 0 && bytes > 0);
+        assert!(device.stats().triangles_split > 0);
+        // The buffers of the first frame are enough for the same again
+        let before = heap_allocations();
+        assert_eq!((0, bytes), frame(&mut device));
+        assert_eq!(0, heap_allocations() - before);
+    }
+
+    #[test]
+    fn partial_clear() {
+        let mut partial = Device::new(160, 96);
+        partial.set_partial_clear(true);
+        let mut full = Device::new(160, 96);
+        let mut cube = Mesh::cube();
+        let frames = [(-3.0, Color::black()), (0.0, Color::black()), (2.5, Color::black()),
+                      (2.5, Color::gray(0.5))];
+        for &(x, background) in &frames {
+            cube.transform.position = Vector3::new(x, 0.0, 0.0);
+            for device in &mut [&mut partial, &mut full] {
+                device.clear(background);
+                device.render(&camera(), &device.viewport(), &[&cube]);
+                device.draw_line(Vector2::new(10.0, 80.0), Vector2::new(40.0, 90.0));
+                device.resolve();
+                device.draw_text(120, 4, "12", Color::white());
+            }
+            assert_eq!(full.read_pixels(), partial.read_pixels());
+            assert_eq!(full.framebuffer.depthbuffer.to_vec(),
+                       partial.framebuffer.depthbuffer.to_vec());
+            assert_eq!(full.framebuffer.overdraw, partial.framebuffer.overdraw);
+        }
+
+        // The cube, the line and the text, out of 5 x 3 tiles
+        let tiles = partial.dirty_tiles().unwrap();
+        assert_eq!(15, tiles.columns() * tiles.rows());
+        assert!(tiles.count() < 8 && tiles.is_dirty(4, 0) && !tiles.is_dirty(0, 0));
+    }
+
+    #[test]
+    fn hierarchical_z() {
+        // A wall in front, drawn first, and a sphere behind it
+        let mut wall = Mesh::cube();
+        wall.transform.position = Vector3::new(0.0, 0.0, 4.0);
+        wall.transform.scale = Vector3::new(3.0, 3.0, 0.1);
+        let mut ball = sphere();
+        ball.transform.position = Vector3::new(1.0, 0.0, -2.0);
+        let render = |hierarchical_z: bool| {
+            let mut device = Device::new(64, 64);
+            device.hierarchical_z = hierarchical_z;
+            device.clear(Color::black());
+            device.render(&camera(), &device.viewport(), &[&wall, &ball]);
+            (device.read_pixels(), device.framebuffer.depthbuffer.to_vec(), device.stats())
+        };
+        let (pixels, depth, stats) = render(true);
+        let (flat_pixels, flat_depth, flat_stats) = render(false);
+        assert_eq!((flat_pixels, flat_depth), (pixels, depth));
+        assert_eq!(flat_stats.pixels_shaded, stats.pixels_shaded);
+        assert!(stats.blocks_rejected > 0 && flat_stats.blocks_rejected == 0);
+    }
+
+    #[test]
+    fn occlusion_culling() {
+        let mut wall = Mesh::cube();
+        wall.transform.position = Vector3::new(0.0, 0.0, 4.0);
+        wall.transform.scale = Vector3::new(3.0, 3.0, 0.1);
+        let mut hidden = sphere();
+        hidden.transform.position = Vector3::new(1.0, 0.0, -2.0);
+        hidden.transform.scale = Vector3::new(0.3, 0.3, 0.3);
+        let mut beside = hidden.clone();
+        beside.transform.position = Vector3::new(6.0, 0.0, -2.0);
+        // Occluders as the test camera sees them, meshes as the view does
+        let render = |occlusion: Option<OcclusionBuffer>, view: &Camera| {
+            let mut device = Device::new(64, 64);
+            device.occlusion = occlusion;
+            device.render_occluders(&camera(), &device.viewport(), &[&wall]);
+            device.clear(Color::black());
+            device.render(view, &device.viewport(), &[&wall, &hidden, &beside]);
+            (device.read_pixels(), device.stats())
+        };
+        let (pixels, stats) = render(Some(OcclusionBuffer::new(16, 16)), &camera());
+        let (all_pixels, all_stats) = render(None, &camera());
+        assert_eq!(all_pixels, pixels);
+        assert_eq!((1, 0), (stats.meshes_occluded, all_stats.meshes_occluded));
+        assert!(stats.triangles_culled > all_stats.triangles_culled);
+
+        // Nothing is hidden from another camera
+        let mut side = camera();
+        side.position = Vector3::new(12.0, 0.0, 8.0);
+        let (_, stats) = render(Some(OcclusionBuffer::new(16, 16)), &side);
+        assert_eq!(0, stats.meshes_occluded);
+    }
+
+    #[test]
+    fn render_queue() {
+        let mut wall = Mesh::cube();
+        wall.transform.position = Vector3::new(0.0, 0.0, -2.0);
+        wall.transform.scale = Vector3::new(3.0, 3.0, 0.1);
+        wall.material.color = Color::rgb(1.0, 0.0, 0.0);
+        let mut glass = Mesh::cube();
+        glass.transform.position = Vector3::new(0.0, 0.0, 2.0);
+        glass.material.color = Color::new(0.0, 0.0, 1.0, 0.5);
+        glass.material.translucent = true;
+        let render = |meshes: &[&Mesh], queued: bool| {
+            let mut device = Device::new(64, 64);
+            device.clear(Color::black());
+            if queued {
+                let mut queue = RenderQueue::new();
+                for mesh in meshes {
+                    queue.push(mesh)
+                }
+                device.render_queue(&camera(), &device.viewport(), &mut queue);
+            } else {
+                device.render(&camera(), &device.viewport(), meshes);
+            }
+            device.resolve();
+            let center = Color::from_argb8888(device.backbuffer()[32 * 64 + 32]);
+            (device.read_pixels(), center, device.stats())
+        };
+        // Queued first, the glass still blends over the wall
+        let (pixels, center, stats) = render(&[&glass, &wall], true);
+        assert_eq!(pixels, render(&[&wall, &glass], false).0);
+        assert!(center.r > 0.0 && center.b > 0.0);
+        assert_eq!((2, 2), (stats.draw_calls, stats.material_changes));
+        let (_, unsorted, _) = render(&[&glass, &wall], false);
+        assert_eq!(0.0, unsorted.b);
+    }
+
+    #[test]
+    fn guard_band() {
+        // A floor reaching from far ahead to behind the camera
+        let floor = Mesh::from_positions("floor",
+                                         &[Vector3::new(-50.0, -2.0, -50.0),
+                                           Vector3::new(50.0, -2.0, -50.0),
+                                           Vector3::new(50.0, -2.0, 50.0),
+                                           Vector3::new(-50.0, -2.0, 50.0)],
+                                         vec![Face::new(0, 2, 1), Face::new(0, 3, 2)]);
+        let mut device = Device::new(64, 64);
+        device.clear(Color::black());
+        device.render(&camera(), &device.viewport(), &[&floor]);
+        let black = Color::black().to_argb8888();
+        let drawn = |device: &Device, y: usize| {
+            device.backbuffer()[y * 64..(y + 1) * 64].iter().filter(|&&p| p != black).count()
+        };
+        assert_eq!((0, 64), (drawn(&device, 20), drawn(&device, 60)));
+        assert_eq!(2, device.stats().triangles_split);
+
+        // Partly off screen, a cube is only cut with no guard band
+        let mut cube = Mesh::cube();
+        cube.transform.position = Vector3::new(5.5, 0.0, 0.0);
+        let render = |guard_band: f64| {
+            let mut device = Device::new(64, 64);
+            device.guard_band = guard_band;
+            device.clear(Color::black());
+            device.render(&camera(), &device.viewport(), &[&cube]);
+            let coverage: usize = (0..64).map(|y| drawn(&device, y)).sum();
+            (coverage, device.stats().triangles_split)
+        };
+        let (coverage, split) = render(4.0);
+        assert_eq!(0, split);
+        assert!(coverage > 0);
+        let (clipped_coverage, split) = render(1.0);
+        assert!(split > 0);
+        assert_eq!(coverage, clipped_coverage);
+    }
+
+    #[test]
+    fn scissor() {
+        let rect = Rect::new(16, 8, 24, 40);
+        let draw = |scissor: Option<Rect>| {
+            let mut device = Device::new(64, 64);
+            device.clear(Color::black());
+            device.set_scissor(scissor);
+            device.clear(Color::rgb(0.0, 0.0, 0.5));
+            device.render(&camera(), &device.viewport(), &[&Mesh::cube()]);
+            device.draw_line_aa(Vector3::new(0.0, 30.0, 0.0), Vector3::new(63.0, 34.0, 0.0));
+            device.resolve();
+            device.draw_text(0, 12, "scissor", Color::white());
+            device.backbuffer().to_vec()
+        };
+        // The same inside, untouched outside
+        let (all, clipped) = (draw(None), draw(Some(rect)));
+        let black = Color::black().to_argb8888();
+        for (i, (&a, &c)) in all.iter().zip(clipped.iter()).enumerate() {
+            assert_eq!(if rect.contains(i % 64, i / 64) { a } else { black }, c);
+        }
+    }
+
+    #[test]
+    fn depth_bias() {
+        // On the front face of the cube
+        let mut decal = Mesh::from_positions("decal",
+                                             &[Vector3::new(-0.5, -0.5, 1.0),
+                                               Vector3::new(0.5, -0.5, 1.0),
+                                               Vector3::new(0.5, 0.5, 1.0),
+                                               Vector3::new(-0.5, 0.5, 1.0)],
+                                             vec![Face::new(0, 1, 2), Face::new(0, 2, 3)]);
+        decal.material.color = Color::rgb(1.0, 0.0, 0.0);
+        let red = |decal: &Mesh| {
+            let mut camera = camera();
+            camera.position = Vector3::new(0.0, 0.0, 4.0);
+            let mut device = Device::new(64, 64);
+            device.clear(Color::black());
+            device.render(&camera, &device.viewport(), &[&Mesh::cube(), decal]);
+            (28..36)
+                .flat_map(|y| (28..36).map(move |x| y * 64 + x))
+                .filter(|&i| Color::from_argb8888(device.backbuffer()[i]).g == 0.0)
+                .count()
+        };
+        // Without bias, the cube shows through in places
+        assert!(red(&decal) < 64);
+        decal.material.depth_bias = DepthBias::new(1e-6, 1.0);
+        assert_eq!(64, red(&decal));
+    }
+
+    #[test]
+    fn render_orthographic() {
+        let mut camera = camera();
+        camera.projection = Projection::Orthographic { height: 4.0 };
+
+        let mut device = Device::new(64, 64);
+        device.clear(Color::black());
+        device.render(&camera, &device.viewport(), &[&Mesh::cube()]);
+        device.resolve();
+
+        // The unit cube spans half the view volume regardless of distance
+        let black = Color::black().to_argb8888();
+        assert!(device.backbuffer()[20 * 64 + 20] != black);
+        assert!(device.backbuffer()[43 * 64 + 43] != black);
+        assert_eq!(black, device.backbuffer()[12 * 64 + 12]);
+        assert_eq!(black, device.backbuffer()[51 * 64 + 51]);
+    }
+
+    #[test]
+    fn handedness() {
+        let mut mesh = Mesh::cube();
+        mesh.transform.position = Vector3::new(3.0, 2.0, 0.0);
+
+        let lit = |camera: &Camera| {
+            let mut device = Device::new(64, 64);
+            device.clear(Color::black());
+            device.render(camera, &device.viewport(), &[&mesh]);
+            let pixels = device.backbuffer();
+            let black = Color::black().to_argb8888();
+            (pixels[16 * 64 + 48] != black, pixels[16 * 64 + 16] != black)
+        };
+
+        // Seen from +z, a right-handed camera shows +x on the right and a
+        // left-handed one on the left, both with +y up.
+        let mut camera = camera();
+        assert_eq!((true, false), lit(&camera));
+        camera.handedness = Handedness::Left;
+        assert_eq!((false, true), lit(&camera));
+    }
+
+    #[test]
+    fn split_viewports() {
+        let mut device = Device::new(64, 32);
+        device.clear(Color::black());
+        let left = Viewport::new(0.0, 0.0, 32.0, 32.0);
+        let right = Viewport::new(32.0, 0.0, 32.0, 32.0);
+        let mut camera = camera();
+        camera.projection = Projection::Orthographic { height: 1.5 };
+        device.render(&camera, &left, &[&Mesh::cube()]);
+
+        // The cube is larger than the left viewport but is clipped to it
+        let black = Color::black().to_argb8888();
+        assert!(device.backbuffer()[16 * 64 + 16] != black);
+        assert!(device.backbuffer()[16 * 64 + 31] != black);
+        assert!(device.backbuffer()[16 * 64 + 32..17 * 64].iter().all(|&p| p == black));
+
+        device.clear_viewport(Color::white(), &right);
+        assert_eq!(Color::white().to_argb8888(), device.backbuffer()[16 * 64 + 32]);
+        assert!(device.backbuffer()[16 * 64 + 16] != Color::white().to_argb8888());
+    }
+
+    #[test]
+    fn resize() {
+        let mut device = Device::new(64, 48);
+        device.set_hdr(true);
+        device.resize(32, 16);
+        assert_eq!((32, 16), (device.width(), device.height()));
+        assert_eq!(32 * 16, device.backbuffer().len());
+
+        device.clear(Color::black());
+        device.render(&camera(), &device.viewport(), &[&Mesh::cube()]);
+        device.resolve();
+        assert!(device.backbuffer().iter().any(|&p| p != Color::black().to_argb8888()));
+    }
+
+    #[test]
+    fn read_depth_pixels() {
+        let mut device = Device::new(64, 48);
+        device.clear(Color::black());
+        device.render(&camera(), &device.viewport(), &[&Mesh::cube()]);
+        let depth = device.read_depth_pixels();
+        assert_eq!(64 * 48 * 4, depth.len());
+        assert_eq!(&[0, 0, 0, 255], &depth[..4]);
+        assert!(depth.chunks(4).any(|p| p[0] == 255));
+    }
+
+    #[test]
+    fn debug_view_depth() {
+        let mut device = Device::new(64, 48);
+        device.set_hdr(true);
+        device.debug_view = DebugView::Depth;
+        device.clear(Color::white());
+        device.render(&camera(), &device.viewport(), &[&Mesh::cube()]);
+        device.resolve();
+        assert_eq!(Color::black().to_argb8888(), device.backbuffer()[0]);
+        assert!(device.backbuffer().iter().any(|&p| p == Color::white().to_argb8888()));
+    }
+
+    #[test]
+    fn debug_view_overdraw() {
+        let mut device = Device::new(4, 4);
+        device.debug_view = DebugView::Overdraw;
+        device.clear(Color::black());
+        device.put_pixel(1, 1, Color::white());
+        device.put_pixel(1, 1, Color::white());
+        device.put_pixel(2, 1, Color::white());
+        device.resolve();
+        assert_eq!(heat_color(0).to_argb8888(), device.backbuffer()[0]);
+        assert_eq!(heat_color(2).to_argb8888(), device.backbuffer()[4 + 1]);
+        assert_eq!(heat_color(1).to_argb8888(), device.backbuffer()[4 + 2]);
+
+        device.clear(Color::black());
+        device.resolve();
+        assert_eq!(heat_color(0).to_argb8888(), device.backbuffer()[4 + 1]);
+    }
+
+    // Turned so the equator rather than a pole faces the camera
+    fn sphere() -> Mesh {
+        let mut sphere = Mesh::sphere(Vector3::zero(), 5.0, 16, 16);
+        let quarter_turn = f64::consts::PI * 0.5;
+        sphere.transform.rotate(Quaternion::from_axis_angle(Vector3::unit_x(), quarter_turn));
+        sphere
+    }
+
+    #[test]
+    fn debug_view_normals() {
+        let mut device = Device::new(64, 48);
+        device.debug_view = DebugView::Normals;
+        device.clear(Color::black());
+        let sphere = sphere();
+        device.render(&camera(), &device.viewport(), &[&sphere]);
+        // Next to the center the sphere faces the camera, along +z. The
+        // center itself lies on a vertex, shared by several triangles.
+        let center = Color::from_argb8888(device.backbuffer()[23 * 64 + 33]);
+        assert!((center.r - 0.5).abs() < 0.1);
+        assert!((center.g - 0.5).abs() < 0.1);
+        assert!(center.b > 0.9);
+    }
+
+    #[test]
+    fn debug_view_uv_checker() {
+        let sphere = sphere();
+        let mut device = Device::new(64, 48);
+        device.clear(Color::black());
+
+        device.debug_view = DebugView::Uv;
+        device.render(&camera(), &device.viewport(), &[&sphere]);
+        let shaded: Vec<Color> = device.backbuffer()
+            .iter()
+            .filter(|&&p| p != Color::black().to_argb8888())
+            .map(|&p| Color::from_argb8888(p))
+            .collect();
+        assert!(shaded.iter().all(|c| c.b == 0.0));
+        assert!(shaded.iter().any(|c| c.r != shaded[0].r));
+
+        device.debug_view = DebugView::Checker;
+        device.clear(Color::black());
+        device.render(&camera(), &device.viewport(), &[&sphere]);
+        let dark = Color::gray(0.2).to_argb8888();
+        let light = Color::gray(0.8).to_argb8888();
+        let pixels = device.backbuffer();
+        assert!(pixels.iter().any(|&p| p == dark));
+        assert!(pixels.iter().any(|&p| p == light));
+        let background = Color::black().to_argb8888();
+        assert!(pixels.iter().all(|&p| p == dark || p == light || p == background));
+    }
+
+    #[test]
+    fn render_debug() {
+        let mut device = Device::new(64, 48);
+        device.clear(Color::black());
+        let mut debug = DebugDraw::new();
+        debug.draw_axes(&Matrix4::identity(), 4.0);
+        device.render_debug(&camera(), &device.viewport(), &debug);
+
+        // The x axis runs to the right of the center in red, y up in green
+        let red = Color::from_argb8888(device.backbuffer()[24 * 64 + 40]);
+        assert!(red.r > 0.5 && red.g == 0.0 && red.b == 0.0);
+        let green = Color::from_argb8888(device.backbuffer()[16 * 64 + 32]);
+        assert!(green.g > 0.5 && green.r == 0.0 && green.b == 0.0);
+    }
+
+    #[test]
+    fn line_styles() {
+        let mut device = Device::new(40, 20);
+        device.gamma_correct = false;
+        device.clear(Color::black());
+        let red = Color::rgb(1.0, 0.0, 0.0);
+        let blue = Color::rgb(0.0, 0.0, 1.0);
+        device.draw_line_styled(Vector3::new(2.0, 10.0, 0.0),
+                                red,
+                                Vector3::new(37.0, 10.0, 0.0),
+                                blue,
+                                &LineStyle::solid());
+        let left = Color::from_argb8888(device.backbuffer()[10 * 40 + 4]);
+        let right = Color::from_argb8888(device.backbuffer()[10 * 40 + 35]);
+        assert!(left.r > 0.8 && left.b < 0.2);
+        assert!(right.b > 0.8 && right.r < 0.2);
+
+        // Five rows thick, with a gap every four pixels
+        device.clear(Color::black());
+        let style = LineStyle {
+            thickness: 5.0,
+            dash: Some((4.0, 4.0)),
+            depth_test: false,
+        };
+        device.draw_line_styled(Vector3::new(2.0, 10.0, 0.0),
+                                Color::white(),
+                                Vector3::new(37.0, 10.0, 0.0),
+                                Color::white(),
+                                &style);
+        let white = Color::white().to_argb8888();
+        let pixel = |x: usize, y: usize| device.backbuffer()[y * 40 + x];
+        for y in 8..13 {
+            assert_eq!(white, pixel(3, y));
+            assert_eq!(Color::black().to_argb8888(), pixel(7, y));
+        }
+        assert_eq!(Color::black().to_argb8888(), pixel(3, 14));
+    }
+
+    #[test]
+    fn line_depth_test() {
+        let mut device = Device::new(64, 48);
+        device.clear(Color::black());
+        device.render(&camera(), &device.viewport(), &[&Mesh::cube()]);
+        let covered = device.backbuffer()[24 * 64 + 32];
+
+        // Far behind the cube, then just in front of the camera
+        let line = |z| (Vector3::new(0.0, 24.0, z), Vector3::new(63.0, 24.0, z));
+        let (from, to) = line(1e-6);
+        device.draw_line_aa(from, to);
+        assert_eq!(covered, device.backbuffer()[24 * 64 + 32]);
+        assert_eq!(Color::white().to_argb8888(), device.backbuffer()[24 * 64 + 2]);
+
+        let (from, to) = line(0.9);
+        device.draw_line_aa(from, to);
+        assert_eq!(Color::white().to_argb8888(), device.backbuffer()[24 * 64 + 32]);
+    }
+
+    #[test]
+    fn solid_wireframe() {
+        let mut device = Device::new(64, 48);
+        device.clear(Color::black());
+        device.render(&camera(), &device.viewport(), &[&Mesh::cube()]);
+        let solid = device.backbuffer().to_vec();
+
+        device.render_mode = RenderMode::SolidWireframe;
+        device.clear(Color::black());
+        device.render(&camera(), &device.viewport(), &[&Mesh::cube()]);
+        let white = Color::white().to_argb8888();
+        assert!(!solid.contains(&white));
+        assert!(device.backbuffer().contains(&white));
+    }
+
+    #[test]
+    fn hidden_line() {
+        let mut device = Device::new(64, 48);
+        device.render_mode = RenderMode::HiddenLine;
+        device.clear(Color::black());
+        // Close to the cube the back face is much smaller than the front
+        let mut camera = camera();
+        camera.position = Vector3::new(0.0, 0.0, 4.0);
+        device.render(&camera, &device.viewport(), &[&Mesh::cube()]);
+
+        // Only edges are drawn, the faces stay the background color
+        let black = Color::black().to_argb8888();
+        let row = &device.backbuffer()[30 * 64..31 * 64];
+        assert!(row.iter().filter(|&&p| p == black).count() > 48);
+        // The right edge of the front face is visible, the one of the back
+        // face about 8 pixels further in is not
+        assert!(row[48..56].iter().any(|&p| p != black));
+        assert!(row[40..47].iter().all(|&p| p == black));
+    }
+
+    #[test]
+    fn line_clipping() {
+        let mut device = Device::new(64, 48);
+        device.clear(Color::black());
+        // Far off screen on both sides, drawn without walking the whole line
+        device.draw_line_aa(Vector3::new(-1e9, 10.0, 0.0), Vector3::new(1e9, 10.0, 0.0));
+        let white = Color::white().to_argb8888();
+        assert!(device.backbuffer()[10 * 64..11 * 64].iter().all(|&p| p == white));
+
+        // From behind the camera to the origin, only the part in front is drawn
+        device.clear(Color::black());
+        let mut debug = DebugDraw::new();
+        debug.draw_line(Vector3::new(0.0, -1.0, 30.0), Vector3::zero(), Color::white());
+        device.render_debug(&camera(), &device.viewport(), &debug);
+        assert!(device.backbuffer().contains(&white));
+        assert_eq!(Color::black().to_argb8888(), device.backbuffer()[0]);
+    }
+
+    #[test]
+    fn shapes() {
+        let mut device = Device::new(40, 40);
+        let white = Color::white().to_argb8888();
+        let black = Color::black().to_argb8888();
+        let center = Vector2::new(20.0, 20.0);
+        let pixel = |device: &Device, x: usize, y: usize| device.backbuffer()[y * 40 + x];
+
+        device.clear(Color::black());
+        device.draw_circle(center, 10.0, Color::white(), &ShapeStyle::fill());
+        assert_eq!(white, pixel(&device, 20, 20));
+        assert_eq!(white, pixel(&device, 29, 20));
+        assert_eq!(black, pixel(&device, 31, 20));
+        assert_eq!(black, pixel(&device, 28, 28));
+
+        device.clear(Color::black());
+        let radii = Vector2::new(15.0, 5.0);
+        device.draw_ellipse(center, radii, Color::white(), &ShapeStyle::outline());
+        assert_eq!(black, pixel(&device, 20, 20));
+        assert_eq!(white, pixel(&device, 35, 20));
+        assert_eq!(white, pixel(&device, 20, 15));
+
+        // The right half, without antialiasing
+        device.clear(Color::black());
+        let style = ShapeStyle { antialias: false, ..ShapeStyle::fill() };
+        let half_turn = f64::consts::PI;
+        device.draw_arc(center,
+                        Vector2::new(10.0, 10.0),
+                        -half_turn * 0.5,
+                        half_turn * 0.5,
+                        Color::white(),
+                        &style);
+        assert_eq!(white, pixel(&device, 25, 20));
+        assert_eq!(black, pixel(&device, 15, 20));
+        assert!(device.backbuffer().iter().all(|&p| p == white || p == black));
+    }
+
+    #[test]
+    fn fill_polygon() {
+        let mut device = Device::new(40, 40);
+        let white = Color::white().to_argb8888();
+        let pixel = |device: &Device, x: usize, y: usize| device.backbuffer()[y * 40 + x];
+
+        device.clear(Color::black());
+        let square = [Vector2::new(10.0, 10.0),
+                      Vector2::new(20.0, 10.0),
+                      Vector2::new(20.0, 20.0),
+                      Vector2::new(10.0, 20.0)];
+        device.fill_polygon(&square, Color::white());
+        assert_eq!(100, device.backbuffer().iter().filter(|&&p| p == white).count());
+        assert_eq!(white, pixel(&device, 10, 10));
+        assert!(pixel(&device, 20, 20) != white);
+
+        // A pentagram leaves its center empty, but not its points. It may
+        // reach outside the device as well.
+        device.clear(Color::black());
+        let star: Vec<Vector2> = (0..5)
+            .map(|i| {
+                let angle = i as f64 * 4.0 * f64::consts::PI / 5.0 - f64::consts::PI * 0.5;
+                Vector2::new(20.0 + angle.cos() * 25.0, 20.0 + angle.sin() * 25.0)
+            })
+            .collect();
+        device.fill_polygon(&star, Color::white());
+        assert!(pixel(&device, 20, 20) != white);
+        assert_eq!(white, pixel(&device, 20, 2));
+    }
+
+    #[test]
+    fn draw_curve() {
+        let mut device = Device::new(64, 48);
+        device.clear(Color::black());
+        let black = Color::black().to_argb8888();
+        let curve = Curve::Cubic(Vector2::new(4.0, 40.0),
+                                 Vector2::new(4.0, 4.0),
+                                 Vector2::new(60.0, 4.0),
+                                 Vector2::new(60.0, 40.0));
+        device.draw_curve(&curve, Color::white());
+
+        // Every point along the curve is drawn, the inside stays empty
+        for i in 0..33 {
+            let p = curve.point(i as f64 / 32.0);
+            let pixel = device.backbuffer()[p.y.round() as usize * 64 + p.x.round() as usize];
+            assert!(pixel != black, "nothing at {:?}", p);
+        }
+        assert_eq!(black, device.backbuffer()[30 * 64 + 32]);
+    }
+
+    #[test]
+    fn render_to_target() {
+        let mut device = Device::new(64, 48);
+        device.clear(Color::black());
+        let mut target = RenderTarget::new(32, 32);
+        device.render_to_target(&mut target, |device| {
+            assert_eq!(32, device.width());
+            device.clear(Color::rgb(1.0, 0.0, 0.0));
+            device.render(&camera(), &device.viewport(), &[&sphere()]);
+        });
+
+        // The device is left as it was
+        assert_eq!(64, device.width());
+        assert!(!device.is_hdr());
+        assert!(device.backbuffer().iter().all(|&p| p == Color::black().to_argb8888()));
+
+        // Red around the sphere, which covers the center
+        let red = Color::rgb(1.0, 0.0, 0.0);
+        assert_eq!(red, target.color()[0]);
+        assert!(target.color()[16 * 32 + 16] != red);
+        assert!(target.depth().get(16 * 32 + 16) > 0.0);
+        assert_eq!(0.0, target.depth().get(0));
+
+        // Used as a texture, it shows on the sphere
+        device.render_to_target(&mut target, |device| device.clear(red));
+        device.texture = Some(target.texture());
+        device.render(&camera(), &device.viewport(), &[&sphere()]);
+        let (r, g, b, _) = Color::from_argb8888(device.backbuffer()[24 * 64 + 32]).to_u8();
+        assert!(r > 200 && g < 50 && b < 50);
+
+        // Turning HDR off while drawing leaves the target cleared
+        device.render_to_target(&mut target, |device| device.set_hdr(false));
+        assert_eq!(32 * 32, target.color().len());
+        assert!(target.color().iter().all(|&c| c == Color::black()));
+        assert!(!device.is_hdr());
+    }
+
+    #[test]
+    fn draw_text() {
+        let mut device = Device::new(32, 32);
+        device.clear(Color::black());
+        device.draw_text(0, 0, "_\n_", Color::white());
+        // The underscore fills the bottom row of its glyph
+        let white = Color::white().to_argb8888();
+        assert_eq!(white, device.backbuffer()[7 * 32]);
+        assert_eq!(white, device.backbuffer()[15 * 32 + 7]);
+        assert_eq!(16, device.backbuffer().iter().filter(|&&p| p == white).count());
+    }
+
+    #[test]
+    fn msaa() {
+        // Coverage of a white triangle on black, summed over the pixels
+        let coverage = |msaa: Msaa| {
+            let mut device = Device::new(64, 48);
+            device.set_msaa(msaa);
+            device.gamma_correct = false;
+            device.texture = Some(Texture::filled(1, 1, Color::white()));
+            device.clear(Color::black());
+            device.draw_triangle(Vector3::new(4.0, 4.0, 0.5),
+                                 Vector3::new(4.0, 40.0, 0.5),
+                                 Vector3::new(60.0, 4.0, 0.5));
+            device.resolve();
+            let values: Vec<f64> = device.backbuffer()
+                .iter()
+                .map(|&p| Color::from_argb8888(p).r)
+                .collect();
+            let partial = values.iter().filter(|&&v| v > 0.0 && v < 1.0).count();
+            (values.iter().sum::<f64>(), partial)
+        };
+
+        // Edge pixels get partial coverage, the total close to the area
+        let area = 56.0 * 36.0 * 0.5;
+        let (_, partial) = coverage(Msaa::Off);
+        assert_eq!(0, partial);
+        for &msaa in &[Msaa::X2, Msaa::X4] {
+            let (sum, partial) = coverage(msaa);
+            assert!(partial > 0);
+            assert!((sum - area).abs() < 10.0, "{:?} covers {}", msaa, sum);
+        }
+
+        // Lines set every sample of their pixels, and depth tests against
+        // the nearest sample
+        let mut device = Device::new(64, 48);
+        device.set_msaa(Msaa::X4);
+        device.clear(Color::black());
+        device.draw_triangle(Vector3::new(4.0, 4.0, 0.5),
+                             Vector3::new(4.0, 40.0, 0.5),
+                             Vector3::new(60.0, 4.0, 0.5));
+        device.draw_line_styled(Vector3::new(0.0, 20.0, 0.25),
+                                Color::white(),
+                                Vector3::new(63.0, 20.0, 0.25),
+                                Color::white(),
+                                &LineStyle::solid());
+        device.resolve();
+        let white = Color::white().to_argb8888();
+        assert!(device.backbuffer()[20 * 64 + 2] == white);
+        assert!(device.backbuffer()[20 * 64 + 10] != white);
+    }
+
+    #[test]
+    fn supersampling() {
+        let mut device = Device::new(32, 24);
+        device.set_supersampling(4);
+        assert_eq!((32, 24), (device.width(), device.height()));
+        assert_eq!(32 * 24, device.backbuffer().len());
+
+        // Coordinates stay in presented pixels, edges come out blended
+        device.gamma_correct = false;
+        device.texture = Some(Texture::filled(1, 1, Color::white()));
+        device.clear(Color::black());
+        device.draw_triangle(Vector3::new(2.0, 2.0, 0.5),
+                             Vector3::new(2.0, 20.0, 0.5),
+                             Vector3::new(30.0, 2.0, 0.5));
+        device.resolve();
+        // Without gamma correction samples are averaged as stored
+        let values: Vec<f64> = device.backbuffer()
+            .iter()
+            .map(|&p| Color::from_argb8888(p).r)
+            .collect();
+        let area = 28.0 * 18.0 * 0.5;
+        assert!((values.iter().sum::<f64>() - area).abs() < 5.0);
+        assert!(values.iter().any(|&v| v > 0.0 && v < 1.0));
+
+        // With it they are averaged in linear space, then encoded
+        device.gamma_correct = true;
+        device.clear(Color::black());
+        device.draw_triangle(Vector3::new(2.0, 2.0, 0.5),
+                             Vector3::new(2.0, 20.0, 0.5),
+                             Vector3::new(30.0, 2.0, 0.5));
+        device.resolve();
+        let linear: Vec<f64> = device.backbuffer()
+            .iter()
+            .map(|&p| Color::from_argb8888(p).to_linear().r)
+            .collect();
+        assert!((linear.iter().sum::<f64>() - area).abs() < 5.0);
+        assert!(linear.iter().zip(values.iter()).any(|(&l, &v)| l != v));
+
+        // Overlays go on the presented frame
+        device.draw_text(0, 16, "#", Color::rgb(1.0, 0.0, 0.0));
+        let red = Color::rgb(1.0, 0.0, 0.0).to_argb8888();
+        assert!(device.backbuffer()[16 * 32..24 * 32].contains(&red));
+
+        device.set_supersampling(1);
+        assert_eq!(32 * 24, device.backbuffer().len());
+    }
+
+    #[test]
+    fn accumulation() {
+        assert_eq!(0.75, super::halton(3, 2));
+        assert!((super::halton(5, 3) - 7.0 / 9.0).abs() < 1e-12);
+
+        let mut device = Device::new(48, 48);
+        device.gamma_correct = false;
+        device.texture = Some(Texture::filled(1, 1, Color::white()));
+        device.clear(Color::black());
+        device.render(&camera(), &device.viewport(), &[&Mesh::triangle()]);
+        let single = device.backbuffer().to_vec();
+        let hard = |pixels: &[u32]| pixels.iter().all(|&p| p == 0xff000000 || p == 0xffffffff);
+        assert!(hard(&single));
+
+        // Jittered frames average to partly covered pixels along the edges,
+        // with about the same coverage as a single frame
+        device.set_accumulation(true);
+        for _ in 0..16 {
+            device.clear(Color::black());
+            device.render(&camera(), &device.viewport(), &[&Mesh::triangle()]);
+            device.resolve();
+        }
+        assert_eq!(16, device.accumulated_frames());
+        assert!(!hard(device.backbuffer()));
+        let coverage = |pixels: &[u32]| {
+            pixels.iter().map(|&p| Color::from_argb8888(p).r).sum::<f64>()
+        };
+        let edge = single.iter().filter(|&&p| p != 0xff000000).count() as f64;
+        assert!((coverage(device.backbuffer()) - coverage(&single)).abs() < edge.sqrt() * 2.0);
+
+        device.reset_accumulation();
+        assert!(device.is_accumulating());
+        assert_eq!(0, device.accumulated_frames());
+        device.set_accumulation(false);
+        assert!(!device.is_accumulating());
+    }
+
+    #[test]
+    fn shadows() {
+        // A cube above the ground, lit from behind and above so its shadow
+        // falls towards the camera
+        let mut cube = Mesh::cube();
+        cube.transform.scale = Vector3::one() * 0.5;
+        let mut ground = Mesh::cube();
+        ground.transform.scale = Vector3::new(4.0, 0.01, 4.0);
+        ground.transform.position = Vector3::new(0.0, -1.0, 0.0);
+        let light = Camera {
+            position: Vector3::new(0.0, 6.0, -6.0),
+            target: Vector3::zero(),
+            fov: 0.0,
+            znear: 0.1,
+            zfar: 20.0,
+            projection: Projection::Orthographic { height: 8.0 },
+            handedness: Handedness::Right,
+        };
+        let camera = Camera {
+            position: Vector3::new(0.0, 10.0, 6.0),
+            ..camera()
+        };
+
+        let mut device = Device::new(64, 64);
+        device.gamma_correct = false;
+        device.texture = Some(Texture::filled(1, 1, Color::white()));
+        let viewport = device.viewport();
+        let view_projection = camera.view_matrix() * camera.projection_matrix(viewport.aspect());
+        let brightness = |device: &Device, p: Vector3| {
+            let p = viewport.project(Vector3::transform_coordinate(&p, &view_projection));
+            let offset = p.y.round() as usize * 64 + p.x.round() as usize;
+            Color::from_argb8888(device.backbuffer()[offset]).r
+        };
+        let (shadowed, lit) = (Vector3::new(0.0, -1.0, 1.5), Vector3::new(2.5, -1.0, 1.5));
+        let draw = |device: &mut Device| {
+            device.render_shadows(&light, &[&cube, &ground]);
+            device.clear(Color::black());
+            device.render(&camera, &viewport, &[&cube, &ground]);
+        };
+
+        // Nothing happens without a shadow map
+        draw(&mut device);
+        assert_eq!(1.0, brightness(&device, shadowed));
+
+        device.shadow = Some(ShadowMap::new(256));
+        draw(&mut device);
+        assert!(device.shadow.as_ref().unwrap().depth().iter().any(|d| d > 0.0));
+        assert_eq!(1.0, brightness(&device, lit));
+        // Darkened by the strength of the shadow
+        assert!((brightness(&device, shadowed) - 0.4).abs() < 0.01);
+        // The top of the cube faces the light
+        assert_eq!(1.0, brightness(&device, Vector3::new(0.0, 0.5, 0.0)));
+    }
+
+    #[test]
+    fn deferred() {
+        let mut device = Device::new(64, 48);
+        device.gamma_correct = false;
+        device.texture = Some(Texture::filled(1, 1, Color::white()));
+        let red = Color::rgb(1.0, 0.0, 0.0);
+        let blue = Color::rgb(0.0, 0.0, 1.0);
+        device.lights = vec![PointLight::new(Vector3::new(8.0, 0.0, 4.0), red, 12.0),
+                             PointLight::new(Vector3::new(-8.0, 0.0, 4.0), blue, 12.0)];
+        let draw = |device: &mut Device| {
+            device.clear(Color::black());
+            device.render(&camera(), &device.viewport(), &[&sphere()]);
+            device.backbuffer().to_vec()
+        };
+
+        // Each side lit by its light, over the ambient light
+        let forward = draw(&mut device);
+        let (r, g, b, _) = Color::from_argb8888(forward[20 * 64 + 46]).to_u8();
+        assert!(r > b && b > 0 && g > 0);
+        let (r, _, b, _) = Color::from_argb8888(forward[20 * 64 + 18]).to_u8();
+        assert!(b > r);
+
+        // The same picture when lit from the G-buffer, which is used up
+        device.set_deferred(true);
+        assert_eq!(forward, draw(&mut device));
+        let gbuffer = device.gbuffer().unwrap();
+        assert!(!gbuffer.covered().iter().any(|&c| c));
+        assert_eq!(Color::white(), gbuffer.albedo()[20 * 64 + 32]);
+        let normal = gbuffer.normal()[20 * 64 + 32];
+        assert!((normal.length() - 1.0).abs() < 1e-9 && normal.z > 0.5);
+
+        // Lines go over the lit surfaces
+        device.render_mode = RenderMode::SolidWireframe;
+        let wireframe = draw(&mut device);
+        assert!(wireframe != forward);
+        assert!(wireframe.contains(&Color::white().to_argb8888()));
+
+        device.set_deferred(false);
+        assert!(device.gbuffer().is_none());
+    }
+
+    #[test]
+    fn ssao() {
+        // A cube standing on the ground, making a crease along its base
+        let mut ground = Mesh::cube();
+        ground.transform.scale = Vector3::new(5.0, 0.01, 5.0);
+        ground.transform.position = Vector3::new(0.0, -1.01, 0.0);
+        let camera = Camera {
+            position: Vector3::new(0.0, 4.0, 8.0),
+            target: Vector3::new(0.0, -1.0, 0.0),
+            ..camera()
+        };
+
+        let mut device = Device::new(96, 64);
+        device.gamma_correct = false;
+        device.texture = Some(Texture::filled(1, 1, Color::white()));
+        device.set_deferred(true);
+        device.ssao = Some(Ssao::new());
+        device.clear(Color::black());
+        let viewport = device.viewport();
+        device.render(&camera, &viewport, &[&Mesh::cube(), &ground]);
+
+        let view_projection = camera.view_matrix() * camera.projection_matrix(viewport.aspect());
+        let brightness = |p: Vector3| {
+            let p = viewport.project(Vector3::transform_coordinate(&p, &view_projection));
+            let offset = p.y.round() as usize * 96 + p.x.round() as usize;
+            Color::from_argb8888(device.backbuffer()[offset]).r
+        };
+        // Darker next to the cube than out in the open
+        let crease = brightness(Vector3::new(0.0, -1.0, 1.1));
+        assert!(brightness(Vector3::new(3.5, -1.0, 3.5)) > 0.95);
+        assert!(crease < 0.9, "{}", crease);
+        assert!(brightness(Vector3::new(0.0, 1.0, 0.0)) > 0.95);
+    }
+
+    #[test]
+    fn fog() {
+        let mut device = Device::new(32, 32);
+        device.gamma_correct = false;
+        device.texture = Some(Texture::filled(1, 1, Color::white()));
+        let viewport = device.viewport();
+        let view_projection = camera().view_matrix() *
+                              camera().projection_matrix(viewport.aspect());
+        // Inside the triangle, 16 units in front of the camera
+        let p = Vector3::transform_coordinate(&Vector3::new(0.5, -0.5, -1.0), &view_projection);
+        let p = viewport.project(p);
+        let offset = p.y.round() as usize * 32 + p.x.round() as usize;
+        let draw = |device: &mut Device| {
+            device.clear(Color::black());
+            device.render(&camera(), &viewport, &[&Mesh::triangle()]);
+            Color::from_argb8888(device.backbuffer()[offset])
+        };
+
+        // Half way into linear fog, the same with deferred shading
+        let blue = Color::rgb(0.0, 0.0, 1.0);
+        device.fog = Some(Fog::new(blue, FogMode::Linear { start: 0.0, end: 32.0 }));
+        let c = draw(&mut device);
+        assert!((c.r - 0.5).abs() < 0.01 && (c.b - 1.0).abs() < 0.01);
+        device.set_deferred(true);
+        assert_eq!(c, draw(&mut device));
+        device.set_deferred(false);
+
+        // A scene's own fog is used while rendering it
+        let mut scene = Scene::new();
+        scene.add(Node::with_mesh(Mesh::triangle()), None);
+        scene.fog = Some(Fog::new(Color::black(), FogMode::Exponential { density: 0.25 }));
+        device.clear(Color::black());
+        device.render_scene(&camera(), &viewport, &scene);
+        let c = Color::from_argb8888(device.backbuffer()[offset]);
+        assert!(c.r < 0.02 && c.b < 0.02);
+        assert_eq!(blue, device.fog.unwrap().color);
+    }
+
+    #[test]
+    fn reflection() {
+        // Green behind the camera and red in front of it
+        let green = Color::rgb(0.0, 1.0, 0.0);
+        let environment = CubeMap::from_fn(8, |d| {
+            if d.z > 0.0 { green } else { Color::rgb(1.0, 0.0, 0.0) }
+        });
+        let mut device = Device::new(32, 32);
+        device.gamma_correct = false;
+        device.environment = Some(Environment::Cube(environment));
+        let viewport = device.viewport();
+        let view_projection = camera().view_matrix() *
+                              camera().projection_matrix(viewport.aspect());
+        let p = Vector3::transform_coordinate(&Vector3::new(0.5, -0.5, -1.0), &view_projection);
+        let p = viewport.project(p);
+        let offset = p.y.round() as usize * 32 + p.x.round() as usize;
+        let draw = |device: &mut Device, mesh: &Mesh| {
+            device.clear(Color::black());
+            device.render(&camera(), &viewport, &[mesh]);
+            Color::from_argb8888(device.backbuffer()[offset])
+        };
+
+        // Facing the camera, half of the white surface mirrors what is
+        // behind it, the same with deferred shading
+        let mut triangle = Mesh::triangle();
+        triangle.material = Material {
+            texture: Some(Texture::filled(1, 1, Color::white())),
+            ..Material::reflective(0.5)
+        };
+        let c = draw(&mut device, &triangle);
+        assert!((c.r - 0.5).abs() < 0.01 && (c.g - 1.0).abs() < 0.01 && (c.b - 0.5).abs() < 0.01);
+        device.set_deferred(true);
+        assert_eq!(c, draw(&mut device, &triangle));
+        device.set_deferred(false);
+
+        triangle.material.reflectivity = 0.0;
+        assert_eq!(Color::white(), draw(&mut device, &triangle));
+        triangle.material.color = green;
+        assert_eq!(green, draw(&mut device, &triangle));
+    }
+
+    #[test]
+    fn normal_map() {
+        let mut device = Device::new(32, 32);
+        device.gamma_correct = false;
+        device.debug_view = DebugView::Normals;
+        let viewport = device.viewport();
+        let view_projection = camera().view_matrix() *
+                              camera().projection_matrix(viewport.aspect());
+        let p = Vector3::transform_coordinate(&Vector3::new(0.5, -0.5, -1.0), &view_projection);
+        let p = viewport.project(p);
+        let offset = p.y.round() as usize * 32 + p.x.round() as usize;
+        let draw = |device: &mut Device, mesh: &Mesh| {
+            device.clear(Color::black());
+            device.render(&camera(), &viewport, &[mesh]);
+            Color::from_argb8888(device.backbuffer()[offset]).to_u8()
+        };
+
+        // Facing +z, with u to the right and v down
+        let mut triangle = Mesh::triangle();
+        let uvs = [Vector2::new(0.0, 1.0), Vector2::new(1.0, 1.0), Vector2::new(1.0, 0.0)];
+        for (v, &uv) in triangle.vertices.iter_mut().zip(&uvs) {
+            v.uv = uv;
+        }
+        triangle.compute_normals();
+        triangle.compute_tangents();
+        assert_eq!((128, 128, 255, 255), draw(&mut device, &triangle));
+
+        // Bent along the tangent, then up the texture
+        triangle.material.normal_map = Some(Texture::filled(1, 1, Color::rgb(1.0, 0.5, 0.5)));
+        assert_eq!((255, 128, 128, 255), draw(&mut device, &triangle));
+        triangle.material.normal_map = Some(Texture::filled(1, 1, Color::rgb(0.5, 1.0, 0.5)));
+        assert_eq!((128, 255, 128, 255), draw(&mut device, &triangle));
+
+        // Lit by a light to the right, the bent normal faces it
+        device.debug_view = DebugView::Off;
+        device.texture = Some(Texture::filled(1, 1, Color::white()));
+        device.lights = vec![PointLight::new(Vector3::new(8.0, 0.0, -1.0), Color::white(), 20.0)];
+        triangle.material.normal_map = Some(Texture::filled(1, 1, Color::rgb(1.0, 0.5, 0.5)));
+        let bent = draw(&mut device, &triangle);
+        triangle.material.normal_map = None;
+        assert!(bent.0 > draw(&mut device, &triangle).0 + 50);
+        device.set_deferred(true);
+        triangle.material.normal_map = Some(Texture::filled(1, 1, Color::rgb(1.0, 0.5, 0.5)));
+        assert_eq!(bent, draw(&mut device, &triangle));
+    }
+
+    #[test]
+    fn vertex_colors() {
+        let mut device = Device::new(32, 32);
+        device.gamma_correct = false;
+        device.texture = Some(Texture::filled(1, 1, Color::white()));
+        let viewport = device.viewport();
+        let view_projection = camera().view_matrix() *
+                              camera().projection_matrix(viewport.aspect());
+        let p = Vector3::transform_coordinate(&Vector3::new(0.5, -0.5, -1.0), &view_projection);
+        let p = viewport.project(p);
+        let offset = p.y.round() as usize * 32 + p.x.round() as usize;
+        let draw = |device: &mut Device, mesh: &Mesh| {
+            device.clear(Color::black());
+            device.render(&camera(), &viewport, &[mesh]);
+            Color::from_argb8888(device.backbuffer()[offset]).to_u8()
+        };
+
+        let mut triangle = Mesh::triangle();
+        let white = draw(&mut device, &triangle);
+        for v in &mut triangle.vertices {
+            v.color = Color::rgb(1.0, 0.0, 0.0);
+        }
+        assert_eq!((white.0, 0, 0, 255), draw(&mut device, &triangle));
+
+        // Blended between the corners, the point nearest the second
+        let colors = [Color::rgb(1.0, 0.0, 0.0),
+                      Color::rgb(0.0, 1.0, 0.0),
+                      Color::rgb(0.0, 0.0, 1.0)];
+        for (v, &color) in triangle.vertices.iter_mut().zip(&colors) {
+            v.color = color;
+        }
+        let c = draw(&mut device, &triangle);
+        assert!(c.1 > c.0 && c.1 > c.2 && c.0 > 0 && c.2 > 0, "{:?}", c);
+        device.set_deferred(true);
+        assert_eq!(c, draw(&mut device, &triangle));
+    }
+
+    #[test]
+    fn instancing() {
+        let mut device = Device::new(64, 32);
+        device.gamma_correct = false;
+        device.texture = Some(Texture::filled(1, 1, Color::white()));
+        let viewport = device.viewport();
+        let cube = Mesh::cube();
+        let instances = [Instance::new(Matrix4::translation(Vector3::new(-3.0, 0.0, 0.0)))
+                             .with_color(Color::rgb(1.0, 0.0, 0.0)),
+                         Instance::new(Matrix4::translation(Vector3::new(3.0, 0.0, 0.0)))
+                             .with_color(Color::rgb(0.0, 1.0, 0.0)),
+                         Instance::new(Matrix4::translation(Vector3::new(0.0, 40.0, 0.0)))];
+        device.clear(Color::black());
+        device.render_instanced(&camera(), &viewport, &cube, &instances);
+
+        let view_projection = camera().view_matrix() *
+                              camera().projection_matrix(viewport.aspect());
+        let color = |x: f64| {
+            let p = Vector3::transform_coordinate(&Vector3::new(x, 0.0, 1.0), &view_projection);
+            let p = viewport.project(p);
+            Color::from_argb8888(device.backbuffer()[p.y as usize * 64 + p.x as usize]).to_u8()
+        };
+        let (left, middle, right) = (color(-3.0), color(0.0), color(3.0));
+        assert!(left.0 > 0 && left.1 == 0 && left.2 == 0, "{:?}", left);
+        assert!(right.1 > 0 && right.0 == 0 && right.2 == 0, "{:?}", right);
+        assert_eq!((0, 0, 0, 255), middle);
+
+        // Each instance counts its triangles, the one out of view culled
+        let stats = device.stats();
+        assert_eq!(36, stats.triangles_submitted);
+        assert_eq!(12, stats.triangles_culled);
+    }
+
+    #[test]
+    fn render_morphed() {
+        let mut device = Device::new(32, 32);
+        let mut cube = Mesh::cube();
+        let up = vec![Vector3::new(0.0, 4.0, 0.0); cube.vertices.len()];
+        let i = cube.add_morph_target(MorphTarget::new("Up", up));
+        let covered = |device: &Device, y: usize| {
+            device.framebuffer.depthbuffer.get(y * 32 + 16) > 0.0
+        };
+        device.render(&camera(), &device.viewport(), &[&cube]);
+        assert!(covered(&device, 16) && !covered(&device, 7));
+
+        // Drawn where the blend puts it, and culled by its blended bounds
+        cube.morph_weights[i] = 1.0;
+        device.clear(Color::black());
+        device.render(&camera(), &device.viewport(), &[&cube]);
+        assert!(!covered(&device, 16) && covered(&device, 7));
+        cube.morph_weights[i] = 10.0;
+        device.clear(Color::black());
+        device.render(&camera(), &device.viewport(), &[&cube]);
+        assert_eq!(12, device.stats().triangles_culled);
+    }
+
+    #[test]
+    fn render_lods() {
+        let mut device = Device::new(32, 32);
+        let lod = Lod::new(Mesh::icosphere(1.0, 2), LodSelection::Distance)
+            .with_level(Mesh::icosahedron(1.0), 10.0);
+        let mut near = camera();
+        near.position = Vector3::new(0.0, 0.0, 5.0);
+        device.render_lods(&near, &device.viewport(), &[&lod]);
+        assert_eq!(320, device.stats().triangles_submitted);
+        device.reset_stats();
+        device.render_lods(&camera(), &device.viewport(), &[&lod]);
+        assert_eq!(20, device.stats().triangles_submitted);
+    }
+
+    #[test]
+    fn render_sdf() {
+        let mut device = Device::new(32, 32);
+        device.gamma_correct = false;
+        let ball = |p: Vector3| p.length() - 2.0;
+        let white = Material::new();
+        device.render_sdf(&camera(), &device.viewport(), ball, &white);
+        let pixel = |device: &Device, x: usize, y: usize| device.backbuffer()[y * 32 + x];
+        assert_eq!(Color::white().to_argb8888(), pixel(&device, 16, 16));
+        assert_eq!(0, pixel(&device, 1, 1));
+
+        // The same depth as a mesh, so a larger one hides it and a smaller
+        // one doesn't
+        let cube = |half: f64| {
+            move |p: Vector3| {
+                let q = Vector3::new(p.x.abs() - half, p.y.abs() - half, p.z.abs() - half);
+                q.max(Vector3::zero()).length() + q.x.max(q.y).max(q.z).min(0.0)
+            }
+        };
+        device.clear(Color::black());
+        device.render_sdf(&camera(), &device.viewport(), cube(1.0), &white);
+        let marched = device.framebuffer.depthbuffer.get(16 * 32 + 16);
+        let mut mesh = Mesh::cube();
+        mesh.material.color = Color::rgb(1.0, 0.0, 0.0);
+        device.clear(Color::black());
+        device.render(&camera(), &device.viewport(), &[&mesh]);
+        assert!((device.framebuffer.depthbuffer.get(16 * 32 + 16) / marched - 1.0).abs() < 1e-6);
+        device.render_sdf(&camera(), &device.viewport(), cube(0.9), &white);
+        assert!(Color::from_argb8888(pixel(&device, 16, 16)).g == 0.0);
+        device.render_sdf(&camera(), &device.viewport(), cube(1.1), &white);
+        assert_eq!(Color::white().to_argb8888(), pixel(&device, 16, 16));
+
+        // Lit from the side
+        device.clear(Color::black());
+        device.ambient = Color::black();
+        device.lights = vec![PointLight::new(Vector3::new(10.0, 0.0, 0.0), Color::white(), 30.0)];
+        device.render_sdf(&camera(), &device.viewport(), ball, &white);
+        let red = |x: usize| Color::from_argb8888(pixel(&device, x, 16)).r;
+        assert!(red(20) > red(12));
+    }
+
+    #[test]
+    fn render_points() {
+        let mut device = Device::new(32, 32);
+        device.gamma_correct = false;
+        let red = Color::rgb(1.0, 0.0, 0.0);
+        let mut cloud = PointCloud::new("Scan").with_point_size(3.0);
+        cloud.add(Vector3::zero(), red);
+        // Behind the camera
+        cloud.add(Vector3::new(0.0, 0.0, 20.0), Color::white());
+        device.render_points(&camera(), &device.viewport(), &[&cloud]);
+        let pixel = |device: &Device, x: usize, y: usize| device.backbuffer()[y * 32 + x];
+        for x in 13..19 {
+            let expected = if (15..18).contains(&x) { red.to_argb8888() } else { 0 };
+            assert_eq!(expected, pixel(&device, x, 16));
+            assert_eq!(expected, pixel(&device, 16, x));
+        }
+        assert_eq!(9, device.stats().pixels_shaded);
+
+        // Hidden inside a cube, and in front of it once moved
+        device.clear(Color::black());
+        device.render(&camera(), &device.viewport(), &[&Mesh::cube()]);
+        let cube = pixel(&device, 16, 16);
+        device.render_points(&camera(), &device.viewport(), &[&cloud]);
+        assert_eq!(cube, pixel(&device, 16, 16));
+        cloud.transform.position = Vector3::new(0.0, 0.0, 2.0);
+        device.render_points(&camera(), &device.viewport(), &[&cloud]);
+        assert_eq!(red.to_argb8888(), pixel(&device, 16, 16));
+    }
+
+    #[test]
+    fn render_particles() {
+        let mut device = Device::new(32, 32);
+        device.gamma_correct = false;
+        let mut system = ParticleSystem::new();
+        system.gravity = Vector3::zero();
+        let mut emitter = Emitter::new(Vector3::zero());
+        emitter.rate = 1.0;
+        emitter.lifetime = 1.0;
+        emitter.velocity = Vector3::zero();
+        emitter.spread = 0.0;
+        emitter.size = 2.0;
+        system.add_emitter(emitter);
+        system.update(1.0);
+        system.emitters.clear();
+        let row = |device: &Device| {
+            let red = |x: usize| Color::from_argb8888(device.backbuffer()[16 * 32 + x]).r;
+            (0..32).filter(|&x| red(x) > 0.0).count()
+        };
+
+        // Quads shrink with distance, points don't
+        device.render_particles(&camera(), &device.viewport(), &system);
+        assert_eq!(Color::white().to_argb8888(), device.backbuffer()[16 * 32 + 16]);
+        assert_eq!(5, row(&device));
+        assert!(device.framebuffer.depthbuffer.get(16 * 32 + 16) > 0.0);
+        system.style = ParticleStyle::Points(3.0);
+        device.clear(Color::black());
+        device.render_particles(&camera(), &device.viewport(), &system);
+        assert_eq!(3, row(&device));
+
+        // Fading out, blended without writing depth, and hidden by a cube
+        system.update(0.5);
+        device.clear(Color::black());
+        device.render_particles(&camera(), &device.viewport(), &system);
+        let gray = Color::from_argb8888(device.backbuffer()[16 * 32 + 16]);
+        assert!((gray.r - 0.5).abs() < 0.01);
+        assert_eq!(0.0, device.framebuffer.depthbuffer.get(16 * 32 + 16));
+        device.render(&camera(), &device.viewport(), &[&Mesh::cube()]);
+        let cube = device.backbuffer()[16 * 32 + 16];
+        device.render_particles(&camera(), &device.viewport(), &system);
+        assert_eq!(cube, device.backbuffer()[16 * 32 + 16]);
+    }
+
+    #[test]
+    fn render_billboards() {
+        let mut device = Device::new(32, 32);
+        device.gamma_correct = false;
+        let red = Color::rgb(1.0, 0.0, 0.0);
+        // Opaque on the left and clear on the right
+        let clear = Color::new(0.0, 0.0, 0.0, 0.0);
+        let texture = Texture::new(4, 1, vec![red, red, clear, clear]);
+        let billboard = Billboard::new(Vector3::zero(), Vector2::new(2.0, 2.0))
+            .with_region(Vector2::new(0.0, 0.0), Vector2::new(0.5, 1.0));
+        device.render_billboards(&camera(), &device.viewport(), &[billboard], None);
+        let covered = |device: &Device| {
+            (0..32).filter(|&x| device.backbuffer()[16 * 32 + x] != 0).count()
+        };
+        assert_eq!(Color::white().to_argb8888(), device.backbuffer()[16 * 32 + 16]);
+        assert_eq!(5, covered(&device));
+        assert!(device.framebuffer.depthbuffer.get(16 * 32 + 16) > 0.0);
+
+        let tinted = billboard.with_region(Vector2::new(0.0, 0.0), Vector2::new(1.0, 1.0))
+            .with_color(Color::rgb(0.5, 1.0, 1.0));
+        let mut device = Device::new(32, 32);
+        device.gamma_correct = false;
+        device.render_billboards(&camera(), &device.viewport(), &[tinted], Some(&texture));
+        assert_eq!(Color::rgb(0.5, 0.0, 0.0).to_argb8888(), device.backbuffer()[16 * 32 + 15]);
+        assert_eq!(0, device.backbuffer()[16 * 32 + 17]);
+        assert_eq!(0.0, device.framebuffer.depthbuffer.get(16 * 32 + 17));
+
+        // Hidden inside a cube, and in front of it once moved
+        device.clear(Color::black());
+        device.render(&camera(), &device.viewport(), &[&Mesh::cube()]);
+        let cube = device.backbuffer()[16 * 32 + 16];
+        device.render_billboards(&camera(), &device.viewport(), &[billboard], None);
+        assert_eq!(cube, device.backbuffer()[16 * 32 + 16]);
+        let front = Billboard { position: Vector3::new(0.0, 0.0, 2.0), ..billboard };
+        device.render_billboards(&camera(), &device.viewport(), &[front], None);
+        assert_eq!(Color::white().to_argb8888(), device.backbuffer()[16 * 32 + 16]);
+    }
+
+    #[test]
+    fn draw_sprites() {
+        let mut device = Device::new(32, 32);
+        device.gamma_correct = false;
+        let (red, green) = (Color::rgb(1.0, 0.0, 0.0), Color::rgb(0.0, 1.0, 0.0));
+        let atlas = Texture::new(4, 1, vec![red, red, green, green]);
+        let icon = Sprite::new(Vector2::new(4.0, 4.0), Vector2::new(8.0, 8.0))
+            .with_region(Vector2::new(0.0, 0.0), Vector2::new(0.5, 1.0));
+        let overlay = Sprite::new(Vector2::new(8.0, 8.0), Vector2::new(2.0, 2.0))
+            .with_color(Color::new(0.0, 0.0, 1.0, 0.5));
+        device.draw_sprites(&[icon, overlay], Some(&atlas));
+        let pixel = |device: &Device, x: usize, y: usize| device.backbuffer()[y * 32 + x];
+        assert_eq!(red.to_argb8888(), pixel(&device, 7, 7));
+        assert_eq!((0, 0), (pixel(&device, 3, 4), pixel(&device, 12, 4)));
+        assert_ne!(0, pixel(&device, 11, 11));
+        // Blended over the icon, not depth tested
+        let blended = Color::from_argb8888(pixel(&device, 9, 9));
+        assert!(blended.g < 0.01 && (blended.r - 0.5).abs() < 0.01);
+        assert_eq!(0.0, device.framebuffer.depthbuffer.get(9 * 32 + 9));
+    }
+
+    #[test]
+    fn pbr() {
+        let mut device = Device::new(64, 48);
+        device.gamma_correct = false;
+        device.ambient = Color::black();
+        device.texture = Some(Texture::filled(1, 1, Color::white()));
+        device.lights = vec![PointLight::new(Vector3::new(0.0, 0.0, 12.0), Color::white(), 30.0)];
+        let draw = |device: &mut Device, mesh: &Mesh| {
+            device.clear(Color::black());
+            device.render(&camera(), &device.viewport(), &[mesh]);
+            device.backbuffer().to_vec()
+        };
+
+        // A highlight in the middle where the light is mirrored to the eye,
+        // while elsewhere some of the light is reflected away
+        let basic = draw(&mut device, &sphere());
+        let polished = Mesh { material: Material::pbr(Color::white(), 0.0, 0.3), ..sphere() };
+        let pbr = draw(&mut device, &polished);
+        let red = |frame: &[u32], y: usize| Color::from_argb8888(frame[y * 64 + 32]).to_u8().0;
+        assert!(red(&pbr, 23) > red(&basic, 23) + 50);
+        assert!(red(&pbr, 19) < red(&basic, 19));
+
+        // The same lit from the G-buffer
+        device.set_deferred(true);
+        assert_eq!(pbr, draw(&mut device, &polished));
+    }
+
+    #[test]
+    fn toon() {
+        let mut device = Device::new(64, 48);
+        device.gamma_correct = false;
+        device.ambient = Color::black();
+        device.texture = Some(Texture::filled(1, 1, Color::white()));
+        device.lights = vec![PointLight::new(Vector3::new(6.0, 6.0, 12.0), Color::white(), 40.0)];
+        let draw = |device: &mut Device, mesh: &Mesh| {
+            device.clear(Color::black());
+            device.render(&camera(), &device.viewport(), &[mesh]);
+            device.backbuffer().to_vec()
+        };
+        let green = Color::rgb(0.0, 1.0, 0.0).to_argb8888();
+        let plain = draw(&mut device, &sphere());
+        assert!(!plain.contains(&green));
+        // Where the sphere ends along a row
+        let edge = (0..64).rev().find(|&x| plain[20 * 64 + x] != plain[0]).unwrap();
+
+        // Lit in thirds, with a two pixel outline around the silhouette
+        let mut sphere = sphere();
+        sphere.material = Material::toon(Color::white(), 3);
+        sphere.material.outline = Some(Outline::new(Color::rgb(0.0, 1.0, 0.0), 2.0));
+        let toon = draw(&mut device, &sphere);
+        for y in 0..48 {
+            for x in 0..64 {
+                let (r, g, _, _) = Color::from_argb8888(toon[y * 64 + x]).to_u8();
+                assert!([0, 85, 170, 255].contains(&r) && (r == g || g == 255));
+            }
+        }
+        assert_eq!(green, toon[20 * 64 + edge + 1]);
+        assert_eq!(green, toon[20 * 64 + edge + 2]);
+        assert_eq!(plain[0], toon[20 * 64 + edge + 4]);
+        assert!(toon[20 * 64 + 32] != green);
+
+        // The outline covers what is further behind, and stays when the
+        // sky fills the background
+        let sky = CubeMap::from_fn(4, |_| Color::white());
+        device.render_skybox(&camera(), &device.viewport(), &sky);
+        assert_eq!(green, device.backbuffer()[20 * 64 + edge + 1]);
+    }
+
+    #[test]
+    fn skybox() {
+        // White above the horizon, black below
+        let sky = CubeMap::from_fn(8, |d| if d.y > 0.0 { Color::white() } else { Color::black() });
+        let mut device = Device::new(64, 48);
+        let viewport = device.viewport();
+        device.clear(Color::rgb(1.0, 0.0, 0.0));
+        device.render(&camera(), &viewport, &[&sphere()]);
+        let sphere_color = device.backbuffer()[20 * 64 + 32];
+        device.render_skybox(&camera(), &viewport, &sky);
+        assert_eq!(Color::white().to_argb8888(), device.backbuffer()[0]);
+        assert_eq!(Color::black().to_argb8888(), device.backbuffer()[47 * 64]);
+        assert_eq!(sphere_color, device.backbuffer()[20 * 64 + 32]);
+
+        // Every background sample is replaced, leaving no red along edges
+        device.set_msaa(Msaa::X4);
+        device.clear(Color::rgb(1.0, 0.0, 0.0));
+        device.render(&camera(), &viewport, &[&sphere()]);
+        device.render_skybox(&camera(), &viewport, &sky);
+        device.resolve();
+        assert!(device.backbuffer().iter().all(|&p| {
+            let (r, g, _, _) = Color::from_argb8888(p).to_u8();
+            r == g
+        }));
+    }
+
+    #[test]
+    fn post_process() {
+        let mut device = Device::new(8, 8);
+        device.clear(Color::white());
+        device.post = PostChain::new().then(Invert);
+        device.resolve();
+        assert!(device.backbuffer().iter().all(|&p| p == 0xff000000));
+
+        // With HDR the passes leave the buffer as it is, so resolving again
+        // gives the same frame
+        device.set_hdr(true);
+        device.tone_mapping = ToneMapping::Clamp;
+        device.clear(Color::gray(0.5));
+        device.post = PostChain::new().then(|frame: &mut Frame| for c in frame.color.iter_mut() {
+            *c = *c * 4.0
+        });
+        device.resolve();
+        let frame = device.backbuffer().to_vec();
+        device.resolve();
+        assert!(frame[0] != Color::gray(0.5).to_argb8888());
+        assert_eq!(frame, device.backbuffer());
+    }
+
+    #[test]
+    fn resolve_hdr() {
+        let clear_color = Color::from_argb8888(0xff224466);
+        let mut device = Device::new(8, 8);
+        device.set_hdr(true);
+        device.tone_mapping = ToneMapping::Clamp;
+        device.clear(clear_color);
+        device.resolve();
+        assert!(device.is_hdr());
+        assert!(device.backbuffer().iter().all(|&p| p == 0xff224466));
+    }
+}
@@ -0,0 +1,85 @@
+
+use super::color::Color;
+
+// How fog thickens with view space depth, the distance from the camera
+// along its view direction.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum FogMode {
+    // None before start, growing evenly to full at end.
+    Linear { start: f64, end: f64 },
+    // e^-(density * depth) of the surface is left.
+    Exponential { density: f64 },
+    // e^-(density * depth)^2, clearer up close and thicker further out.
+    ExponentialSquared { density: f64 },
+}
+
+// Fades surfaces into the color with their distance, applied to each pixel
+// after lighting. Matching the color to the clear color makes distant
+// geometry disappear into the background.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Fog {
+    pub color: Color,
+    pub mode: FogMode,
+}
+
+impl Fog {
+    pub fn new(color: Color, mode: FogMode) -> Fog {
+        Fog {
+            color: color,
+            mode: mode,
+        }
+    }
+
+    // Fraction of the surface color left at the depth, 1 without any fog.
+    pub fn visibility(&self, depth: f64) -> f64 {
+        let v = match self.mode {
+            FogMode::Linear { start, end } => {
+                if end <= start {
+                    if depth < start { 1.0 } else { 0.0 }
+                } else {
+                    (end - depth) / (end - start)
+                }
+            }
+            FogMode::Exponential { density } => (-density * depth).exp(),
+            FogMode::ExponentialSquared { density } => {
+                let d = density * depth;
+                (-d * d).exp()
+            }
+        };
+        v.clamp(0.0, 1.0)
+    }
+
+    // The color seen through the fog at the depth. Both colors are in the
+    // space shading happens in.
+    pub fn apply(&self, color: Color, fog_color: Color, depth: f64) -> Color {
+        let c = fog_color.lerp(color, self.visibility(depth));
+        Color::new(c.r, c.g, c.b, color.a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::super::color::Color;
+    use super::{Fog, FogMode};
+
+    #[test]
+    fn visibility() {
+        let linear = Fog::new(Color::black(), FogMode::Linear { start: 10.0, end: 20.0 });
+        assert_eq!(1.0, linear.visibility(5.0));
+        assert_eq!(0.5, linear.visibility(15.0));
+        assert_eq!(0.0, linear.visibility(30.0));
+
+        let exp = Fog::new(Color::black(), FogMode::Exponential { density: 0.1 });
+        assert_eq!(1.0, exp.visibility(0.0));
+        assert!((exp.visibility(10.0) - (-1.0f64).exp()).abs() < 1e-12);
+
+        // Clearer than exp up close, thicker far away
+        let exp2 = Fog::new(Color::black(), FogMode::ExponentialSquared { density: 0.1 });
+        assert!(exp2.visibility(5.0) > exp.visibility(5.0));
+        assert!(exp2.visibility(20.0) < exp.visibility(20.0));
+
+        let c = linear.apply(Color::white(), Color::black(), 15.0);
+        assert_eq!(Color::new(0.5, 0.5, 0.5, 1.0), c);
+    }
+}
@@ -0,0 +1,171 @@
+
+use std::ops::{Add, Mul};
+
+use super::color::Color;
+
+// Smallest contrast, relative to the brightest pixel around, that counts as
+// an edge.
+const EDGE_THRESHOLD: f64 = 0.125;
+// Absolute floor of the edge threshold, so dark noise is left alone.
+const EDGE_THRESHOLD_MIN: f64 = 0.0312;
+// How much of the subpixel blend is applied, 0 for sharp and 1 for soft.
+const SUBPIXEL_QUALITY: f64 = 0.75;
+// Strides of the search for the ends of an edge, growing as it goes.
+const SEARCH_STEPS: [f64; 12] = [1.0, 1.0, 1.0, 1.0, 1.0, 1.5, 2.0, 2.0, 2.0, 2.0, 4.0, 8.0];
+
+// Perceptual brightness of an encoded color.
+fn luma(c: Color) -> f64 {
+    c.r * 0.299 + c.g * 0.587 + c.b * 0.114
+}
+
+// Bilinear sample at (x, y), with pixel centers at whole coordinates and
+// clamped to the edges of the image.
+fn sample<T>(pixels: &[T], width: usize, height: usize, x: f64, y: f64) -> T
+    where T: Copy + Add<Output = T> + Mul<f64, Output = T>
+{
+    let x = x.max(0.0).min(width as f64 - 1.0);
+    let y = y.max(0.0).min(height as f64 - 1.0);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+    let top = pixels[y0 * width + x0] * (1.0 - fx) + pixels[y0 * width + x1] * fx;
+    let bottom = pixels[y1 * width + x0] * (1.0 - fx) + pixels[y1 * width + x1] * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+// Fast approximate antialiasing of ARGB8888 pixels, as in FXAA 3.11 by
+// Timothy Lottes. Finds edges by contrast in luma and blends across them,
+// by how far along the edge the pixel is from its ends.
+pub fn fxaa(argb: &mut [u32], width: usize, height: usize) {
+    if width == 0 || height == 0 {
+        return;
+    }
+    let colors: Vec<Color> = argb.iter().map(|&p| Color::from_argb8888(p)).collect();
+    let lumas: Vec<f64> = colors.iter().map(|&c| luma(c)).collect();
+    let at = |x: i64, y: i64| {
+        let x = x.max(0).min(width as i64 - 1) as usize;
+        let y = y.max(0).min(height as i64 - 1) as usize;
+        lumas[y * width + x]
+    };
+    let luma_at = |x: f64, y: f64| sample(&lumas, width, height, x, y);
+
+    for y in 0..height as i64 {
+        for x in 0..width as i64 {
+            let m = at(x, y);
+            let (n, s, w, e) = (at(x, y - 1), at(x, y + 1), at(x - 1, y), at(x + 1, y));
+            let max = m.max(n).max(s).max(w).max(e);
+            let min = m.min(n).min(s).min(w).min(e);
+            let range = max - min;
+            if range < EDGE_THRESHOLD_MIN.max(max * EDGE_THRESHOLD) {
+                continue;
+            }
+
+            let (nw, ne) = (at(x - 1, y - 1), at(x + 1, y - 1));
+            let (sw, se) = (at(x - 1, y + 1), at(x + 1, y + 1));
+            let edge_horizontal = (nw + sw - 2.0 * w).abs() + (n + s - 2.0 * m).abs() * 2.0 +
+                                  (ne + se - 2.0 * e).abs();
+            let edge_vertical = (nw + ne - 2.0 * n).abs() + (w + e - 2.0 * m).abs() * 2.0 +
+                                (sw + se - 2.0 * s).abs();
+            let horizontal = edge_horizontal >= edge_vertical;
+
+            // Which side of the pixel the edge lies on, towards the steeper
+            // gradient, and a step across it
+            let (luma1, luma2) = if horizontal { (n, s) } else { (w, e) };
+            let (gradient1, gradient2) = (luma1 - m, luma2 - m);
+            let steepest1 = gradient1.abs() >= gradient2.abs();
+            let gradient = gradient1.abs().max(gradient2.abs()) * 0.25;
+            let (step, local_average) = if steepest1 {
+                (-1.0, (luma1 + m) * 0.5)
+            } else {
+                (1.0, (luma2 + m) * 0.5)
+            };
+
+            // Walk along the middle of the edge both ways until the luma
+            // there no longer matches, which is where it ends
+            let (cx, cy) = (x as f64, y as f64);
+            let (ex, ey, along_x, along_y) = if horizontal {
+                (cx, cy + step * 0.5, 1.0, 0.0)
+            } else {
+                (cx + step * 0.5, cy, 0.0, 1.0)
+            };
+            let search = |direction: f64| {
+                let mut distance = 0.0;
+                let mut delta = 0.0;
+                for &stride in SEARCH_STEPS.iter() {
+                    distance += stride;
+                    delta = luma_at(ex + along_x * distance * direction,
+                                    ey + along_y * distance * direction) -
+                            local_average;
+                    if delta.abs() >= gradient {
+                        break;
+                    }
+                }
+                (distance, delta)
+            };
+            let (distance1, delta1) = search(-1.0);
+            let (distance2, delta2) = search(1.0);
+
+            // Blend by the position along the edge, when the nearer end
+            // shows the edge turning away from this pixel
+            let (distance, delta) = if distance1 < distance2 {
+                (distance1, delta1)
+            } else {
+                (distance2, delta2)
+            };
+            let center_smaller = m < local_average;
+            let edge_offset = if (delta < 0.0) != center_smaller {
+                0.5 - distance / (distance1 + distance2)
+            } else {
+                0.0
+            };
+
+            // Blend for features thinner than a pixel, by how much the pixel
+            // stands out from its neighbourhood
+            let average = ((n + s + w + e) * 2.0 + nw + ne + sw + se) / 12.0;
+            let subpixel = ((average - m).abs() / range).clamp(0.0, 1.0);
+            let subpixel = (-2.0 * subpixel + 3.0) * subpixel * subpixel;
+            let subpixel_offset = subpixel * subpixel * SUBPIXEL_QUALITY;
+
+            let offset = edge_offset.max(subpixel_offset) * step;
+            let (sx, sy) = if horizontal { (cx, cy + offset) } else { (cx + offset, cy) };
+            let i = y as usize * width + x as usize;
+            let c = sample(&colors, width, height, sx, sy);
+            argb[i] = Color::new(c.r, c.g, c.b, colors[i].a).to_argb8888();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::fxaa;
+
+    #[test]
+    fn edges() {
+        // Flat areas are left alone
+        let mut flat = vec![0xff336699; 16];
+        fxaa(&mut flat, 4, 4);
+        assert!(flat.iter().all(|&p| p == 0xff336699));
+
+        // A jagged edge between black and white, one pixel to the right
+        // every other row
+        let (width, height) = (16, 16);
+        let mut pixels: Vec<u32> = (0..width * height)
+            .map(|i| if i % width < 4 + (i / width) / 2 { 0xffffffff } else { 0xff000000 })
+            .collect();
+        let original = pixels.clone();
+        fxaa(&mut pixels, width, height);
+
+        // Pixels along the steps turn gray, those away from the edge stay
+        let gray = |p: u32| p != 0xffffffff && p != 0xff000000;
+        assert!(pixels.iter().any(|&p| gray(p)));
+        for y in 0..height {
+            for x in 0..width {
+                let edge = 4 + y / 2;
+                if x + 2 < edge || x > edge + 1 {
+                    assert_eq!(original[y * width + x], pixels[y * width + x]);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,97 @@
+
+use std::ops::Range;
+
+use math::vector::Vector3;
+
+use super::color::Color;
+use super::material::Surface;
+
+// Surface attributes of each pixel for deferred shading, written by the
+// geometry pass and lit afterwards in one pass over the screen. Depth is
+// kept in the device's depth buffer. Pixels the last lighting pass has not
+// seen yet are marked covered.
+#[derive(Debug,Clone)]
+pub struct GBuffer {
+    width: usize,
+    height: usize,
+    albedo: Box<[Color]>,
+    // World space, unit length.
+    normal: Box<[Vector3]>,
+    position: Box<[Vector3]>,
+    // The material at each pixel.
+    surface: Box<[Surface]>,
+    covered: Box<[bool]>,
+}
+
+impl GBuffer {
+    pub fn new(width: usize, height: usize) -> GBuffer {
+        let size = width * height;
+        GBuffer {
+            width: width,
+            height: height,
+            albedo: vec![Color::black(); size].into_boxed_slice(),
+            normal: vec![Vector3::zero(); size].into_boxed_slice(),
+            position: vec![Vector3::zero(); size].into_boxed_slice(),
+            surface: vec![Surface::default(); size].into_boxed_slice(),
+            covered: vec![false; size].into_boxed_slice(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn albedo(&self) -> &[Color] {
+        &self.albedo
+    }
+
+    pub fn normal(&self) -> &[Vector3] {
+        &self.normal
+    }
+
+    pub fn position(&self) -> &[Vector3] {
+        &self.position
+    }
+
+    pub fn surface(&self) -> &[Surface] {
+        &self.surface
+    }
+
+    pub fn covered(&self) -> &[bool] {
+        &self.covered
+    }
+
+    pub(crate) fn write(&mut self,
+                        offset: usize,
+                        albedo: Color,
+                        normal: Vector3,
+                        position: Vector3,
+                        surface: Surface) {
+        self.albedo[offset] = albedo;
+        self.normal[offset] = normal;
+        self.position[offset] = position;
+        self.surface[offset] = surface;
+        self.covered[offset] = true;
+    }
+
+    // Leaves the pixel to whatever was drawn over it, e.g. a line.
+    pub(crate) fn uncover(&mut self, offset: usize) {
+        self.covered[offset] = false
+    }
+
+    pub(crate) fn clear(&mut self) {
+        for covered in self.covered.iter_mut() {
+            *covered = false
+        }
+    }
+
+    pub(crate) fn clear_range(&mut self, range: Range<usize>) {
+        for covered in self.covered[range].iter_mut() {
+            *covered = false
+        }
+    }
+}
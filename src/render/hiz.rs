@@ -0,0 +1,105 @@
+
+use super::depth::DepthBuffer;
+
+// Pixels along each side of a block.
+pub const BLOCK_SIZE: usize = 8;
+
+// A coarse level over the depth buffer, holding for each block of pixels a
+// depth no nearer than the farthest stored in it. Where a triangle is no
+// nearer than that anywhere over the block, all its pixels there fail the
+// depth test, so the rasterizer skips the block instead of testing them one
+// by one.
+//
+// Drawing only brings depth nearer, which keeps the bounds valid whoever
+// draws. The rasterizer marks the blocks it wrote, and their bounds are
+// raised to the depth buffer again the next time they are needed.
+#[derive(Debug,Clone)]
+pub struct HiZ {
+    width: usize,
+    height: usize,
+    columns: usize,
+    farthest: Box<[f64]>,
+    // Blocks whose bound may be lower than their depth by now, or unknown.
+    stale: Box<[bool]>,
+}
+
+impl HiZ {
+    // All stale, to be read from the depth buffer when first needed.
+    pub fn new(width: usize, height: usize) -> HiZ {
+        let columns = width.div_ceil(BLOCK_SIZE);
+        let blocks = columns * height.div_ceil(BLOCK_SIZE);
+        HiZ {
+            width: width,
+            height: height,
+            columns: columns,
+            farthest: vec![0.0; blocks].into_boxed_slice(),
+            stale: vec![true; blocks].into_boxed_slice(),
+        }
+    }
+
+    // For a depth buffer cleared to 0.
+    pub fn clear(&mut self) {
+        for (farthest, stale) in self.farthest.iter_mut().zip(self.stale.iter_mut()) {
+            *farthest = 0.0;
+            *stale = false
+        }
+    }
+
+    // For a depth buffer changed in ways other than drawing, e.g. cleared
+    // in part.
+    pub fn invalidate(&mut self) {
+        for stale in self.stale.iter_mut() {
+            *stale = true
+        }
+    }
+
+    // Marks the block as written since its bound was taken.
+    pub fn mark(&mut self, column: usize, row: usize) {
+        self.stale[row * self.columns + column] = true
+    }
+
+    // The bound of the block, taken from the depth buffer first if stale.
+    pub fn farthest(&mut self, column: usize, row: usize, depth: &DepthBuffer) -> f64 {
+        let block = row * self.columns + column;
+        if self.stale[block] {
+            let x_end = ((column + 1) * BLOCK_SIZE).min(self.width);
+            let y_end = ((row + 1) * BLOCK_SIZE).min(self.height);
+            let mut farthest = f64::INFINITY;
+            for y in row * BLOCK_SIZE..y_end {
+                for x in column * BLOCK_SIZE..x_end {
+                    farthest = farthest.min(depth.get(y * self.width + x))
+                }
+            }
+            self.farthest[block] = farthest;
+            self.stale[block] = false
+        }
+        self.farthest[block]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::super::depth::{DepthBuffer, DepthFormat};
+    use super::{HiZ, BLOCK_SIZE};
+
+    #[test]
+    fn bounds() {
+        let (width, height) = (BLOCK_SIZE + 3, BLOCK_SIZE);
+        let mut depth = DepthBuffer::new(DepthFormat::F64, width * height);
+        let mut hiz = HiZ::new(width, height);
+        depth.fill(0.5);
+        depth.set(width + 2, 0.25);
+        assert_eq!((0.25, 0.5), (hiz.farthest(0, 0, &depth), hiz.farthest(1, 0, &depth)));
+
+        // Kept until marked, however the depth changes
+        depth.fill(0.75);
+        assert_eq!(0.25, hiz.farthest(0, 0, &depth));
+        hiz.mark(0, 0);
+        assert_eq!(0.75, hiz.farthest(0, 0, &depth));
+        hiz.clear();
+        assert_eq!(0.0, hiz.farthest(1, 0, &depth));
+        hiz.invalidate();
+        assert_eq!(0.75, hiz.farthest(1, 0, &depth));
+    }
+}
@@ -0,0 +1,109 @@
+
+use std::io;
+use std::io::Write;
+
+use png;
+
+use super::color::Color;
+
+pub fn argb_to_rgba(argb: &[u32]) -> Vec<u8> {
+    let mut rgba = Vec::with_capacity(argb.len() * 4);
+    for &pixel in argb {
+        let (r, g, b, a) = Color::from_argb8888(pixel).to_u8();
+        rgba.push(r);
+        rgba.push(g);
+        rgba.push(b);
+        rgba.push(a);
+    }
+    rgba
+}
+
+// Box filters each factor x factor block of an image into one pixel. An
+// sRGB encoded image is averaged in linear space, others as they are.
+pub fn downsample(argb: &[u32],
+                  width: usize,
+                  height: usize,
+                  factor: usize,
+                  srgb: bool)
+                  -> Vec<u32> {
+    let out_width = width / factor;
+    let out_height = height / factor;
+    let weight = 1.0 / (factor * factor) as f64;
+    let mut out = Vec::with_capacity(out_width * out_height);
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let mut sum = Color::new(0.0, 0.0, 0.0, 0.0);
+            for sy in 0..factor {
+                let row = (y * factor + sy) * width;
+                for sx in 0..factor {
+                    let pixel = Color::from_argb8888(argb[row + x * factor + sx]);
+                    sum = sum + if srgb { pixel.to_linear() } else { pixel };
+                }
+            }
+            let average = sum * weight;
+            out.push(if srgb { average.to_srgb() } else { average }.to_argb8888());
+        }
+    }
+    out
+}
+
+// Pixel data is expected as tightly packed RGBA8 rows, top row first, as
+// returned by Device::read_pixels.
+
+pub fn write_png<W: Write>(w: W, width: usize, height: usize, rgba: &[u8]) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)?;
+    Ok(())
+}
+
+// Binary (P6) PPM. The alpha channel is dropped.
+pub fn write_ppm<W: Write>(mut w: W, width: usize, height: usize, rgba: &[u8]) -> io::Result<()> {
+    write!(w, "P6\n{} {}\n255\n", width, height)?;
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    for pixel in rgba.chunks(4) {
+        rgb.extend_from_slice(&pixel[..3]);
+    }
+    w.write_all(&rgb)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use png;
+    use super::{downsample, write_png, write_ppm};
+
+    #[test]
+    fn ppm() {
+        let rgba = [1, 2, 3, 255, 4, 5, 6, 255];
+        let mut out = Vec::new();
+        write_ppm(&mut out, 2, 1, &rgba).unwrap();
+        assert_eq!(b"P6\n2 1\n255\n\x01\x02\x03\x04\x05\x06".to_vec(), out);
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let rgba = [1, 2, 3, 255, 4, 5, 6, 128, 7, 8, 9, 0, 10, 11, 12, 255];
+        let mut out = Vec::new();
+        write_png(&mut out, 2, 2, &rgba).unwrap();
+
+        let decoder = png::Decoder::new(&out[..]);
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((2, 2), (info.width, info.height));
+        assert_eq!(&rgba[..], &buf[..info.buffer_size()]);
+    }
+
+    #[test]
+    fn downsample_box_filter() {
+        let argb = [0xff000000, 0xffffffff, 0xff102030, 0xff102030,
+                    0xffffffff, 0xff000000, 0xff102030, 0xff102030];
+        let out = downsample(&argb, 4, 2, 2, true);
+        assert_eq!(vec![0xffbcbcbc, 0xff102030], out);
+        let out = downsample(&argb, 4, 2, 2, false);
+        assert_eq!(vec![0xff808080, 0xff102030], out);
+    }
+}
@@ -0,0 +1,238 @@
+
+use std::f64;
+
+use math::vector::Vector3;
+
+use super::color::Color;
+use super::material::{Diffuse, Shading, Surface};
+
+// Light shining equally in all directions from a point, fading out to
+// nothing at radius.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct PointLight {
+    pub position: Vector3,
+    pub color: Color,
+    pub intensity: f64,
+    pub radius: f64,
+}
+
+impl PointLight {
+    pub fn new(position: Vector3, color: Color, radius: f64) -> PointLight {
+        PointLight {
+            position: position,
+            color: color,
+            intensity: 1.0,
+            radius: radius,
+        }
+    }
+
+    // Unit direction from the world position to the light and the fraction
+    // of the light left at that distance, before the angle of the surface.
+    // None out of reach.
+    pub fn incident(&self, position: Vector3) -> Option<(Vector3, f64)> {
+        let to_light = self.position - position;
+        let distance = to_light.length();
+        if distance >= self.radius || distance == 0.0 {
+            return None;
+        }
+        let falloff = 1.0 - distance / self.radius;
+        Some((to_light * (1.0 / distance), falloff * falloff))
+    }
+
+    // Diffuse light reaching a surface at the world position facing along
+    // the unit normal.
+    pub fn irradiance(&self, position: Vector3, normal: Vector3) -> Color {
+        match self.incident(position) {
+            Some((direction, falloff)) => {
+                self.color * (normal.dot(direction).max(0.0) * falloff * self.intensity)
+            }
+            None => Color::new(0.0, 0.0, 0.0, 0.0),
+        }
+    }
+}
+
+// The albedo lit by the ambient color and the lights, keeping its alpha.
+pub fn illuminate(albedo: Color,
+                  position: Vector3,
+                  normal: Vector3,
+                  ambient: Color,
+                  lights: &[PointLight])
+                  -> Color {
+    let normal = if normal.length_sqr() > 0.0 { normal.normalize() } else { normal };
+    let light = lights.iter().fold(ambient, |sum, light| sum + light.irradiance(position, normal));
+    Color::new(albedo.r * light.r, albedo.g * light.g, albedo.b * light.b, albedo.a)
+}
+
+// The albedo lit as by illuminate, with the diffuse light of each light
+// rounded up to a whole number of bands between none and full.
+pub fn illuminate_toon(albedo: Color,
+                       position: Vector3,
+                       normal: Vector3,
+                       ambient: Color,
+                       lights: &[PointLight],
+                       bands: usize)
+                       -> Color {
+    let normal = if normal.length_sqr() > 0.0 { normal.normalize() } else { normal };
+    let bands = bands.max(1) as f64;
+    let light = lights.iter().fold(ambient, |sum, light| {
+        match light.incident(position) {
+            Some((direction, falloff)) => {
+                let diffuse = normal.dot(direction).max(0.0) * falloff;
+                sum + light.color * ((diffuse * bands).ceil() / bands * light.intensity)
+            }
+            None => sum,
+        }
+    });
+    Color::new(albedo.r * light.r, albedo.g * light.g, albedo.b * light.b, albedo.a)
+}
+
+// The albedo lit with the metallic-roughness model of the surface, seen
+// along the view direction from the eye. Lights are scaled so the Lambert
+// diffuse part matches illuminate, and the ambient light lights the albedo
+// as it does there.
+pub fn illuminate_pbr(albedo: Color,
+                      surface: &Surface,
+                      position: Vector3,
+                      normal: Vector3,
+                      view: Vector3,
+                      ambient: Color,
+                      lights: &[PointLight])
+                      -> Color {
+    if normal.length_sqr() == 0.0 || view.length_sqr() == 0.0 {
+        return illuminate(albedo, position, normal, ambient, lights);
+    }
+    let v = view.normalize() * -1.0;
+    let n = normal.normalize();
+    // Towards the eye, whichever way the faces are wound
+    let n = if n.dot(v) < 0.0 { n * -1.0 } else { n };
+    let n_v = n.dot(v).max(1e-4);
+
+    let metallic = surface.metallic;
+    let alpha = (surface.roughness * surface.roughness).max(1e-3);
+    let alpha2 = alpha * alpha;
+    let k = (surface.roughness + 1.0) * (surface.roughness + 1.0) / 8.0;
+    let white = Color::white();
+    // Reflectance head on, tinted by the albedo for metals
+    let f0 = Color::gray(0.04).lerp(albedo, metallic);
+
+    let mut light = ambient * albedo;
+    for point in lights {
+        let (l, radiance) = match point.incident(position) {
+            Some((l, falloff)) => (l, point.color * (falloff * point.intensity)),
+            None => continue,
+        };
+        let n_l = n.dot(l);
+        if n_l <= 0.0 {
+            continue;
+        }
+        let h = (l + v).normalize();
+        let (n_h, v_h) = (n.dot(h).max(0.0), v.dot(h).max(0.0));
+
+        // GGX distribution, Smith-Schlick geometry and Schlick Fresnel
+        let d = n_h * n_h * (alpha2 - 1.0) + 1.0;
+        let distribution = alpha2 / (f64::consts::PI * d * d);
+        let geometry = n_l / (n_l * (1.0 - k) + k) * (n_v / (n_v * (1.0 - k) + k));
+        let fresnel = f0 + (white - f0) * (1.0 - v_h).powi(5);
+        let specular = fresnel * (distribution * geometry / (4.0 * n_l * n_v));
+
+        let diffuse = match surface.shading {
+            Shading::Pbr(Diffuse::OrenNayar) => oren_nayar(n, l, v, surface.roughness),
+            _ => 1.0,
+        };
+        let diffuse = (white - fresnel) * albedo * ((1.0 - metallic) * diffuse);
+        light = light + (diffuse + specular * f64::consts::PI) * radiance * n_l;
+    }
+    Color::new(light.r, light.g, light.b, albedo.a)
+}
+
+// Oren-Nayar diffuse relative to Lambert, for the unit normal, light and
+// view directions, with the roughness as the spread of the facets in radians.
+fn oren_nayar(n: Vector3, l: Vector3, v: Vector3, roughness: f64) -> f64 {
+    let s2 = roughness * roughness;
+    let a = 1.0 - 0.5 * s2 / (s2 + 0.33);
+    let b = 0.45 * s2 / (s2 + 0.09);
+    let (n_l, n_v) = (n.dot(l).clamp(-1.0, 1.0), n.dot(v).clamp(-1.0, 1.0));
+    // Cosine of the angle between the light and view around the normal
+    let (lp, vp) = (l - n * n_l, v - n * n_v);
+    let cos_phi = if lp.length_sqr() > 0.0 && vp.length_sqr() > 0.0 {
+        lp.normalize().dot(vp.normalize()).max(0.0)
+    } else {
+        0.0
+    };
+    let (theta_l, theta_v) = (n_l.acos(), n_v.acos());
+    let (alpha, beta) = (theta_l.max(theta_v), theta_l.min(theta_v));
+    a + b * cos_phi * alpha.sin() * beta.tan()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::{Vector2, Vector3};
+    use super::super::color::Color;
+    use super::super::material::{Diffuse, Material, Shading};
+    use super::{illuminate, illuminate_pbr, illuminate_toon, PointLight};
+
+    #[test]
+    fn irradiance() {
+        let light = PointLight::new(Vector3::new(0.0, 2.0, 0.0), Color::white(), 4.0);
+        let up = Vector3::unit_y();
+        // Facing the light, half way to the radius
+        assert_eq!(Color::gray(0.25).r, light.irradiance(Vector3::zero(), up).r);
+        // Facing away, and out of reach
+        assert_eq!(0.0, light.irradiance(Vector3::zero(), up * -1.0).r);
+        assert_eq!(0.0, light.irradiance(Vector3::new(0.0, -3.0, 0.0), up).r);
+
+        let lights = [light, light];
+        let lit = illuminate(Color::new(1.0, 0.5, 0.0, 0.5),
+                             Vector3::zero(),
+                             up * 2.0,
+                             Color::gray(0.5),
+                             &lights);
+        assert_eq!(Color::new(1.0, 0.5, 0.0, 0.5), lit);
+    }
+    #[test]
+    fn toon() {
+        let lights = [PointLight::new(Vector3::zero(), Color::white(), 100.0)];
+        let toon = |x: f64| {
+            let position = Vector3::new(x, 0.0, -1.0);
+            let normal = Vector3::unit_z();
+            illuminate_toon(Color::white(), position, normal, Color::black(), &lights, 4).r
+        };
+        // Steps of a quarter, lit less and less away from the light
+        assert_eq!(1.0, toon(0.0));
+        assert_eq!(0.75, toon(1.5));
+        assert_eq!(0.5, toon(2.0));
+        assert_eq!(0.0, toon(-1.0e9));
+    }
+
+    #[test]
+    fn pbr() {
+        let lights = [PointLight::new(Vector3::new(0.0, 4.0, 0.0), Color::white(), 100.0)];
+        let (up, down) = (Vector3::unit_y(), Vector3::unit_y() * -1.0);
+        let black = Color::black();
+        let lit = |material: &Material, view: Vector3| {
+            let surface = material.surface(Vector2::zero());
+            illuminate_pbr(Color::gray(0.5), &surface, Vector3::zero(), up, view, black, &lights).r
+        };
+
+        // A rough dielectric is close to Lambert, a little more with the
+        // highlight
+        let basic = illuminate(Color::gray(0.5), Vector3::zero(), up, black, &lights).r;
+        let rough = lit(&rough_dielectric(), down);
+        assert!(rough > basic * 0.9 && rough < basic * 1.3, "{} {}", rough, basic);
+
+        // Polished metal shows the light in the mirror direction only
+        let metal = Material::pbr(Color::white(), 1.0, 0.2);
+        let mirrored = lit(&metal, down);
+        let aside = lit(&metal, Vector3::new(1.0, -1.0, 0.0));
+        assert!(mirrored > aside * 5.0, "{} {}", mirrored, aside);
+
+        // Oren-Nayar is darker head on
+        let clay = Material { shading: Shading::Pbr(Diffuse::OrenNayar), ..rough_dielectric() };
+        assert!(lit(&clay, down) < lit(&rough_dielectric(), down));
+    }
+
+    fn rough_dielectric() -> Material {
+        Material::pbr(Color::white(), 0.0, 1.0)
+    }
+}
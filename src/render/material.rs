@@ -0,0 +1,192 @@
+
+use math::vector::Vector2;
+
+use super::color::Color;
+use super::depth::DepthBias;
+use super::texture::Texture;
+
+// Diffuse reflection of the physically based model.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Diffuse {
+    // The same brightness from every direction.
+    Lambert,
+    // Rough surfaces like clay or cloth, flatter than Lambert and brighter
+    // towards the light, with the roughness as the spread of the facets.
+    OrenNayar,
+}
+
+// How lights shade a material.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Shading {
+    // Diffuse light only, as meshes have always been lit.
+    Basic,
+    // Metallic-roughness model with GGX specular highlights, as authored
+    // for glTF.
+    Pbr(Diffuse),
+    // Cartoon look, the light of each light stepped into flat bands.
+    Toon { bands: usize },
+}
+
+// Line drawn around a mesh where it stands out in front of what is behind
+// it, i.e. along its silhouette and the edges of parts in front of others.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Outline {
+    // As for the clear color.
+    pub color: Color,
+    // In pixels.
+    pub width: f64,
+}
+
+impl Outline {
+    pub fn new(color: Color, width: f64) -> Outline {
+        Outline {
+            color: color,
+            width: width,
+        }
+    }
+}
+
+// How a mesh's surface looks. The default gives the device's usual shading.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Material {
+    // Multiplies the surface color, in the space shading happens in.
+    pub color: Color,
+    // Sampled by the mesh's texture coordinates in place of the device's
+    // texture and the default shading.
+    pub texture: Option<Texture>,
+    // Tangent space normals bending the shading normal of meshes with
+    // tangents, x to the right and y up the texture, each mapped from
+    // [-1, 1] to [0, 1]. Texels are used as they are, not linearized.
+    pub normal_map: Option<Texture>,
+    // How much of the device's environment the surface mirrors, from 0 for
+    // none to 1 for a perfect mirror.
+    pub reflectivity: f64,
+    pub shading: Shading,
+    // From 0 for a dielectric to 1 for a metal, with Shading::Pbr.
+    pub metallic: f64,
+    // From 0 for a polished surface to 1 for a fully rough one, with
+    // Shading::Pbr.
+    pub roughness: f64,
+    // Scales roughness by the green channel and metallic by the blue, as in
+    // glTF. Texels are used as they are.
+    pub metallic_roughness_map: Option<Texture>,
+    // Ambient light reaching the surface in the red channel, as in glTF.
+    pub occlusion_map: Option<Texture>,
+    pub outline: Option<Outline>,
+    // Blended over what is behind by the alpha of its shaded color instead
+    // of hiding it, without writing depth. Drawn opaque with deferred
+    // shading. It takes drawing after the opaque meshes, back to front, as
+    // a RenderQueue does.
+    pub translucent: bool,
+    // Pulls the mesh towards the camera in the depth test, e.g. for a decal
+    // lying on another.
+    pub depth_bias: DepthBias,
+}
+
+// The parameters of a material at one point of a surface, with its textures
+// sampled.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Surface {
+    pub shading: Shading,
+    pub metallic: f64,
+    pub roughness: f64,
+    // Scale for the ambient light.
+    pub occlusion: f64,
+    pub reflectivity: f64,
+}
+
+impl Material {
+    pub fn new() -> Material {
+        Material {
+            color: Color::white(),
+            texture: None,
+            normal_map: None,
+            reflectivity: 0.0,
+            shading: Shading::Basic,
+            metallic: 0.0,
+            roughness: 0.5,
+            metallic_roughness_map: None,
+            occlusion_map: None,
+            outline: None,
+            translucent: false,
+            depth_bias: DepthBias::default(),
+        }
+    }
+
+    pub fn reflective(reflectivity: f64) -> Material {
+        Material { reflectivity: reflectivity, ..Material::new() }
+    }
+
+    // Physically based with Lambert diffuse.
+    pub fn pbr(color: Color, metallic: f64, roughness: f64) -> Material {
+        Material {
+            color: color,
+            shading: Shading::Pbr(Diffuse::Lambert),
+            metallic: metallic,
+            roughness: roughness,
+            ..Material::new()
+        }
+    }
+
+    // Toon shaded in the number of bands, outlined in black.
+    pub fn toon(color: Color, bands: usize) -> Material {
+        Material {
+            color: color,
+            shading: Shading::Toon { bands: bands },
+            outline: Some(Outline::new(Color::black(), 1.0)),
+            ..Material::new()
+        }
+    }
+
+    // The material at the texture coordinates.
+    pub fn surface(&self, uv: Vector2) -> Surface {
+        let (roughness, metallic) = match self.metallic_roughness_map {
+            Some(ref map) => {
+                let texel = map.sample(uv);
+                (self.roughness * texel.g, self.metallic * texel.b)
+            }
+            None => (self.roughness, self.metallic),
+        };
+        Surface {
+            shading: self.shading,
+            metallic: metallic.clamp(0.0, 1.0),
+            roughness: roughness.clamp(0.0, 1.0),
+            occlusion: self.occlusion_map.as_ref().map_or(1.0, |map| map.sample(uv).r),
+            reflectivity: self.reflectivity,
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material::new()
+    }
+}
+
+impl Default for Surface {
+    fn default() -> Surface {
+        Material::new().surface(Vector2::zero())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector2;
+    use super::super::color::Color;
+    use super::super::texture::Texture;
+    use super::Material;
+
+    #[test]
+    fn surface() {
+        let mut material = Material::pbr(Color::white(), 1.0, 0.8);
+        let uv = Vector2::new(0.5, 0.5);
+        assert_eq!(1.0, material.surface(uv).metallic);
+        assert_eq!(1.0, material.surface(uv).occlusion);
+
+        material.metallic_roughness_map = Some(Texture::filled(1, 1, Color::rgb(0.0, 0.5, 0.25)));
+        material.occlusion_map = Some(Texture::filled(1, 1, Color::rgb(0.75, 0.0, 0.0)));
+        let surface = material.surface(uv);
+        assert_eq!((0.25, 0.4, 0.75), (surface.metallic, surface.roughness, surface.occlusion));
+    }
+}
@@ -0,0 +1,30 @@
+pub mod arena;
+pub mod color;
+pub mod tonemap;
+pub mod image;
+pub mod viewport;
+pub mod texture;
+pub mod material;
+pub mod depth;
+pub mod hiz;
+pub mod tiles;
+pub mod target;
+pub mod shadow;
+pub mod light;
+pub mod gbuffer;
+pub mod ssao;
+pub mod occlusion;
+pub mod fog;
+pub mod clip;
+pub mod debug;
+pub mod post;
+pub mod fxaa;
+pub mod font;
+pub mod stats;
+pub mod particles;
+pub mod raymarch;
+pub mod sprite;
+pub mod queue;
+pub mod device;
+pub mod offline;
+pub mod recorder;
@@ -0,0 +1,200 @@
+
+use math::matrix::Matrix4;
+use math::vector::{Vector2, Vector3};
+
+use geometry::mesh::Mesh;
+
+use super::viewport::Viewport;
+
+// A small depth buffer of big meshes in front, e.g. walls and terrain,
+// filled by Device::render_occluders. Rendering from the same camera skips
+// meshes whose bounding box lies behind it everywhere it covers, before
+// their vertices are transformed. Occluders are sampled at pixel centers,
+// so boxes are tested against a pixel more on each side, which also holds
+// the depth between the centers.
+#[derive(Debug,Clone)]
+pub struct OcclusionBuffer {
+    width: usize,
+    height: usize,
+    // 0 where nothing is in front, larger nearer as for the depth buffer.
+    depth: Box<[f64]>,
+    view_projection: Matrix4,
+}
+
+impl OcclusionBuffer {
+    // A quarter of the frame along each side or less does.
+    pub fn new(width: usize, height: usize) -> OcclusionBuffer {
+        OcclusionBuffer {
+            width: width,
+            height: height,
+            depth: vec![0.0; width * height].into_boxed_slice(),
+            view_projection: Matrix4::identity(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn depth(&self) -> &[f64] {
+        &self.depth
+    }
+
+    pub fn view_projection(&self) -> Matrix4 {
+        self.view_projection
+    }
+
+    // Empties the buffer for occluders seen through the matrix.
+    pub fn clear(&mut self, view_projection: Matrix4) {
+        for d in self.depth.iter_mut() {
+            *d = 0.0
+        }
+        self.view_projection = view_projection
+    }
+
+    fn viewport(&self) -> Viewport {
+        Viewport::new(0.0, 0.0, self.width as f64, self.height as f64)
+    }
+
+    // Draws the faces of the mesh placed by the world matrix, either way
+    // round. Faces reaching behind the near plane are left out.
+    pub fn draw(&mut self, mesh: &Mesh, world: &Matrix4) {
+        let transform = *world * self.view_projection;
+        let viewport = self.viewport();
+        let deformed;
+        let vertices = if mesh.is_deformed() {
+            deformed = mesh.deformed_vertices();
+            &deformed
+        } else {
+            &mesh.vertices
+        };
+        for face in mesh.faces() {
+            let corners = [face.a, face.b, face.c];
+            let mut screen = [Vector3::zero(); 3];
+            let mut visible = true;
+            for (s, &i) in screen.iter_mut().zip(corners.iter()) {
+                let clip = match vertices.get(i as usize) {
+                    Some(vertex) => Vector3::transform(&vertex.position, &transform),
+                    None => {
+                        visible = false;
+                        break;
+                    }
+                };
+                let ndc = clip.xyz() / clip.w;
+                if clip.w <= 0.0 || ndc.z > 1.0 {
+                    visible = false;
+                    break;
+                }
+                *s = viewport.project(ndc)
+            }
+            if visible {
+                self.triangle(&screen)
+            }
+        }
+    }
+
+    fn triangle(&mut self, v: &[Vector3; 3]) {
+        let (a, b, c) = (v[0].xy(), v[1].xy(), v[2].xy());
+        let area = (b - a).cross(c - a);
+        if area == 0.0 {
+            return;
+        }
+        let min = a.min(b).min(c).max(Vector2::zero());
+        let max = a.max(b).max(c).min(Vector2::new(self.width as f64, self.height as f64));
+        for y in min.y.floor() as usize..max.y.ceil() as usize {
+            for x in min.x.floor() as usize..max.x.ceil() as usize {
+                let p = Vector2::new(x as f64 + 0.5, y as f64 + 0.5);
+                let w0 = (b - p).cross(c - p) / area;
+                let w1 = (c - p).cross(a - p) / area;
+                let w2 = (a - p).cross(b - p) / area;
+                if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                    continue;
+                }
+                let z = w0 * v[0].z + w1 * v[1].z + w2 * v[2].z;
+                let offset = y * self.width + x;
+                if z > self.depth[offset] {
+                    self.depth[offset] = z
+                }
+            }
+        }
+    }
+
+    // Whether the box, placed by the matrix taking it to clip space, is
+    // behind the occluders wherever it would be on screen.
+    pub fn is_occluded(&self, min: Vector3, max: Vector3, world_view_projection: &Matrix4) -> bool {
+        let viewport = self.viewport();
+        let mut screen_min = Vector2::new(f64::INFINITY, f64::INFINITY);
+        let mut screen_max = Vector2::new(f64::NEG_INFINITY, f64::NEG_INFINITY);
+        let mut nearest = f64::NEG_INFINITY;
+        for i in 0..8 {
+            let corner = Vector3::new(if i & 1 == 0 { min.x } else { max.x },
+                                      if i & 2 == 0 { min.y } else { max.y },
+                                      if i & 4 == 0 { min.z } else { max.z });
+            let clip = Vector3::transform(&corner, world_view_projection);
+            // Reaching past the camera, it may cover anything
+            if clip.w <= 0.0 {
+                return false;
+            }
+            let p = viewport.project(clip.xyz() / clip.w);
+            screen_min = screen_min.min(p.xy());
+            screen_max = screen_max.max(p.xy());
+            nearest = nearest.max(p.z)
+        }
+
+        // Only the part on screen can be seen
+        let size = Vector2::new(self.width as f64, self.height as f64);
+        if screen_min.x >= size.x || screen_min.y >= size.y || screen_max.x <= 0.0 ||
+           screen_max.y <= 0.0 {
+            return false;
+        }
+        let one = Vector2::new(1.0, 1.0);
+        let screen_min = (screen_min - one).max(Vector2::zero());
+        let screen_max = (screen_max + one).min(size);
+        for y in screen_min.y.floor() as usize..screen_max.y.ceil() as usize {
+            for x in screen_min.x.floor() as usize..screen_max.x.ceil() as usize {
+                if self.depth[y * self.width + x] <= nearest {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::matrix::Matrix4;
+    use math::vector::Vector3;
+    use geometry::mesh::Mesh;
+    use super::OcclusionBuffer;
+
+    #[test]
+    fn occluded() {
+        let view_projection = Matrix4::look_at_rh(Vector3::new(0.0, 0.0, 10.0),
+                                                  Vector3::zero(),
+                                                  Vector3::unit_y()) *
+                              Matrix4::perspective_rh(1.0, 1.0, 0.1, 100.0);
+        let mut occlusion = OcclusionBuffer::new(32, 32);
+        occlusion.clear(view_projection);
+        let wall = Matrix4::scale(Vector3::new(2.0, 2.0, 0.1)) *
+                   Matrix4::translation(Vector3::new(0.0, 0.0, 4.0));
+        occlusion.draw(&Mesh::cube(), &wall);
+        assert!(occlusion.depth().iter().any(|&d| d > 0.0));
+
+        let hidden = |position: Vector3| {
+            occlusion.is_occluded(Vector3::new(-0.5, -0.5, -0.5),
+                                  Vector3::new(0.5, 0.5, 0.5),
+                                  &(Matrix4::translation(position) * view_projection))
+        };
+        assert!(hidden(Vector3::zero()));
+        // In front of the wall, beside it, or reaching past the camera
+        assert!(!hidden(Vector3::new(0.0, 0.0, 6.0)));
+        assert!(!hidden(Vector3::new(2.5, 0.0, 0.0)));
+        assert!(!hidden(Vector3::new(0.0, 0.0, 10.0)));
+    }
+}
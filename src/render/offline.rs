@@ -0,0 +1,170 @@
+
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::path::Path;
+
+use geometry::mesh::Mesh;
+
+use super::color::Color;
+use super::device::{Camera, Device};
+use super::image;
+use super::tonemap::ToneMapping;
+use super::viewport::Viewport;
+
+// Renders single frames at arbitrary resolutions, independent of any window.
+// The frame is rendered in tiles so memory use is bounded by the tile size
+// rather than the (supersampled) output resolution. With accumulation above
+// 1 every tile is rendered that many times with jittered projections and
+// averaged, smoothing edges further without more memory.
+#[derive(Debug,Clone)]
+pub struct OfflineRender {
+    pub width: usize,
+    pub height: usize,
+    pub supersampling: usize,
+    pub accumulation: usize,
+    pub tile_size: usize,
+    pub clear_color: Color,
+    pub gamma_correct: bool,
+    pub tone_mapping: Option<ToneMapping>,
+    pub exposure: f64,
+}
+
+impl OfflineRender {
+    pub fn new(width: usize, height: usize) -> OfflineRender {
+        OfflineRender {
+            width: width,
+            height: height,
+            supersampling: 2,
+            accumulation: 1,
+            tile_size: 256,
+            clear_color: Color::black(),
+            gamma_correct: true,
+            tone_mapping: None,
+            exposure: 1.0,
+        }
+    }
+
+    // Returns the final image as ARGB8888 pixels, top row first.
+    pub fn render(&self, camera: &Camera, meshes: &[&Mesh]) -> Vec<u32> {
+        self.render_with(|device, viewport| device.render(camera, viewport, meshes))
+    }
+
+    // The same with draw rendering each tile, given the viewport of the
+    // whole frame, e.g. to set up the device for a scene and render it.
+    pub fn render_with<F>(&self, draw: F) -> Vec<u32>
+        where F: Fn(&mut Device, &Viewport)
+    {
+        let ss = self.supersampling.max(1);
+        let tile_size = self.tile_size.max(1);
+        let mut pixels = vec![0; self.width * self.height];
+
+        for ty in (0..self.height).step_by(tile_size) {
+            for tx in (0..self.width).step_by(tile_size) {
+                let tile_width = tile_size.min(self.width - tx);
+                let tile_height = tile_size.min(self.height - ty);
+
+                let mut device = Device::new(tile_width * ss, tile_height * ss);
+                device.gamma_correct = self.gamma_correct;
+                if let Some(tone_mapping) = self.tone_mapping {
+                    device.set_hdr(true);
+                    device.tone_mapping = tone_mapping;
+                    device.exposure = self.exposure;
+                }
+                // The whole frame, shifted so this tile lands on the device
+                let viewport = Viewport::new(-((tx * ss) as f64),
+                                             -((ty * ss) as f64),
+                                             (self.width * ss) as f64,
+                                             (self.height * ss) as f64);
+                device.set_accumulation(self.accumulation > 1);
+                for _ in 0..self.accumulation.max(1) {
+                    device.clear(self.clear_color);
+                    draw(&mut device, &viewport);
+                    device.resolve();
+                }
+
+                let tile = image::downsample(device.backbuffer(),
+                                             device.width(),
+                                             device.height(),
+                                             ss,
+                                             self.gamma_correct);
+                for y in 0..tile_height {
+                    let src = &tile[y * tile_width..(y + 1) * tile_width];
+                    let offset = (ty + y) * self.width + tx;
+                    pixels[offset..offset + tile_width].copy_from_slice(src);
+                }
+            }
+        }
+
+        pixels
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self,
+                                    camera: &Camera,
+                                    meshes: &[&Mesh],
+                                    path: P)
+                                    -> io::Result<()> {
+        self.save_png_with(path, |device, viewport| device.render(camera, viewport, meshes))
+    }
+
+    pub fn save_png_with<P, F>(&self, path: P, draw: F) -> io::Result<()>
+        where P: AsRef<Path>,
+              F: Fn(&mut Device, &Viewport)
+    {
+        let pixels = image::argb_to_rgba(&self.render_with(draw));
+        let file = BufWriter::new(File::create(path)?);
+        image::write_png(file, self.width, self.height, &pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::f64;
+    use math::vector::Vector3;
+    use geometry::mesh::Mesh;
+    use super::super::color::Color;
+    use super::super::device::{Camera, Device, Handedness, Projection};
+    use super::OfflineRender;
+
+    fn camera() -> Camera {
+        Camera {
+            position: Vector3::new(0.0, 0.0, 15.0),
+            target: Vector3::zero(),
+            fov: 45.0 * f64::consts::PI / 180.0,
+            znear: 0.01,
+            zfar: 100.0,
+            projection: Projection::Perspective,
+            handedness: Handedness::Right,
+        }
+    }
+
+    #[test]
+    fn tiles_match_single_pass() {
+        let mesh = Mesh::triangle();
+        let mut device = Device::new(50, 30);
+        device.clear(Color::black());
+        device.render(&camera(), &device.viewport(), &[&mesh]);
+
+        let mut offline = OfflineRender::new(50, 30);
+        offline.supersampling = 1;
+        offline.tile_size = 16;
+        let pixels = offline.render(&camera(), &[&mesh]);
+
+        // Pixel centers lying exactly on an edge may land on either side
+        // depending on rounding of the tile offset.
+        let differing = device.backbuffer()
+            .iter()
+            .zip(pixels.iter())
+            .filter(|&(a, b)| a != b)
+            .count();
+        assert!(differing < 10);
+        assert!(pixels.iter().any(|&p| p != Color::black().to_argb8888()));
+
+        // Drawing the same through render_with gives the same
+        let drawn = offline.render_with(|device, viewport| {
+            device.render(&camera(), viewport, &[&mesh])
+        });
+        assert_eq!(pixels, drawn);
+    }
+}
@@ -0,0 +1,238 @@
+
+use math::vector::Vector3;
+
+use super::color::Color;
+
+// How Device::render_particles draws each particle.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum ParticleStyle {
+    // Squares facing the camera, size world units wide, so they shrink with
+    // distance.
+    Quads,
+    // Squares of this many presented pixels at any distance.
+    Points(f64),
+}
+
+// Spawns particles at a steady rate from its position, each flying off with
+// the velocity plus a random part up to spread long, and fading from the
+// start to the end color, alpha included, over its lifetime.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Emitter {
+    pub position: Vector3,
+    // Particles per second.
+    pub rate: f64,
+    // Seconds.
+    pub lifetime: f64,
+    pub velocity: Vector3,
+    pub spread: f64,
+    pub size: f64,
+    pub start_color: Color,
+    pub end_color: Color,
+}
+
+impl Emitter {
+    pub fn new(position: Vector3) -> Emitter {
+        Emitter {
+            position: position,
+            rate: 50.0,
+            lifetime: 2.0,
+            velocity: Vector3::new(0.0, 2.0, 0.0),
+            spread: 1.0,
+            size: 0.1,
+            start_color: Color::white(),
+            end_color: Color::new(1.0, 1.0, 1.0, 0.0),
+        }
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Particle {
+    pub position: Vector3,
+    pub velocity: Vector3,
+    // Seconds since it was spawned.
+    pub age: f64,
+    pub lifetime: f64,
+    pub size: f64,
+    pub start_color: Color,
+    pub end_color: Color,
+}
+
+impl Particle {
+    // The color at its age.
+    pub fn color(&self) -> Color {
+        self.start_color.lerp(self.end_color, (self.age / self.lifetime).min(1.0))
+    }
+}
+
+// Particles simulated on the CPU, moved by their velocity and gravity and
+// removed at the end of their lifetime. Call update once a frame with the
+// time since the last one.
+#[derive(Debug,Clone)]
+pub struct ParticleSystem {
+    pub emitters: Vec<Emitter>,
+    pub gravity: Vector3,
+    // Emitters wait while this many particles are alive.
+    pub max_particles: usize,
+    pub style: ParticleStyle,
+    particles: Vec<Particle>,
+    // Fraction of a particle each emitter is owed, carried between updates
+    owed: Vec<f64>,
+    seed: u32,
+}
+
+impl ParticleSystem {
+    pub fn new() -> ParticleSystem {
+        ParticleSystem {
+            emitters: Vec::new(),
+            gravity: Vector3::new(0.0, -9.81, 0.0),
+            max_particles: 10000,
+            style: ParticleStyle::Quads,
+            particles: Vec::new(),
+            owed: Vec::new(),
+            seed: 0x9e37_79b9,
+        }
+    }
+
+    pub fn add_emitter(&mut self, emitter: Emitter) {
+        self.emitters.push(emitter)
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.owed.clear();
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        let gravity = self.gravity;
+        for p in &mut self.particles {
+            p.age += dt;
+            p.velocity = p.velocity + gravity * dt;
+            p.position = p.position + p.velocity * dt;
+        }
+        self.particles.retain(|p| p.age < p.lifetime);
+
+        self.owed.resize(self.emitters.len(), 0.0);
+        for i in 0..self.emitters.len() {
+            let emitter = self.emitters[i];
+            self.owed[i] += emitter.rate * dt;
+            while self.owed[i] >= 1.0 {
+                self.owed[i] -= 1.0;
+                if self.particles.len() >= self.max_particles {
+                    continue;
+                }
+                let velocity = emitter.velocity + self.random_in_ball() * emitter.spread;
+                self.particles.push(Particle {
+                    position: emitter.position,
+                    velocity: velocity,
+                    age: 0.0,
+                    lifetime: emitter.lifetime,
+                    size: emitter.size,
+                    start_color: emitter.start_color,
+                    end_color: emitter.end_color,
+                });
+            }
+        }
+    }
+
+    // Xorshift, in [0, 1).
+    fn random(&mut self) -> f64 {
+        let mut x = self.seed;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.seed = x;
+        x as f64 / (u32::MAX as f64 + 1.0)
+    }
+
+    // Uniform inside the unit sphere, by rejection.
+    fn random_in_ball(&mut self) -> Vector3 {
+        loop {
+            let p = Vector3::new(self.random() * 2.0 - 1.0,
+                                 self.random() * 2.0 - 1.0,
+                                 self.random() * 2.0 - 1.0);
+            if p.dot(p) <= 1.0 {
+                return p;
+            }
+        }
+    }
+}
+
+impl Default for ParticleSystem {
+    fn default() -> ParticleSystem {
+        ParticleSystem::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use super::super::color::Color;
+    use super::{Emitter, ParticleSystem};
+
+    #[test]
+    fn emit_and_expire() {
+        let mut system = ParticleSystem::new();
+        let mut emitter = Emitter::new(Vector3::zero());
+        emitter.rate = 4.0;
+        emitter.lifetime = 1.0;
+        emitter.spread = 0.5;
+        system.add_emitter(emitter);
+        // Rates below a particle a frame add up
+        system.update(0.125);
+        assert!(system.is_empty());
+        system.update(0.125);
+        assert_eq!(1, system.len());
+        // As many expire as are spawned
+        for _ in 0..14 {
+            system.update(0.125);
+        }
+        assert_eq!(4, system.len());
+        for p in system.particles() {
+            assert!((p.velocity - emitter.velocity).length() <= 0.5 + 9.81);
+            assert!(p.age < 1.0);
+        }
+
+        system.max_particles = 4;
+        system.clear();
+        system.update(1.0);
+        assert_eq!(4, system.len());
+        system.emitters.clear();
+        system.update(1.0);
+        assert!(system.is_empty());
+    }
+
+    #[test]
+    fn motion_and_color() {
+        let mut system = ParticleSystem::new();
+        let mut emitter = Emitter::new(Vector3::zero());
+        emitter.rate = 1.0;
+        emitter.spread = 0.0;
+        emitter.velocity = Vector3::new(1.0, 0.0, 0.0);
+        emitter.start_color = Color::rgb(1.0, 0.0, 0.0);
+        emitter.end_color = Color::new(0.0, 0.0, 1.0, 0.0);
+        system.add_emitter(emitter);
+        system.gravity = Vector3::new(0.0, -2.0, 0.0);
+        system.update(1.0);
+        assert_eq!(Vector3::zero(), system.particles()[0].position);
+        system.emitters.clear();
+        system.update(0.5);
+        system.update(0.5);
+        let p = system.particles()[0];
+        assert_eq!(Vector3::new(1.0, -1.5, 0.0), p.position);
+        assert_eq!(Vector3::new(1.0, -2.0, 0.0), p.velocity);
+        assert_eq!(Color::new(0.5, 0.0, 0.5, 0.5), p.color());
+    }
+}
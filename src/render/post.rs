@@ -0,0 +1,332 @@
+
+use std::fmt;
+
+use math::vector::Vector2;
+
+use super::color::Color;
+use super::depth::DepthBuffer;
+
+// The frame a post process works on, in the color space shading happens in
+// and before tone mapping, so HDR colors may exceed 1. Depth is as written
+// by the device, 0 where nothing was drawn.
+pub struct Frame<'a> {
+    pub width: usize,
+    pub height: usize,
+    pub color: &'a mut [Color],
+    pub depth: &'a DepthBuffer,
+}
+
+// Color at the pixel nearest to (x, y), clamped to the edges of the image.
+fn nearest(color: &[Color], width: usize, height: usize, x: f64, y: f64) -> Color {
+    let x = x.round().max(0.0).min(width as f64 - 1.0) as usize;
+    let y = y.round().max(0.0).min(height as f64 - 1.0) as usize;
+    color[y * width + x]
+}
+
+impl<'a> Frame<'a> {
+    pub fn get(&self, x: f64, y: f64) -> Color {
+        nearest(self.color, self.width, self.height, x, y)
+    }
+
+    // Offset of the pixel from the center of the frame, scaled so the
+    // corners are at distance 1.
+    fn centered(&self, x: usize, y: usize) -> Vector2 {
+        let half = Vector2::new(self.width as f64, self.height as f64) * 0.5;
+        let d = Vector2::new(x as f64 + 0.5, y as f64 + 0.5) - half;
+        d * (1.0 / half.length())
+    }
+}
+
+// A full screen pass over the frame, run by Device::resolve.
+pub trait PostProcess {
+    fn apply(&self, frame: &mut Frame);
+}
+
+impl<F: Fn(&mut Frame)> PostProcess for F {
+    fn apply(&self, frame: &mut Frame) {
+        self(frame)
+    }
+}
+
+// Post processes applied in the order they were added.
+#[derive(Default)]
+pub struct PostChain {
+    passes: Vec<Box<dyn PostProcess>>,
+}
+
+impl PostChain {
+    pub fn new() -> PostChain {
+        PostChain { passes: Vec::new() }
+    }
+
+    pub fn then<P: PostProcess + 'static>(mut self, pass: P) -> PostChain {
+        self.push(pass);
+        self
+    }
+
+    pub fn push<P: PostProcess + 'static>(&mut self, pass: P) {
+        self.passes.push(Box::new(pass))
+    }
+
+    pub fn len(&self) -> usize {
+        self.passes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.passes.clear()
+    }
+
+    pub fn apply(&self, frame: &mut Frame) {
+        for pass in &self.passes {
+            pass.apply(frame)
+        }
+    }
+}
+
+impl fmt::Debug for PostChain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PostChain").field("passes", &self.passes.len()).finish()
+    }
+}
+
+// Inverts colors clamped to [0, 1].
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Invert;
+
+impl PostProcess for Invert {
+    fn apply(&self, frame: &mut Frame) {
+        for c in frame.color.iter_mut() {
+            let v = c.clamp();
+            *c = Color::new(1.0 - v.r, 1.0 - v.g, 1.0 - v.b, c.a)
+        }
+    }
+}
+
+// Replaces colors by their luminance, with the Rec. 709 weights.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Grayscale;
+
+impl PostProcess for Grayscale {
+    fn apply(&self, frame: &mut Frame) {
+        for c in frame.color.iter_mut() {
+            let y = c.r * 0.2126 + c.g * 0.7152 + c.b * 0.0722;
+            *c = Color::new(y, y, y, c.a)
+        }
+    }
+}
+
+// Darkens towards the corners. Pixels within radius of the center, where
+// the corners are at 1, are left as they are, and the corners are darkened
+// by strength.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Vignette {
+    pub radius: f64,
+    pub strength: f64,
+}
+
+impl PostProcess for Vignette {
+    fn apply(&self, frame: &mut Frame) {
+        let falloff = (1.0 - self.radius).max(1e-6);
+        for y in 0..frame.height {
+            for x in 0..frame.width {
+                let d = frame.centered(x, y).length();
+                let t = ((d - self.radius) / falloff).clamp(0.0, 1.0);
+                let i = y * frame.width + x;
+                let c = frame.color[i];
+                let scaled = c * (1.0 - self.strength * t * t);
+                frame.color[i] = Color::new(scaled.r, scaled.g, scaled.b, c.a)
+            }
+        }
+    }
+}
+
+// Splits red and blue apart towards the edges, as a lens focusing them
+// differently would. Offset is the distance in pixels between the channels
+// and green at the corners.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct ChromaticAberration {
+    pub offset: f64,
+}
+
+impl PostProcess for ChromaticAberration {
+    fn apply(&self, frame: &mut Frame) {
+        let source = frame.color.to_vec();
+        let (width, height) = (frame.width, frame.height);
+        for y in 0..height {
+            for x in 0..width {
+                let d = frame.centered(x, y) * self.offset;
+                let (fx, fy) = (x as f64, y as f64);
+                let i = y * width + x;
+                frame.color[i].r = nearest(&source, width, height, fx + d.x, fy + d.y).r;
+                frame.color[i].b = nearest(&source, width, height, fx - d.x, fy - d.y).b;
+            }
+        }
+    }
+}
+
+// Glow around colors brighter than threshold, most visible with HDR where
+// colors go past 1. The part above the threshold is blurred over radius
+// pixels and added back scaled by intensity.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Bloom {
+    pub threshold: f64,
+    pub intensity: f64,
+    pub radius: f64,
+}
+
+impl PostProcess for Bloom {
+    fn apply(&self, frame: &mut Frame) {
+        let bright: Vec<Color> = frame.color
+            .iter()
+            .map(|c| {
+                Color::new((c.r - self.threshold).max(0.0),
+                           (c.g - self.threshold).max(0.0),
+                           (c.b - self.threshold).max(0.0),
+                           0.0)
+            })
+            .collect();
+        let glow = blur(&bright, frame.width, frame.height, self.radius);
+        for (c, g) in frame.color.iter_mut().zip(glow.iter()) {
+            *c = *c + *g * self.intensity
+        }
+    }
+}
+
+// Weights of a gaussian reaching to radius at three standard deviations,
+// from the center out, summing to 1 over both sides.
+fn gaussian_kernel(radius: f64) -> Vec<f64> {
+    let size = radius.ceil().max(0.0) as usize;
+    let sigma = (radius / 3.0).max(1e-6);
+    let weights: Vec<f64> = (0..size + 1)
+        .map(|i| (-((i * i) as f64) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum = weights[0] + weights[1..].iter().sum::<f64>() * 2.0;
+    weights.iter().map(|w| w / sum).collect()
+}
+
+// Separable gaussian blur, clamping to the edges of the image.
+fn blur(color: &[Color], width: usize, height: usize, radius: f64) -> Vec<Color> {
+    let kernel = gaussian_kernel(radius);
+    let pass = |source: &[Color], dx: i64, dy: i64| {
+        let mut out = Vec::with_capacity(source.len());
+        for y in 0..height as i64 {
+            for x in 0..width as i64 {
+                let mut sum = source[(y * width as i64 + x) as usize] * kernel[0];
+                for (i, &w) in kernel.iter().enumerate().skip(1) {
+                    let i = i as i64;
+                    for &s in &[-1, 1] {
+                        let sx = (x + dx * i * s).max(0).min(width as i64 - 1);
+                        let sy = (y + dy * i * s).max(0).min(height as i64 - 1);
+                        sum = sum + source[(sy * width as i64 + sx) as usize] * w;
+                    }
+                }
+                out.push(sum);
+            }
+        }
+        out
+    };
+    let horizontal = pass(color, 1, 0);
+    pass(&horizontal, 0, 1)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::super::color::Color;
+    use super::super::depth::{DepthBuffer, DepthFormat};
+    use super::{Bloom, ChromaticAberration, Frame, Grayscale, Invert, PostChain, Vignette};
+    use super::gaussian_kernel;
+
+    fn apply(chain: &PostChain, color: &mut [Color], width: usize) {
+        let depth = DepthBuffer::new(DepthFormat::F64, color.len());
+        let height = color.len() / width;
+        chain.apply(&mut Frame {
+            width: width,
+            height: height,
+            color: color,
+            depth: &depth,
+        })
+    }
+
+    #[test]
+    fn chain() {
+        // Runs in order, custom passes included
+        let chain = PostChain::new()
+            .then(Invert)
+            .then(|frame: &mut Frame| for c in frame.color.iter_mut() {
+                *c = *c * 0.5
+            });
+        let mut color = vec![Color::rgb(1.0, 0.0, 0.5), Color::rgb(4.0, -1.0, 0.0)];
+        apply(&chain, &mut color, 2);
+        assert_eq!(Color::new(0.0, 0.5, 0.25, 0.5), color[0]);
+        assert_eq!(Color::new(0.0, 0.5, 0.5, 0.5), color[1]);
+
+        let mut color = vec![Color::rgb(0.0, 1.0, 0.0)];
+        apply(&PostChain::new().then(Grayscale), &mut color, 1);
+        assert!((color[0].r - 0.7152).abs() < 1e-9);
+        assert_eq!(color[0].r, color[0].b);
+        assert_eq!(1.0, color[0].a);
+    }
+
+    #[test]
+    fn vignette() {
+        let vignette = Vignette {
+            radius: 0.5,
+            strength: 1.0,
+        };
+        let mut color = vec![Color::white(); 15 * 15];
+        apply(&PostChain::new().then(vignette), &mut color, 15);
+        // The center stays, the corners fade to black
+        assert_eq!(Color::white(), color[7 * 15 + 7]);
+        assert!(color[0].r < 0.5 && color[0].r < color[15 + 1].r);
+        assert_eq!(1.0, color[0].a);
+    }
+
+    #[test]
+    fn chromatic_aberration() {
+        // A white bar right of the center of a 21x1 frame
+        let mut color = vec![Color::black(); 21];
+        for c in &mut color[14..17] {
+            *c = Color::white()
+        }
+        apply(&PostChain::new().then(ChromaticAberration { offset: 4.0 }),
+              &mut color,
+              21);
+        // Green stays, red moves towards the center and blue away from it
+        assert!(color.iter().enumerate().all(|(i, c)| (c.g == 1.0) == (14..17).contains(&i)));
+        assert_eq!(1.0, color[13].r);
+        assert_eq!(0.0, color[16].r);
+        assert_eq!(0.0, color[14].b);
+        assert_eq!(1.0, color[20].b);
+    }
+
+    #[test]
+    fn bloom() {
+        let kernel = gaussian_kernel(3.0);
+        assert_eq!(4, kernel.len());
+        assert!((kernel[0] + 2.0 * kernel[1..].iter().sum::<f64>() - 1.0).abs() < 1e-9);
+
+        let bloom = Bloom {
+            threshold: 1.0,
+            intensity: 1.0,
+            radius: 3.0,
+        };
+        // A bright pixel in the middle of a dim 11x11 frame
+        let mut color = vec![Color::gray(0.5); 11 * 11];
+        color[5 * 11 + 5] = Color::gray(10.0);
+        apply(&PostChain::new().then(bloom), &mut color, 11);
+
+        // Glows evenly around it, up to the radius
+        let at = |x: usize, y: usize| color[y * 11 + x].r;
+        assert!(at(5, 5) > 10.0);
+        assert!(at(5, 3) > 0.5 && at(5, 3) == at(3, 5) && at(5, 3) == at(7, 5));
+        assert!(at(5, 4) > at(5, 3));
+        assert_eq!(0.5, at(5, 1));
+        assert_eq!(0.5, at(0, 0));
+        assert_eq!(1.0, color[0].a);
+    }
+}
@@ -0,0 +1,145 @@
+
+use std::cmp::Ordering;
+use std::ptr;
+
+use math::matrix::Matrix4;
+use math::vector::Vector3;
+
+use geometry::mesh::{Instance, Mesh};
+
+use super::device::Camera;
+use super::material::Material;
+
+// Draws collected over a frame to be rendered in one go by
+// Device::render_queue, which sorts them first: opaque meshes grouped by
+// material, the groups and the draws in them front to back, so nearer
+// surfaces hide more of what follows from shading, then translucent meshes
+// back to front, each blending over what is behind it.
+#[derive(Debug,Clone,Default)]
+pub struct RenderQueue<'a> {
+    draws: Vec<(&'a Mesh, Instance)>,
+}
+
+impl<'a> RenderQueue<'a> {
+    pub fn new() -> RenderQueue<'a> {
+        RenderQueue { draws: Vec::new() }
+    }
+
+    pub fn push(&mut self, mesh: &'a Mesh) {
+        self.draws.push((mesh, Instance::new(Matrix4::identity())))
+    }
+
+    pub fn push_instance(&mut self, mesh: &'a Mesh, instance: Instance) {
+        self.draws.push((mesh, instance))
+    }
+
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.draws.clear()
+    }
+
+    // In the order they will be drawn once sorted, or as pushed before.
+    pub fn draws(&self) -> &[(&'a Mesh, Instance)] {
+        &self.draws
+    }
+
+    // Orders the draws for the camera, each placed by the center of its
+    // bounds along the view direction.
+    pub fn sort(&mut self, camera: &Camera) {
+        let forward = (camera.target - camera.position).normalize();
+        let distance = |&(mesh, ref instance): &(&Mesh, Instance)| {
+            let (min, max) = mesh.bounds();
+            let world = mesh.transform.matrix() * instance.transform;
+            let center = Vector3::transform_coordinate(&((min + max) * 0.5), &world);
+            (center - camera.position).dot(forward)
+        };
+        let (mut opaque, mut translucent): (Vec<_>, Vec<_>) = self.draws
+            .drain(..)
+            .map(|draw| (distance(&draw), draw))
+            .partition(|&(_, (mesh, _))| !mesh.material.translucent);
+        let nearest_first = |a: &(f64, _), b: &(f64, _)| {
+            a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal)
+        };
+        opaque.sort_by(&nearest_first);
+        translucent.sort_by(|a, b| nearest_first(b, a));
+
+        // Each group goes where its nearest draw is
+        let mut groups: Vec<Vec<(&'a Mesh, Instance)>> = Vec::new();
+        for (_, draw) in opaque {
+            match groups.iter_mut().find(|group| same_material(&group[0].0.material,
+                                                                &draw.0.material)) {
+                Some(group) => group.push(draw),
+                None => groups.push(vec![draw]),
+            }
+        }
+        for group in groups {
+            self.draws.extend(group)
+        }
+        self.draws.extend(translucent.into_iter().map(|(_, draw)| draw))
+    }
+}
+
+// Whether drawing with one material after the other changes nothing, as for
+// instances of a mesh, or copies of a material.
+pub fn same_material(a: &Material, b: &Material) -> bool {
+    ptr::eq(a, b) || a == b
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::f64;
+    use math::matrix::Matrix4;
+    use math::vector::Vector3;
+    use geometry::mesh::{Instance, Mesh};
+    use super::super::color::Color;
+    use super::super::device::{Camera, Handedness, Projection};
+    use super::RenderQueue;
+
+    #[test]
+    fn sorted() {
+        let camera = Camera {
+            position: Vector3::new(0.0, 0.0, 10.0),
+            target: Vector3::zero(),
+            fov: 45.0 * f64::consts::PI / 180.0,
+            znear: 0.1,
+            zfar: 100.0,
+            projection: Projection::Perspective,
+            handedness: Handedness::Right,
+        };
+        let red = Mesh::cube();
+        let mut blue = Mesh::cube();
+        blue.material.color = Color::rgb(0.0, 0.0, 1.0);
+        let mut glass = Mesh::cube();
+        glass.material.translucent = true;
+        let at = |z: f64| Instance::new(Matrix4::translation(Vector3::new(0.0, 0.0, z)));
+
+        let mut queue = RenderQueue::new();
+        queue.push_instance(&glass, at(5.0));
+        queue.push_instance(&red, at(-4.0));
+        queue.push_instance(&blue, at(2.0));
+        queue.push_instance(&glass, at(-1.0));
+        queue.push_instance(&red, at(3.0));
+        queue.push(&blue);
+        assert_eq!(6, queue.len());
+        queue.sort(&camera);
+
+        // Red has the nearest draw, glass goes last and farthest first
+        let order: Vec<(bool, f64)> = queue.draws()
+            .iter()
+            .map(|&(mesh, instance)| (mesh.material == blue.material, instance.transform.m43))
+            .collect();
+        assert_eq!(vec![(false, 3.0), (false, -4.0), (true, 2.0), (true, 0.0), (false, -1.0),
+                        (false, 5.0)],
+                   order);
+        queue.clear();
+        assert!(queue.is_empty());
+    }
+}
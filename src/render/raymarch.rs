@@ -0,0 +1,88 @@
+
+use math::vector::Vector3;
+use geometry::ray::Ray;
+
+// Sphere tracing of signed distance fields: functions giving the distance
+// from a point to the nearest surface, negative inside. Nothing lies closer
+// to the point than that distance, so a ray can safely step that far along
+// at a time, taking small steps near surfaces and large ones away from
+// them. Fields that only bound the distance from below, as most blends and
+// deformations do, work as well but take more steps.
+
+// Steps a ray takes before giving up, e.g. when it grazes a surface.
+pub const MAX_STEPS: usize = 256;
+
+// Distance from the surface counted as on it, relative to how far the ray
+// has gone, so distant surfaces need no more steps than close ones.
+pub const HIT_DISTANCE: f64 = 1e-4;
+
+// Offset around a point the field is sampled at to find its normal.
+pub const NORMAL_OFFSET: f64 = 1e-4;
+
+// Distance along the ray to where it first reaches the surface, within
+// max_distance. The direction must be of unit length. A ray starting inside
+// hits at once.
+pub fn march<F>(sdf: &F, ray: &Ray, max_distance: f64) -> Option<f64>
+    where F: Fn(Vector3) -> f64
+{
+    let mut t = 0.0;
+    for _ in 0..MAX_STEPS {
+        let distance = sdf(ray.point_at(t));
+        if distance < HIT_DISTANCE * t.max(1.0) {
+            return Some(t);
+        }
+        t += distance;
+        if t > max_distance {
+            return None;
+        }
+    }
+    None
+}
+
+// Unit normal of the surface at a point on it, the gradient of the field
+// estimated from four samples at the corners of a tetrahedron around it.
+// Zero where the field is flat.
+pub fn normal<F>(sdf: &F, p: Vector3) -> Vector3
+    where F: Fn(Vector3) -> f64
+{
+    let corners = [Vector3::new(1.0, -1.0, -1.0),
+                   Vector3::new(-1.0, -1.0, 1.0),
+                   Vector3::new(-1.0, 1.0, -1.0),
+                   Vector3::new(1.0, 1.0, 1.0)];
+    let gradient = corners.iter()
+        .fold(Vector3::zero(), |sum, &k| sum + k * sdf(p + k * NORMAL_OFFSET));
+    if gradient.length_sqr() > 0.0 { gradient.normalize() } else { gradient }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use geometry::ray::Ray;
+    use super::{march, normal};
+
+    fn sphere(p: Vector3) -> f64 {
+        p.length() - 2.0
+    }
+
+    #[test]
+    fn march_sphere() {
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 10.0), Vector3::new(0.0, 0.0, -1.0));
+        let t = march(&sphere, &ray, 100.0).unwrap();
+        assert!((t - 8.0).abs() < 1e-3);
+        // Not far enough, passing by, and from inside
+        assert_eq!(None, march(&sphere, &ray, 7.0));
+        let past = Ray::new(Vector3::new(0.0, 2.5, 10.0), Vector3::new(0.0, 0.0, -1.0));
+        assert_eq!(None, march(&sphere, &past, 100.0));
+        assert_eq!(Some(0.0), march(&sphere, &Ray::new(Vector3::zero(), ray.direction), 100.0));
+    }
+
+    #[test]
+    fn normals() {
+        let p = Vector3::new(1.0, 1.0, 1.0).normalize() * 2.0;
+        assert!((normal(&sphere, p) - p.normalize()).length() < 1e-6);
+        let ground = |p: Vector3| p.y;
+        assert!((normal(&ground, Vector3::new(3.0, 0.0, -1.0)) - Vector3::unit_y()).length() < 1e-9);
+        assert_eq!(Vector3::zero(), normal(&|_| 1.0, Vector3::zero()));
+    }
+}
@@ -0,0 +1,156 @@
+
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use gif;
+
+use super::device::Device;
+use super::image;
+
+enum Output {
+    PngSequence(PathBuf),
+    Gif(PathBuf, Option<gif::Encoder<BufWriter<File>>>),
+}
+
+// Captures the device backbuffer once per call to capture, either as
+// numbered PNG files in a directory or as a looping animated GIF.
+pub struct Recorder {
+    output: Output,
+    fps: u32,
+    max_frames: Option<usize>,
+    frame_count: usize,
+}
+
+fn gif_error(e: gif::EncodingError) -> io::Error {
+    io::Error::other(e)
+}
+
+impl Recorder {
+    pub fn png_sequence<P: Into<PathBuf>>(directory: P) -> Recorder {
+        Recorder {
+            output: Output::PngSequence(directory.into()),
+            fps: 60,
+            max_frames: None,
+            frame_count: 0,
+        }
+    }
+
+    pub fn gif<P: Into<PathBuf>>(path: P, fps: u32, duration: f64) -> Recorder {
+        Recorder {
+            output: Output::Gif(path.into(), None),
+            fps: fps,
+            max_frames: Some((fps as f64 * duration).ceil() as usize),
+            frame_count: 0,
+        }
+    }
+
+    // Stops recording once the given number of seconds worth of frames
+    // have been captured.
+    pub fn with_duration(mut self, fps: u32, duration: f64) -> Recorder {
+        self.fps = fps;
+        self.max_frames = Some((fps as f64 * duration).ceil() as usize);
+        self
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.max_frames.is_some_and(|max| self.frame_count >= max)
+    }
+
+    pub fn capture(&mut self, device: &Device) -> io::Result<()> {
+        if self.is_finished() {
+            return Ok(());
+        }
+
+        let mut pixels = device.read_pixels();
+        match self.output {
+            Output::PngSequence(ref directory) => {
+                fs::create_dir_all(directory)?;
+                let path = directory.join(format!("frame_{:05}.png", self.frame_count));
+                let file = BufWriter::new(File::create(path)?);
+                image::write_png(file, device.width(), device.height(), &pixels)?;
+            }
+            Output::Gif(ref path, ref mut encoder) => {
+                let width = device.width() as u16;
+                let height = device.height() as u16;
+                if encoder.is_none() {
+                    let file = BufWriter::new(File::create(path)?);
+                    let mut e = gif::Encoder::new(file, width, height, &[]).map_err(gif_error)?;
+                    e.set_repeat(gif::Repeat::Infinite).map_err(gif_error)?;
+                    *encoder = Some(e);
+                }
+                let mut frame = gif::Frame::from_rgba_speed(width, height, &mut pixels, 10);
+                frame.delay = (100.0 / self.fps as f64).round() as u16;
+                if let Some(ref mut encoder) = *encoder {
+                    encoder.write_frame(&frame).map_err(gif_error)?;
+                }
+            }
+        }
+
+        self.frame_count += 1;
+        Ok(())
+    }
+
+    // Flushes any pending output. Dropping the recorder has the same effect
+    // but swallows errors.
+    pub fn finish(self) -> io::Result<()> {
+        if let Output::Gif(_, Some(encoder)) = self.output {
+            let mut file = encoder.into_inner()?;
+            io::Write::flush(&mut file)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::env;
+    use std::fs;
+    use super::super::color::Color;
+    use super::super::device::Device;
+    use super::Recorder;
+
+    #[test]
+    fn png_sequence() {
+        let directory = env::temp_dir().join("swr_rs_recorder_png_sequence");
+        let _ = fs::remove_dir_all(&directory);
+
+        let mut device = Device::new(4, 4);
+        device.clear(Color::white());
+        let mut recorder = Recorder::png_sequence(&directory).with_duration(2, 1.0);
+        for _ in 0..3 {
+            recorder.capture(&device).unwrap();
+        }
+        recorder.finish().unwrap();
+
+        assert!(directory.join("frame_00000.png").exists());
+        assert!(directory.join("frame_00001.png").exists());
+        assert!(!directory.join("frame_00002.png").exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn gif() {
+        let path = env::temp_dir().join("swr_rs_recorder.gif");
+
+        let mut device = Device::new(4, 4);
+        let mut recorder = Recorder::gif(&path, 10, 0.2);
+        device.clear(Color::white());
+        recorder.capture(&device).unwrap();
+        device.clear(Color::black());
+        recorder.capture(&device).unwrap();
+        assert!(recorder.is_finished());
+        recorder.finish().unwrap();
+
+        let data = fs::read(&path).unwrap();
+        assert_eq!(b"GIF89a", &data[..6]);
+        fs::remove_file(&path).unwrap();
+    }
+}
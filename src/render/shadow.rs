@@ -0,0 +1,119 @@
+
+use math::matrix::Matrix4;
+use math::vector::Vector3;
+
+use super::depth::DepthBuffer;
+use super::target::RenderTarget;
+use super::viewport::Viewport;
+
+// Depth of the scene as seen from a light, rendered by
+// Device::render_shadows and looked up while shading to darken surfaces the
+// light does not reach. An orthographic light camera gives a directional
+// light, a perspective one a spot light.
+#[derive(Debug,Clone)]
+pub struct ShadowMap {
+    target: RenderTarget,
+    view_projection: Matrix4,
+    // Depth a surface may be behind the nearest one seen by the light and
+    // still count as lit, against self-shadowing from the limited
+    // resolution. In the light's depth units.
+    pub bias: f64,
+    // Texels on each side of the one looked up that are compared too,
+    // softening the edges of shadows. 0 for a hard edge.
+    pub pcf_radius: usize,
+    // How much light a shadow takes away, from 0 for none to 1 for black.
+    pub strength: f64,
+}
+
+impl ShadowMap {
+    pub fn new(size: usize) -> ShadowMap {
+        ShadowMap {
+            target: RenderTarget::new(size, size),
+            view_projection: Matrix4::identity(),
+            bias: 0.005,
+            pcf_radius: 1,
+            strength: 0.6,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.target.width()
+    }
+
+    // Depth as seen from the light, 0 where nothing was drawn.
+    pub fn depth(&self) -> &DepthBuffer {
+        self.target.depth()
+    }
+
+    pub fn viewport(&self) -> Viewport {
+        Viewport::new(0.0, 0.0, self.size() as f64, self.size() as f64)
+    }
+
+    pub(crate) fn target_mut(&mut self) -> &mut RenderTarget {
+        &mut self.target
+    }
+
+    pub(crate) fn set_view_projection(&mut self, view_projection: Matrix4) {
+        self.view_projection = view_projection
+    }
+
+    // Fraction of the texels around the world position that see it from the
+    // light, 1 when fully lit. Positions outside the map are lit.
+    pub fn visibility(&self, position: Vector3) -> f64 {
+        let clip = Vector3::transform(&position, &self.view_projection);
+        if clip.w <= 0.0 {
+            return 1.0;
+        }
+        let p = self.viewport().project(clip.xyz() / clip.w);
+        let size = self.size() as i64;
+        let (x, y) = (p.x.round() as i64, p.y.round() as i64);
+        let r = self.pcf_radius as i64;
+        let depth = self.depth();
+        let mut lit = 0;
+        for sy in y - r..y + r + 1 {
+            for sx in x - r..x + r + 1 {
+                let outside = sx < 0 || sy < 0 || sx >= size || sy >= size;
+                // Nearer surfaces have greater depth
+                if outside || depth.get((sy * size + sx) as usize) <= p.z + self.bias {
+                    lit += 1;
+                }
+            }
+        }
+        lit as f64 / ((2 * r + 1) * (2 * r + 1)) as f64
+    }
+
+    // Scale for the color of a surface at the world position.
+    pub fn attenuation(&self, position: Vector3) -> f64 {
+        1.0 - self.strength * (1.0 - self.visibility(position))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use super::ShadowMap;
+
+    #[test]
+    fn visibility() {
+        // With the identity as the light's transform, NDC x and y in [-1, 1]
+        // cover the map
+        let mut shadow = ShadowMap::new(4);
+        shadow.pcf_radius = 0;
+        let (color, mut depth) = shadow.target_mut().take();
+        depth.set(2 * 4 + 2, 0.9);
+        shadow.target_mut().put_back(color, depth);
+
+        // Behind the occluder, in front of it, and beside it
+        assert_eq!(0.0, shadow.visibility(Vector3::new(0.0, 0.0, 0.5)));
+        assert_eq!(1.0, shadow.visibility(Vector3::new(0.0, 0.0, 0.95)));
+        assert_eq!(1.0, shadow.visibility(Vector3::new(-0.5, 0.5, 0.5)));
+        assert_eq!(1.0, shadow.visibility(Vector3::new(4.0, 0.0, 0.5)));
+
+        // Filtering counts the lit texels around
+        shadow.pcf_radius = 1;
+        assert_eq!(8.0 / 9.0, shadow.visibility(Vector3::new(0.0, 0.0, 0.5)));
+        shadow.strength = 0.5;
+        assert!((shadow.attenuation(Vector3::new(0.0, 0.0, 0.5)) - 17.0 / 18.0).abs() < 1e-12);
+    }
+}
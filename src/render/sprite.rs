@@ -0,0 +1,77 @@
+
+use math::vector::{Vector2, Vector3};
+
+use super::color::Color;
+
+// A textured rectangle drawn facing the camera at a point in the world,
+// size world units wide and high, e.g. a label or an impostor standing in
+// for a distant mesh. See Device::render_billboards.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Billboard {
+    pub position: Vector3,
+    pub size: Vector2,
+    // Corners of the part of the texture shown, top left first, so several
+    // billboards can share an atlas.
+    pub uv_min: Vector2,
+    pub uv_max: Vector2,
+    // Multiplies the texture, which is white without one.
+    pub color: Color,
+}
+
+impl Billboard {
+    pub fn new(position: Vector3, size: Vector2) -> Billboard {
+        Billboard {
+            position: position,
+            size: size,
+            uv_min: Vector2::zero(),
+            uv_max: Vector2::new(1.0, 1.0),
+            color: Color::white(),
+        }
+    }
+
+    pub fn with_region(mut self, uv_min: Vector2, uv_max: Vector2) -> Billboard {
+        self.uv_min = uv_min;
+        self.uv_max = uv_max;
+        self
+    }
+
+    pub fn with_color(mut self, color: Color) -> Billboard {
+        self.color = color;
+        self
+    }
+}
+
+// A textured rectangle in screen space, with its top left corner at
+// position and size wide and high in presented pixels, e.g. a HUD icon.
+// See Device::draw_sprites.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Sprite {
+    pub position: Vector2,
+    pub size: Vector2,
+    pub uv_min: Vector2,
+    pub uv_max: Vector2,
+    pub color: Color,
+}
+
+impl Sprite {
+    pub fn new(position: Vector2, size: Vector2) -> Sprite {
+        Sprite {
+            position: position,
+            size: size,
+            uv_min: Vector2::zero(),
+            uv_max: Vector2::new(1.0, 1.0),
+            color: Color::white(),
+        }
+    }
+
+    pub fn with_region(mut self, uv_min: Vector2, uv_max: Vector2) -> Sprite {
+        self.uv_min = uv_min;
+        self.uv_max = uv_max;
+        self
+    }
+
+    pub fn with_color(mut self, color: Color) -> Sprite {
+        self.color = color;
+        self
+    }
+}
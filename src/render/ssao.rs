@@ -0,0 +1,187 @@
+
+use std::f64;
+
+use math::matrix::Matrix4;
+use math::vector::Vector3;
+
+use super::depth::DepthBuffer;
+use super::gbuffer::GBuffer;
+use super::viewport::Viewport;
+
+// Screen space ambient occlusion, run by the deferred lighting pass to
+// darken ambient light in creases and corners. Points in a hemisphere
+// around each pixel's surface are tested against the depth buffer, and the
+// fraction hidden behind other surfaces is blurred to hide the noise.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Ssao {
+    // Reach of the hemisphere in world units.
+    pub radius: f64,
+    pub samples: usize,
+    // Fraction of its depth a sample must be behind a surface to be
+    // occluded by it, against surfaces occluding themselves.
+    pub bias: f64,
+    // Pixels on each side averaged by the blur.
+    pub blur: usize,
+    // How much occlusion takes away, from 0 for none to 1 for all.
+    pub strength: f64,
+}
+
+// Rotations of the kernel around the normal repeat every this many pixels,
+// the blur then averaging them out.
+const NOISE_SIZE: usize = 4;
+
+impl Ssao {
+    pub fn new() -> Ssao {
+        Ssao {
+            radius: 0.5,
+            samples: 16,
+            bias: 0.002,
+            blur: 2,
+            strength: 1.0,
+        }
+    }
+
+    // Points in the unit hemisphere around +z, spread by the golden angle
+    // and closer to the center for the first ones.
+    pub fn kernel(&self) -> Vec<Vector3> {
+        let golden_angle = f64::consts::PI * (3.0 - 5.0f64.sqrt());
+        let n = self.samples as f64;
+        (0..self.samples)
+            .map(|i| {
+                let i = i as f64;
+                let z = 1.0 - (i + 0.5) / n;
+                let r = (1.0 - z * z).sqrt();
+                let angle = i * golden_angle;
+                let t = (i + 1.0) / n;
+                let scale = 0.1 + 0.9 * t * t;
+                Vector3::new(r * angle.cos(), r * angle.sin(), z) * scale
+            })
+            .collect()
+    }
+
+    // Ambient light left at each covered pixel of the G-buffer, 1 where
+    // nothing occludes it and for the pixels not covered. The eye,
+    // view_projection and viewport are those the pixels were rendered with.
+    pub fn occlusion(&self,
+                     gbuffer: &GBuffer,
+                     depth: &DepthBuffer,
+                     eye: Vector3,
+                     view_projection: &Matrix4,
+                     viewport: &Viewport)
+                     -> Vec<f64> {
+        let (width, height) = (gbuffer.width(), gbuffer.height());
+        let kernel = self.kernel();
+        let covered = gbuffer.covered();
+        let mut visibility = vec![1.0; width * height];
+        for y in 0..height {
+            for x in 0..width {
+                let offset = y * width + x;
+                if !covered[offset] || kernel.is_empty() {
+                    continue;
+                }
+                let position = gbuffer.position()[offset];
+                // Towards the eye, whichever way the faces are wound
+                let normal = gbuffer.normal()[offset];
+                let normal = if normal.dot(eye - position) < 0.0 { normal * -1.0 } else { normal };
+                let noise = (y % NOISE_SIZE * NOISE_SIZE + x % NOISE_SIZE) as f64;
+                let angle = noise * f64::consts::PI * 2.0 / (NOISE_SIZE * NOISE_SIZE) as f64;
+                let (tangent, bitangent) = tangent_frame(normal, angle);
+
+                let mut occluded = 0;
+                for k in &kernel {
+                    let sample = position +
+                                 (tangent * k.x + bitangent * k.y + normal * k.z) * self.radius;
+                    let clip = Vector3::transform(&sample, view_projection);
+                    if clip.w <= 0.0 {
+                        continue;
+                    }
+                    let p = viewport.project(clip.xyz() / clip.w);
+                    let (sx, sy) = (p.x.round(), p.y.round());
+                    if sx < 0.0 || sy < 0.0 || sx >= width as f64 || sy >= height as f64 {
+                        continue;
+                    }
+                    let o = sy as usize * width + sx as usize;
+                    // Nearer surfaces have greater depth. Ones far from the
+                    // pixel, e.g. in front of it on screen, do not count.
+                    if depth.get(o) > p.z * (1.0 + self.bias) && covered[o] &&
+                       (gbuffer.position()[o] - position).length() < self.radius {
+                        occluded += 1;
+                    }
+                }
+                visibility[offset] = 1.0 - occluded as f64 / kernel.len() as f64;
+            }
+        }
+
+        let blurred = blur(&visibility, covered, width, height, self.blur);
+        blurred.iter().map(|&v| 1.0 - self.strength * (1.0 - v)).collect()
+    }
+}
+
+impl Default for Ssao {
+    fn default() -> Ssao {
+        Ssao::new()
+    }
+}
+
+// Unit vectors perpendicular to the normal and each other, turned around it
+// by the angle.
+fn tangent_frame(normal: Vector3, angle: f64) -> (Vector3, Vector3) {
+    let helper = if normal.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+    let t = helper.cross(normal).normalize();
+    let b = normal.cross(t);
+    let (sin, cos) = angle.sin_cos();
+    (t * cos + b * sin, b * cos - t * sin)
+}
+
+// Box blur over the covered pixels only, so the background does not bleed
+// into the edges of surfaces.
+fn blur(values: &[f64], covered: &[bool], width: usize, height: usize, radius: usize) -> Vec<f64> {
+    let mut out = values.to_vec();
+    for y in 0..height {
+        for x in 0..width {
+            if !covered[y * width + x] {
+                continue;
+            }
+            let (mut sum, mut count) = (0.0, 0);
+            for sy in y.saturating_sub(radius)..(y + radius + 1).min(height) {
+                for sx in x.saturating_sub(radius)..(x + radius + 1).min(width) {
+                    if covered[sy * width + sx] {
+                        sum += values[sy * width + sx];
+                        count += 1;
+                    }
+                }
+            }
+            out[y * width + x] = sum / count as f64;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use super::{blur, tangent_frame, Ssao};
+
+    #[test]
+    fn kernel() {
+        let kernel = Ssao::new().kernel();
+        assert_eq!(16, kernel.len());
+        // Inside the hemisphere, growing outwards
+        assert!(kernel.iter().all(|k| k.z > 0.0 && k.length() <= 1.0 + 1e-9));
+        assert!(kernel[0].length() < kernel[15].length());
+
+        let normal = Vector3::new(0.0, 0.6, 0.8);
+        let (t, b) = tangent_frame(normal, 1.0);
+        assert!(t.dot(normal).abs() < 1e-9 && b.dot(normal).abs() < 1e-9);
+        assert!(t.dot(b).abs() < 1e-9 && (t.length() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn blur_covered() {
+        let values = [0.0, 1.0, 1.0, 0.5];
+        let covered = [true, true, true, false];
+        let blurred = blur(&values, &covered, 4, 1, 1);
+        assert_eq!(vec![0.5, 2.0 / 3.0, 1.0, 0.5], blurred);
+    }
+}
@@ -0,0 +1,229 @@
+
+use std::fmt;
+
+use profiler;
+use profiler::StageTime;
+
+use super::color::Color;
+use super::device::Device;
+use super::font;
+
+// Counters gathered by the device while rendering, see Device::stats.
+#[derive(Debug,Clone,Copy,Default,PartialEq)]
+pub struct RenderStats {
+    pub triangles_submitted: usize,
+    // Triangles of meshes outside the view frustum, and triangles whose
+    // screen bounds miss the viewport.
+    pub triangles_culled: usize,
+    // Triangles partly outside the viewport, rasterized up to its edges.
+    pub triangles_clipped: usize,
+    // Triangles reaching past the guard band or behind the near plane, cut
+    // into smaller ones, see Device::guard_band. Those cut away entirely
+    // count as culled.
+    pub triangles_split: usize,
+    pub pixels_shaded: usize,
+    // Blocks of triangles skipped whole by the hierarchical depth test.
+    pub blocks_rejected: usize,
+    // Meshes hidden behind the occluders, their triangles counted culled.
+    pub meshes_occluded: usize,
+    // Meshes drawn once culled, and how many times the material changed
+    // from one to the next, the first counted, see RenderQueue.
+    pub draw_calls: usize,
+    pub material_changes: usize,
+}
+
+// How fast a run of Device::render_benchmark went, with the counters of
+// all its frames together.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Benchmark {
+    pub frames: usize,
+    pub seconds: f64,
+    pub render: RenderStats,
+}
+
+impl Benchmark {
+    pub fn frames_per_second(&self) -> f64 {
+        self.frames as f64 / self.seconds.max(1e-12)
+    }
+
+    // Triangles drawn, those culled left out.
+    pub fn triangles_per_second(&self) -> f64 {
+        let drawn = self.render.triangles_submitted - self.render.triangles_culled;
+        drawn as f64 / self.seconds.max(1e-12)
+    }
+
+    // The fill rate.
+    pub fn pixels_per_second(&self) -> f64 {
+        self.render.pixels_shaded as f64 / self.seconds.max(1e-12)
+    }
+}
+
+impl fmt::Display for Benchmark {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{:.1} fps, {:.2} ms a frame, {:.2} Mtris/s, {:.2} Mpixels/s",
+               self.frames_per_second(),
+               self.seconds * 1000.0 / self.frames.max(1) as f64,
+               self.triangles_per_second() * 1e-6,
+               self.pixels_per_second() * 1e-6)
+    }
+}
+
+// Frame timing and the render statistics of the last frame, drawn as a text
+// overlay in the top left corner of the backbuffer.
+#[derive(Debug,Clone,Default)]
+pub struct Stats {
+    // Seconds spent on the last frame.
+    pub frame_time: f64,
+    // Exponential moving average of frame_time, steadier to read.
+    pub average_frame_time: f64,
+    // Of the frame time, the part spent working rather than waiting for
+    // the next frame, and the rate frames are paced to, None if uncapped.
+    // Set by whatever paces the loop, e.g. a FramePacer.
+    pub busy_time: f64,
+    pub target_fps: Option<f64>,
+    pub render: RenderStats,
+    // Profiled stages of the frame, see profile_scope.
+    pub stages: Vec<StageTime>,
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats {
+            frame_time: 0.0,
+            average_frame_time: 0.0,
+            busy_time: 0.0,
+            target_fps: None,
+            render: RenderStats::default(),
+            stages: Vec::new(),
+        }
+    }
+
+    // Records the frame and takes the device counters and profiled stages,
+    // resetting them for the next frame. Call once per frame after
+    // rendering.
+    pub fn end_frame(&mut self, frame_time: f64, device: &mut Device) {
+        self.frame_time = frame_time;
+        self.average_frame_time = if self.average_frame_time > 0.0 {
+            self.average_frame_time * 0.9 + frame_time * 0.1
+        } else {
+            frame_time
+        };
+        self.render = device.stats();
+        device.reset_stats();
+        self.stages = profiler::end_frame();
+    }
+
+    pub fn fps(&self) -> f64 {
+        if self.average_frame_time > 0.0 {
+            1.0 / self.average_frame_time
+        } else {
+            0.0
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let cap = self.target_fps.map_or("uncapped".to_string(), |fps| format!("cap {:.0}", fps));
+        let mut lines = vec![format!("{:.0} fps {:.2} ms",
+                                     self.fps(),
+                                     self.average_frame_time * 1000.0),
+                             format!("{} busy {:.2} ms", cap, self.busy_time * 1000.0),
+                             format!("tris {}", self.render.triangles_submitted),
+                             format!("culled {}", self.render.triangles_culled),
+                             format!("clipped {}", self.render.triangles_clipped),
+                             format!("split {}", self.render.triangles_split),
+                             format!("pixels {}", self.render.pixels_shaded),
+                             format!("hi-z {}", self.render.blocks_rejected),
+                             format!("occluded {}", self.render.meshes_occluded),
+                             format!("draws {}", self.render.draw_calls),
+                             format!("materials {}", self.render.material_changes)];
+        for stage in &self.stages {
+            lines.push(format!("{} {:.2} ms", stage.name, stage.seconds * 1000.0));
+        }
+        lines
+    }
+
+    // Draws onto the backbuffer, so call it after Device::resolve.
+    pub fn draw(&self, device: &mut Device) {
+        for (i, line) in self.lines().iter().enumerate() {
+            let y = (4 + i * (font::GLYPH_HEIGHT + 2)) as i32;
+            // Shadowed to stay readable on bright backgrounds
+            device.draw_text(5, y + 1, line, Color::black());
+            device.draw_text(4, y, line, Color::white());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::f64;
+    use math::vector::Vector3;
+    use geometry::mesh::Mesh;
+    use super::super::color::Color;
+    use super::super::device::{Camera, Device, Handedness, Projection};
+    use super::{RenderStats, Stats};
+
+    fn camera(target: Vector3) -> Camera {
+        Camera {
+            position: Vector3::new(0.0, 0.0, 15.0),
+            target: target,
+            fov: 45.0 * f64::consts::PI / 180.0,
+            znear: 0.01,
+            zfar: 100.0,
+            projection: Projection::Perspective,
+            handedness: Handedness::Right,
+        }
+    }
+
+    #[test]
+    fn counters() {
+        let mut device = Device::new(64, 48);
+        let mut stats = Stats::new();
+        device.clear(Color::black());
+        device.render(&camera(Vector3::zero()), &device.viewport(), &[&Mesh::cube()]);
+        stats.end_frame(0.02, &mut device);
+
+        assert_eq!(12, stats.render.triangles_submitted);
+        assert_eq!(0, stats.render.triangles_culled);
+        assert_eq!(0, stats.render.triangles_clipped);
+        assert!(stats.render.pixels_shaded > 0);
+        assert_eq!((1, 1), (stats.render.draw_calls, stats.render.material_changes));
+        assert_eq!(RenderStats::default(), device.stats());
+        assert!((stats.fps() - 50.0).abs() < 1e-9);
+        let stages: Vec<&str> = stats.stages.iter().map(|s| s.name).collect();
+        assert_eq!(vec!["clear", "transform", "raster"], stages);
+        assert!(stats.lines().iter().any(|l| l.starts_with("raster ")));
+
+        // Looking away from the cube
+        let behind = Vector3::new(0.0, 0.0, 30.0);
+        device.render(&camera(behind), &device.viewport(), &[&Mesh::cube()]);
+        stats.end_frame(0.02, &mut device);
+        assert_eq!(12, stats.render.triangles_submitted);
+        assert_eq!(12, stats.render.triangles_culled);
+        assert_eq!(0, stats.render.pixels_shaded);
+    }
+
+    #[test]
+    fn clipped() {
+        let mut device = Device::new(64, 48);
+        let mut stats = Stats::new();
+        // Close enough for the cube to overflow the viewport
+        let mut camera = camera(Vector3::zero());
+        camera.position = Vector3::new(0.0, 0.0, 2.0);
+        device.render(&camera, &device.viewport(), &[&Mesh::cube()]);
+        stats.end_frame(0.02, &mut device);
+        assert!(stats.render.triangles_clipped > 0);
+    }
+
+    #[test]
+    fn draw() {
+        let mut device = Device::new(160, 64);
+        device.clear(Color::black());
+        Stats::new().draw(&mut device);
+        let white = Color::white().to_argb8888();
+        assert!(device.backbuffer().contains(&white));
+        // Only the top left corner is covered
+        assert!(device.backbuffer()[63 * 160..].iter().all(|&p| p != white));
+    }
+}
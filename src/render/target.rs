@@ -0,0 +1,63 @@
+
+use std::mem;
+
+use super::color::Color;
+use super::depth::{DepthBuffer, DepthFormat};
+use super::texture::Texture;
+
+// Offscreen color and depth buffers a device can render into with
+// Device::render_to_target, to use the picture in a later pass. Color is
+// kept unresolved, as it would be in the device's HDR buffer.
+#[derive(Debug,Clone)]
+pub struct RenderTarget {
+    width: usize,
+    height: usize,
+    color: Box<[Color]>,
+    depth: DepthBuffer,
+}
+
+impl RenderTarget {
+    pub fn new(width: usize, height: usize) -> RenderTarget {
+        RenderTarget {
+            width: width,
+            height: height,
+            color: vec![Color::black(); width * height].into_boxed_slice(),
+            depth: DepthBuffer::new(DepthFormat::F64, width * height),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn color(&self) -> &[Color] {
+        &self.color
+    }
+
+    // Depth as written by the device, 0 where nothing was drawn.
+    pub fn depth(&self) -> &DepthBuffer {
+        &self.depth
+    }
+
+    // Copy of the color buffer, e.g. for Device::texture.
+    pub fn texture(&self) -> Texture {
+        Texture::new(self.width, self.height, self.color.to_vec())
+    }
+
+    // Moves the buffers out for the device to render into, leaving the
+    // target empty until they are put back.
+    pub(crate) fn take(&mut self) -> (Box<[Color]>, DepthBuffer) {
+        let empty_color: Box<[Color]> = Vec::new().into_boxed_slice();
+        let empty_depth = DepthBuffer::new(DepthFormat::F64, 0);
+        (mem::replace(&mut self.color, empty_color), mem::replace(&mut self.depth, empty_depth))
+    }
+
+    pub(crate) fn put_back(&mut self, color: Box<[Color]>, depth: DepthBuffer) {
+        self.color = color;
+        self.depth = depth;
+    }
+}
@@ -0,0 +1,281 @@
+
+use std::f64;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use png;
+
+use error::{Result, SwrError};
+use math::vector::{Vector2, Vector3};
+
+use super::color::Color;
+
+// Image sampled by texture coordinates, with u growing to the right and v
+// downwards from the top row. Texels are colors in the space shading
+// happens in, i.e. linear when the device is gamma correct.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Texture {
+    width: usize,
+    height: usize,
+    texels: Vec<Color>,
+}
+
+impl Texture {
+    pub fn new(width: usize, height: usize, texels: Vec<Color>) -> Texture {
+        assert_eq!(width * height, texels.len());
+        Texture {
+            width: width,
+            height: height,
+            texels: texels,
+        }
+    }
+
+    pub fn filled(width: usize, height: usize, color: Color) -> Texture {
+        Texture::new(width, height, vec![color; width * height])
+    }
+
+    // A PNG image, None for other formats or broken files. The texels are
+    // the image's colors as they are, so color textures authored in sRGB
+    // still need linearizing.
+    pub fn read_png(bytes: &[u8]) -> Option<Texture> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().ok()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).ok()?;
+        let pixels = &buffer[..info.buffer_size()];
+        let texels: Vec<Color> = match info.color_type {
+            png::ColorType::Grayscale => {
+                pixels.iter().map(|&v| Color::from_u8(v, v, v, 255)).collect()
+            }
+            png::ColorType::GrayscaleAlpha => {
+                pixels.chunks(2).map(|p| Color::from_u8(p[0], p[0], p[0], p[1])).collect()
+            }
+            png::ColorType::Rgb => {
+                pixels.chunks(3).map(|p| Color::from_u8(p[0], p[1], p[2], 255)).collect()
+            }
+            png::ColorType::Rgba => {
+                pixels.chunks(4).map(|p| Color::from_u8(p[0], p[1], p[2], p[3])).collect()
+            }
+            png::ColorType::Indexed => return None,
+        };
+        Some(Texture::new(info.width as usize, info.height as usize, texels))
+    }
+
+    pub fn load_png<P: AsRef<Path>>(path: P) -> Result<Texture> {
+        let mut bytes = Vec::new();
+        File::open(path.as_ref())?.read_to_end(&mut bytes)?;
+        Texture::read_png(&bytes).ok_or_else(|| {
+            SwrError::format(format!("{} is not a PNG image", path.as_ref().display()))
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn texels(&self) -> &[Color] {
+        &self.texels
+    }
+
+    // Texel at the wrapped coordinates, so the texture repeats.
+    pub fn texel(&self, x: i64, y: i64) -> Color {
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.rem_euclid(self.height as i64) as usize;
+        self.texels[y * self.width + x]
+    }
+
+    // Bilinear filtered sample. Texel centers lie at half coordinates, and
+    // the texture repeats outside [0, 1].
+    pub fn sample(&self, uv: Vector2) -> Color {
+        let x = uv.x * self.width as f64 - 0.5;
+        let y = uv.y * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self.texel(x0, y0).lerp(self.texel(x0 + 1, y0), fx);
+        let bottom = self.texel(x0, y0 + 1).lerp(self.texel(x0 + 1, y0 + 1), fx);
+        top.lerp(bottom, fy)
+    }
+}
+
+// Six square textures on the faces of a cube around the origin, sampled by
+// direction, e.g. for a sky. Faces are in the order +x, -x, +y, -y, +z, -z
+// and laid out as seen from the inside, as for OpenGL.
+#[derive(Debug,Clone,PartialEq)]
+pub struct CubeMap {
+    faces: Vec<Texture>,
+}
+
+impl CubeMap {
+    pub fn new(faces: [Texture; 6]) -> CubeMap {
+        let size = faces[0].width;
+        assert!(faces.iter().all(|f| f.width == size && f.height == size));
+        CubeMap { faces: faces.to_vec() }
+    }
+
+    // Faces of the size filled by the color in each texel's direction.
+    pub fn from_fn<F: Fn(Vector3) -> Color>(size: usize, color: F) -> CubeMap {
+        let faces: Vec<Texture> = (0..6)
+            .map(|face| {
+                let mut texels = Vec::with_capacity(size * size);
+                for y in 0..size {
+                    for x in 0..size {
+                        let s = (x as f64 + 0.5) / size as f64 * 2.0 - 1.0;
+                        let t = (y as f64 + 0.5) / size as f64 * 2.0 - 1.0;
+                        texels.push(color(face_direction(face, s, t).normalize()));
+                    }
+                }
+                Texture::new(size, size, texels)
+            })
+            .collect();
+        CubeMap { faces: faces }
+    }
+
+    pub fn size(&self) -> usize {
+        self.faces[0].width
+    }
+
+    pub fn face(&self, index: usize) -> &Texture {
+        &self.faces[index]
+    }
+
+    // Bilinear sample of the face the direction points at. Samples stop
+    // half a texel from the edges of the face rather than wrap around it.
+    pub fn sample(&self, direction: Vector3) -> Color {
+        let (x, y, z) = (direction.x, direction.y, direction.z);
+        let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+        let (face, s, t, major) = if ax >= ay && ax >= az {
+            if x > 0.0 { (0, -z, -y, ax) } else { (1, z, -y, ax) }
+        } else if ay >= az {
+            if y > 0.0 { (2, x, z, ay) } else { (3, x, -z, ay) }
+        } else if z > 0.0 {
+            (4, x, -y, az)
+        } else {
+            (5, -x, -y, az)
+        };
+        if major == 0.0 {
+            return Color::black();
+        }
+        let half_texel = 0.5 / self.size() as f64;
+        let edge = |c: f64| ((c / major + 1.0) * 0.5).clamp(half_texel, 1.0 - half_texel);
+        self.faces[face].sample(Vector2::new(edge(s), edge(t)))
+    }
+}
+
+// Direction through the point (s, t) in [-1, 1] of the face.
+fn face_direction(face: usize, s: f64, t: f64) -> Vector3 {
+    match face {
+        0 => Vector3::new(1.0, -t, -s),
+        1 => Vector3::new(-1.0, -t, s),
+        2 => Vector3::new(s, 1.0, t),
+        3 => Vector3::new(s, -1.0, -t),
+        4 => Vector3::new(s, -t, 1.0),
+        _ => Vector3::new(-s, -t, -1.0),
+    }
+}
+
+// Surroundings seen in every direction, reflected by materials.
+#[derive(Debug,Clone,PartialEq)]
+pub enum Environment {
+    Cube(CubeMap),
+    // Equirectangular panorama: longitude across, starting and ending
+    // behind -z, and latitude down from straight up.
+    Panorama(Texture),
+}
+
+impl Environment {
+    pub fn sample(&self, direction: Vector3) -> Color {
+        match *self {
+            Environment::Cube(ref cube) => cube.sample(direction),
+            Environment::Panorama(ref texture) => {
+                let length = direction.length();
+                if length == 0.0 {
+                    return Color::black();
+                }
+                let d = direction * (1.0 / length);
+                let u = 0.5 + d.x.atan2(-d.z) / (2.0 * f64::consts::PI);
+                // Across the poles would wrap to the other one
+                let half_texel = 0.5 / texture.height as f64;
+                let v = (d.y.clamp(-1.0, 1.0).acos() / f64::consts::PI)
+                    .clamp(half_texel, 1.0 - half_texel);
+                texture.sample(Vector2::new(u, v))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::{Vector2, Vector3};
+    use super::super::color::Color;
+    use super::{CubeMap, Environment, Texture};
+
+    #[test]
+    fn sample() {
+        let texture = Texture::new(2, 1, vec![Color::black(), Color::white()]);
+        // Texel centers are exact, between them colors blend
+        assert_eq!(Color::black(), texture.sample(Vector2::new(0.25, 0.5)));
+        assert_eq!(Color::white(), texture.sample(Vector2::new(0.75, 0.5)));
+        assert_eq!(Color::gray(0.5), texture.sample(Vector2::new(0.5, 0.5)));
+        // Repeats, so the left edge blends with the right
+        assert_eq!(Color::gray(0.5), texture.sample(Vector2::new(0.0, 0.5)));
+        assert_eq!(Color::white(), texture.sample(Vector2::new(1.75, 0.5)));
+        assert_eq!(Color::white(), texture.texel(-1, 3));
+    }
+
+    #[test]
+    fn cube_map() {
+        let colors = [Color::rgb(1.0, 0.0, 0.0),
+                      Color::rgb(0.0, 1.0, 0.0),
+                      Color::rgb(0.0, 0.0, 1.0),
+                      Color::rgb(1.0, 1.0, 0.0),
+                      Color::rgb(0.0, 1.0, 1.0),
+                      Color::rgb(1.0, 0.0, 1.0)];
+        let faces = [Texture::filled(2, 2, colors[0]),
+                     Texture::filled(2, 2, colors[1]),
+                     Texture::filled(2, 2, colors[2]),
+                     Texture::filled(2, 2, colors[3]),
+                     Texture::filled(2, 2, colors[4]),
+                     Texture::filled(2, 2, colors[5])];
+        let cube = CubeMap::new(faces);
+        let axes = [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()];
+        for (i, &axis) in axes.iter().enumerate() {
+            assert_eq!(colors[i * 2], cube.sample(axis * 3.0));
+            assert_eq!(colors[i * 2 + 1], cube.sample(axis * -1.0));
+        }
+
+        // Built from directions, sampling gives them back
+        let cube = CubeMap::from_fn(16, |d| Color::rgb(d.x, d.y, d.z));
+        for &d in &[Vector3::new(1.0, 2.0, 3.0), Vector3::new(-0.5, 0.1, -1.0)] {
+            let c = cube.sample(d);
+            let d = d.normalize();
+            let error = Vector3::new(c.r, c.g, c.b) - d;
+            assert!(error.length() < 0.05, "{:?} for {:?}", c, d);
+        }
+    }
+    #[test]
+    fn panorama() {
+        let colors = vec![Color::black(),
+                          Color::rgb(1.0, 0.0, 0.0),
+                          Color::white(),
+                          Color::gray(0.5)];
+        let around = Environment::Panorama(Texture::new(4, 1, colors.clone()));
+        // Texel centers, going around from behind -z
+        assert_eq!(colors[1], around.sample(Vector3::new(-1.0, 0.0, -1.0)));
+        assert_eq!(colors[2], around.sample(Vector3::new(1.0, 0.0, -1.0)));
+        assert_eq!(colors[3], around.sample(Vector3::new(1.0, 0.0, 1.0)));
+
+        let sky = Environment::Panorama(Texture::new(1, 2, vec![Color::white(), Color::black()]));
+        assert_eq!(Color::white(), sky.sample(Vector3::unit_y()));
+        assert_eq!(Color::black(), sky.sample(Vector3::unit_y() * -2.0));
+        assert_eq!(Color::gray(0.5), sky.sample(Vector3::unit_x()));
+    }
+}
@@ -0,0 +1,132 @@
+
+use std::ops::Range;
+
+// Pixels along each side of a tile.
+pub const TILE_SIZE: usize = 32;
+
+// Which tiles of a width x height frame have been drawn to since they were
+// last cleared, so a clear can skip the rest. Marking is conservative: a
+// tile may be marked that nothing ended up changing, never the other way.
+#[derive(Debug,Clone)]
+pub struct DirtyTiles {
+    width: usize,
+    height: usize,
+    columns: usize,
+    rows: usize,
+    dirty: Box<[bool]>,
+}
+
+impl DirtyTiles {
+    // All dirty, as nothing is known about freshly allocated buffers.
+    pub fn new(width: usize, height: usize) -> DirtyTiles {
+        let columns = width.div_ceil(TILE_SIZE);
+        let rows = height.div_ceil(TILE_SIZE);
+        DirtyTiles {
+            width: width,
+            height: height,
+            columns: columns,
+            rows: rows,
+            dirty: vec![true; columns * rows].into_boxed_slice(),
+        }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn is_dirty(&self, column: usize, row: usize) -> bool {
+        self.dirty[row * self.columns + column]
+    }
+
+    pub fn count(&self) -> usize {
+        self.dirty.iter().filter(|&&d| d).count()
+    }
+
+    // Marks the tile of the pixel at offset, counted in rows of width.
+    pub fn mark(&mut self, offset: usize) {
+        let (x, y) = (offset % self.width, offset / self.width);
+        self.dirty[(y / TILE_SIZE) * self.columns + x / TILE_SIZE] = true
+    }
+
+    // Marks the tiles overlapping the pixels in the ranges, which may reach
+    // past the frame.
+    pub fn mark_rect(&mut self, x: Range<usize>, y: Range<usize>) {
+        let (x_end, y_end) = (x.end.min(self.width), y.end.min(self.height));
+        if x.start >= x_end || y.start >= y_end {
+            return;
+        }
+        for row in y.start / TILE_SIZE..(y_end - 1) / TILE_SIZE + 1 {
+            for column in x.start / TILE_SIZE..(x_end - 1) / TILE_SIZE + 1 {
+                self.dirty[row * self.columns + column] = true
+            }
+        }
+    }
+
+    pub fn mark_all(&mut self) {
+        for d in self.dirty.iter_mut() {
+            *d = true
+        }
+    }
+
+    // Replaces the spans with the pixel offsets of the dirty tiles, in
+    // order and joined where they meet, so a frame with all of them dirty
+    // is a single span. Marks them clean.
+    pub fn take_spans(&mut self, spans: &mut Vec<Range<usize>>) {
+        spans.clear();
+        for row in 0..self.rows {
+            let y_end = ((row + 1) * TILE_SIZE).min(self.height);
+            for y in row * TILE_SIZE..y_end {
+                for column in 0..self.columns {
+                    if !self.dirty[row * self.columns + column] {
+                        continue;
+                    }
+                    let start = y * self.width + column * TILE_SIZE;
+                    let end = y * self.width + ((column + 1) * TILE_SIZE).min(self.width);
+                    match spans.last_mut() {
+                        Some(last) if last.end == start => last.end = end,
+                        _ => spans.push(start..end),
+                    }
+                }
+            }
+        }
+        for d in self.dirty.iter_mut() {
+            *d = false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{DirtyTiles, TILE_SIZE};
+
+    #[test]
+    fn spans() {
+        let (width, height) = (TILE_SIZE * 3 + 5, TILE_SIZE + 2);
+        let mut tiles = DirtyTiles::new(width, height);
+        assert_eq!((4, 2, 8), (tiles.columns(), tiles.rows(), tiles.count()));
+        let mut spans = Vec::new();
+        tiles.take_spans(&mut spans);
+        assert_eq!(vec![0..width * height], spans);
+        assert_eq!(0, tiles.count());
+
+        // The last column and row are narrower
+        tiles.mark(width * (height - 1) + width - 1);
+        tiles.mark_rect(TILE_SIZE - 1..TILE_SIZE + 1, 0..1);
+        assert!(tiles.is_dirty(3, 1) && tiles.is_dirty(0, 0) && tiles.is_dirty(1, 0));
+        assert_eq!(3, tiles.count());
+        tiles.take_spans(&mut spans);
+        assert_eq!(TILE_SIZE + 2, spans.len());
+        assert_eq!(0..TILE_SIZE * 2, spans[0]);
+        assert_eq!(width * (height - 1) + TILE_SIZE * 3..width * height, spans[TILE_SIZE + 1]);
+
+        // Out of the frame or empty
+        tiles.mark_rect(width..width + 10, 0..height);
+        tiles.mark_rect(0..width, 3..3);
+        assert_eq!(0, tiles.count());
+    }
+}
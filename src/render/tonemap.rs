@@ -0,0 +1,70 @@
+
+use super::color::Color;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum ToneMapping {
+    Clamp,
+    Reinhard,
+    Aces,
+}
+
+fn reinhard(x: f64) -> f64 {
+    x / (1.0 + x)
+}
+
+// https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/
+fn aces(x: f64) -> f64 {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    ((x * (a * x + b)) / (x * (c * x + d) + e)).max(0.0).min(1.0)
+}
+
+impl ToneMapping {
+    pub fn apply(self, color: Color, exposure: f64) -> Color {
+        let f: fn(f64) -> f64 = match self {
+            ToneMapping::Clamp => |x| x.max(0.0).min(1.0),
+            ToneMapping::Reinhard => reinhard,
+            ToneMapping::Aces => aces,
+        };
+        Color::new(f(color.r * exposure),
+                   f(color.g * exposure),
+                   f(color.b * exposure),
+                   color.a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::ToneMapping;
+    use super::super::color::Color;
+
+    #[test]
+    fn clamp() {
+        let c = ToneMapping::Clamp.apply(Color::rgb(2.0, 0.5, -1.0), 1.0);
+        assert_eq!(Color::rgb(1.0, 0.5, 0.0), c);
+    }
+
+    #[test]
+    fn reinhard() {
+        let c = ToneMapping::Reinhard.apply(Color::rgb(1.0, 3.0, 0.0), 1.0);
+        assert_eq!(Color::rgb(0.5, 0.75, 0.0), c);
+    }
+
+    #[test]
+    fn exposure() {
+        let c = ToneMapping::Reinhard.apply(Color::gray(0.5), 2.0);
+        assert_eq!(Color::gray(0.5), c);
+    }
+
+    #[test]
+    fn aces_stays_in_range() {
+        let c = ToneMapping::Aces.apply(Color::rgb(0.0, 1.0, 1000.0), 1.0);
+        assert!(c.r.abs() < 1e-9);
+        assert!(c.g > 0.0 && c.g < 1.0);
+        assert_eq!(1.0, c.b);
+    }
+}
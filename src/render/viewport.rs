@@ -0,0 +1,129 @@
+
+use math::vector::Vector3;
+use math::single::Vector3f;
+
+// Rectangle of the render target that NDC is mapped to, in pixels. It may
+// extend past the target, e.g. to render one tile of a larger frame, and
+// only the part inside the target is drawn. NDC depth in [0, 1] is mapped
+// to [min_depth, max_depth].
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Viewport {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub min_depth: f64,
+    pub max_depth: f64,
+}
+
+impl Viewport {
+    pub fn new(x: f64, y: f64, width: f64, height: f64) -> Viewport {
+        Viewport {
+            x: x,
+            y: y,
+            width: width,
+            height: height,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }
+    }
+
+    pub fn aspect(&self) -> f64 {
+        self.width / self.height
+    }
+
+    // NDC to screen coordinates, flipping y so the top row is 0.
+    pub fn project(&self, ndc: Vector3) -> Vector3 {
+        Vector3::new(self.x + (ndc.x + 1.0) * self.width * 0.5,
+                     self.y + (1.0 - ndc.y) * self.height * 0.5,
+                     self.min_depth + ndc.z * (self.max_depth - self.min_depth))
+    }
+
+    // The same in f32, for Precision::Single.
+    pub fn project_single(&self, ndc: Vector3f) -> Vector3f {
+        let (x, y) = (self.x as f32, self.y as f32);
+        let (width, height) = (self.width as f32, self.height as f32);
+        let (min_depth, max_depth) = (self.min_depth as f32, self.max_depth as f32);
+        Vector3f::new(x + (ndc.x + 1.0) * width * 0.5,
+                      y + (1.0 - ndc.y) * height * 0.5,
+                      min_depth + ndc.z * (max_depth - min_depth))
+    }
+
+    pub fn unproject(&self, screen: Vector3) -> Vector3 {
+        Vector3::new((screen.x - self.x) / self.width * 2.0 - 1.0,
+                     1.0 - (screen.y - self.y) / self.height * 2.0,
+                     (screen.z - self.min_depth) / (self.max_depth - self.min_depth))
+    }
+}
+
+// Rectangle of whole pixels from its top left corner, e.g. a scissor.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Rect {
+        Rect {
+            x: x,
+            y: y,
+            width: width,
+            height: height,
+        }
+    }
+
+    pub fn right(&self) -> usize {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> usize {
+        self.y + self.height
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && y >= self.y && x < self.right() && y < self.bottom()
+    }
+
+    // The part inside both, empty where they don't overlap.
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        let (right, bottom) = (self.right().min(other.right()), self.bottom().min(other.bottom()));
+        Rect::new(x, y, right.saturating_sub(x), bottom.saturating_sub(y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use math::single::Vector3f;
+    use super::{Rect, Viewport};
+
+    #[test]
+    fn project() {
+        let mut viewport = Viewport::new(10.0, 20.0, 100.0, 50.0);
+        viewport.min_depth = 0.5;
+        assert_eq!(Vector3::new(10.0, 20.0, 1.0),
+                   viewport.project(Vector3::new(-1.0, 1.0, 1.0)));
+        assert_eq!(Vector3::new(110.0, 70.0, 0.5),
+                   viewport.project(Vector3::new(1.0, -1.0, 0.0)));
+
+        let ndc = Vector3::new(0.25, -0.5, 0.75);
+        assert_eq!(ndc, viewport.unproject(viewport.project(ndc)));
+        assert_eq!(Vector3f::from(viewport.project(ndc)),
+                   viewport.project_single(Vector3f::from(ndc)));
+    }
+
+    #[test]
+    fn rect() {
+        let rect = Rect::new(2, 3, 4, 5);
+        assert_eq!((6, 8), (rect.right(), rect.bottom()));
+        assert!(rect.contains(2, 3) && rect.contains(5, 7));
+        assert!(!rect.contains(6, 3) && !rect.contains(2, 8));
+        assert_eq!(Rect::new(4, 3, 2, 2), rect.intersect(&Rect::new(4, 0, 10, 5)));
+        assert_eq!(0, rect.intersect(&Rect::new(10, 10, 2, 2)).width);
+    }
+}
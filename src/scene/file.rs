@@ -0,0 +1,420 @@
+
+use error::{Result, SwrError};
+use math::quaternion::Quaternion;
+use math::vector::Vector3;
+use geometry::gltf;
+use geometry::mesh::Mesh;
+use render::color::Color;
+use render::device::{Camera, Device, Handedness, Projection};
+use render::fog::{Fog, FogMode};
+use render::light::PointLight;
+use render::material::{Diffuse, Material, Shading};
+use render::texture::Texture;
+use super::graph::{Node, NodeId, Scene};
+use super::watch::Watcher;
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde_json;
+use serde_json::Value;
+
+// Scenes described in JSON, so they can be changed without rebuilding, e.g.
+//
+// {
+//   "camera": { "position": [0, 2, 10], "target": [0, 0, 0], "fov": 45 },
+//   "ambient": [0.1, 0.1, 0.1],
+//   "background": [0.13, 0.13, 0.13],
+//   "fog": { "color": [0.5, 0.6, 0.7], "start": 10, "end": 40 },
+//   "lights": [{ "position": [5, 5, 5], "color": [1, 1, 1], "radius": 20 }],
+//   "nodes": [
+//     { "name": "Floor", "mesh": { "primitive": "plane", "width": 10, "depth": 10 } },
+//     { "mesh": "cube", "position": [0, 1, 0], "rotation": [0, 45, 0],
+//       "material": { "color": [1, 0.2, 0.2], "shading": "pbr", "roughness": 0.3 },
+//       "children": [{ "model": "models/teapot.ply", "scale": 0.5 }] }
+//   ]
+// }
+//
+// Everything is optional. Colors are three or four numbers, authored in
+// sRGB like mesh colors, and rotations are Euler angles in degrees. The
+// camera's fov is in degrees too, and "orthographic" with a height in
+// place of it makes an orthographic camera. Fog is linear with "start"
+// and "end" or exponential with "density", squared with "squared": true.
+//
+// A node's mesh is a primitive, given by name or as an object with the
+// primitive's parameters, or a model file: .obj, .ply, .stl, or .gltf and .glb,
+// whose nodes become children of the node keeping their own materials.
+// Paths are relative to the scene file, as are material textures, PNG
+// images linearized like glTF base colors.
+
+// A loaded scene file, with what it sets up besides the nodes.
+#[derive(Debug)]
+pub struct SceneFile {
+    pub scene: Scene,
+    pub camera: Option<Camera>,
+    pub lights: Vec<PointLight>,
+    pub ambient: Option<Color>,
+    // The clear color.
+    pub background: Option<Color>,
+    // The scene file itself when loaded from one, then the models and
+    // textures it uses, for watching. Buffers and images glTF files refer
+    // to are left out.
+    pub files: Vec<PathBuf>,
+}
+
+impl SceneFile {
+    // Replaces the device's lights with the file's, and its ambient light
+    // if the file has one.
+    pub fn apply(&self, device: &mut Device) {
+        device.lights = self.lights.clone();
+        if let Some(ambient) = self.ambient {
+            device.ambient = ambient;
+        }
+    }
+
+    // Watches the files, to load the scene again when one changes.
+    pub fn watcher(&self) -> Watcher {
+        let mut watcher = Watcher::new();
+        for path in &self.files {
+            watcher.watch(path);
+        }
+        watcher
+    }
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<SceneFile> {
+    let path = path.as_ref();
+    let mut text = String::new();
+    File::open(path)?.read_to_string(&mut text)?;
+    let mut file = read(&text, path.parent().unwrap_or_else(|| Path::new("")))?;
+    file.files.insert(0, path.to_path_buf());
+    Ok(file)
+}
+
+// The scene the text describes, with the files it refers to looked up in
+// the directory.
+pub fn read(text: &str, directory: &Path) -> Result<SceneFile> {
+    let document: Value = serde_json::from_str(text).map_err(invalid)?;
+    if !document.is_object() {
+        return Err(invalid("a scene file holds an object"));
+    }
+    let mut files = Vec::new();
+    for value in array(&document, "nodes") {
+        referenced(value, directory, &mut files);
+    }
+    let mut scene = Scene::new();
+    scene.fog = document.get("fog").map(fog);
+    for value in array(&document, "nodes") {
+        node(value, directory, &mut scene, None)?;
+    }
+    Ok(SceneFile {
+        scene: scene,
+        camera: document.get("camera").map(camera),
+        lights: array(&document, "lights").iter().map(light).collect(),
+        ambient: color(&document, "ambient"),
+        background: color(&document, "background"),
+        files: files,
+    })
+}
+
+// The model and texture files of the node and its children.
+fn referenced(value: &Value, directory: &Path, files: &mut Vec<PathBuf>) {
+    let model = value.get("model").and_then(Value::as_str);
+    let texture = value.get("material").and_then(|m| m.get("texture")).and_then(Value::as_str);
+    for path in model.into_iter().chain(texture) {
+        let path = directory.join(path);
+        if !files.contains(&path) {
+            files.push(path);
+        }
+    }
+    for child in array(value, "children") {
+        referenced(child, directory, files);
+    }
+}
+
+fn invalid<E: ToString>(e: E) -> SwrError {
+    SwrError::format(e.to_string())
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> &'a [Value] {
+    value.get(key).and_then(Value::as_array).map_or(&[], |a| &a[..])
+}
+
+fn number(value: &Value, key: &str, default: f64) -> f64 {
+    value.get(key).and_then(Value::as_f64).unwrap_or(default)
+}
+
+fn count(value: &Value, key: &str, default: usize) -> usize {
+    value.get(key).and_then(Value::as_u64).map_or(default, |n| n as usize)
+}
+
+fn vector(value: &Value, key: &str, default: Vector3) -> Vector3 {
+    let v: Vec<f64> = array(value, key).iter().filter_map(Value::as_f64).collect();
+    if v.len() == 3 { Vector3::new(v[0], v[1], v[2]) } else { default }
+}
+
+fn color(value: &Value, key: &str) -> Option<Color> {
+    let c: Vec<f64> = array(value, key).iter().filter_map(Value::as_f64).collect();
+    match c.len() {
+        3 => Some(Color::rgb(c[0], c[1], c[2])),
+        4 => Some(Color::new(c[0], c[1], c[2], c[3])),
+        _ => None,
+    }
+}
+
+fn camera(value: &Value) -> Camera {
+    let projection = match value.get("orthographic").and_then(Value::as_f64) {
+        Some(height) => Projection::Orthographic { height: height },
+        None => Projection::Perspective,
+    };
+    Camera {
+        position: vector(value, "position", Vector3::new(0.0, 0.0, 10.0)),
+        target: vector(value, "target", Vector3::zero()),
+        fov: number(value, "fov", 45.0).to_radians(),
+        znear: number(value, "near", 0.1),
+        zfar: number(value, "far", 100.0),
+        projection: projection,
+        handedness: Handedness::Right,
+    }
+}
+
+fn light(value: &Value) -> PointLight {
+    let mut light = PointLight::new(vector(value, "position", Vector3::zero()),
+                                    color(value, "color").unwrap_or_else(Color::white),
+                                    number(value, "radius", 10.0));
+    light.intensity = number(value, "intensity", 1.0);
+    light
+}
+
+fn fog(value: &Value) -> Fog {
+    let density = value.get("density").and_then(Value::as_f64);
+    let squared = value.get("squared").and_then(Value::as_bool).unwrap_or(false);
+    let mode = match density {
+        Some(density) if squared => FogMode::ExponentialSquared { density: density },
+        Some(density) => FogMode::Exponential { density: density },
+        None => {
+            FogMode::Linear {
+                start: number(value, "start", 10.0),
+                end: number(value, "end", 50.0),
+            }
+        }
+    };
+    Fog::new(color(value, "color").unwrap_or_else(Color::white), mode)
+}
+
+fn node(value: &Value,
+        directory: &Path,
+        scene: &mut Scene,
+        parent: Option<NodeId>)
+        -> Result<NodeId> {
+    let mut model = None;
+    let mut node = match (value.get("mesh"), value.get("model").and_then(Value::as_str)) {
+        (Some(mesh), _) => Node::with_mesh(mesh_primitive(mesh)?),
+        (None, Some(path)) => {
+            let path = directory.join(path);
+            match &extension(&path)[..] {
+                "gltf" | "glb" => {
+                    model = Some(gltf::load(&path)?);
+                    Node::new(&path.file_stem().map_or(String::new(), |s| {
+                        s.to_string_lossy().into_owned()
+                    }))
+                }
+                _ => Node::with_mesh(load_mesh(&path)?),
+            }
+        }
+        (None, None) => Node::new("Node"),
+    };
+    if let Some(name) = value.get("name").and_then(Value::as_str) {
+        node.name = name.to_string();
+    }
+    if let (Some(mesh), Some(m)) = (node.mesh.as_mut(), value.get("material")) {
+        mesh.material = material(m, directory)?;
+    }
+    node.transform.position = vector(value, "position", Vector3::zero());
+    let rotation = vector(value, "rotation", Vector3::zero());
+    node.transform.rotation = Quaternion::from_euler_angle_degrees(rotation);
+    node.transform.scale = match value.get("scale").and_then(Value::as_f64) {
+        Some(s) => Vector3::new(s, s, s),
+        None => vector(value, "scale", Vector3::one()),
+    };
+
+    let id = scene.add(node, parent);
+    if let Some(model) = model {
+        scene.graft(model, Some(id));
+    }
+    for child in array(value, "children") {
+        self::node(child, directory, scene, Some(id))?;
+    }
+    Ok(id)
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().and_then(|e| e.to_str()).map_or(String::new(), |e| e.to_lowercase())
+}
+
+// A model file of a single mesh, .obj, .ply or .stl.
+pub fn load_mesh<P: AsRef<Path>>(path: P) -> Result<Mesh> {
+    let path = path.as_ref();
+    match &extension(path)[..] {
+        "obj" => Mesh::from_obj(path),
+        "ply" => Mesh::from_ply(path),
+        "stl" => Mesh::from_stl(path),
+        _ => Err(invalid(format!("unsupported model {}", path.display()))),
+    }
+}
+
+// A primitive by its name in scene files, at its default size.
+pub fn primitive(name: &str) -> Result<Mesh> {
+    mesh_primitive(&Value::from(name))
+}
+
+// A primitive by name with its default size, or an object naming it along
+// with its parameters.
+fn mesh_primitive(value: &Value) -> Result<Mesh> {
+    let name = match value.as_str() {
+        Some(name) => name,
+        None => {
+            value.get("primitive")
+                .and_then(Value::as_str)
+                .ok_or_else(|| invalid("mesh without a primitive"))?
+        }
+    };
+    let radius = number(value, "radius", 1.0);
+    let height = number(value, "height", 2.0);
+    let segments = count(value, "segments", 24);
+    let mesh = match name {
+        "triangle" => Mesh::triangle(),
+        "cube" => Mesh::cube(),
+        "plane" => {
+            let subdivisions = count(value, "subdivisions", 1);
+            Mesh::plane(number(value, "width", 2.0),
+                        number(value, "depth", 2.0),
+                        subdivisions,
+                        subdivisions)
+        }
+        "sphere" => {
+            Mesh::sphere(Vector3::zero(),
+                         radius,
+                         count(value, "slices", 24),
+                         count(value, "stacks", 16))
+        }
+        "icosphere" => Mesh::icosphere(radius, count(value, "subdivisions", 2)),
+        "tetrahedron" => Mesh::tetrahedron(radius),
+        "octahedron" => Mesh::octahedron(radius),
+        "icosahedron" => Mesh::icosahedron(radius),
+        "cylinder" => Mesh::cylinder(radius, height, segments),
+        "cone" => Mesh::cone(radius, height, segments),
+        "capsule" => Mesh::capsule(radius, height, segments),
+        "torus" => {
+            Mesh::torus(radius,
+                        number(value, "ring_radius", 0.25),
+                        count(value, "sides", 16) as u32,
+                        count(value, "rings", 32) as u32)
+        }
+        _ => return Err(invalid(format!("unknown primitive {}", name))),
+    };
+    Ok(mesh)
+}
+
+fn material(value: &Value, directory: &Path) -> Result<Material> {
+    let mut material = Material::new();
+    material.color = color(value, "color").unwrap_or_else(Color::white);
+    material.reflectivity = number(value, "reflectivity", 0.0);
+    material.metallic = number(value, "metallic", 0.0);
+    material.roughness = number(value, "roughness", 0.5);
+    material.shading = match value.get("shading").and_then(Value::as_str) {
+        None | Some("basic") => Shading::Basic,
+        Some("pbr") => Shading::Pbr(Diffuse::Lambert),
+        Some("oren_nayar") => Shading::Pbr(Diffuse::OrenNayar),
+        Some("toon") => Shading::Toon { bands: count(value, "bands", 3) },
+        Some(other) => return Err(invalid(format!("unknown shading {}", other))),
+    };
+    if let Some(path) = value.get("texture").and_then(Value::as_str) {
+        let texture = Texture::load_png(directory.join(path))?;
+        let texels = texture.texels().iter().map(|c| c.to_linear()).collect();
+        material.texture = Some(Texture::new(texture.width(), texture.height(), texels));
+    }
+    Ok(material)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::env;
+    use std::f64;
+    use std::fs;
+    use std::path::Path;
+    use math::vector::Vector3;
+    use geometry::mesh::Mesh;
+    use render::color::Color;
+    use render::device::{Device, Projection};
+    use render::fog::FogMode;
+    use render::material::Shading;
+    use super::read;
+
+    #[test]
+    fn read_scene() {
+        let text = r#"{
+            "camera": { "position": [0, 2, 10], "fov": 60, "far": 50 },
+            "ambient": [0.2, 0.2, 0.2],
+            "fog": { "color": [0.5, 0.5, 0.5], "density": 0.1 },
+            "lights": [{ "position": [1, 2, 3], "color": [1, 0, 0], "radius": 5 }],
+            "nodes": [
+                { "name": "Floor", "mesh": { "primitive": "plane", "subdivisions": 4 } },
+                { "mesh": "cube", "position": [0, 1, 0], "scale": 2,
+                  "material": { "color": [1, 0, 0, 0.5], "shading": "toon", "bands": 4 },
+                  "children": [{ "name": "Pivot", "rotation": [0, 90, 0] }] }
+            ]
+        }"#;
+        let file = read(text, Path::new("")).unwrap();
+        let camera = file.camera.as_ref().unwrap();
+        assert_eq!(Vector3::new(0.0, 2.0, 10.0), camera.position);
+        assert_eq!(Vector3::zero(), camera.target);
+        assert!((camera.fov - f64::consts::PI / 3.0).abs() < 1e-12);
+        assert_eq!((50.0, Projection::Perspective), (camera.zfar, camera.projection));
+        assert_eq!(FogMode::Exponential { density: 0.1 }, file.scene.fog.as_ref().unwrap().mode);
+
+        let scene = &file.scene;
+        assert_eq!(2, scene.roots().len());
+        let floor = scene.node(scene.find("Floor").unwrap());
+        assert_eq!(16 * 2, floor.mesh.as_ref().unwrap().face_count());
+        let cube = scene.node(scene.find("Cube").unwrap());
+        assert_eq!(Vector3::new(2.0, 2.0, 2.0), cube.transform.scale);
+        let material = &cube.mesh.as_ref().unwrap().material;
+        assert_eq!(Color::new(1.0, 0.0, 0.0, 0.5), material.color);
+        assert_eq!(Shading::Toon { bands: 4 }, material.shading);
+        let pivot = scene.find("Pivot").unwrap();
+        assert_eq!(Some(scene.find("Cube").unwrap()), scene.node(pivot).parent());
+        let turned = scene.node(pivot).transform.rotation.rotate(Vector3::unit_x());
+        assert!(turned.x.abs() < 1e-12);
+
+        let mut device = Device::new(4, 4);
+        file.apply(&mut device);
+        assert_eq!((1, Color::rgb(0.2, 0.2, 0.2)), (device.lights.len(), device.ambient));
+        assert_eq!(5.0, device.lights[0].radius);
+
+        let demo = read(include_str!("../../scenes/demo.json"), Path::new("")).unwrap();
+        assert_eq!(2, demo.lights.len());
+
+        assert!(read("[]", Path::new("")).is_err());
+        assert!(read(r#"{ "nodes": [{ "mesh": "teapot" }] }"#, Path::new("")).is_err());
+        assert!(read(r#"{ "nodes": [{ "model": "missing.ply" }] }"#, Path::new("")).is_err());
+    }
+
+    #[test]
+    fn read_models() {
+        let directory = env::temp_dir().join("swr_rs_scene_file");
+        fs::create_dir_all(&directory).unwrap();
+        Mesh::cube().save_ply(directory.join("cube.ply")).unwrap();
+        Mesh::triangle().save_obj(directory.join("triangle.obj")).unwrap();
+        let text = r#"{ "nodes": [{ "name": "Model", "model": "cube.ply",
+                                    "children": [{ "model": "triangle.obj" }] }] }"#;
+        let file = read(text, &directory).unwrap();
+        let model = file.scene.node(file.scene.find("Model").unwrap());
+        assert_eq!(12, model.mesh.as_ref().unwrap().face_count());
+        assert!(file.scene.find("triangle").is_some());
+        assert_eq!(vec![directory.join("cube.ply"), directory.join("triangle.obj")], file.files);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
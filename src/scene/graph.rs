@@ -0,0 +1,266 @@
+
+use math::matrix::Matrix4;
+use math::transform::Transform;
+use geometry::mesh::Mesh;
+use geometry::ray::{Ray, RayHit};
+use render::device::Camera;
+use render::fog::Fog;
+use render::viewport::Viewport;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+pub struct NodeId(usize);
+
+#[derive(Debug)]
+pub struct Node {
+    pub name: String,
+    pub transform: Transform,
+    pub mesh: Option<Mesh>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl Node {
+    pub fn new(name: &str) -> Node {
+        Node {
+            name: name.to_string(),
+            transform: Transform::identity(),
+            mesh: None,
+            parent: None,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn with_mesh(mesh: Mesh) -> Node {
+        let name = mesh.name.clone();
+        Node { mesh: Some(mesh), ..Node::new(&name) }
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+// Nodes are stored in a flat list and refer to each other by NodeId. A
+// node's world matrix is its local transform followed by its parent's world
+// matrix.
+#[derive(Debug,Default)]
+pub struct Scene {
+    nodes: Vec<Node>,
+    roots: Vec<NodeId>,
+    // Used by Device::render_scene in place of the device's fog.
+    pub fog: Option<Fog>,
+}
+
+impl Scene {
+    pub fn new() -> Scene {
+        Scene::default()
+    }
+
+    pub fn add(&mut self, mut node: Node, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        node.parent = parent;
+        node.children.clear();
+        self.nodes.push(node);
+        match parent {
+            Some(parent) => self.nodes[parent.0].children.push(id),
+            None => self.roots.push(id),
+        }
+        id
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    // Moves the nodes of the other scene under the parent, keeping their
+    // hierarchy. Returns the new ids of its roots.
+    pub fn graft(&mut self, other: Scene, parent: Option<NodeId>) -> Vec<NodeId> {
+        let mut nodes: Vec<Option<Node>> = other.nodes.into_iter().map(Some).collect();
+        other.roots.iter().map(|&root| self.graft_node(&mut nodes, root, parent)).collect()
+    }
+
+    fn graft_node(&mut self,
+                  nodes: &mut [Option<Node>],
+                  id: NodeId,
+                  parent: Option<NodeId>)
+                  -> NodeId {
+        let node = nodes[id.0].take().expect("node reached twice");
+        let children = node.children.clone();
+        let new = self.add(node, parent);
+        for child in children {
+            self.graft_node(nodes, child, Some(new));
+        }
+        new
+    }
+
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes.iter().position(|n| n.name == name).map(NodeId)
+    }
+
+    pub fn world_matrix(&self, id: NodeId) -> Matrix4 {
+        let node = self.node(id);
+        let local = node.transform.matrix();
+        match node.parent {
+            Some(parent) => local * self.world_matrix(parent),
+            None => local,
+        }
+    }
+
+    // Visits every node depth first, parents before children, passing the
+    // node's world matrix along.
+    pub fn traverse<'a, F: FnMut(NodeId, &'a Node, &Matrix4)>(&'a self, mut f: F) {
+        for &root in &self.roots {
+            self.traverse_node(root, &Matrix4::identity(), &mut f);
+        }
+    }
+
+    fn traverse_node<'a, F: FnMut(NodeId, &'a Node, &Matrix4)>(&'a self,
+                                                                id: NodeId,
+                                                                parent: &Matrix4,
+                                                                f: &mut F) {
+        let node = self.node(id);
+        let world = node.transform.matrix() * *parent;
+        f(id, node, &world);
+        for &child in &node.children {
+            self.traverse_node(child, &world, f);
+        }
+    }
+
+    pub fn meshes(&self) -> Vec<(&Mesh, Matrix4)> {
+        let mut meshes = Vec::new();
+        self.traverse(|_, node, world| {
+            if let Some(ref mesh) = node.mesh {
+                meshes.push((mesh, *world));
+            }
+        });
+        meshes
+    }
+
+    // Nearest node whose mesh is hit by the ray, in world space.
+    pub fn raycast(&self, ray: &Ray) -> Option<(NodeId, RayHit)> {
+        let mut nearest: Option<(NodeId, RayHit)> = None;
+        self.traverse(|id, node, world| {
+            if let Some(ref mesh) = node.mesh {
+                if let Some(hit) = mesh.raycast_with_parent(ray, world) {
+                    if nearest.is_none_or(|(_, nearest)| hit.distance < nearest.distance) {
+                        nearest = Some((id, hit));
+                    }
+                }
+            }
+        });
+        nearest
+    }
+
+    // The node and triangle under a point of the viewport, e.g. the mouse
+    // position when the scene is rendered with the same camera and viewport.
+    pub fn pick(&self,
+                camera: &Camera,
+                viewport: &Viewport,
+                screen_x: f64,
+                screen_y: f64)
+                -> Option<(NodeId, RayHit)> {
+        self.raycast(&camera.screen_ray(viewport, screen_x, screen_y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use math::vector::Vector3;
+    use math::transform::Transform;
+    use math::quaternion::Quaternion;
+    use geometry::mesh::Mesh;
+    use render::device::{Camera, Handedness, Projection};
+    use render::viewport::Viewport;
+    use super::{Node, Scene};
+
+    fn assert_near(expected: Vector3, actual: Vector3) {
+        assert!((expected - actual).length() < 1e-9, "{:?} != {:?}", expected, actual);
+    }
+
+    #[test]
+    fn hierarchy() {
+        let mut scene = Scene::new();
+        let planet = scene.add(Node::new("planet"), None);
+        let moon = scene.add(Node::with_mesh(Mesh::cube()), Some(planet));
+
+        scene.node_mut(planet).transform = Transform::new(Vector3::new(5.0, 0.0, 0.0),
+                                                          Quaternion::from_euler_angle_degrees(Vector3::new(0.0, 90.0, 0.0)),
+                                                          Vector3::one());
+        scene.node_mut(moon).transform.position = Vector3::new(2.0, 0.0, 0.0);
+
+        assert_eq!(&[moon], scene.node(planet).children());
+        assert_eq!(Some(planet), scene.node(moon).parent());
+        assert_eq!(Some(moon), scene.find("Cube"));
+
+        let world = scene.world_matrix(moon);
+        let position = Vector3::transform_coordinate(&Vector3::zero(), &world);
+        assert_near(Vector3::new(5.0, -2.0, 0.0), position);
+
+        let meshes = scene.meshes();
+        assert_eq!(1, meshes.len());
+        assert_eq!(world, meshes[0].1);
+    }
+
+    #[test]
+    fn graft() {
+        let mut model = Scene::new();
+        let body = model.add(Node::new("body"), None);
+        model.add(Node::with_mesh(Mesh::cube()), Some(body));
+        model.add(Node::new("antenna"), None);
+
+        let mut scene = Scene::new();
+        let root = scene.add(Node::new("root"), None);
+        let roots = scene.graft(model, Some(root));
+        assert_eq!(2, roots.len());
+        assert_eq!(&roots[..], scene.node(root).children());
+        assert_eq!(Some(roots[0]), scene.node(scene.find("Cube").unwrap()).parent());
+        assert_eq!("antenna", scene.node(roots[1]).name);
+        assert_eq!(&[root], scene.roots());
+    }
+
+    #[test]
+    fn pick() {
+        let mut scene = Scene::new();
+        let cube = scene.add(Node::with_mesh(Mesh::cube()), None);
+        let mut sphere = Node::with_mesh(Mesh::sphere(Vector3::zero(), 1.0, 8, 8));
+        sphere.transform.position = Vector3::new(4.0, 0.0, 0.0);
+        let sphere = scene.add(sphere, Some(cube));
+
+        let camera = Camera {
+            position: Vector3::new(0.0, 0.0, 10.0),
+            target: Vector3::zero(),
+            fov: 60f64.to_radians(),
+            znear: 0.1,
+            zfar: 100.0,
+            projection: Projection::Perspective,
+            handedness: Handedness::Right,
+        };
+        let viewport = Viewport::new(0.0, 0.0, 200.0, 100.0);
+        let view_projection = camera.view_matrix() * camera.projection_matrix(viewport.aspect());
+        let screen = |p: Vector3| viewport.project(Vector3::transform_coordinate(&p, &view_projection));
+
+        let (id, hit) = scene.pick(&camera, &viewport, 100.0, 50.0).unwrap();
+        assert_eq!(cube, id);
+        // The ray starts on the near plane
+        assert!((hit.distance - 8.9).abs() < 1e-6);
+
+        let gap = screen(Vector3::new(2.0, 0.0, 0.0));
+        assert!(scene.pick(&camera, &viewport, gap.x, gap.y).is_none());
+
+        let center = screen(Vector3::new(4.0, 0.0, 0.0));
+        assert_eq!(sphere, scene.pick(&camera, &viewport, center.x, center.y).unwrap().0);
+    }
+}
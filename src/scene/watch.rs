@@ -0,0 +1,86 @@
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// Notices files changing by their modification times, so scenes and assets
+// can be loaded again while the program runs. Cheap enough to poll every
+// frame.
+#[derive(Debug,Clone,Default)]
+pub struct Watcher {
+    // None for a file that isn't there
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl Watcher {
+    pub fn new() -> Watcher {
+        Watcher { files: Vec::new() }
+    }
+
+    // Starts from the file as it is now, which needn't exist yet.
+    pub fn watch<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        if !self.files.iter().any(|f| f.0 == path) {
+            self.files.push((path.to_path_buf(), modified(path)));
+        }
+    }
+
+    pub fn paths(&self) -> Vec<&Path> {
+        self.files.iter().map(|f| f.0.as_path()).collect()
+    }
+
+    // The files written, made or removed since the last poll, or since
+    // they were watched.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        for &mut (ref path, ref mut time) in &mut self.files {
+            let now = modified(path);
+            if now != *time {
+                *time = now;
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::env;
+    use std::fs;
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+    use super::Watcher;
+
+    #[test]
+    fn poll() {
+        let directory = env::temp_dir().join("swr_rs_watch");
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let (a, b) = (directory.join("a.json"), directory.join("b.png"));
+        fs::write(&a, "{}").unwrap();
+
+        let mut watcher = Watcher::new();
+        watcher.watch(&a);
+        watcher.watch(&b);
+        watcher.watch(&a);
+        assert_eq!(2, watcher.paths().len());
+        assert!(watcher.poll().is_empty());
+
+        // Set back, as saving twice in a second can keep the same time
+        let earlier = SystemTime::now() - Duration::from_secs(60);
+        File::options().write(true).open(&a).unwrap().set_modified(earlier).unwrap();
+        assert_eq!(vec![a.clone()], watcher.poll());
+        assert!(watcher.poll().is_empty());
+
+        fs::write(&b, "").unwrap();
+        fs::remove_file(&a).unwrap();
+        assert_eq!(vec![a, b], watcher.poll());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,224 @@
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use math::transform::Transform;
+use math::vector::Vector3;
+
+fn seconds(duration: Duration) -> f64 {
+    duration.subsec_nanos() as f64 * 1e-9 + duration.as_secs() as f64
+}
+
+// Paces a render loop to a target frame rate by sleeping off what is left
+// of each frame, like vsync without the display, or lets it run uncapped.
+// Call begin_frame at the top of the loop and end_frame at the bottom.
+#[derive(Debug,Clone)]
+pub struct FramePacer {
+    // Frames per second, None for as fast as the loop runs.
+    pub target_fps: Option<f64>,
+    frame_start: Instant,
+    delta: f64,
+    frame_count: u64,
+}
+
+impl FramePacer {
+    pub fn new(target_fps: Option<f64>) -> FramePacer {
+        FramePacer {
+            target_fps: target_fps,
+            frame_start: Instant::now(),
+            delta: 0.0,
+            frame_count: 0,
+        }
+    }
+
+    pub fn uncapped() -> FramePacer {
+        FramePacer::new(None)
+    }
+
+    // Seconds since the previous frame began, 0 for the first.
+    pub fn begin_frame(&mut self) -> f64 {
+        let now = Instant::now();
+        self.delta = if self.frame_count > 0 {
+            seconds(now - self.frame_start)
+        } else {
+            0.0
+        };
+        self.frame_start = now;
+        self.frame_count += 1;
+        self.delta
+    }
+
+    // Sleeps until the frame has taken its share of a second. A frame that
+    // ran over isn't made up for by shortening the next.
+    pub fn end_frame(&mut self) {
+        let target = match self.target_fps {
+            Some(fps) if fps > 0.0 => Duration::from_secs_f64(1.0 / fps),
+            _ => return,
+        };
+        let spent = self.frame_start.elapsed();
+        if target > spent {
+            thread::sleep(target - spent);
+        }
+    }
+
+    // What begin_frame last returned.
+    pub fn delta(&self) -> f64 {
+        self.delta
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    // Seconds the current frame has run so far, without the sleep.
+    pub fn busy_time(&self) -> f64 {
+        seconds(self.frame_start.elapsed())
+    }
+}
+
+impl Default for FramePacer {
+    fn default() -> FramePacer {
+        FramePacer::new(Some(60.0))
+    }
+}
+
+// Runs a simulation in steps of the same length however long frames take,
+// so it behaves the same at any frame rate. Each frame, advance by the
+// frame's time and run the steps it returns, then draw the state blended
+// between the last two steps by alpha, see Interpolated.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct FixedTimestep {
+    // Seconds.
+    pub step: f64,
+    // Steps run at most per frame, the time past them dropped, so a stall
+    // such as dragging the window doesn't leave ever more to catch up on.
+    pub max_steps: usize,
+    // Time not yet stepped through
+    accumulator: f64,
+}
+
+impl FixedTimestep {
+    pub fn new(step: f64) -> FixedTimestep {
+        FixedTimestep {
+            step: step,
+            max_steps: 8,
+            accumulator: 0.0,
+        }
+    }
+
+    // The steps due with dt more seconds passed.
+    pub fn advance(&mut self, dt: f64) -> usize {
+        self.accumulator += dt.max(0.0);
+        let steps = (self.accumulator / self.step).floor() as usize;
+        if steps > self.max_steps {
+            self.accumulator = 0.0;
+            return self.max_steps;
+        }
+        self.accumulator -= steps as f64 * self.step;
+        steps
+    }
+
+    // How far the time is from the last step to the next, from 0 to 1.
+    pub fn alpha(&self) -> f64 {
+        (self.accumulator / self.step).min(1.0)
+    }
+}
+
+// Values that can be blended, t going from self at 0 to b at 1.
+pub trait Lerp {
+    fn lerp(self, b: Self, t: f64) -> Self;
+}
+
+impl Lerp for f64 {
+    fn lerp(self, b: f64, t: f64) -> f64 {
+        self + (b - self) * t
+    }
+}
+
+impl Lerp for Vector3 {
+    fn lerp(self, b: Vector3, t: f64) -> Vector3 {
+        Vector3::lerp(self, b, t)
+    }
+}
+
+impl Lerp for Transform {
+    fn lerp(self, b: Transform, t: f64) -> Transform {
+        Transform::lerp(self, b, t)
+    }
+}
+
+// A simulated value as of the last two steps, for drawing in between.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Interpolated<T> {
+    pub previous: T,
+    pub current: T,
+}
+
+impl<T: Lerp + Copy> Interpolated<T> {
+    pub fn new(value: T) -> Interpolated<T> {
+        Interpolated {
+            previous: value,
+            current: value,
+        }
+    }
+
+    // Call at the start of each step, before changing current.
+    pub fn step(&mut self) {
+        self.previous = self.current;
+    }
+
+    // Moves to the value without blending, e.g. after a teleport.
+    pub fn set(&mut self, value: T) {
+        self.previous = value;
+        self.current = value;
+    }
+
+    // Blended by FixedTimestep::alpha.
+    pub fn get(&self, alpha: f64) -> T {
+        self.previous.lerp(self.current, alpha)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{FixedTimestep, FramePacer, Interpolated};
+
+    #[test]
+    fn pacing() {
+        let mut pacer = FramePacer::new(Some(100.0));
+        assert_eq!(0.0, pacer.begin_frame());
+        pacer.end_frame();
+        // Sleeping can only run over
+        let delta = pacer.begin_frame();
+        assert!(delta >= 0.0099);
+        assert_eq!((delta, 2), (pacer.delta(), pacer.frame_count()));
+
+        pacer.target_fps = None;
+        pacer.end_frame();
+        assert!(pacer.begin_frame() < 0.5);
+    }
+
+    #[test]
+    fn fixed_timestep() {
+        let mut timestep = FixedTimestep::new(0.25);
+        let mut position = Interpolated::new(0.0);
+        let mut steps = 0;
+        // Whatever the frame times, the same steps
+        for &dt in &[0.1, 0.3, 0.05, 0.6, 0.2] {
+            for _ in 0..timestep.advance(dt) {
+                position.step();
+                position.current += 1.0;
+                steps += 1;
+            }
+        }
+        assert_eq!(5, steps);
+        assert!(timestep.alpha() < 1e-9);
+        timestep.advance(0.125);
+        assert_eq!(4.5, position.get(timestep.alpha()));
+
+        // Stalls are dropped past the most steps
+        assert_eq!(8, timestep.advance(100.0));
+        assert_eq!((0, 0.0), (timestep.advance(0.0), timestep.alpha()));
+    }
+}